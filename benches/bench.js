@@ -0,0 +1,16 @@
+export const componentizeJsBenchApi = {
+    noop: function() {},
+    noopAsync: async function() {},
+    echoString: function(v) {
+        return v
+    },
+    echoListU8: function(v) {
+        return v
+    },
+    echoListU32: function(v) {
+        return v
+    },
+    echoListString: function(v) {
+        return v
+    }
+}
@@ -0,0 +1,208 @@
+#![deny(warnings)]
+
+use {
+    componentize_js::{Componentizer, Wit},
+    criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main},
+    std::{hint::black_box, sync::LazyLock},
+    tokio::runtime::Runtime,
+    wasmtime::{
+        Config, Engine, Store,
+        component::{Component, Linker, ResourceTable},
+    },
+    wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView},
+};
+
+wasmtime::component::bindgen!({
+    path: "benches/bench.wit",
+    world: "bench",
+    exports: { default: async | task_exit },
+});
+
+struct Ctx {
+    wasi: WasiCtx,
+    table: ResourceTable,
+}
+
+impl WasiView for Ctx {
+    fn ctx(&mut self) -> WasiCtxView<'_> {
+        WasiCtxView {
+            ctx: &mut self.wasi,
+            table: &mut self.table,
+        }
+    }
+}
+
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| Runtime::new().unwrap());
+
+static ENGINE: LazyLock<Engine> = LazyLock::new(|| {
+    let mut config = Config::new();
+    config.async_support(true);
+    config.wasm_component_model(true);
+    config.wasm_component_model_async(true);
+    Engine::new(&config).unwrap()
+});
+
+static PRE: LazyLock<BenchPre<Ctx>> = LazyLock::new(|| {
+    RUNTIME.block_on(async {
+        let component = Componentizer::new(
+            Wit::<String>::String(include_str!("bench.wit")),
+            include_str!("bench.js"),
+        )
+        .componentize()
+        .await
+        .unwrap();
+
+        let mut linker = Linker::new(&ENGINE);
+        wasmtime_wasi::p2::add_to_linker_async(&mut linker).unwrap();
+        BenchPre::new(
+            linker
+                .instantiate_pre(&Component::new(&ENGINE, &component).unwrap())
+                .unwrap(),
+        )
+        .unwrap()
+    })
+});
+
+const SIZES: &[usize] = &[16, 1024, 64 * 1024];
+
+fn store() -> Store<Ctx> {
+    Store::new(
+        &ENGINE,
+        Ctx {
+            wasi: WasiCtxBuilder::new().build(),
+            table: ResourceTable::new(),
+        },
+    )
+}
+
+async fn instantiate() -> (Store<Ctx>, Bench) {
+    let mut store = store();
+    let instance = PRE.instantiate_async(&mut store).await.unwrap();
+    (store, instance)
+}
+
+fn instantiation(c: &mut Criterion) {
+    c.bench_function("instantiate", |b| {
+        b.to_async(&*RUNTIME)
+            .iter(|| async { black_box(instantiate().await) })
+    });
+}
+
+fn sync_export(c: &mut Criterion) {
+    let (mut store, instance) = RUNTIME.block_on(instantiate());
+    c.bench_function("sync export", |b| {
+        b.iter(|| {
+            RUNTIME
+                .block_on(instance.componentize_js_bench_api().call_noop(&mut store))
+                .unwrap()
+        })
+    });
+}
+
+fn async_export(c: &mut Criterion) {
+    let (mut store, instance) = RUNTIME.block_on(instantiate());
+    c.bench_function("async export", |b| {
+        b.iter(|| {
+            RUNTIME
+                .block_on(store.run_concurrent(async |accessor| {
+                    instance
+                        .componentize_js_bench_api()
+                        .call_noop_async(accessor)
+                        .await
+                }))
+                .unwrap()
+                .unwrap()
+        })
+    });
+}
+
+fn strings(c: &mut Criterion) {
+    let (mut store, instance) = RUNTIME.block_on(instantiate());
+    let mut group = c.benchmark_group("echo string");
+    for &size in SIZES {
+        let value = "x".repeat(size);
+        group.throughput(Throughput::Bytes(size.try_into().unwrap()));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &value, |b, value| {
+            b.iter(|| {
+                RUNTIME
+                    .block_on(
+                        instance
+                            .componentize_js_bench_api()
+                            .call_echo_string(&mut store, value),
+                    )
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn lists(c: &mut Criterion) {
+    let (mut store, instance) = RUNTIME.block_on(instantiate());
+
+    let mut group = c.benchmark_group("echo list<u8>");
+    for &size in SIZES {
+        let value = vec![42_u8; size];
+        group.throughput(Throughput::Bytes(size.try_into().unwrap()));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &value, |b, value| {
+            b.iter(|| {
+                RUNTIME
+                    .block_on(
+                        instance
+                            .componentize_js_bench_api()
+                            .call_echo_list_u8(&mut store, value),
+                    )
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("echo list<u32>");
+    for &size in SIZES {
+        let value = vec![42_u32; size];
+        group.throughput(Throughput::Elements(size.try_into().unwrap()));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &value, |b, value| {
+            b.iter(|| {
+                RUNTIME
+                    .block_on(
+                        instance
+                            .componentize_js_bench_api()
+                            .call_echo_list_u32(&mut store, value),
+                    )
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+
+    // Lists of strings can't use the typed array fast path, so we use smaller
+    // sizes here to keep the run time reasonable.
+    let mut group = c.benchmark_group("echo list<string>");
+    for &size in &SIZES[..2] {
+        let value = vec!["hello".to_string(); size];
+        group.throughput(Throughput::Elements(size.try_into().unwrap()));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &value, |b, value| {
+            b.iter(|| {
+                RUNTIME
+                    .block_on(
+                        instance
+                            .componentize_js_bench_api()
+                            .call_echo_list_string(&mut store, value),
+                    )
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    instantiation,
+    sync_export,
+    async_export,
+    strings,
+    lists
+);
+criterion_main!(benches);
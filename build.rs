@@ -130,6 +130,10 @@ fn make_runtime(out_dir: &Path, wasi_sdk: &Path, name: &str) -> anyhow::Result<(
         cmd.arg("--release");
     }
 
+    if env::var_os("CARGO_FEATURE_INTL").is_some() {
+        cmd.arg("--features=intl");
+    }
+
     for (key, _) in env::vars_os() {
         if key
             .to_str()
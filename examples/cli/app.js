@@ -3,10 +3,10 @@ import * as witWorld from "wit-world"
 
 export const wasiCliRun030Rc20260106 = {
     run: async function() {
-        // As of this writing, the `componentize-js` runtime doesn't have
-        // `console.log`, so we must use the raw WASI bindings directly, which
-        // is... verbose.
-        
+        // `console.log("Hello, world!")` would do the same thing; here we use
+        // the raw WASI bindings directly to demonstrate streams, which is...
+        // verbose.
+
         const [tx, rx] = witWorld.u8Stream()
         using _tx = tx, _rx = rx
         const write = stdout.writeViaStream(rx)
@@ -1,117 +1,58 @@
-import { Request, Response, Fields } from "wasi:http/types@0.3.0-rc-2026-01-06"
-import * as client from "wasi:http/client@0.3.0-rc-2026-01-06"
-import * as stderr from "wasi:cli/stderr@0.3.0-rc-2026-01-06"
-import * as witWorld from "wit-world"
+import { fromWasiRequest, toWasiResponse } from "componentize-js:http"
 import { IncrementalSHA256 as Sha256 } from "./sha256.js"
 
-const decoder = new TextDecoder()
 const encoder = new TextEncoder()
 
 export const wasiHttpHandler030Rc20260106 = {
     handle: async function(request) {
-        const method = request.getMethod().tag
-        const path = request.getPathWithQuery()
-        const headers = request.getHeaders().copyAll()
-
-        if (method === "get" && path === "/hash-all") {
-            const urls = headers
-                  .filter(([k, _]) => k === "url")
-                  .map(([_, v]) => decoder.decode(v))
+        return toWasiResponse(await handle(fromWasiRequest(request)))
+    }
+}
 
-            const [tx, rx] = witWorld.u8Stream()
-            hashAll(urls, tx).catch((error) => log(error.toString()))
+async function handle(request) {
+    const path = request.url.replace(/^[^:]+:\/\/[^/]*/, "")
 
-            return Response.new(
-                Fields.fromList([["content-type", encoder.encode("text/plain")]]),
-                rx,
-                trailersFuture()
-            )[0]
-        } else if (method === "post" && path === "/echo") {
-            const [rx, trailers] = Request.consumeBody(request, unitFuture())
+    if (request.method === "GET" && path === "/hash-all") {
+        const urls = request.headers.get("url")?.split(", ") ?? []
 
-            return Response.new(
-                Fields.fromList(headers.filter(([k, _]) => k === "content-type")),
-                rx,
-                trailers
-            )[0]
-        } else {
-            const response = Response.new(new Fields(), undefined, trailersFuture())[0]
-            response.setStatusCode(400)
-            return response
+        return new Response(ReadableStream.from(hashAll(urls)), {
+            headers: { "content-type": "text/plain" }
+        })
+    } else if (request.method === "POST" && path === "/echo") {
+        const headers = new Headers()
+        const contentType = request.headers.get("content-type")
+        if (contentType !== null) {
+            headers.set("content-type", contentType)
         }
+
+        return new Response(request.body, { headers })
+    } else {
+        return new Response("bad request\n", { status: 400 })
     }
 }
 
-async function hashAll(urls, tx) {
-    using _tx = tx
+async function* hashAll(urls) {
     let promises = urls.map((url) => [url, sha256(url)])
     while (promises.length > 0) {
         const [url, hash] = await Promise.race(promises.map(([_, v]) => v))
         promises = promises.filter(([k, _]) => k !== url)
-        await tx.writeAll(encoder.encode(`${url}: ${hash}\n`))
+        yield encoder.encode(`${url}: ${hash}\n`)
     }
 }
 
 async function sha256(url) {
-    // TODO: use a proper URL parser
-    const schemeDelimiter = url.indexOf("://")
-    if (schemeDelimiter === -1) {
-        return [url, "unable to parse URL"]
-    }
-    const schemeString = url.substring(0, schemeDelimiter)
-    const remaining = url.substring(schemeDelimiter + 3)
-    const authorityDelimiter = remaining.indexOf("/")
-    const authority = authorityDelimiter === -1 ? remaining : remaining.substring(0, authorityDelimiter)
-    const path = authorityDelimiter === -1 ? "/" : remaining.substring(authorityDelimiter)
-
-    let scheme
-    switch (schemeString) {
-    case "http":
-        scheme = { tag: "http" }
-        break
-    case "http":
-        scheme = { tag: "https" }
-        break
-    default:
-        scheme = { tag: "other", val: schemeString }
-        break
-    }
-
-    using request = Request.new(new Fields(), undefined, trailersFuture(), undefined)[0]
-    request.setScheme(scheme)
-    request.setAuthority(authority)
-    request.setPathWithQuery(path)
-
-    const response = await client.send(request)
-    const status = response.getStatusCode()
-    if (status < 200 || status > 299) {
-        return [url, `unexpected status: ${status}`]
-    }
+    try {
+        const response = await fetch(url)
+        if (!response.ok) {
+            return [url, `unexpected status: ${response.status}`]
+        }
 
-    using rx = Response.consumeBody(response, unitFuture())[0]
-    const hasher = new Sha256()
-    while (!rx.writerDropped) {
-        const chunk = await rx.read(16 * 1024)
-        hasher.update(chunk)
+        const hasher = new Sha256()
+        for await (const chunk of response.body) {
+            hasher.update(chunk)
+        }
+        return [url, hasher.digest()]
+    } catch (error) {
+        return [url, error.message]
     }
-    return [url, hasher.digest()]
-}
-
-async function log(message) {
-    const [tx, rx] = witWorld.u8Stream()
-    using _tx = tx, _rx = rx
-    const write = stderr.writeViaStream(rx)
-    await tx.writeAll(encoder.encode(message))
-    tx[Symbol.dispose]()
-    await write
-}
-
-function trailersFuture() {
-    return witWorld.resultOptionWasiHttpTypes030Rc20260106FieldsWasiHttpTypes030Rc20260106ErrorCodeFuture(
-        () => { tag: 'ok' }
-    )[1]
-}
-
-function unitFuture() {
-    return witWorld.resultUnitWasiHttpTypes030Rc20260106ErrorCodeFuture(() => { tag: 'ok' })[1]
 }
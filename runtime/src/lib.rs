@@ -6,21 +6,29 @@
 
 use {
     anyhow::{Context as _, anyhow, bail},
+    bindings::{Coercion, ExportLayout, InitError, Mapping, Options, Prefs, SourceMap},
     heck::{ToLowerCamelCase as _, ToUpperCamelCase as _},
     mozjs::{
         context::JSContext,
         conversions::{Utf8Chars, jsstr_to_string},
+        error::throw_type_error,
         gc::Handle,
         glue::{
             CallObjectTracer, CallValueTracer, CreateRustJSPrincipals, DestroyRustJSPrincipals,
-            GetBigInt64ArrayLengthAndData, GetBigUint64ArrayLengthAndData, JSPrincipalsCallbacks,
-            PrintAndClearException, RUST_SYMBOL_TO_JSID,
+            GetBigInt64ArrayLengthAndData, GetBigUint64ArrayLengthAndData,
+            GetCrossCompartmentWrapper, GetOpaqueWrapper, JSPrincipalsCallbacks,
+            PrintAndClearException, RUST_SYMBOL_TO_JSID, WrapperNew,
         },
         jsapi::{
-            ExceptionStackBehavior, GCTraceKindToAscii, Handle as RawHandle, HandleValueArray,
-            Heap, JS_CallArgsFromVp, JS_GetFunctionObject, JS_HoldPrincipals, JSAutoRealm,
-            JSCLASS_GLOBAL_FLAGS, JSClass, JSClassOps, JSContext as RawJSContext, JSObject,
-            JSTracer, ModuleErrorBehaviour, OnNewGlobalHookOption, PromiseState, PropertyKey,
+            ExceptionStackBehavior, GCReason, GCTraceKindToAscii, GetContextRealm,
+            GetFunctionNativeReserved, GetObjectRealmOrNull, GetRealmPrincipals,
+            Handle as RawHandle, HandleValueArray, Heap, JS_CallArgsFromVp, JS_GC,
+            JS_GetFunctionObject, JS_HoldPrincipals, JS_SetGCCallback, JS_SetGCParameter,
+            JS_SetNativeStackQuota, JS_SetSecurityCallbacks, JS_SetWrapObjectCallbacks,
+            JSAutoRealm, JSCLASS_GLOBAL_FLAGS, JSClass, JSClassOps, JSContext as RawJSContext,
+            JSGCParamKey, JSGCStatus, JSObject, JSPrincipals, JSSecurityCallbacks, JSTracer,
+            JSWrapObjectCallbacks, ModuleErrorBehaviour, NewFunctionWithReserved,
+            OnNewGlobalHookOption, PromiseState, PropertyKey, SetFunctionNativeReserved,
             SetModuleResolveHook, SymbolCode, ToBigInt64, ToBigUint64, TraceKind, Value,
         },
         jsval::{
@@ -29,19 +37,21 @@ use {
         },
         rooted,
         rust::{
-            self, CompileOptionsWrapper, JSEngine, RealmOptions, Runtime, ToString,
+            self, CompileOptionsWrapper, JSEngine, RealmOptions, Runtime, SIMPLE_GLOBAL_CLASS,
+            ToBoolean, ToNumber, ToString,
             wrappers2::{
                 BigIntFromInt64, BigIntFromUint64, CompileModule1, Construct1, CurrentGlobalOrNull,
                 Evaluate2, GetArrayLength, GetModuleRequestSpecifier, GetPromiseState,
-                GetWellKnownSymbol, InitRealmStandardClasses, IsPromiseObject,
+                GetWellKnownSymbol, InitRealmStandardClasses, IsArrayObject1, IsPromiseObject,
                 JS_AddExtraGCRootsTracer, JS_CallFunctionValue, JS_ClearPendingException,
-                JS_DeleteProperty1, JS_GetElement, JS_GetPendingException, JS_GetProperty,
-                JS_InitDestroyPrincipalsCallback, JS_IsExceptionPending, JS_NewBigInt64Array,
-                JS_NewBigUint64Array, JS_NewFunction, JS_NewGlobalObject, JS_NewObject,
-                JS_NewObjectWithGivenProto, JS_NewStringCopyUTF8N, JS_SetElement,
-                JS_SetPendingException, JS_SetProperty, JS_SetPropertyById, ModuleEvaluate,
-                ModuleLink, NewArrayObject, NewArrayObject1, NewPromiseObject, ResolvePromise,
-                RunJobs, ThrowOnModuleEvaluationFailure,
+                JS_DefineDebuggerObject, JS_DeleteProperty1, JS_GetElement, JS_GetPendingException,
+                JS_GetProperty, JS_GetPropertyById, JS_InitDestroyPrincipalsCallback,
+                JS_IsExceptionPending, JS_NewBigInt64Array, JS_NewBigUint64Array, JS_NewFunction,
+                JS_NewGlobalObject, JS_NewObject, JS_NewObjectWithGivenProto,
+                JS_NewStringCopyUTF8N, JS_SetElement, JS_SetPendingException, JS_SetProperty,
+                JS_SetPropertyById, JS_StructuredClone, JS_WrapObject, JS_WrapValue,
+                ModuleEvaluate, ModuleLink, NewArrayObject, NewArrayObject1, NewPromiseObject,
+                ResetRealmMathRandomSeed, ResolvePromise, RunJobs, ThrowOnModuleEvaluationFailure,
             },
         },
         typedarray::{
@@ -50,17 +60,22 @@ use {
             Uint16Array, Uint32, Uint32Array,
         },
     },
+    sha1::Sha1,
+    sha2::{Digest as _, Sha256, Sha384, Sha512},
     std::{
         alloc::{self, Layout},
         collections::{HashMap, HashSet},
+        env,
         ffi::{CStr, CString, c_char, c_void},
-        fs,
+        fmt, fs,
         hash::{BuildHasherDefault, DefaultHasher, Hash, Hasher},
+        io::{self, Read as _, Write as _},
         marker::PhantomData,
         mem,
         ptr::{self, NonNull},
         slice,
         sync::{Arc, Mutex, OnceLock},
+        time::{Instant, SystemTime, UNIX_EPOCH},
     },
     wit_dylib_ffi::{
         self as wit, Call, ExportFunction, Interpreter, List, Type, Wit, WitOption, WitResult,
@@ -86,6 +101,11 @@ unsafe extern "C" {
     fn subtask_drop(task: u32);
 }
 #[link(wasm_import_module = "$root")]
+unsafe extern "C" {
+    #[link_name = "[async-lower][subtask-cancel]"]
+    fn subtask_cancel(task: u32) -> u32;
+}
+#[link(wasm_import_module = "$root")]
 unsafe extern "C" {
     #[link_name = "[waitable-set-new]"]
     fn waitable_set_new() -> u32;
@@ -101,6 +121,11 @@ unsafe extern "C" {
     fn waitable_set_drop(set: u32);
 }
 #[link(wasm_import_module = "$root")]
+unsafe extern "C" {
+    #[link_name = "[backpressure-set]"]
+    fn backpressure_set(enabled: u32);
+}
+#[link(wasm_import_module = "$root")]
 unsafe extern "C" {
     #[link_name = "[context-get-0]"]
     fn context_get() -> u32;
@@ -122,6 +147,8 @@ const EVENT_CANCELLED: u32 = 6;
 const STATUS_STARTING: u32 = 0;
 const STATUS_STARTED: u32 = 1;
 const STATUS_RETURNED: u32 = 2;
+const STATUS_START_CANCELLED: u32 = 3;
+const STATUS_RETURN_CANCELLED: u32 = 4;
 
 const CALLBACK_CODE_EXIT: u32 = 0;
 const CALLBACK_CODE_WAIT: u32 = 2;
@@ -321,6 +348,7 @@ impl<T> Table<T> {
 type MyCallTracedSet = HashSet<ArcHash<Mutex<MyCallTraced>>, BuildHasherDefault<DefaultHasher>>;
 type TransmitTracedSet = HashSet<ArcHash<Mutex<TransmitTraced>>, BuildHasherDefault<DefaultHasher>>;
 type ModuleMap = HashMap<String, Box<Heap<*mut JSObject>>, BuildHasherDefault<DefaultHasher>>;
+type MarshallerMap = HashMap<String, Box<Heap<*mut JSObject>>, BuildHasherDefault<DefaultHasher>>;
 
 static WIT: OnceLock<Wit> = OnceLock::new();
 static CONTEXT: OnceLock<SyncSend<NonNull<RawJSContext>>> = OnceLock::new();
@@ -334,8 +362,47 @@ static EXPORTED_RESOURCES: Mutex<SyncSend<Table<Box<Heap<*mut JSObject>>>>> =
 static MODULES: Mutex<SyncSend<ModuleMap>> =
     Mutex::new(SyncSend(HashMap::with_hasher(BuildHasherDefault::new())));
 static MAIN_MODULE: Mutex<Option<SyncSend<Box<Heap<*mut JSObject>>>>> = Mutex::new(None);
+static GC_START: Mutex<Option<Instant>> = Mutex::new(None);
+static COERCION: OnceLock<Coercion> = OnceLock::new();
+static EXPORT_LAYOUT: OnceLock<ExportLayout> = OnceLock::new();
+/// Generated code appended to the script, retained for hot reloading.
+static BINDINGS: OnceLock<String> = OnceLock::new();
+/// Source map for the script, if provided, used to rewrite stack traces.
+static SOURCE_MAP: OnceLock<SourceMap> = OnceLock::new();
+static PROFILER: Mutex<Option<SyncSend<Box<Heap<*mut JSObject>>>>> = Mutex::new(None);
+static MARSHALLERS: Mutex<SyncSend<MarshallerMap>> =
+    Mutex::new(SyncSend(HashMap::with_hasher(BuildHasherDefault::new())));
+/// Globals of the realms created via `componentModel.createSandbox`, indexed by
+/// sandbox ID.
+static SANDBOXES: Mutex<SyncSend<Vec<Box<Heap<*mut JSObject>>>>> = Mutex::new(SyncSend(Vec::new()));
+/// Imported functions made available to sandboxes, indexed by the first
+/// reserved slot of the sandbox function which forwards to each (see
+/// `call_sandbox_import`).
+static SANDBOX_IMPORTS: Mutex<SyncSend<Vec<Box<Heap<*mut JSObject>>>>> =
+    Mutex::new(SyncSend(Vec::new()));
+/// Principals of the main realm, which is trusted with transparent access to
+/// objects from sandboxes (see `wrap_object`).
+static MAIN_PRINCIPALS: OnceLock<SyncSend<*mut JSPrincipals>> = OnceLock::new();
+static SECURITY_CALLBACKS: JSSecurityCallbacks = JSSecurityCallbacks {
+    contentSecurityPolicyAllows: None,
+    codeForEvalGets: None,
+    subsumes: Some(subsumes),
+};
+static WRAP_OBJECT_CALLBACKS: JSWrapObjectCallbacks = JSWrapObjectCallbacks {
+    wrap: Some(wrap_object),
+    preWrap: None,
+};
+/// The monotonic and wall clock times (the latter in milliseconds since the
+/// Unix epoch) corresponding to `performance.timeOrigin`, determined when first
+/// needed and cleared before the snapshot is taken.
+static TIME_ORIGIN: Mutex<Option<(Instant, f64)>> = Mutex::new(None);
+/// Whether `Math.random` has been reseeded since instantiation (see
+/// `reseed_math_random`).
+static MATH_RANDOM_RESEEDED: Mutex<bool> = Mutex::new(false);
+
+fn init_runtime(options: &Options) -> anyhow::Result<()> {
+    let prefs = &options.prefs;
 
-fn init_runtime() -> anyhow::Result<()> {
     let engine = JSEngine::init()
         .map_err(|e| anyhow!("{e:?}"))
         .context("JSEngine::init failed")?;
@@ -355,20 +422,38 @@ fn init_runtime() -> anyhow::Result<()> {
         JS_AddExtraGCRootsTracer(cx, Some(trace_roots), ptr::null_mut());
     }
 
-    let realm_options = RealmOptions::default();
+    if options.gc_logging {
+        unsafe {
+            JS_SetGCCallback(cx.raw_cx(), Some(log_gc), ptr::null_mut());
+        }
+    }
 
-    let principals = unsafe {
-        let raw = CreateRustJSPrincipals(
-            &JSPrincipalsCallbacks {
-                write: None,
-                isSystemOrAddonPrincipal: None,
-            },
-            ptr::null_mut(),
-        );
-        JS_InitDestroyPrincipalsCallback(cx, Some(DestroyRustJSPrincipals));
-        JS_HoldPrincipals(raw);
-        raw
-    };
+    if let Some(bytes) = options.max_heap_bytes {
+        unsafe { JS_SetGCParameter(cx.raw_cx(), JSGCParamKey::JSGC_MAX_BYTES, bytes) }
+    }
+
+    if let Some(bytes) = options.stack_size {
+        // Leave some headroom below the quota for native frames which don't
+        // check it (e.g. those of the host bindings and `libc`).
+        let quota = usize::try_from(bytes).unwrap() / 8 * 7;
+        unsafe { JS_SetNativeStackQuota(cx.raw_cx(), quota, quota, quota) }
+    }
+
+    let mut realm_options = RealmOptions::default();
+    realm_options.creationOptions_.sharedMemoryAndAtomics_ = prefs.shared_memory;
+    realm_options.creationOptions_.toSource_ = prefs.to_source;
+    realm_options.creationOptions_.forceUTC_ = prefs.force_utc;
+
+    unsafe { JS_InitDestroyPrincipalsCallback(cx, Some(DestroyRustJSPrincipals)) }
+    unsafe {
+        JS_SetSecurityCallbacks(cx.raw_cx(), &SECURITY_CALLBACKS);
+        JS_SetWrapObjectCallbacks(cx.raw_cx(), &WRAP_OBJECT_CALLBACKS);
+    }
+    let principals = new_principals();
+    MAIN_PRINCIPALS
+        .set(SyncSend(principals))
+        .map_err(drop)
+        .unwrap();
 
     let global_class_ops = Box::into_raw(Box::new(JSClassOps {
         addProperty: None,
@@ -421,10 +506,234 @@ fn init_runtime() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Create a new, held set of principals.
+///
+/// Each realm gets its own principals (see `new_sandbox`) so that SpiderMonkey
+/// treats code in different realms as having different origins.
+fn new_principals() -> *mut JSPrincipals {
+    unsafe {
+        let raw = CreateRustJSPrincipals(
+            &JSPrincipalsCallbacks {
+                write: None,
+                isSystemOrAddonPrincipal: None,
+            },
+            ptr::null_mut(),
+        );
+        JS_HoldPrincipals(raw);
+        raw
+    }
+}
+
+/// Determine whether code with the `first` principals may see objects (e.g.
+/// stack frames) belonging to the `second`.
+///
+/// Each realm only subsumes itself, so the main realm and sandboxes are
+/// mutually non-subsuming.  The exception is the principal-less realm used for
+/// `Debugger` (see `new_debugger_global`), which subsumes everything.
+unsafe extern "C" fn subsumes(first: *mut JSPrincipals, second: *mut JSPrincipals) -> bool {
+    first.is_null() || first == second
+}
+
+/// Create a cross-compartment wrapper for `obj` in the current compartment.
+///
+/// A sandbox gets an opaque wrapper, which exposes no properties and can't be
+/// called, for any object from a realm it doesn't subsume, so it has no way to
+/// reach e.g. the main realm's `Function` constructor.  The main realm is
+/// trusted with transparent wrappers so that it can use the values returned by
+/// code evaluated in sandboxes.
+unsafe extern "C" fn wrap_object(
+    cx: *mut RawJSContext,
+    _: RawHandle<*mut JSObject>,
+    obj: RawHandle<*mut JSObject>,
+) -> *mut JSObject {
+    let target = unsafe { GetRealmPrincipals(GetContextRealm(cx)) };
+    let origin = unsafe { GetRealmPrincipals(GetObjectRealmOrNull(obj.get())) };
+    let handler =
+        if target == MAIN_PRINCIPALS.get().unwrap().0 || unsafe { subsumes(target, origin) } {
+            unsafe { GetCrossCompartmentWrapper() }
+        } else {
+            unsafe { GetOpaqueWrapper() }
+        };
+    unsafe { WrapperNew(cx, obj, handler, ptr::null(), false) }
+}
+
+/// Log the start and end of each garbage collection to stderr, along with the
+/// reason it was triggered and how long it took.
+///
+/// Note that this is called once per whole collection rather than for each
+/// slice of an incremental collection, so the duration includes any time spent
+/// running script between slices.
+unsafe extern "C" fn log_gc(
+    _: *mut RawJSContext,
+    status: JSGCStatus,
+    reason: GCReason,
+    _: *mut c_void,
+) {
+    let mut start = GC_START.try_lock().unwrap();
+    match status {
+        JSGCStatus::JSGC_BEGIN => {
+            eprintln!("componentize-js: GC begin (reason: {reason:?})");
+            *start = Some(Instant::now());
+        }
+        JSGCStatus::JSGC_END => {
+            let duration = start
+                .take()
+                .map(|start| format!("{:?}", start.elapsed()))
+                .unwrap_or_else(|| "unknown".into());
+            eprintln!("componentize-js: GC end (reason: {reason:?}, duration: {duration})");
+        }
+    }
+}
+
 fn context() -> JSContext {
     unsafe { JSContext::from_ptr(CONTEXT.get().unwrap().0) }
 }
 
+/// Reseed `Math.random` in the main realm and each sandbox realm from the host
+/// (via `wasi:random`) the first time this is called.
+///
+/// Any realm which called `Math.random` during initialization would otherwise
+/// carry its generator state into the snapshot, and every instance of the
+/// component would produce the same sequence.
+fn reseed_math_random(cx: &mut JSContext) {
+    let mut reseeded = MATH_RANDOM_RESEEDED.try_lock().unwrap();
+    if *reseeded {
+        return;
+    }
+    *reseeded = true;
+
+    unsafe { ResetRealmMathRandomSeed(cx) }
+    for sandbox in &SANDBOXES.try_lock().unwrap().0 {
+        let _realm = JSAutoRealm::new(unsafe { cx.raw_cx_no_gc() }, sandbox.get());
+        unsafe { ResetRealmMathRandomSeed(cx) }
+    }
+}
+
+/// Reader for the `init` parameters embedded in the component by the host when
+/// it skips the snapshot.
+///
+/// This must be kept in sync with the encoder in the host's `deferred` module.
+struct Payload<'a>(&'a [u8]);
+
+impl<'a> Payload<'a> {
+    fn bytes(&mut self, len: usize) -> &'a [u8] {
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        bytes
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes(1)[0]
+    }
+
+    fn bool(&mut self) -> bool {
+        self.u8() != 0
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.bytes(4).try_into().unwrap())
+    }
+
+    fn len(&mut self) -> usize {
+        usize::try_from(self.u32()).unwrap()
+    }
+
+    fn str(&mut self) -> &'a str {
+        let len = self.len();
+        std::str::from_utf8(self.bytes(len)).unwrap()
+    }
+
+    fn option<T>(&mut self, read: impl FnOnce(&mut Self) -> T) -> Option<T> {
+        self.bool().then(|| read(self))
+    }
+
+    fn list<T>(&mut self, mut read: impl FnMut(&mut Self) -> T) -> Vec<T> {
+        (0..self.len()).map(|_| read(self)).collect()
+    }
+}
+
+/// Initialize the runtime using the parameters embedded by the host, if it
+/// skipped the snapshot and we haven't done so already.
+///
+/// Errors thrown while evaluating the script are turned into traps, while
+/// warnings are printed to stderr.
+fn init_deferred() {
+    #[link(wasm_import_module = "env")]
+    unsafe extern "C" {
+        fn componentize_js_deferred_payload() -> *const u8;
+        fn componentize_js_deferred_payload_len() -> usize;
+    }
+
+    if CONTEXT.get().is_some() {
+        return;
+    }
+
+    let len = unsafe { componentize_js_deferred_payload_len() };
+    if len == 0 {
+        return;
+    }
+    let mut payload =
+        Payload(unsafe { slice::from_raw_parts(componentize_js_deferred_payload(), len) });
+
+    // Note that struct fields are evaluated in the order written, which must
+    // match the encoding order.
+    let options = Options {
+        prefs: Prefs {
+            weak_refs: payload.bool(),
+            shared_memory: payload.bool(),
+            iterator_helpers: payload.bool(),
+            to_source: payload.bool(),
+            force_utc: payload.bool(),
+            temporal: payload.bool(),
+        },
+        gc_logging: payload.bool(),
+        heap_census: payload.bool(),
+        profile: payload.bool(),
+        coercion: match payload.u8() {
+            0 => Coercion::Standard,
+            1 => Coercion::Strict,
+            _ => Coercion::Lenient,
+        },
+        freeze_intrinsics: payload.bool(),
+        max_heap_bytes: payload.option(Payload::u32),
+        stack_size: payload.option(Payload::u32),
+        export_layout: match payload.u8() {
+            0 => ExportLayout::Flat,
+            _ => ExportLayout::Qualified,
+        },
+        source_map: payload.option(|payload| SourceMap {
+            sources: payload.list(|payload| payload.str().into()),
+            mappings: payload.list(|payload| Mapping {
+                generated_line: payload.u32(),
+                generated_column: payload.u32(),
+                source: payload.u32(),
+                line: payload.u32(),
+                column: payload.u32(),
+            }),
+        }),
+    };
+    let globals = payload.str();
+    let modules = payload.list(|payload| (payload.str(), payload.str()));
+    let script = payload.str();
+    let bindings = payload.str();
+
+    match init(&options, globals, &modules, script, bindings) {
+        Ok(warnings) => {
+            for warning in warnings {
+                eprintln!("warning: {warning}");
+            }
+        }
+        Err(e) => match e.downcast::<ScriptError>() {
+            Ok(ScriptError {
+                message,
+                stack: Some(stack),
+            }) => panic!("{message}\n{stack}"),
+            Ok(ScriptError { message, .. }) => panic!("{message}"),
+            Err(e) => panic!("{e:?}"),
+        },
+    }
+}
+
 fn get(cx: &mut JSContext, object: Handle<'_, *mut JSObject>, name: &CStr) -> Value {
     rooted!(&in(cx) let mut value = UndefinedValue());
     if !unsafe {
@@ -453,6 +762,22 @@ fn set(
     }
 }
 
+fn get_with_symbol(
+    cx: &mut JSContext,
+    object: Handle<'_, *mut JSObject>,
+    code: SymbolCode,
+) -> Value {
+    rooted!(&in(cx) let symbol = unsafe { GetWellKnownSymbol(cx, code) });
+    rooted!(&in(cx) let mut key = PropertyKey::default());
+    unsafe { RUST_SYMBOL_TO_JSID(symbol.get(), key.handle_mut().into()) }
+    rooted!(&in(cx) let mut value = UndefinedValue());
+    if !unsafe { JS_GetPropertyById(cx, object, key.handle(), value.handle_mut()) } {
+        unsafe { PrintAndClearException(cx.raw_cx()) }
+        panic!("JS_GetPropertyById failed")
+    }
+    value.get()
+}
+
 fn set_with_symbol(
     cx: &mut JSContext,
     object: Handle<'_, *mut JSObject>,
@@ -468,6 +793,25 @@ fn set_with_symbol(
     }
 }
 
+/// Make the specified stream reader an async iterable and give it a
+/// `toReadableStream` method (see `_componentizeJsStreamIterator` and
+/// `_componentizeJsToReadableStream` in `globals.js`).
+fn set_stream_reader_helpers(cx: &mut JSContext, rx: Handle<'_, *mut JSObject>) {
+    rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+    rooted!(&in(cx) let iterator = get(cx, global_object.handle(), c"_componentizeJsStreamIterator"));
+    set_with_symbol(cx, rx, SymbolCode::asyncIterator, iterator.handle());
+    rooted!(&in(cx) let to_readable = get(cx, global_object.handle(), c"_componentizeJsToReadableStream"));
+    set(cx, rx, c"toReadableStream", to_readable.handle());
+}
+
+/// Give the specified future reader a `toPromise` method (see
+/// `_componentizeJsToPromise` in `globals.js`).
+fn set_future_reader_helpers(cx: &mut JSContext, rx: Handle<'_, *mut JSObject>) {
+    rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+    rooted!(&in(cx) let to_promise = get(cx, global_object.handle(), c"_componentizeJsToPromise"));
+    set(cx, rx, c"toPromise", to_promise.handle());
+}
+
 fn get_length(cx: &mut JSContext, object: Handle<'_, *mut JSObject>) -> u32 {
     let mut length = 0;
     if !unsafe { GetArrayLength(cx, object, &mut length) } {
@@ -477,6 +821,15 @@ fn get_length(cx: &mut JSContext, object: Handle<'_, *mut JSObject>) -> u32 {
     length
 }
 
+fn is_array(cx: &mut JSContext, object: Handle<'_, *mut JSObject>) -> bool {
+    let mut is_array = false;
+    if !unsafe { IsArrayObject1(cx, object, &mut is_array) } {
+        unsafe { PrintAndClearException(cx.raw_cx()) }
+        panic!("IsArrayObject failed")
+    }
+    is_array
+}
+
 fn get_element(cx: &mut JSContext, object: Handle<'_, *mut JSObject>, index: u32) -> Value {
     rooted!(&in(cx) let mut value = UndefinedValue());
     if !unsafe { JS_GetElement(cx, object, index, value.handle_mut()) } {
@@ -511,12 +864,21 @@ fn call(
     fun: Handle<'_, Value>,
     args: &HandleValueArray,
 ) -> Value {
+    try_call(cx, object, fun, args)
+        .unwrap_or_else(|| panic!("{}", describe_exception(cx, "JS_CallFunctionValue")))
+}
+
+/// Like `call`, but return `None` rather than panicking if the function throws,
+/// leaving the exception pending on `cx`.
+fn try_call(
+    cx: &mut JSContext,
+    object: Handle<'_, *mut JSObject>,
+    fun: Handle<'_, Value>,
+    args: &HandleValueArray,
+) -> Option<Value> {
     rooted!(&in(cx) let mut result = UndefinedValue());
-    if !unsafe { JS_CallFunctionValue(cx, object, fun, args, result.handle_mut()) } {
-        unsafe { PrintAndClearException(cx.raw_cx()) }
-        panic!("JS_CallFunctionValue failed")
-    }
-    result.get()
+    unsafe { JS_CallFunctionValue(cx, object, fun, args, result.handle_mut()) }
+        .then(|| result.get())
 }
 
 fn wrap(cx: &mut JSContext, fun: JsFunction) -> Value {
@@ -833,26 +1195,26 @@ fn handle_import_result(
                 }),
                 "err" => {
                     rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
-                    rooted!(&in(cx) let class = get(cx, global_object.handle(), c"ComponentError"));
+                    rooted!(&in(cx) let make_error = get(cx, global_object.handle(), c"_componentizeJsMakeError"));
+                    let key = match ty.err().and_then(error_class_key) {
+                        Some(key) => StringValue(unsafe {
+                            &*JS_NewStringCopyUTF8N(cx, &*Utf8Chars::from(key.as_str()))
+                        }),
+                        None => UndefinedValue(),
+                    };
+                    rooted!(&in(cx) let key = key);
                     let param = if ty.err().is_some() {
                         get(cx, wrapper.handle(), c"val")
                     } else {
                         UndefinedValue()
                     };
-                    rooted!(&in(cx) let mut result = ptr::null_mut::<JSObject>());
-                    rooted!(&in(cx) let params = vec![param]);
-                    if !unsafe {
-                        Construct1(
-                            cx,
-                            class.handle(),
-                            &HandleValueArray::from(&params),
-                            result.handle_mut(),
-                        )
-                    } {
-                        unsafe { PrintAndClearException(cx.raw_cx()) }
-                        panic!("Construct1 failed")
-                    }
-                    Err(ObjectValue(result.get()))
+                    rooted!(&in(cx) let params = vec![key.get(), param]);
+                    Err(call(
+                        cx,
+                        global_object.handle(),
+                        make_error.handle(),
+                        &HandleValueArray::from(&params),
+                    ))
                 }
                 _ => unreachable!(),
             }
@@ -862,6 +1224,114 @@ fn handle_import_result(
     }
 }
 
+/// Settle the promise returned by an async import call whose subtask has
+/// either returned or been cancelled, as indicated by `status`.
+fn finish_import_call(cx: &mut JSContext, subtask: u32, status: u32) {
+    unsafe {
+        waitable_join(subtask, 0);
+        subtask_drop(subtask);
+    }
+
+    let Pending::ImportCall {
+        index,
+        buffer,
+        ref mut call,
+    } = CURRENT_TASK_STATE
+        .try_lock()
+        .unwrap()
+        .as_mut()
+        .unwrap()
+        .0
+        .pending
+        .remove(&subtask)
+        .unwrap()
+    else {
+        unreachable!()
+    };
+
+    let func = WIT.get().unwrap().import_func(index);
+
+    let (result, ok) = if status == STATUS_RETURNED {
+        unsafe { func.lift_import_async_result(call, buffer) };
+        assert!(call.len() < 4);
+
+        match handle_import_result(cx, call, func.result()) {
+            Ok(value) => (value.unwrap_or_else(UndefinedValue), true),
+            Err(value) => (value, false),
+        }
+    } else {
+        rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+        rooted!(&in(cx) let abort_error = get(cx, global_object.handle(), c"_componentizeJsAbortError"));
+        rooted!(&in(cx) let params = Vec::<Value>::new());
+        let error = self::call(
+            cx,
+            global_object.handle(),
+            abort_error.handle(),
+            &HandleValueArray::from(&params),
+        );
+        (error, false)
+    };
+    rooted!(&in(cx) let params = vec![result]);
+
+    rooted!(&in(cx) let reject = call.pop());
+    rooted!(&in(cx) let resolve = call.pop());
+
+    let resolve_or_reject = if ok { resolve } else { reject };
+
+    if try_call(
+        cx,
+        Handle::<*mut JSObject>::null(),
+        resolve_or_reject.handle(),
+        &HandleValueArray::from(&params),
+    )
+    .is_none()
+    {
+        panic!(
+            "failed to settle promise for import `{}`: {}",
+            func.name(),
+            describe_exception(cx, "JS_CallFunctionValue")
+        );
+    }
+}
+
+/// Request cancellation of the specified async import call subtask, if it's
+/// still pending.  The promise returned by the call will be rejected with an
+/// `AbortError` unless the subtask returns before it can be cancelled.
+unsafe extern "C" fn cancel_import(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    assert_eq!(argc, 1);
+
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+    args.rval().set(UndefinedValue());
+
+    let subtask = args.index(0);
+    if !subtask.is_number() {
+        // The call completed synchronously, so there's nothing to cancel.
+        return true;
+    }
+    let subtask = to_number(subtask.get()) as u32;
+
+    let pending = CURRENT_TASK_STATE
+        .try_lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|state| state.0.pending.contains_key(&subtask));
+    if !pending {
+        // Either the subtask has already returned or been cancelled, or it
+        // belongs to another task.
+        return true;
+    }
+
+    let status = unsafe { subtask_cancel(subtask) };
+    if status != RETURN_CODE_BLOCKED {
+        finish_import_call(cx, subtask, status);
+    }
+    // Otherwise, we'll finish the call when we receive an `EVENT_SUBTASK` for
+    // the subtask.
+
+    true
+}
+
 unsafe extern "C" fn call_import(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
     assert!(argc >= 2);
 
@@ -877,6 +1347,15 @@ unsafe extern "C" fn call_import(cx: *mut RawJSContext, argc: u32, vp: *mut Valu
         .import_func(usize::try_from(index.to_int32()).unwrap());
     assert_eq!(func.params().len(), usize::try_from(length).unwrap());
 
+    for (index, ty) in func.params().enumerate() {
+        let value = get_element(cx, params.handle(), u32::try_from(index).unwrap());
+        let path = &mut format!("{}.param[{index}]", func.name());
+        if let Err(message) = check_value(cx, value, ty, path) {
+            unsafe { throw_type_error(cx.raw_cx(), &message) };
+            return false;
+        }
+    }
+
     let mut call = MyCall::new();
     for index in 0..length {
         call.push(get_element(cx, params.handle(), length - index - 1));
@@ -889,6 +1368,28 @@ unsafe extern "C" fn call_import(cx: *mut RawJSContext, argc: u32, vp: *mut Valu
         let reject = args.index(3);
 
         if let Some(pending) = unsafe { func.call_import_async(&mut call) } {
+            let mut state = CURRENT_TASK_STATE.try_lock().unwrap();
+            let Some(SyncSend(state)) = state.as_mut() else {
+                // Pending calls belong to the current task, so there must be
+                // one (e.g. not a sync export, nor a microtask run after one
+                // returns).  Calls which complete immediately are fine, though.
+                // Nothing would ever receive this call's result, so cancel it.
+                unsafe {
+                    if subtask_cancel(pending.subtask) != RETURN_CODE_BLOCKED {
+                        subtask_drop(pending.subtask);
+                    }
+                    throw_type_error(
+                        cx.raw_cx(),
+                        &format!(
+                            "async import `{}` may only be called from an async export \
+                             unless it completes immediately",
+                            func.name()
+                        ),
+                    )
+                };
+                return false;
+            };
+
             // Push the `resolve` and `reject` callbacks onto the call stack
             // where they can be traced; we'll pop them off again when we
             // receive an `EVENT_SUBTASK`/`STATUS_RETURNED` for the subtask.
@@ -898,8 +1399,6 @@ unsafe extern "C" fn call_import(cx: *mut RawJSContext, argc: u32, vp: *mut Valu
                 .stack
                 .extend([Heap::boxed(resolve.get()), Heap::boxed(reject.get())]);
 
-            let mut state = CURRENT_TASK_STATE.try_lock().unwrap();
-            let state = &mut state.as_mut().unwrap().0;
             if state.waitable_set.is_none() {
                 state.waitable_set = Some(unsafe { waitable_set_new() });
             }
@@ -912,18 +1411,31 @@ unsafe extern "C" fn call_import(cx: *mut RawJSContext, argc: u32, vp: *mut Valu
                     buffer: pending.buffer,
                 },
             );
+
+            // Return the subtask so the caller can cancel it if desired (see
+            // `_componentizeJsCallAsyncImport` in `globals.js`).
+            args.rval().set(UInt32Value(pending.subtask));
+            return true;
         } else {
             rooted!(&in(cx) let mut result = UndefinedValue());
             if func.result().is_some() {
                 result.set(call.pop());
             }
             rooted!(&in(cx) let params = vec![result.get()]);
-            self::call(
+            if try_call(
                 cx,
                 Handle::<*mut JSObject>::null(),
                 unsafe { Handle::from_raw(resolve) },
                 &HandleValueArray::from(&params),
-            );
+            )
+            .is_none()
+            {
+                panic!(
+                    "failed to settle promise for import `{}`: {}",
+                    func.name(),
+                    describe_exception(cx, "JS_CallFunctionValue")
+                );
+            }
         }
 
         args.rval().set(UndefinedValue())
@@ -950,9 +1462,62 @@ unsafe extern "C" fn call_import(cx: *mut RawJSContext, argc: u32, vp: *mut Valu
     true
 }
 
+/// If `ty` is a variant, enum, or record type, return the key under which
+/// `globals.js` stores the corresponding `ComponentError` subclass.
+fn error_class_key(ty: Type) -> Option<String> {
+    let (interface, name) = match ty {
+        Type::Variant(ty) => (ty.interface(), ty.name()),
+        Type::Enum(ty) => (ty.interface(), ty.name()),
+        Type::Record(ty) => (ty.interface(), ty.name()),
+        _ => return None,
+    };
+    Some(qualified_name(interface, name))
+}
+
+/// Qualify a type name with the name of the interface it belongs to, if any,
+/// e.g. `wasi:clocks/wall-clock@0.2.0.datetime`.
+fn qualified_name(interface: Option<&str>, name: &str) -> String {
+    match interface {
+        Some(interface) => format!("{interface}.{name}"),
+        None => name.to_string(),
+    }
+}
+
+fn is_component_error(cx: &mut JSContext, value: Value) -> bool {
+    if !value.is_object() {
+        return false;
+    }
+    rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+    rooted!(&in(cx) let is_component_error = get(cx, global_object.handle(), c"_componentizeJsIsComponentError"));
+    rooted!(&in(cx) let params = vec![value]);
+    call(
+        cx,
+        global_object.handle(),
+        is_component_error.handle(),
+        &HandleValueArray::from(&params),
+    )
+    .to_boolean()
+}
+
+/// Return the `message` of the specified exception if it's an `Error` (or
+/// anything else with a string `message` property), or else its string
+/// representation.
+fn exception_message(cx: &mut JSContext, exception: Value) -> Value {
+    rooted!(&in(cx) let exception = exception);
+    if exception.is_object() {
+        rooted!(&in(cx) let object = exception.to_object());
+        let message = get(cx, object.handle(), c"message");
+        if message.is_string() {
+            return message;
+        }
+    }
+    StringValue(unsafe { &*ToString(cx.raw_cx(), exception.handle()) })
+}
+
 fn handle_export_result(
     cx: &mut JSContext,
     call: &mut MyCall<'_>,
+    name: &str,
     ty: Option<Type>,
     value: Handle<'_, Value>,
     fulfilled: bool,
@@ -960,46 +1525,67 @@ fn handle_export_result(
     match ty {
         Some(Type::Result(ty)) => {
             rooted!(&in(cx) let mut value = value.get());
+            let mut unexpected = None;
             if !fulfilled {
-                if !value.is_object() {
-                    panic!("caught unexpected exception of non-object type");
-                }
-                rooted!(&in(cx) let object = value.to_object());
-                rooted!(&in(cx) let constructor = get(cx, object.handle(), c"constructor").to_object());
-                let name = &unsafe {
-                    jsstr_to_string(
-                        cx.raw_cx(),
-                        NonNull::new(get(cx, constructor.handle(), c"name").to_string()).unwrap(),
-                    )
-                };
-                if "ComponentError" != name {
-                    let string = unsafe {
-                        jsstr_to_string(
-                            cx.raw_cx(),
-                            NonNull::new(ToString(cx.raw_cx(), value.handle())).unwrap(),
-                        )
-                    };
-                    panic!(
-                        "caught unexpected exception; expected `ComponentError`, got `{string}`"
-                    );
-                }
-                if ty.err().is_some() {
-                    value.set(get(cx, object.handle(), c"payload"));
+                if is_component_error(cx, value.get()) {
+                    if ty.err().is_some() {
+                        rooted!(&in(cx) let object = value.to_object());
+                        value.set(get(cx, object.handle(), c"payload"));
+                    }
+                } else {
+                    unexpected = Some(ScriptError::new(cx, value.handle()));
+                    // Any other exception is lowered as its message if the
+                    // error type is `string`, or else as the error payload
+                    // as-is (if there is one), subject to the usual checks
+                    // below.
+                    if let Some(Type::String) = ty.err() {
+                        value.set(exception_message(cx, value.get()));
+                    }
                 }
             }
 
-            if (fulfilled && ty.ok().is_some()) || (!fulfilled && ty.err().is_some()) {
+            let (payload, case) = if fulfilled {
+                (ty.ok(), "ok")
+            } else {
+                (ty.err(), "err")
+            };
+            if let Some(payload) = payload {
+                let path = &mut format!("{name}.result.{case}");
+                if let Err(message) = check_value(cx, value.get(), payload, path) {
+                    if let Some(exception) = unexpected {
+                        panic!(
+                            "export `{name}` threw an exception which could not be lowered \
+                             as an error ({message}): {}",
+                            exception.describe()
+                        );
+                    }
+                    panic!("{message}");
+                }
                 call.push(value.get());
             }
             call.push_result(ty, !fulfilled)
         }
-        Some(_) => {
+        Some(ty) => {
             if !fulfilled {
-                panic!("caught unexpected exception for infallible exported function type");
+                panic!(
+                    "export `{name}` threw: {}",
+                    ScriptError::new(cx, value).describe()
+                );
+            }
+            let path = &mut format!("{name}.result");
+            if let Err(message) = check_value(cx, value.get(), ty, path) {
+                panic!("{message}");
             }
             call.push(value.get());
         }
-        None => {}
+        None => {
+            if !fulfilled {
+                panic!(
+                    "export `{name}` threw: {}",
+                    ScriptError::new(cx, value).describe()
+                );
+            }
+        }
     }
 }
 
@@ -1019,7 +1605,14 @@ unsafe extern "C" fn call_task_return(cx: *mut RawJSContext, argc: u32, vp: *mut
     let mut call = MyCall::new();
 
     rooted!(&in(cx) let mut value = value.get());
-    handle_export_result(cx, &mut call, func.result(), value.handle(), fulfilled);
+    handle_export_result(
+        cx,
+        &mut call,
+        func.name(),
+        func.result(),
+        value.handle(),
+        fulfilled,
+    );
 
     func.call_task_return(&mut call);
 
@@ -1066,6 +1659,25 @@ unsafe extern "C" fn log(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bo
     true
 }
 
+/// Write the string passed as the second argument, followed by a newline, to
+/// stdout (if the first argument is 1) or stderr (otherwise).
+///
+/// This backs `console` (see `globals.js`).  Write errors are ignored, as they
+/// are for `console` in other runtimes.
+unsafe extern "C" fn print(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    assert_eq!(argc, 2);
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let stream = args.index(0).to_int32();
+    let message = unsafe { jsstr_to_string(cx, NonNull::new(args.index(1).to_string()).unwrap()) };
+    _ = if stream == 1 {
+        writeln!(io::stdout(), "{message}")
+    } else {
+        writeln!(io::stderr(), "{message}")
+    };
+    args.rval().set(UndefinedValue());
+    true
+}
+
 unsafe extern "C" fn stream_write(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
     assert_eq!(argc, 1);
 
@@ -1344,6 +1956,8 @@ unsafe extern "C" fn make_stream(cx: *mut RawJSContext, argc: u32, vp: *mut Valu
     rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
     rooted!(&in(cx) let write_all = get(cx, global_object.handle(), c"_componentizeJsWriteAll"));
     set(cx, tx.handle(), c"writeAll", write_all.handle());
+    rooted!(&in(cx) let to_writable = get(cx, global_object.handle(), c"_componentizeJsToWritableStream"));
+    set(cx, tx.handle(), c"toWritableStream", to_writable.handle());
 
     rooted!(&in(cx) let rx = unsafe { JS_NewObject(cx, ptr::null_mut()) });
     set(cx, rx.handle(), TYPE_FIELD_NAME, unsafe {
@@ -1353,6 +1967,8 @@ unsafe extern "C" fn make_stream(cx: *mut RawJSContext, argc: u32, vp: *mut Valu
     rooted!(&in(cx) let mut read = wrap(cx, stream_read));
     set(cx, rx.handle(), c"read", read.handle());
 
+    set_stream_reader_helpers(cx, rx.handle());
+
     rooted!(&in(cx) let mut dispose = wrap(cx, stream_drop_readable));
     set_with_symbol(cx, rx.handle(), SymbolCode::dispose, dispose.handle());
 
@@ -1578,6 +2194,8 @@ unsafe extern "C" fn make_future(cx: *mut RawJSContext, argc: u32, vp: *mut Valu
     rooted!(&in(cx) let mut dispose = wrap(cx, future_drop_readable));
     set_with_symbol(cx, rx.handle(), SymbolCode::dispose, dispose.handle());
 
+    set_future_reader_helpers(cx, rx.handle());
+
     register_resource(cx, rx.handle(), rx_handle);
 
     rooted!(&in(cx) let elements = vec![ObjectValue(tx.get()), ObjectValue(rx.get())]);
@@ -1615,24 +2233,544 @@ unsafe extern "C" fn encode_utf8(cx: *mut RawJSContext, argc: u32, vp: *mut Valu
     true
 }
 
-unsafe extern "C" fn decode_utf8(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+/// Enable or disable backpressure, i.e. ask the host to hold off on (or resume)
+/// starting new calls to this component's exports.
+unsafe extern "C" fn set_backpressure(_cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
     assert_eq!(argc, 1);
 
     let args = unsafe { JS_CallArgsFromVp(argc, vp) };
-    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
-    let (data, length) = unsafe { Uint8::length_and_data(args.index(0).to_object()) };
+    let enabled = unsafe { ToBoolean(Handle::from_raw(args.index(0))) };
+    unsafe { backpressure_set(enabled.into()) }
 
-    let string = str::from_utf8(unsafe { slice::from_raw_parts(data, length) })
-        .unwrap()
-        .to_string();
+    args.rval().set(UndefinedValue());
+    true
+}
 
-    args.rval().set(StringValue(unsafe {
-        &*JS_NewStringCopyUTF8N(cx, &*Utf8Chars::from(string.as_str()))
+/// Read all of stdin, returning it as a `Uint8Array`.
+unsafe extern "C" fn read_stdin(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+
+    let mut bytes = Vec::new();
+    if let Err(e) = io::stdin().read_to_end(&mut bytes) {
+        unsafe { throw_type_error(cx.raw_cx(), &format!("unable to read stdin: {e}")) };
+        return false;
+    }
+
+    rooted!(&in(cx) let mut array = ptr::null_mut::<JSObject>());
+    unsafe {
+        Uint8Array::create(cx.raw_cx(), CreateWith::Slice(&bytes), array.handle_mut()).unwrap()
+    }
+    args.rval().set(ObjectValue(array.get()));
+
+    true
+}
+
+unsafe extern "C" fn decode_utf8(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    assert_eq!(argc, 1);
+
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+    let (data, length) = unsafe { Uint8::length_and_data(args.index(0).to_object()) };
+
+    // Like the Web's `TextDecoder` (in its default, non-fatal mode), replace
+    // invalid sequences rather than throwing.
+    let string = String::from_utf8_lossy(unsafe { slice::from_raw_parts(data, length) });
+
+    args.rval().set(StringValue(unsafe {
+        &*JS_NewStringCopyUTF8N(cx, &*Utf8Chars::from(&*string))
     }));
 
     true
 }
 
+fn time_origin() -> (Instant, f64) {
+    *TIME_ORIGIN.try_lock().unwrap().get_or_insert_with(|| {
+        (
+            Instant::now(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64()
+                * 1000.0,
+        )
+    })
+}
+
+/// Return the number of milliseconds elapsed since `performance.timeOrigin`.
+unsafe extern "C" fn performance_now(_cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let (origin, _) = time_origin();
+    args.rval()
+        .set(DoubleValue(origin.elapsed().as_secs_f64() * 1000.0));
+    true
+}
+
+/// Return `performance.timeOrigin`, i.e. the wall clock time (in milliseconds
+/// since the Unix epoch) from which `performance.now` is measured.
+unsafe extern "C" fn time_origin_millis(_cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let (_, origin) = time_origin();
+    args.rval().set(DoubleValue(origin));
+    true
+}
+
+/// Fill the `Uint8Array` passed as the only argument with random bytes.
+///
+/// This uses `getentropy`, which `wasi-libc` implements using `wasi:random`,
+/// so it always draws fresh entropy from the host, even for code running after
+/// the snapshot is taken.
+unsafe extern "C" fn fill_random(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    assert_eq!(argc, 1);
+
+    unsafe extern "C" {
+        fn getentropy(buffer: *mut u8, length: usize) -> i32;
+    }
+
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let (data, length) = unsafe { Uint8::length_and_data(args.index(0).to_object()) };
+
+    // `getentropy` accepts at most 256 bytes at a time.
+    for offset in (0..length).step_by(256) {
+        if unsafe { getentropy(data.add(offset), (length - offset).min(256)) } != 0 {
+            unsafe { throw_type_error(cx, "unable to get random bytes from the host") };
+            return false;
+        }
+    }
+
+    args.rval().set(UndefinedValue());
+    true
+}
+
+/// Hash the `Uint8Array` passed as the second argument using the algorithm
+/// named by the first (e.g. `SHA-256`), returning the digest as a `Uint8Array`.
+unsafe extern "C" fn digest(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    assert_eq!(argc, 2);
+
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+    let algorithm = unsafe {
+        jsstr_to_string(
+            cx.raw_cx(),
+            NonNull::new(args.index(0).to_string()).unwrap(),
+        )
+    };
+    let (data, length) = unsafe { Uint8::length_and_data(args.index(1).to_object()) };
+    let data = unsafe { slice::from_raw_parts(data, length) };
+
+    let digest = match algorithm.as_str() {
+        "SHA-1" => Sha1::digest(data).to_vec(),
+        "SHA-256" => Sha256::digest(data).to_vec(),
+        "SHA-384" => Sha384::digest(data).to_vec(),
+        "SHA-512" => Sha512::digest(data).to_vec(),
+        _ => {
+            unsafe {
+                throw_type_error(
+                    cx.raw_cx(),
+                    &format!("unsupported digest algorithm: {algorithm}"),
+                )
+            };
+            return false;
+        }
+    };
+
+    rooted!(&in(cx) let mut array = ptr::null_mut::<JSObject>());
+    unsafe {
+        Uint8Array::create(cx.raw_cx(), CreateWith::Slice(&digest), array.handle_mut()).unwrap()
+    }
+    args.rval().set(ObjectValue(array.get()));
+
+    true
+}
+
+/// Deep-copy the only argument using SpiderMonkey's implementation of the
+/// structured clone algorithm, throwing if it contains a value which can't be
+/// cloned (e.g. a function).
+unsafe extern "C" fn structured_clone(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    assert_eq!(argc, 1);
+
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+    rooted!(&in(cx) let mut clone = UndefinedValue());
+    if !unsafe {
+        JS_StructuredClone(
+            cx,
+            Handle::from_raw(args.index(0)),
+            clone.handle_mut(),
+            ptr::null(),
+            ptr::null_mut(),
+        )
+    } {
+        return false;
+    }
+    args.rval().set(clone.get());
+
+    true
+}
+
+/// Return the environment variables reported by the host (via
+/// `wasi:cli/environment#get-environment`) as a flat array of alternating names
+/// and values.
+unsafe extern "C" fn get_environment(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    assert_eq!(argc, 0);
+
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+    let vars = env::vars_os().collect::<Vec<_>>();
+    rooted!(&in(cx) let array = unsafe { NewArrayObject1(cx, vars.len() * 2) });
+    for (index, string) in vars
+        .iter()
+        .flat_map(|(name, value)| [name, value])
+        .enumerate()
+    {
+        rooted!(&in(cx) let value = StringValue(unsafe {
+            &*JS_NewStringCopyUTF8N(cx, &*Utf8Chars::from(&*string.to_string_lossy()))
+        }));
+        set_element(
+            cx,
+            array.handle(),
+            u32::try_from(index).unwrap(),
+            value.handle(),
+        );
+    }
+    args.rval().set(ObjectValue(array.get()));
+
+    true
+}
+
+/// Register the `toWit` and/or `fromWit` functions of the object passed as the
+/// second argument as marshalling hooks for the record or resource type named
+/// by the first argument (e.g. `wasi:clocks/wall-clock@0.2.0.datetime`, or
+/// just `point` for a type defined at the world level).
+unsafe extern "C" fn register_marshaller(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+
+    if argc < 2 || !args.index(0).is_string() || !args.index(1).is_object() {
+        unsafe {
+            throw_type_error(
+                cx.raw_cx(),
+                "registerMarshaller expects a type name and an object with \
+                 `toWit` and/or `fromWit` functions",
+            )
+        };
+        return false;
+    }
+
+    let name = unsafe {
+        jsstr_to_string(
+            cx.raw_cx(),
+            NonNull::new(args.index(0).to_string()).unwrap(),
+        )
+    };
+    MARSHALLERS
+        .try_lock()
+        .unwrap()
+        .0
+        .insert(name, Heap::boxed(args.index(1).to_object()));
+
+    args.rval().set(UndefinedValue());
+
+    true
+}
+
+/// Create a sandbox: a global in a new compartment with its own principals,
+/// whose `imports` global holds an object for each of the import modules named
+/// by the array passed as the only argument, and nothing else beyond the
+/// standard classes.  Returns the ID to pass to `evaluate_in_sandbox`.
+///
+/// Rather than the modules' namespaces, which would only be visible to the
+/// sandbox via opaque wrappers (see `wrap_object`), each object holds functions
+/// created in the sandbox which forward to the module's exported functions
+/// (see `call_sandbox_import`).  Those functions and objects are frozen and
+/// have no prototype, so they can't be used to reach the sandbox's own
+/// `Function` constructor either.
+unsafe extern "C" fn new_sandbox(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+
+    if argc < 1 || !args.index(0).is_object() {
+        unsafe {
+            throw_type_error(
+                cx.raw_cx(),
+                "createSandbox expects an array of import names",
+            )
+        };
+        return false;
+    }
+
+    rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+    rooted!(&in(cx) let function_names = get(cx, global_object.handle(), c"_componentizeJsFunctionNames"));
+    rooted!(&in(cx) let names = args.index(0).to_object());
+    let mut imports = Vec::new();
+    for index in 0..get_length(cx, names.handle()) {
+        rooted!(&in(cx) let name = get_element(cx, names.handle(), index));
+        let name = unsafe {
+            jsstr_to_string(
+                cx.raw_cx(),
+                NonNull::new(ToString(cx.raw_cx(), name.handle())).unwrap(),
+            )
+        };
+        let module = MODULES.try_lock().unwrap().0.get(&name).map(|v| v.get());
+        let Some(module) = module else {
+            unsafe { throw_type_error(cx.raw_cx(), &format!("no such import: `{name}`")) };
+            return false;
+        };
+        rooted!(&in(cx) let module = module);
+        rooted!(&in(cx) let namespace = unsafe {
+            mozjs::rust::wrappers2::GetModuleNamespace(cx, module.handle())
+        });
+        rooted!(&in(cx) let params = vec![ObjectValue(namespace.get())]);
+        rooted!(&in(cx) let keys = call(
+            cx,
+            global_object.handle(),
+            function_names.handle(),
+            &HandleValueArray::from(&params),
+        )
+        .to_object());
+        let mut functions = Vec::new();
+        for index in 0..get_length(cx, keys.handle()) {
+            rooted!(&in(cx) let key = get_element(cx, keys.handle(), index));
+            let key =
+                unsafe { jsstr_to_string(cx.raw_cx(), NonNull::new(key.to_string()).unwrap()) };
+            let key = CString::new(key).unwrap();
+            let function = get(cx, namespace.handle(), &key).to_object();
+            let mut sandbox_imports = SANDBOX_IMPORTS.try_lock().unwrap();
+            functions.push((key, sandbox_imports.0.len()));
+            sandbox_imports.0.push(Heap::boxed(function));
+        }
+        imports.push((CString::new(name).unwrap(), functions));
+    }
+
+    rooted!(&in(cx) let sandbox = unsafe {
+        JS_NewGlobalObject(
+            cx,
+            &SIMPLE_GLOBAL_CLASS,
+            new_principals(),
+            OnNewGlobalHookOption::DontFireOnNewGlobalHook,
+            &*RealmOptions::default(),
+        )
+    });
+    if sandbox.get().is_null() {
+        return false;
+    }
+
+    {
+        let _realm = JSAutoRealm::new(unsafe { cx.raw_cx_no_gc() }, sandbox.get());
+
+        if !unsafe { InitRealmStandardClasses(cx) } {
+            return false;
+        }
+
+        rooted!(&in(cx) let object = unsafe { JS_NewObject(cx, ptr::null_mut()) });
+        for (name, functions) in imports {
+            rooted!(&in(cx) let namespace = unsafe { JS_NewObject(cx, ptr::null_mut()) });
+            for (key, index) in functions {
+                let function = unsafe {
+                    NewFunctionWithReserved(
+                        cx.raw_cx(),
+                        Some(call_sandbox_import),
+                        0,
+                        0,
+                        key.as_ptr(),
+                    )
+                };
+                if function.is_null() {
+                    return false;
+                }
+                rooted!(&in(cx) let function = unsafe { JS_GetFunctionObject(function) });
+                unsafe {
+                    SetFunctionNativeReserved(
+                        function.get(),
+                        0,
+                        &UInt32Value(u32::try_from(index).unwrap()),
+                    )
+                };
+                rooted!(&in(cx) let function = ObjectValue(function.get()));
+                set(cx, namespace.handle(), &key, function.handle());
+            }
+            rooted!(&in(cx) let namespace = ObjectValue(namespace.get()));
+            set(cx, object.handle(), &name, namespace.handle());
+        }
+        rooted!(&in(cx) let object = ObjectValue(object.get()));
+        set(cx, sandbox.handle(), c"imports", object.handle());
+
+        if evaluate_script(
+            cx,
+            c"sandbox",
+            "for (const namespace of Object.values(imports)) {
+                for (const f of Object.values(namespace)) {
+                    Object.freeze(Object.setPrototypeOf(f, null))
+                }
+                Object.freeze(Object.setPrototypeOf(namespace, null))
+            }
+            Object.freeze(imports)",
+        )
+        .is_err()
+        {
+            return false;
+        }
+    }
+
+    let mut sandboxes = SANDBOXES.try_lock().unwrap();
+    args.rval()
+        .set(UInt32Value(u32::try_from(sandboxes.0.len()).unwrap()));
+    sandboxes.0.push(Heap::boxed(sandbox.get()));
+
+    true
+}
+
+/// Call the imported function which the callee (created by `new_sandbox`)
+/// forwards to.
+///
+/// The arguments are structured-cloned into the main realm, and the result (or
+/// exception) is structured-cloned back into the sandbox, so neither side ever
+/// sees an object belonging to the other.  Values which can't be cloned (e.g.
+/// functions, promises, and resources) cause a `DataCloneError` to be thrown.
+unsafe extern "C" fn call_sandbox_import(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+
+    let index = unsafe { (*GetFunctionNativeReserved(args.callee(), 0)).to_int32() as u32 };
+    rooted!(&in(cx) let function = SANDBOX_IMPORTS.try_lock().unwrap().0
+        [usize::try_from(index).unwrap()]
+    .get());
+    rooted!(&in(cx) let params = (0..argc).map(|index| *args.index(index)).collect::<Vec<_>>());
+    rooted!(&in(cx) let params = ObjectValue(unsafe {
+        NewArrayObject(cx, &HandleValueArray::from(&params))
+    }));
+
+    rooted!(&in(cx) let mut result = UndefinedValue());
+    let (ok, exception) = {
+        let _realm = JSAutoRealm::new(unsafe { cx.raw_cx_no_gc() }, function.get());
+
+        rooted!(&in(cx) let mut clone = UndefinedValue());
+        if !unsafe {
+            JS_StructuredClone(
+                cx,
+                params.handle(),
+                clone.handle_mut(),
+                ptr::null(),
+                ptr::null_mut(),
+            )
+        } {
+            return false;
+        }
+        rooted!(&in(cx) let clone = clone.to_object());
+        rooted!(&in(cx) let params = (0..argc)
+            .map(|index| get_element(cx, clone.handle(), index))
+            .collect::<Vec<_>>());
+
+        rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+        rooted!(&in(cx) let function = ObjectValue(function.get()));
+        let ok = match try_call(
+            cx,
+            global_object.handle(),
+            function.handle(),
+            &HandleValueArray::from(&params),
+        ) {
+            Some(value) => {
+                result.set(value);
+                true
+            }
+            None => false,
+        };
+        let exception = !ok
+            && unsafe { JS_IsExceptionPending(cx) }
+            && unsafe { JS_GetPendingException(cx, result.handle_mut()) };
+        if exception {
+            unsafe { JS_ClearPendingException(cx) };
+        }
+        (ok, exception)
+    };
+
+    if !(ok || exception) {
+        // The call was terminated rather than throwing.
+        return false;
+    }
+
+    rooted!(&in(cx) let mut clone = UndefinedValue());
+    if !unsafe {
+        JS_StructuredClone(
+            cx,
+            result.handle(),
+            clone.handle_mut(),
+            ptr::null(),
+            ptr::null_mut(),
+        )
+    } {
+        return false;
+    }
+
+    if ok {
+        args.rval().set(clone.get());
+    } else {
+        unsafe { JS_SetPendingException(cx, clone.handle(), ExceptionStackBehavior::DoNotCapture) };
+    }
+
+    ok
+}
+
+/// Evaluate the script passed as the second argument in the sandbox whose ID
+/// is passed as the first argument, returning the completion value (or
+/// rethrowing any exception) wrapped for use in the caller's realm.
+unsafe extern "C" fn evaluate_in_sandbox(cx: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    assert_eq!(argc, 2);
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    let cx = &mut unsafe { JSContext::from_ptr(NonNull::new(cx).unwrap()) };
+
+    let id = usize::try_from(args.index(0).to_int32()).unwrap();
+    let script = unsafe {
+        jsstr_to_string(
+            cx.raw_cx(),
+            NonNull::new(args.index(1).to_string()).unwrap(),
+        )
+    };
+    rooted!(&in(cx) let sandbox = SANDBOXES.try_lock().unwrap().0[id].get());
+
+    rooted!(&in(cx) let mut result = UndefinedValue());
+    let (ok, exception) = {
+        let _realm = JSAutoRealm::new(unsafe { cx.raw_cx_no_gc() }, sandbox.get());
+        let compile_options = CompileOptionsWrapper::new(cx, c"sandbox".into(), 1);
+        let ok = unsafe {
+            Evaluate2(
+                cx,
+                compile_options.ptr,
+                &mut rust::transform_str_to_source_text(&script),
+                result.handle_mut(),
+            )
+        };
+        let exception = !ok
+            && unsafe { JS_IsExceptionPending(cx) }
+            && unsafe { JS_GetPendingException(cx, result.handle_mut()) };
+        if exception {
+            unsafe { JS_ClearPendingException(cx) };
+        }
+        (ok, exception)
+    };
+
+    if !(ok || exception) {
+        // The script was terminated rather than throwing.
+        return false;
+    }
+
+    // Values created in the sandbox must be wrapped before the caller's realm
+    // can see them, including any exception thrown.
+    if !unsafe { JS_WrapValue(cx, result.handle_mut()) } {
+        return false;
+    }
+
+    if ok {
+        args.rval().set(result.get());
+    } else {
+        unsafe {
+            JS_SetPendingException(cx, result.handle(), ExceptionStackBehavior::DoNotCapture)
+        };
+    }
+
+    ok
+}
+
 unsafe extern "C" fn resolve_import(
     cx: *mut RawJSContext,
     _: RawHandle<Value>,
@@ -1684,6 +2822,121 @@ unsafe extern "C" fn resolve_import(
     module
 }
 
+/// An exception thrown by user code during initialization.
+#[derive(Debug)]
+struct ScriptError {
+    message: String,
+    stack: Option<String>,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl ScriptError {
+    fn new(cx: &mut JSContext, exception: Handle<'_, Value>) -> Self {
+        let message = unsafe {
+            jsstr_to_string(
+                cx.raw_cx(),
+                NonNull::new(ToString(cx.raw_cx(), exception)).unwrap(),
+            )
+        };
+
+        let stack = if exception.is_object() {
+            rooted!(&in(cx) let object = exception.to_object());
+            let stack = get(cx, object.handle(), c"stack");
+            stack.is_string().then(|| {
+                apply_source_map(&unsafe {
+                    jsstr_to_string(cx.raw_cx(), NonNull::new(stack.to_string()).unwrap())
+                })
+            })
+        } else {
+            None
+        };
+
+        Self { message, stack }
+    }
+
+    /// Return the message followed by the stack trace, if any.
+    fn describe(&self) -> String {
+        match &self.stack {
+            Some(stack) => format!("{}\n{stack}", self.message),
+            None => self.message.clone(),
+        }
+    }
+}
+
+/// Rewrite positions within the main module in `stack` (e.g. `foo@script:3:5`)
+/// to refer to the original sources according to `SOURCE_MAP`, if set.
+fn apply_source_map(stack: &str) -> String {
+    let Some(source_map) = SOURCE_MAP.get() else {
+        return stack.into();
+    };
+
+    stack
+        .split('\n')
+        .map(|frame| {
+            // Stack positions are one-based, while source maps are zero-based.
+            if let Some((function, position)) = frame.rsplit_once("@script:")
+                && let Some((line, column)) = position.split_once(':')
+                && let (Ok(line), Ok(column)) = (line.parse::<u32>(), column.parse::<u32>())
+                && let Some(line) = line.checked_sub(1)
+            {
+                let column = column.saturating_sub(1);
+                let index = source_map.mappings.partition_point(|mapping| {
+                    (mapping.generated_line, mapping.generated_column) <= (line, column)
+                });
+                if let Some(mapping) = index
+                    .checked_sub(1)
+                    .map(|index| &source_map.mappings[index])
+                    .filter(|mapping| mapping.generated_line == line)
+                {
+                    return format!(
+                        "{function}@{}:{}:{}",
+                        source_map.sources[usize::try_from(mapping.source).unwrap()],
+                        mapping.line + 1,
+                        mapping.column + 1
+                    );
+                }
+            }
+            frame.into()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Take the pending exception from `cx` and convert it to a `ScriptError`,
+/// falling back to `what` if there is no pending exception (e.g. if the script
+/// was terminated rather than throwing).
+fn take_exception(cx: &mut JSContext, what: &str) -> anyhow::Error {
+    if !unsafe { JS_IsExceptionPending(cx) } {
+        return anyhow!("{what} failed");
+    }
+
+    rooted!(&in(cx) let mut exception = UndefinedValue());
+    if !unsafe { JS_GetPendingException(cx, exception.handle_mut()) } {
+        unsafe { PrintAndClearException(cx.raw_cx()) }
+        return anyhow!("{what} failed");
+    }
+    unsafe { JS_ClearPendingException(cx) };
+
+    ScriptError::new(cx, exception.handle()).into()
+}
+
+/// Take the pending exception from `cx` (if any) and describe it, including its
+/// stack trace, for use in a panic message.
+fn describe_exception(cx: &mut JSContext, what: &str) -> String {
+    let error = take_exception(cx, what);
+    match error.downcast_ref::<ScriptError>() {
+        Some(error) => error.describe(),
+        None => error.to_string(),
+    }
+}
+
 fn evaluate(cx: &mut JSContext, name: &str, script: &str) -> anyhow::Result<*mut JSObject> {
     let compile_options = CompileOptionsWrapper::new(cx, CString::new(name)?, 1);
     let module = unsafe {
@@ -1694,20 +2947,17 @@ fn evaluate(cx: &mut JSContext, name: &str, script: &str) -> anyhow::Result<*mut
         )
     };
     if module.is_null() {
-        unsafe { PrintAndClearException(cx.raw_cx()) }
-        bail!("CompileModule1 failed")
+        return Err(take_exception(cx, "CompileModule1"));
     }
 
     rooted!(&in(cx) let module = module);
     if !unsafe { ModuleLink(cx, module.handle()) } {
-        unsafe { PrintAndClearException(cx.raw_cx()) }
-        bail!("ModuleLink failed")
+        return Err(take_exception(cx, "ModuleLink"));
     }
 
     rooted!(&in(cx) let mut result = UndefinedValue());
     if !unsafe { ModuleEvaluate(cx, module.handle(), result.handle_mut()) } {
-        unsafe { PrintAndClearException(cx.raw_cx()) }
-        bail!("ModuleEvaluate failed")
+        return Err(take_exception(cx, "ModuleEvaluate"));
     }
 
     unsafe { RunJobs(cx) }
@@ -1720,8 +2970,7 @@ fn evaluate(cx: &mut JSContext, name: &str, script: &str) -> anyhow::Result<*mut
             ModuleErrorBehaviour::ThrowModuleErrorsSync,
         )
     } {
-        unsafe { PrintAndClearException(cx.raw_cx()) }
-        bail!("ThrowOnModuleEvaluationFailure failed")
+        return Err(take_exception(cx, "ThrowOnModuleEvaluationFailure"));
     }
 
     assert!(unsafe { IsPromiseObject(result.handle()) });
@@ -1732,42 +2981,198 @@ fn evaluate(cx: &mut JSContext, name: &str, script: &str) -> anyhow::Result<*mut
     Ok(module.get())
 }
 
-fn init(globals: &str, modules: &[(&str, &str)], script: &str) -> anyhow::Result<()> {
-    init_runtime()?;
+fn evaluate_script(cx: &mut JSContext, name: &CStr, script: &str) -> anyhow::Result<()> {
+    let compile_options = CompileOptionsWrapper::new(cx, name.into(), 1);
+    rooted!(&in(cx) let mut result = UndefinedValue());
+    if !unsafe {
+        Evaluate2(
+            cx,
+            compile_options.ptr,
+            &mut rust::transform_str_to_source_text(script),
+            result.handle_mut(),
+        )
+    } {
+        return Err(take_exception(cx, "Evaluate2"));
+    }
+    Ok(())
+}
+
+/// Remove any standard globals and prototype methods disabled by `prefs`
+/// which SpiderMonkey doesn't give us a realm option for.
+fn apply_prefs(cx: &mut JSContext, prefs: &Prefs) -> anyhow::Result<()> {
+    rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+
+    if !prefs.weak_refs {
+        // Note that `globals.js` has already created the
+        // `FinalizationRegistry` we use for resource finalization by the time
+        // we get here, so removing the constructor won't affect that.
+        for name in [c"WeakRef", c"FinalizationRegistry"] {
+            delete(cx, global_object.handle(), name);
+        }
+    }
+
+    if !prefs.iterator_helpers {
+        evaluate_script(
+            cx,
+            c"prefs",
+            "{\n\
+             const proto = Object.getPrototypeOf(Object.getPrototypeOf([][Symbol.iterator]()))\n\
+             for (const name of ['map', 'filter', 'take', 'drop', 'flatMap', 'reduce',\n\
+                                 'toArray', 'forEach', 'some', 'every', 'find']) {\n\
+                 delete proto[name]\n\
+             }\n\
+             delete globalThis.Iterator\n\
+             }",
+        )?;
+    }
+
+    if prefs.temporal {
+        if get(cx, global_object.handle(), c"Temporal").is_undefined() {
+            bail!(
+                "the `temporal` preference requires a runtime built with SpiderMonkey's \
+                 `Intl` support (i.e. the `intl` feature)"
+            );
+        }
+    } else {
+        delete(cx, global_object.handle(), c"Temporal");
+    }
+
+    Ok(())
+}
+
+/// Deep-freeze the standard globals, everything reachable from them, and the
+/// "anonymous" intrinsics which aren't reachable from any global (e.g. the
+/// generator and iterator prototypes), in the style of SES's `lockdown`.
+///
+/// Standard globals are identified by being non-enumerable, which
+/// distinguishes them from properties created by `var` declarations in scripts
+/// (e.g. `globals.js`) or assignments to `globalThis`.  The global object
+/// itself is left extensible.
+fn freeze_intrinsics(cx: &mut JSContext) -> anyhow::Result<()> {
+    evaluate_script(
+        cx,
+        c"freeze",
+        "{\n\
+         // The global object itself (reachable via `globalThis.globalThis`)\n\
+         // is deliberately left unfrozen.\n\
+         const seen = new Set([globalThis])\n\
+         const harden = (value) => {\n\
+             if ((typeof value !== 'object' && typeof value !== 'function')\n\
+                 || value === null\n\
+                 || seen.has(value)) return\n\
+             seen.add(value)\n\
+             Object.freeze(value)\n\
+             harden(Object.getPrototypeOf(value))\n\
+             for (const key of Reflect.ownKeys(value)) {\n\
+                 const { value: v, get, set } = Object.getOwnPropertyDescriptor(value, key)\n\
+                 harden(v)\n\
+                 harden(get)\n\
+                 harden(set)\n\
+             }\n\
+         }\n\
+         for (const name of Reflect.ownKeys(globalThis)) {\n\
+             const descriptor = Object.getOwnPropertyDescriptor(globalThis, name)\n\
+             if (descriptor.enumerable) continue\n\
+             harden(descriptor.value)\n\
+             harden(descriptor.get)\n\
+             harden(descriptor.set)\n\
+             Object.defineProperty(globalThis, name, 'value' in descriptor\n\
+                 ? { writable: false, configurable: false }\n\
+                 : { configurable: false })\n\
+         }\n\
+         for (const value of [\n\
+             function* () {},\n\
+             async function () {},\n\
+             async function* () {},\n\
+             [][Symbol.iterator](),\n\
+             new Map()[Symbol.iterator](),\n\
+             new Set()[Symbol.iterator](),\n\
+             ''[Symbol.iterator](),\n\
+             /./[Symbol.matchAll](''),\n\
+             (function* () {})(),\n\
+             (async function* () {})(),\n\
+         ]) {\n\
+             harden(Object.getPrototypeOf(value))\n\
+         }\n\
+         }",
+    )
+}
+
+fn init(
+    options: &Options,
+    globals: &str,
+    modules: &[(&str, &str)],
+    script: &str,
+    bindings: &str,
+) -> anyhow::Result<Vec<String>> {
+    init_runtime(options)?;
+
+    COERCION.set(options.coercion).map_err(drop).unwrap();
+    EXPORT_LAYOUT
+        .set(options.export_layout)
+        .map_err(drop)
+        .unwrap();
+    if let Some(source_map) = &options.source_map {
+        SOURCE_MAP.set(source_map.clone()).map_err(drop).unwrap();
+    }
 
     let cx = &mut context();
 
     for (name, func) in [
         (c"_componentizeJsCallImport", call_import as JsFunction),
+        (c"_componentizeJsCancelImport", cancel_import as JsFunction),
         (
             c"_componentizeJsCallTaskReturn",
             call_task_return as JsFunction,
         ),
         (c"_componentizeJsDropResource", drop_resource as JsFunction),
         (c"_componentizeJsLog", log as JsFunction),
+        (c"_componentizeJsPrint", print as JsFunction),
+        (c"_componentizeJsFillRandom", fill_random as JsFunction),
+        (c"_componentizeJsDigest", digest as JsFunction),
+        (
+            c"_componentizeJsStructuredClone",
+            structured_clone as JsFunction,
+        ),
+        (
+            c"_componentizeJsGetEnvironment",
+            get_environment as JsFunction,
+        ),
+        (
+            c"_componentizeJsPerformanceNow",
+            performance_now as JsFunction,
+        ),
+        (
+            c"_componentizeJsTimeOrigin",
+            time_origin_millis as JsFunction,
+        ),
         (c"_componentizeJsMakeStream", make_stream as JsFunction),
         (c"_componentizeJsMakeFuture", make_future as JsFunction),
         (c"_componentizeJsEncodeUtf8", encode_utf8 as JsFunction),
         (c"_componentizeJsDecodeUtf8", decode_utf8 as JsFunction),
+        (c"_componentizeJsReadStdin", read_stdin as JsFunction),
+        (
+            c"_componentizeJsSetBackpressure",
+            set_backpressure as JsFunction,
+        ),
+        (
+            c"_componentizeJsRegisterMarshaller",
+            register_marshaller as JsFunction,
+        ),
+        (c"_componentizeJsNewSandbox", new_sandbox as JsFunction),
+        (
+            c"_componentizeJsEvaluateInSandbox",
+            evaluate_in_sandbox as JsFunction,
+        ),
     ] {
         rooted!(&in(cx) let mut func = wrap(cx, func));
         rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
         set(cx, global_object.handle(), name, func.handle());
     }
 
-    let compile_options = CompileOptionsWrapper::new(cx, c"script".into(), 1);
-    rooted!(&in(cx) let mut result = UndefinedValue());
-    if !unsafe {
-        Evaluate2(
-            cx,
-            compile_options.ptr,
-            &mut rust::transform_str_to_source_text(globals),
-            result.handle_mut(),
-        )
-    } {
-        unsafe { PrintAndClearException(cx.raw_cx()) }
-        bail!("Evaluate2 failed")
-    }
+    evaluate_script(cx, c"script", globals)?;
+
+    apply_prefs(cx, &options.prefs)?;
 
     for &(name, script) in modules {
         let module = evaluate(cx, name, script)?;
@@ -1778,12 +3183,303 @@ fn init(globals: &str, modules: &[(&str, &str)], script: &str) -> anyhow::Result
             .insert(name.into(), Heap::boxed(module));
     }
 
-    let module = evaluate(cx, "script", script)?;
+    BINDINGS.set(bindings.into()).map_err(drop).unwrap();
+
+    let warnings = evaluate_main(cx, script)?;
+
+    // From now on, imports are real rather than stubs (see
+    // `_componentizeJsWriteConsole` in `globals.js`).
+    {
+        rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+        rooted!(&in(cx) let initialized = BooleanValue(true));
+        set(
+            cx,
+            global_object.handle(),
+            c"_componentizeJsInitialized",
+            initialized.handle(),
+        );
+    }
+
+    if options.freeze_intrinsics {
+        freeze_intrinsics(cx)?;
+    }
+
+    if options.profile {
+        start_profiler(cx)?;
+    }
+
+    if options.heap_census {
+        print_heap_census(cx)?;
+    }
+
+    Ok(warnings)
+}
+
+/// Replace the main module with `script`, for hot reloading a component whose
+/// `init` export was retained.
+fn reload(script: &str) -> anyhow::Result<Vec<String>> {
+    evaluate_main(&mut context(), script)
+}
+
+/// Evaluate `script`, followed by the generated bindings, as the main module,
+/// returning any warnings about its exports.
+fn evaluate_main(cx: &mut JSContext, script: &str) -> anyhow::Result<Vec<String>> {
+    let bindings = BINDINGS.get().unwrap();
+    let module = evaluate(cx, "script", &format!("{script}\n{bindings}"))?;
     *MAIN_MODULE.try_lock().unwrap() = Some(SyncSend(Heap::boxed(module)));
 
+    // Make the module namespace available to the generated async export
+    // wrappers, which use it to find interfaces exported under names which
+    // aren't valid identifiers.
+    {
+        rooted!(&in(cx) let module = module);
+        rooted!(&in(cx) let namespace = ObjectValue(unsafe {
+            mozjs::rust::wrappers2::GetModuleNamespace(cx, module.handle())
+        }));
+        rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+        set(
+            cx,
+            global_object.handle(),
+            c"_componentizeJsExports",
+            namespace.handle(),
+        );
+    }
+
+    Ok(unknown_exports(cx))
+}
+
+/// Describe any exports of the main module which don't correspond to exports
+/// of the world, and thus will never be called.
+fn unknown_exports(cx: &mut JSContext) -> Vec<String> {
+    rooted!(&in(cx) let module = MAIN_MODULE.try_lock().unwrap().as_ref().unwrap().0.get());
+    rooted!(&in(cx) let namespace = unsafe {
+        mozjs::rust::wrappers2::GetModuleNamespace(cx, module.handle())
+    });
+    rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+    rooted!(&in(cx) let check = get(cx, global_object.handle(), c"_componentizeJsUnknownExports"));
+    rooted!(&in(cx) let params = vec![ObjectValue(namespace.get())]);
+    let warnings = call(
+        cx,
+        global_object.handle(),
+        check.handle(),
+        &HandleValueArray::from(&params),
+    );
+    let warnings =
+        unsafe { jsstr_to_string(cx.raw_cx(), NonNull::new(warnings.to_string()).unwrap()) };
+    warnings.lines().map(String::from).collect()
+}
+
+/// Create a new global containing the `Debugger` constructor and a `debuggee`
+/// property referring to the current global.
+///
+/// A `Debugger` must live in a different compartment than its debuggees, so we
+/// can't simply define it on the current global.
+fn new_debugger_global(cx: &mut JSContext) -> anyhow::Result<*mut JSObject> {
+    rooted!(&in(cx) let mut debuggee = unsafe { CurrentGlobalOrNull(cx) });
+
+    rooted!(&in(cx) let debugger_global = unsafe {
+        JS_NewGlobalObject(
+            cx,
+            &SIMPLE_GLOBAL_CLASS,
+            ptr::null_mut(),
+            OnNewGlobalHookOption::DontFireOnNewGlobalHook,
+            &*RealmOptions::default(),
+        )
+    });
+    if debugger_global.get().is_null() {
+        bail!("JS_NewGlobalObject failed")
+    }
+
+    let _realm = JSAutoRealm::new(unsafe { cx.raw_cx_no_gc() }, debugger_global.get());
+
+    if !unsafe { InitRealmStandardClasses(cx) } {
+        bail!("InitRealmStandardClasses failed")
+    }
+    if !unsafe { JS_DefineDebuggerObject(cx, debugger_global.handle()) } {
+        return Err(take_exception(cx, "JS_DefineDebuggerObject"));
+    }
+    if !unsafe { JS_WrapObject(cx, debuggee.handle_mut()) } {
+        return Err(take_exception(cx, "JS_WrapObject"));
+    }
+    rooted!(&in(cx) let debuggee = ObjectValue(debuggee.get()));
+    set(cx, debugger_global.handle(), c"debuggee", debuggee.handle());
+
+    Ok(debugger_global.get())
+}
+
+/// Print a census of the heap (object counts and sizes grouped by class, plus
+/// totals for strings, scripts, and everything else) to stderr using
+/// SpiderMonkey's `Debugger.Memory` API.
+///
+/// This is intended to be called just before the snapshot is taken so that
+/// users can see what their script has retained at module scope.
+fn print_heap_census(cx: &mut JSContext) -> anyhow::Result<()> {
+    // Collect garbage first so that only live cells are counted.
+    unsafe { JS_GC(cx.raw_cx(), GCReason::API) }
+
+    // The debugger global is only rooted within this block, so that the final
+    // collection below can reclaim it.
+    let report = {
+        rooted!(&in(cx) let debugger_global = new_debugger_global(cx)?);
+        let _realm = JSAutoRealm::new(unsafe { cx.raw_cx_no_gc() }, debugger_global.get());
+
+        evaluate_script(
+            cx,
+            c"census",
+            "{\n\
+             const dbg = new Debugger(debuggee)\n\
+             const count = { by: 'count', count: true, bytes: true }\n\
+             const census = dbg.memory.takeCensus({ breakdown: {\n\
+                 by: 'coarseType',\n\
+                 objects: { by: 'objectClass', then: count, other: count },\n\
+                 strings: count,\n\
+                 scripts: count,\n\
+                 other: count,\n\
+                 domNode: count,\n\
+             } })\n\
+             dbg.removeAllDebuggees()\n\
+             const rows = Object.entries(census.objects)\n\
+             for (const name of ['strings', 'scripts', 'other']) {\n\
+                 rows.push([`(${name})`, census[name]])\n\
+             }\n\
+             rows.sort(([, a], [, b]) => b.bytes - a.bytes)\n\
+             globalThis.report = rows\n\
+                 .map(([name, { count, bytes }]) =>\n\
+                     `${String(bytes).padStart(12)} ${String(count).padStart(10)}  ${name}`)\n\
+                 .join('\\n')\n\
+             }",
+        )?;
+
+        let report = get(cx, debugger_global.handle(), c"report");
+        unsafe { jsstr_to_string(cx.raw_cx(), NonNull::new(report.to_string()).unwrap()) }
+    };
+
+    eprintln!(
+        "componentize-js: heap census before snapshot:\n{:>12} {:>10}  class\n{report}",
+        "bytes", "count"
+    );
+
+    // Collect the debugger global and everything it allocated so none of it
+    // ends up in the snapshot.
+    unsafe { JS_GC(cx.raw_cx(), GCReason::API) }
+
     Ok(())
 }
 
+/// Attach a `Debugger` to the current global which records the time spent in
+/// each JS function while `profile_begin` is in effect.
+///
+/// Timings are accumulated per call stack and printed by `profile_end` in the
+/// "folded stacks" format understood by `flamegraph.pl`, `inferno`, etc.
+fn start_profiler(cx: &mut JSContext) -> anyhow::Result<()> {
+    rooted!(&in(cx) let debugger_global = new_debugger_global(cx)?);
+
+    let _realm = JSAutoRealm::new(unsafe { cx.raw_cx_no_gc() }, debugger_global.get());
+
+    rooted!(&in(cx) let now = wrap(cx, now as JsFunction));
+    set(
+        cx,
+        debugger_global.handle(),
+        c"_componentizeJsNow",
+        now.handle(),
+    );
+
+    evaluate_script(
+        cx,
+        c"profiler",
+        "{\n\
+         const dbg = new Debugger(debuggee)\n\
+         const stack = []\n\
+         const totals = new Map()\n\
+         let root = null\n\
+         dbg.onEnterFrame = (frame) => {\n\
+             if (root === null) return\n\
+             const name = frame.callee?.displayName ?? `(${frame.type})`\n\
+             const location = frame.script ? `${frame.script.url}:${frame.script.startLine}` : '?'\n\
+             const entry = { name: `${name} (${location})`.replaceAll(';', ','), \
+                             start: _componentizeJsNow(), children: 0 }\n\
+             stack.push(entry)\n\
+             frame.onPop = () => {\n\
+                 const index = stack.lastIndexOf(entry)\n\
+                 if (index < 0) return\n\
+                 const elapsed = _componentizeJsNow() - entry.start\n\
+                 const key = [root, ...stack.slice(0, index + 1).map((e) => e.name)].join(';')\n\
+                 totals.set(key, (totals.get(key) ?? 0) + elapsed - entry.children)\n\
+                 stack.length = index\n\
+                 if (index > 0) stack[index - 1].children += elapsed\n\
+             }\n\
+         }\n\
+         globalThis.begin = (name) => { root = name }\n\
+         globalThis.end = () => {\n\
+             root = null\n\
+             stack.length = 0\n\
+             const lines = Array.from(totals, ([key, micros]) => `${key} ${Math.round(micros)}`)\n\
+             totals.clear()\n\
+             return lines.join('\\n')\n\
+         }\n\
+         }",
+    )?;
+
+    *PROFILER.try_lock().unwrap() = Some(SyncSend(Heap::boxed(debugger_global.get())));
+
+    Ok(())
+}
+
+/// Start attributing JS execution time to `name` if profiling is enabled.
+fn profile_begin(cx: &mut JSContext, name: &str) {
+    let Some(profiler) = PROFILER.try_lock().unwrap().as_ref().map(|v| v.0.get()) else {
+        return;
+    };
+    rooted!(&in(cx) let profiler = profiler);
+    let _realm = JSAutoRealm::new(unsafe { cx.raw_cx_no_gc() }, profiler.get());
+
+    let name = name.replace(';', ",");
+    rooted!(&in(cx) let begin = get(cx, profiler.handle(), c"begin"));
+    rooted!(&in(cx) let params = vec![StringValue(unsafe {
+        &*JS_NewStringCopyUTF8N(cx, &*Utf8Chars::from(name.as_str()))
+    })]);
+    call(
+        cx,
+        profiler.handle(),
+        begin.handle(),
+        &HandleValueArray::from(&params),
+    );
+}
+
+/// Stop profiling (if enabled) and print the folded stacks recorded since the
+/// last call to `profile_begin` to stderr.
+fn profile_end(cx: &mut JSContext) {
+    let Some(profiler) = PROFILER.try_lock().unwrap().as_ref().map(|v| v.0.get()) else {
+        return;
+    };
+    rooted!(&in(cx) let profiler = profiler);
+    let _realm = JSAutoRealm::new(unsafe { cx.raw_cx_no_gc() }, profiler.get());
+
+    rooted!(&in(cx) let end = get(cx, profiler.handle(), c"end"));
+    rooted!(&in(cx) let params = Vec::<Value>::new());
+    let report = call(
+        cx,
+        profiler.handle(),
+        end.handle(),
+        &HandleValueArray::from(&params),
+    );
+    let report = unsafe { jsstr_to_string(cx.raw_cx(), NonNull::new(report.to_string()).unwrap()) };
+    if !report.is_empty() {
+        eprintln!("componentize-js: profile (folded stacks, microseconds):\n{report}");
+    }
+}
+
+unsafe extern "C" fn now(_: *mut RawJSContext, argc: u32, vp: *mut Value) -> bool {
+    assert_eq!(argc, 0);
+    let args = unsafe { JS_CallArgsFromVp(argc, vp) };
+    // Use the monotonic clock (relative to `performance.timeOrigin`) so that
+    // durations can't be skewed by wall clock adjustments.
+    let (origin, _) = time_origin();
+    let micros = origin.elapsed().as_secs_f64() * 1_000_000.0;
+    args.rval().set(DoubleValue(micros));
+    true
+}
+
 fn poll(cx: &mut JSContext) -> u32 {
     unsafe { RunJobs(cx) }
 
@@ -1805,16 +3501,43 @@ fn poll(cx: &mut JSContext) -> u32 {
 struct MyExports;
 
 impl bindings::Guest for MyExports {
-    fn init(globals: String, modules: Vec<(String, String)>, script: String) -> Result<(), String> {
-        let result = init(
-            &globals,
-            &modules
-                .iter()
-                .map(|(a, b)| (a.as_str(), b.as_str()))
-                .collect::<Vec<_>>(),
-            &script,
-        )
-        .map_err(|e| format!("{e:?}"));
+    fn init(
+        options: Options,
+        globals: String,
+        modules: Vec<(String, String)>,
+        script: String,
+        bindings: String,
+    ) -> Result<Vec<String>, InitError> {
+        // If the runtime has already been initialized, this is a hot reload
+        // (i.e. the host is calling the retained `init` export of a generated
+        // component), so we just replace the main module.
+        let reloading = CONTEXT.get().is_some();
+
+        let result = if reloading {
+            reload(&script)
+        } else {
+            init(
+                &options,
+                &globals,
+                &modules
+                    .iter()
+                    .map(|(a, b)| (a.as_str(), b.as_str()))
+                    .collect::<Vec<_>>(),
+                &script,
+                &bindings,
+            )
+        }
+        .map_err(|e| match e.downcast::<ScriptError>() {
+            Ok(ScriptError { message, stack }) => InitError { message, stack },
+            Err(e) => InitError {
+                message: format!("{e:?}"),
+                stack: None,
+            },
+        });
+
+        if reloading {
+            return result;
+        }
 
         // This tells the WASI Preview 1 component adapter to reset its state.
         // In particular, we want it to forget about any open handles and
@@ -1826,19 +3549,28 @@ impl bindings::Guest for MyExports {
             fn reset_adapter_state();
         }
 
-        // This tells wasi-libc to reset its preopen state, forcing
-        // re-initialization at runtime.
+        // This tells wasi-libc to reset its preopen and environment state,
+        // forcing re-initialization at runtime so that e.g. `process.env`
+        // reflects the host's environment rather than the one used during
+        // initialization.
         #[link(wasm_import_module = "env")]
         unsafe extern "C" {
             #[link_name = "__wasilibc_reset_preopens"]
             fn wasilibc_reset_preopens();
+            #[link_name = "__wasilibc_deinitialize_environ"]
+            fn wasilibc_deinitialize_environ();
         }
 
         unsafe {
             reset_adapter_state();
             wasilibc_reset_preopens();
+            wasilibc_deinitialize_environ();
         }
 
+        // Monotonic clock readings taken now are meaningless once the snapshot
+        // is instantiated elsewhere, so start `performance.now` from scratch.
+        *TIME_ORIGIN.try_lock().unwrap() = None;
+
         result
     }
 }
@@ -1851,7 +3583,11 @@ impl MyInterpreter {
             *CURRENT_TASK_STATE.try_lock().unwrap() = Some(SyncSend(TaskState::default()));
         }
 
+        init_deferred();
         let cx = &mut context();
+        reseed_math_random(cx);
+        profile_begin(cx, func.name());
+
         rooted!(&in(cx) let mut module = MAIN_MODULE.try_lock().unwrap().as_ref().unwrap().0.get());
         rooted!(&in(cx) let mut object = unsafe {
             mozjs::rust::wrappers2::GetModuleNamespace(cx, module.handle())
@@ -1866,7 +3602,7 @@ impl MyInterpreter {
                 get(
                     cx,
                     object.handle(),
-                    &CString::new(mangle_name(interface)).unwrap(),
+                    &CString::new(export_name(interface)).unwrap(),
                 )
                 .to_object(),
             );
@@ -1904,11 +3640,15 @@ impl MyInterpreter {
             );
             rooted!(&in(cx) let class = class);
             if class.is_undefined() {
-                panic!("export `{}` not defined", ty.to_upper_camel_case());
+                panic!(
+                    "export `{}` not defined for `{}`",
+                    ty.to_upper_camel_case(),
+                    func.name()
+                );
             }
             rooted!(&in(cx) let mut result = ptr::null_mut::<JSObject>());
             rooted!(&in(cx) let params = params(call, 0));
-            if !unsafe {
+            if unsafe {
                 Construct1(
                     cx,
                     class.handle(),
@@ -1916,10 +3656,11 @@ impl MyInterpreter {
                     result.handle_mut(),
                 )
             } {
-                unsafe { PrintAndClearException(cx.raw_cx()) }
-                panic!("Construct1 failed")
+                ObjectValue(result.get())
+            } else {
+                // Leave the exception pending; we'll handle it below.
+                UndefinedValue()
             }
-            ObjectValue(result.get())
         } else if let Some(name) = func.name().strip_prefix("[method]") {
             let (ty, name) = name.split_once('.').unwrap();
             let class = get(
@@ -1929,7 +3670,11 @@ impl MyInterpreter {
             );
             rooted!(&in(cx) let class = class);
             if class.is_undefined() {
-                panic!("export `{}` not defined", ty.to_upper_camel_case());
+                panic!(
+                    "export `{}` not defined for `{}`",
+                    ty.to_upper_camel_case(),
+                    func.name()
+                );
             }
             rooted!(&in(cx) let object = class.to_object());
             let function = get(
@@ -1939,16 +3684,21 @@ impl MyInterpreter {
             );
             rooted!(&in(cx) let function = function);
             if function.is_undefined() {
-                panic!("export `{}` not defined", mangle_name(func.name()));
+                panic!(
+                    "export `{}` not defined for `{}`",
+                    mangle_name(func.name()),
+                    func.name()
+                );
             }
             rooted!(&in(cx) let params = params(call, 1));
             rooted!(&in(cx) let this = call.pop().to_object());
-            self::call(
+            try_call(
                 cx,
                 this.handle(),
                 function.handle(),
                 &HandleValueArray::from(&params),
             )
+            .unwrap_or_else(UndefinedValue)
         } else if let Some(name) = func.name().strip_prefix("[static]") {
             let (ty, name) = name.split_once('.').unwrap();
             let class = get(
@@ -1958,7 +3708,11 @@ impl MyInterpreter {
             );
             rooted!(&in(cx) let class = class);
             if class.is_undefined() {
-                panic!("export `{}` not defined", ty.to_upper_camel_case());
+                panic!(
+                    "export `{}` not defined for `{}`",
+                    ty.to_upper_camel_case(),
+                    func.name()
+                );
             }
             rooted!(&in(cx) let object = class.to_object());
             let function = get(
@@ -1968,15 +3722,20 @@ impl MyInterpreter {
             );
             rooted!(&in(cx) let function = function);
             if function.is_undefined() {
-                panic!("export `{}` not defined", mangle_name(func.name()));
+                panic!(
+                    "export `{}` not defined for `{}`",
+                    mangle_name(func.name()),
+                    func.name()
+                );
             }
             rooted!(&in(cx) let params = params(call, 0));
-            self::call(
+            try_call(
                 cx,
                 object.handle(),
                 function.handle(),
                 &HandleValueArray::from(&params),
             )
+            .unwrap_or_else(UndefinedValue)
         } else {
             let function = get(
                 cx,
@@ -1985,18 +3744,33 @@ impl MyInterpreter {
             );
             rooted!(&in(cx) let function = function);
             if function.is_undefined() {
-                panic!("export `{}` not defined", mangle_name(func.name()));
+                panic!(
+                    "export `{}` not defined for `{}`",
+                    mangle_name(func.name()),
+                    func.name()
+                );
             }
             rooted!(&in(cx) let params = params(call, 0));
-            self::call(
+            try_call(
                 cx,
                 object.handle(),
                 function.handle(),
                 &HandleValueArray::from(&params),
             )
+            .unwrap_or_else(UndefinedValue)
         };
 
-        if async_ {
+        let code = if async_ {
+            // The wrappers generated by `codegen.rs` report rejections via
+            // `_componentizeJsCallTaskReturn`, but the export may still throw
+            // before returning a promise.
+            if unsafe { JS_IsExceptionPending(cx) } {
+                panic!(
+                    "export `{}` threw: {}",
+                    func.name(),
+                    describe_exception(cx, "export")
+                );
+            }
             poll(cx)
         } else {
             rooted!(&in(cx) let mut result = result);
@@ -2004,19 +3778,38 @@ impl MyInterpreter {
             if !fulfilled {
                 rooted!(&in(cx) let mut exception = UndefinedValue());
                 if !unsafe { JS_GetPendingException(cx, exception.handle_mut()) } {
-                    unsafe { PrintAndClearException(cx.raw_cx()) }
-                    panic!("JS_GetPendingException failed")
+                    panic!(
+                        "export `{}` failed: {}",
+                        func.name(),
+                        describe_exception(cx, "JS_GetPendingException")
+                    )
                 }
                 unsafe { JS_ClearPendingException(cx) };
                 result.set(exception.get())
             }
 
-            handle_export_result(cx, call, func.result(), result.handle(), fulfilled);
+            handle_export_result(
+                cx,
+                call,
+                func.name(),
+                func.result(),
+                result.handle(),
+                fulfilled,
+            );
+
+            // Perform a microtask checkpoint before returning (as async
+            // exports do in `poll`), while any borrowed handles are still
+            // valid.
+            unsafe { RunJobs(cx) }
 
             release_borrows(cx, &call.traced);
 
             0
-        }
+        };
+
+        profile_end(cx);
+
+        code
     }
 }
 
@@ -2052,53 +3845,10 @@ impl Interpreter for MyInterpreter {
             self::EVENT_SUBTASK => match event2 {
                 self::STATUS_STARTING => unreachable!(),
                 self::STATUS_STARTED => {}
-                self::STATUS_RETURNED => {
-                    unsafe {
-                        waitable_join(event1, 0);
-                        subtask_drop(event1);
-                    }
-
-                    let Pending::ImportCall {
-                        index,
-                        buffer,
-                        ref mut call,
-                    } = CURRENT_TASK_STATE
-                        .try_lock()
-                        .unwrap()
-                        .as_mut()
-                        .unwrap()
-                        .0
-                        .pending
-                        .remove(&event1)
-                        .unwrap()
-                    else {
-                        unreachable!()
-                    };
-
-                    let func = WIT.get().unwrap().import_func(index);
-
-                    unsafe { func.lift_import_async_result(call, buffer) };
-                    assert!(call.len() < 4);
-
-                    let result = handle_import_result(cx, call, func.result());
-
-                    rooted!(&in(cx) let reject = call.pop());
-                    rooted!(&in(cx) let resolve = call.pop());
-
-                    let (result, resolve_or_reject) = match result {
-                        Ok(value) => (value.unwrap_or_else(UndefinedValue), resolve),
-                        Err(value) => (value, reject),
-                    };
-                    rooted!(&in(cx) let params = vec![result]);
-
-                    self::call(
-                        cx,
-                        Handle::<*mut JSObject>::null(),
-                        resolve_or_reject.handle(),
-                        &HandleValueArray::from(&params),
-                    );
-                }
-                _ => todo!(),
+                self::STATUS_RETURNED
+                | self::STATUS_START_CANCELLED
+                | self::STATUS_RETURN_CANCELLED => finish_import_call(cx, event1, event2),
+                _ => unreachable!(),
             },
             self::EVENT_STREAM_WRITE => {
                 unsafe { waitable_join(event1, 0) };
@@ -2277,11 +4027,17 @@ impl Interpreter for MyInterpreter {
             _ => unreachable!(),
         }
 
-        poll(cx)
+        profile_begin(cx, "(async callback)");
+        let code = poll(cx);
+        profile_end(cx);
+
+        code
     }
 
     fn resource_dtor(ty: wit::Resource, handle: usize) {
+        init_deferred();
         let cx = &mut context();
+        reseed_math_random(cx);
         let wrapper = EXPORTED_RESOURCES.try_lock().unwrap().0.remove(handle);
 
         rooted!(&in(cx) let wrapper = wrapper.get());
@@ -2289,6 +4045,34 @@ impl Interpreter for MyInterpreter {
             ty.index(),
             usize::try_from(get(cx, wrapper.handle(), TYPE_FIELD_NAME).to_int32() as u32).unwrap()
         );
+
+        unregister_resource(cx, wrapper.handle());
+
+        // Give the instance a chance to release whatever it holds, preferring
+        // `[Symbol.dispose]` to a plain `dispose` method if it has both.
+        rooted!(&in(cx) let mut dispose = get_with_symbol(cx, wrapper.handle(), SymbolCode::dispose));
+        if dispose.is_undefined() {
+            dispose.set(get(cx, wrapper.handle(), c"dispose"));
+        }
+        if !dispose.is_undefined() {
+            rooted!(&in(cx) let params = Vec::<Value>::new());
+            rooted!(&in(cx) let mut result = UndefinedValue());
+            if !unsafe {
+                JS_CallFunctionValue(
+                    cx,
+                    wrapper.handle(),
+                    dispose.handle(),
+                    &HandleValueArray::from(&params),
+                    result.handle_mut(),
+                )
+            } {
+                // There's no caller to propagate the exception to, so just
+                // report it.
+                unsafe { PrintAndClearException(cx.raw_cx()) }
+            }
+
+            unsafe { RunJobs(cx) }
+        }
     }
 }
 
@@ -2397,44 +4181,44 @@ impl Call for MyCall<'_> {
     }
 
     fn pop_u8(&mut self) -> u8 {
-        (self.pop().to_int32() as u32).try_into().unwrap()
+        to_number(self.pop()) as u8
     }
 
     fn pop_u16(&mut self) -> u16 {
-        (self.pop().to_int32() as u32).try_into().unwrap()
+        to_number(self.pop()) as u16
     }
 
     fn pop_u32(&mut self) -> u32 {
-        self.pop().to_number() as u32
+        to_number(self.pop()) as u32
     }
 
     fn pop_u64(&mut self) -> u64 {
         let value = self.pop();
-        if value.is_int32() {
-            value.to_int32() as u64
-        } else {
+        if value.is_bigint() {
             unsafe { ToBigUint64(value.to_bigint()) }
+        } else {
+            to_number(value) as u64
         }
     }
 
     fn pop_s8(&mut self) -> i8 {
-        self.pop().to_int32().try_into().unwrap()
+        to_number(self.pop()) as i8
     }
 
     fn pop_s16(&mut self) -> i16 {
-        self.pop().to_int32().try_into().unwrap()
+        to_number(self.pop()) as i16
     }
 
     fn pop_s32(&mut self) -> i32 {
-        self.pop().to_int32()
+        to_number(self.pop()) as i32
     }
 
     fn pop_s64(&mut self) -> i64 {
         let value = self.pop();
-        if value.is_int32() {
-            value.to_int32() as i64
-        } else {
+        if value.is_bigint() {
             unsafe { ToBigInt64(value.to_bigint()) }
+        } else {
+            to_number(value) as i64
         }
     }
 
@@ -2453,14 +4237,14 @@ impl Call for MyCall<'_> {
     }
 
     fn pop_f32(&mut self) -> f32 {
-        let number64 = self.pop().to_number();
+        let number64 = to_number(self.pop());
         let number32 = number64 as f32;
         assert!((number64.is_nan() && number32.is_nan()) || number64 == number32 as f64);
         number32
     }
 
     fn pop_f64(&mut self) -> f64 {
-        self.pop().to_number()
+        to_number(self.pop())
     }
 
     fn pop_string(&mut self) -> &str {
@@ -2474,6 +4258,7 @@ impl Call for MyCall<'_> {
     fn pop_borrow(&mut self, ty: wit::Resource) -> u32 {
         let cx = &mut context();
         let value = self.pop();
+        let value = marshal(cx, ty.interface(), ty.name(), c"toWit", value);
         if let Some(new) = ty.new() {
             // exported resource type
             exported_resource_to_canon(cx, ty, new, value)
@@ -2486,6 +4271,7 @@ impl Call for MyCall<'_> {
     fn pop_own(&mut self, ty: wit::Resource) -> u32 {
         let cx = &mut context();
         let value = self.pop();
+        let value = marshal(cx, ty.interface(), ty.name(), c"toWit", value);
         if let Some(new) = ty.new() {
             // exported resource type
             exported_resource_to_canon(cx, ty, new, value)
@@ -2507,10 +4293,31 @@ impl Call for MyCall<'_> {
             .unwrap()
     }
 
-    fn pop_flags(&mut self, _ty: wit::Flags) -> u32 {
+    fn pop_flags(&mut self, ty: wit::Flags) -> u32 {
         let cx = &mut context();
-        rooted!(&in(cx) let wrapper = self.pop().to_object());
-        get(cx, wrapper.handle(), c"val").to_int32() as u32
+        rooted!(&in(cx) let object = self.pop().to_object());
+        let mut bits = 0;
+        if is_array(cx, object.handle()) {
+            for index in 0..get_length(cx, object.handle()) {
+                let name = get_element(cx, object.handle(), index);
+                let name = unsafe {
+                    jsstr_to_string(cx.raw_cx(), NonNull::new(name.to_string()).unwrap())
+                };
+                bits |= 1 << flag_index(ty, &name).unwrap();
+            }
+        } else {
+            for (index, name) in ty.names().enumerate() {
+                rooted!(&in(cx) let value = get(
+                    cx,
+                    object.handle(),
+                    &CString::new(mangle_name(name)).unwrap(),
+                ));
+                if unsafe { ToBoolean(value.handle()) } {
+                    bits |= 1 << index;
+                }
+            }
+        }
+        bits
     }
 
     fn pop_future(&mut self, _ty: wit::Future) -> u32 {
@@ -2526,7 +4333,7 @@ impl Call for MyCall<'_> {
     }
 
     fn pop_option(&mut self, ty: WitOption) -> u32 {
-        if self.last().is_undefined() {
+        if self.last().is_null_or_undefined() {
             self.pop();
             0
         } else {
@@ -2590,7 +4397,9 @@ impl Call for MyCall<'_> {
 
     fn pop_record(&mut self, ty: wit::Record) {
         let cx = &mut context();
-        rooted!(&in(cx) let record = self.pop().to_object());
+        let value = self.pop();
+        let value = marshal(cx, ty.interface(), ty.name(), c"toWit", value);
+        rooted!(&in(cx) let record = value.to_object());
         for (name, _) in ty.fields() {
             self.push(get(
                 cx,
@@ -2614,15 +4423,23 @@ impl Call for MyCall<'_> {
     }
 
     unsafe fn maybe_pop_list(&mut self, ty: List) -> Option<(*const u8, usize)> {
-        if use_typed_array(ty.ty()) {
-            let (data, length, layout) =
-                unsafe { typed_array_data(ty.ty(), self.pop().to_object()) };
-            let dst = unsafe { alloc::alloc(layout) };
-            unsafe { ptr::copy_nonoverlapping(data, dst, layout.size()) };
-            Some((dst as _, length))
-        } else {
-            None
+        if !use_typed_array(ty.ty()) {
+            return None;
+        }
+
+        let cx = &mut context();
+        rooted!(&in(cx) let array = to_typed_array(cx, ty.ty(), self.last()));
+        if !array.is_object() {
+            // This isn't a typed array, `ArrayBuffer`, or `DataView` (e.g. it's
+            // a plain array), so let the caller convert it element by element.
+            return None;
         }
+        self.pop();
+
+        let (data, length, layout) = unsafe { typed_array_data(ty.ty(), array.to_object()) };
+        let dst = unsafe { alloc::alloc(layout) };
+        unsafe { ptr::copy_nonoverlapping(data, dst, layout.size()) };
+        Some((dst as _, length))
     }
 
     fn pop_list(&mut self, _ty: List) -> usize {
@@ -2682,7 +4499,9 @@ impl Call for MyCall<'_> {
     }
 
     fn push_u64(&mut self, val: u64) {
-        if let Ok(val) = u32::try_from(val) {
+        if let Ok(val) = u32::try_from(val)
+            && coercion() != Coercion::Strict
+        {
             self.push(UInt32Value(val));
         } else {
             let cx = &mut context();
@@ -2691,7 +4510,9 @@ impl Call for MyCall<'_> {
     }
 
     fn push_s64(&mut self, val: i64) {
-        if let Ok(val) = i32::try_from(val) {
+        if let Ok(val) = i32::try_from(val)
+            && coercion() != Coercion::Strict
+        {
             self.push(Int32Value(val));
         } else {
             let cx = &mut context();
@@ -2736,7 +4557,13 @@ impl Call for MyCall<'_> {
                 field.handle(),
             );
         }
-        self.push(ObjectValue(value.get()));
+        self.push(marshal(
+            cx,
+            ty.interface(),
+            ty.name(),
+            c"fromWit",
+            ObjectValue(value.get()),
+        ));
     }
 
     fn push_tuple(&mut self, ty: wit::Tuple) {
@@ -2757,12 +4584,19 @@ impl Call for MyCall<'_> {
         }));
     }
 
-    fn push_flags(&mut self, _ty: wit::Flags, bits: u32) {
+    fn push_flags(&mut self, ty: wit::Flags, bits: u32) {
         let cx = &mut context();
-        rooted!(&in(cx) let wrapper = unsafe { JS_NewObject(cx, ptr::null_mut()) });
-        rooted!(&in(cx) let value = UInt32Value(bits));
-        set(cx, wrapper.handle(), c"val", value.handle());
-        self.push(ObjectValue(wrapper.get()));
+        rooted!(&in(cx) let object = unsafe { JS_NewObject(cx, ptr::null_mut()) });
+        for (index, name) in ty.names().enumerate() {
+            rooted!(&in(cx) let value = BooleanValue(bits & (1 << index) != 0));
+            set(
+                cx,
+                object.handle(),
+                &CString::new(mangle_name(name)).unwrap(),
+                value.handle(),
+            );
+        }
+        self.push(ObjectValue(object.get()));
     }
 
     fn push_enum(&mut self, ty: wit::Enum, discriminant: u32) {
@@ -2776,7 +4610,7 @@ impl Call for MyCall<'_> {
     }
 
     fn push_borrow(&mut self, ty: wit::Resource, handle: u32) {
-        self.push(ObjectValue(if ty.rep().is_some() {
+        let value = ObjectValue(if ty.rep().is_some() {
             // exported resource type
             EXPORTED_RESOURCES
                 .try_lock()
@@ -2796,12 +4630,14 @@ impl Call for MyCall<'_> {
             });
 
             value
-        }));
+        });
+        let value = marshal(&mut context(), ty.interface(), ty.name(), c"fromWit", value);
+        self.push(value);
     }
 
     fn push_own(&mut self, ty: wit::Resource, handle: u32) {
         let cx = &mut context();
-        self.push(ObjectValue(if let Some(rep) = ty.rep() {
+        let value = ObjectValue(if let Some(rep) = ty.rep() {
             // exported resource type
             let rep = unsafe { rep(handle) };
             rooted!(&in(cx) let value = EXPORTED_RESOURCES.try_lock().unwrap().0.remove(rep).get());
@@ -2812,7 +4648,9 @@ impl Call for MyCall<'_> {
         } else {
             // imported resource type
             imported_resource_from_canon(cx, ty.index(), handle, None, Some(ty))
-        }));
+        });
+        let value = marshal(cx, ty.interface(), ty.name(), c"fromWit", value);
+        self.push(value);
     }
 
     fn push_future(&mut self, ty: wit::Future, handle: u32) {
@@ -2824,6 +4662,8 @@ impl Call for MyCall<'_> {
         rooted!(&in(cx) let mut func = wrap(cx, future_read));
         set(cx, rx.handle(), c"read", func.handle());
 
+        set_future_reader_helpers(cx, rx.handle());
+
         self.push(ObjectValue(rx.get()))
     }
 
@@ -2836,6 +4676,8 @@ impl Call for MyCall<'_> {
         rooted!(&in(cx) let mut func = wrap(cx, stream_read));
         set(cx, rx.handle(), c"read", func.handle());
 
+        set_stream_reader_helpers(cx, rx.handle());
+
         self.push(ObjectValue(rx.get()))
     }
 
@@ -2863,6 +4705,10 @@ impl Call for MyCall<'_> {
             if let Type::Option(_) = ty.ty() {
                 let cx = &mut context();
                 rooted!(&in(cx) let wrapper = unsafe { JS_NewObject(cx, ptr::null_mut()) });
+                rooted!(&in(cx) let tag = StringValue(unsafe {
+                    &*JS_NewStringCopyUTF8N(cx, &*Utf8Chars::from("some"))
+                }));
+                set(cx, wrapper.handle(), c"tag", tag.handle());
                 rooted!(&in(cx) let value = self.pop());
                 set(cx, wrapper.handle(), c"val", value.handle());
                 self.push(ObjectValue(wrapper.get()));
@@ -3095,6 +4941,46 @@ unsafe extern "C" fn trace_roots(tracer: *mut JSTracer, _: *mut c_void) {
         }
     }
 
+    for value in MARSHALLERS.try_lock().unwrap().0.values() {
+        unsafe {
+            CallObjectTracer(
+                tracer,
+                value.ptr.get() as *mut _,
+                GCTraceKindToAscii(TraceKind::Object),
+            )
+        }
+    }
+
+    for value in SANDBOXES.try_lock().unwrap().0.iter() {
+        unsafe {
+            CallObjectTracer(
+                tracer,
+                value.ptr.get() as *mut _,
+                GCTraceKindToAscii(TraceKind::Object),
+            )
+        }
+    }
+
+    for value in SANDBOX_IMPORTS.try_lock().unwrap().0.iter() {
+        unsafe {
+            CallObjectTracer(
+                tracer,
+                value.ptr.get() as *mut _,
+                GCTraceKindToAscii(TraceKind::Object),
+            )
+        }
+    }
+
+    if let Some(value) = PROFILER.try_lock().unwrap().as_ref() {
+        unsafe {
+            CallObjectTracer(
+                tracer,
+                value.0.ptr.get() as *mut _,
+                GCTraceKindToAscii(TraceKind::Object),
+            )
+        }
+    }
+
     if let Some(value) = MAIN_MODULE.try_lock().unwrap().as_ref() {
         unsafe {
             CallObjectTracer(
@@ -3106,11 +4992,118 @@ unsafe extern "C" fn trace_roots(tracer: *mut JSTracer, _: *mut c_void) {
     }
 }
 
+fn coercion() -> Coercion {
+    *COERCION.get().unwrap()
+}
+
+/// Pass `value` through the `hook` (i.e. `toWit` or `fromWit`) registered for
+/// the specified record or resource type, if any.
+///
+/// Note that `toWit` hooks are called both when type-checking and when
+/// converting a value, so they should be free of side effects.
+fn marshal(
+    cx: &mut JSContext,
+    interface: Option<&str>,
+    name: &str,
+    hook: &CStr,
+    value: Value,
+) -> Value {
+    rooted!(&in(cx) let value = value);
+
+    let marshaller = {
+        let marshallers = MARSHALLERS.try_lock().unwrap();
+        if marshallers.0.is_empty() {
+            return value.get();
+        }
+        match marshallers.0.get(&qualified_name(interface, name)) {
+            Some(marshaller) => marshaller.get(),
+            None => return value.get(),
+        }
+    };
+
+    rooted!(&in(cx) let marshaller = marshaller);
+    rooted!(&in(cx) let hook = get(cx, marshaller.handle(), hook));
+    if hook.is_undefined() {
+        return value.get();
+    }
+
+    rooted!(&in(cx) let params = vec![value.get()]);
+    call(
+        cx,
+        marshaller.handle(),
+        hook.handle(),
+        &HandleValueArray::from(&params),
+    )
+}
+
+/// Convert `value` to a number, applying JS `ToNumber` if it isn't already
+/// one.
+///
+/// Unless `Coercion::Lenient` is in effect, `check_value` will have already
+/// rejected non-number values, so the `ToNumber` path is normally only taken in
+/// lenient mode.
+fn to_number(value: Value) -> f64 {
+    if value.is_number() {
+        value.to_number()
+    } else {
+        let cx = &mut context();
+        rooted!(&in(cx) let value = value);
+        unsafe { ToNumber(cx.raw_cx(), value.handle()) }.unwrap_or(f64::NAN)
+    }
+}
+
+/// Return the name under which the script is expected to export the object
+/// implementing the specified interface.
+fn export_name(interface: &str) -> String {
+    match EXPORT_LAYOUT.get().unwrap() {
+        ExportLayout::Flat => mangle_name(interface),
+        ExportLayout::Qualified => interface.into(),
+    }
+}
+
 fn mangle_name(name: &str) -> String {
     name.replace(['@', ':', '/', '-', '[', ']', '.'], "_")
         .to_lower_camel_case()
 }
 
+/// Find the bit position of the flag named `name` (either its WIT name or the
+/// camelCased property name used in JS objects) in `ty`.
+fn flag_index(ty: wit::Flags, name: &str) -> Option<usize> {
+    ty.names().position(|v| v == name || mangle_name(v) == name)
+}
+
+/// Convert `value` to a typed array suitable for lowering as a list of WIT type
+/// `ty` (see `_componentizeJsToTypedArray` in `globals.js`).
+fn to_typed_array(cx: &mut JSContext, ty: Type, value: Value) -> Value {
+    let class = match ty {
+        Type::U8 => "Uint8Array",
+        Type::S8 => "Int8Array",
+        Type::U16 => "Uint16Array",
+        Type::S16 => "Int16Array",
+        Type::U32 => "Uint32Array",
+        Type::S32 => "Int32Array",
+        Type::U64 => "BigUint64Array",
+        Type::S64 => "BigInt64Array",
+        Type::F32 => "Float32Array",
+        Type::F64 => "Float64Array",
+        _ => unreachable!(),
+    };
+
+    rooted!(&in(cx) let value = value);
+    rooted!(&in(cx) let class = StringValue(unsafe {
+        &*JS_NewStringCopyUTF8N(cx, &*Utf8Chars::from(class))
+    }));
+    rooted!(&in(cx) let global_object = unsafe { CurrentGlobalOrNull(cx) });
+    rooted!(&in(cx) let convert = get(cx, global_object.handle(), c"_componentizeJsToTypedArray"));
+    rooted!(&in(cx) let params = vec![value.get(), class.get()]);
+    call(
+        cx,
+        global_object.handle(),
+        convert.handle(),
+        &HandleValueArray::from(&params),
+    )
+}
+
 fn use_typed_array(ty: Type) -> bool {
     matches!(
         ty,
@@ -3127,6 +5120,337 @@ fn use_typed_array(ty: Type) -> bool {
     )
 }
 
+/// Check that `value` has the shape expected for a value of WIT type `ty`,
+/// returning a description of the first mismatch found, if any.
+///
+/// `path` describes where `value` came from (e.g. `foo.param[1].headers[3]`)
+/// and is extended as we descend into nested values.
+fn check_value(
+    cx: &mut JSContext,
+    value: Value,
+    ty: Type,
+    path: &mut String,
+) -> Result<(), String> {
+    let value = match ty {
+        Type::Record(ty) => marshal(cx, ty.interface(), ty.name(), c"toWit", value),
+        Type::Own(ty) | Type::Borrow(ty) => marshal(cx, ty.interface(), ty.name(), c"toWit", value),
+        _ => value,
+    };
+    rooted!(&in(cx) let value = value);
+
+    let coercion = coercion();
+    let int_in = |min: f64, max: f64| {
+        if coercion == Coercion::Lenient {
+            !(value.is_symbol() || value.is_bigint())
+        } else {
+            value.is_number()
+                && value.to_number().fract() == 0.0
+                && (min..=max).contains(&value.to_number())
+        }
+    };
+    // Largest integer which can be represented exactly by a JS number:
+    const MAX_SAFE_INTEGER: f64 = ((1_u64 << 53) - 1) as f64;
+
+    let valid = match ty {
+        Type::Bool => value.is_boolean(),
+        Type::U8 => int_in(u8::MIN.into(), u8::MAX.into()),
+        Type::S8 => int_in(i8::MIN.into(), i8::MAX.into()),
+        Type::U16 => int_in(u16::MIN.into(), u16::MAX.into()),
+        Type::S16 => int_in(i16::MIN.into(), i16::MAX.into()),
+        Type::U32 => int_in(u32::MIN.into(), u32::MAX.into()),
+        Type::S32 => int_in(i32::MIN.into(), i32::MAX.into()),
+        Type::U64 | Type::S64 => match coercion {
+            Coercion::Strict => value.is_bigint(),
+            Coercion::Lenient => !value.is_symbol(),
+            Coercion::Standard => {
+                let min = if let Type::U64 = ty {
+                    0.0
+                } else {
+                    -MAX_SAFE_INTEGER
+                };
+                value.is_bigint() || int_in(min, MAX_SAFE_INTEGER)
+            }
+        },
+        Type::F32 | Type::F64 => {
+            value.is_number() || (coercion == Coercion::Lenient && !value.is_symbol())
+        }
+        Type::String => value.is_string(),
+        Type::Char => {
+            value.is_string() && {
+                let string = unsafe {
+                    jsstr_to_string(cx.raw_cx(), NonNull::new(value.to_string()).unwrap())
+                };
+                string.chars().count() == 1
+            }
+        }
+        Type::Enum(ty) => {
+            value.is_string() && {
+                let string = unsafe {
+                    jsstr_to_string(cx.raw_cx(), NonNull::new(value.to_string()).unwrap())
+                };
+                ty.names().any(|name| name == string)
+            }
+        }
+        Type::Option(ty) => {
+            if value.is_null_or_undefined() {
+                return Ok(());
+            }
+            let payload = if let Type::Option(_) = ty.ty() {
+                // The outer `some` must be explicitly wrapped so it can be
+                // distinguished from the inner `none`.
+                let wrapper = match tagged(cx, value.get()) {
+                    Some((tag, wrapper)) if tag == "some" => wrapper,
+                    _ => return mismatch(cx, value.get(), Type::Option(ty), path),
+                };
+                rooted!(&in(cx) let wrapper = wrapper);
+                get(cx, wrapper.handle(), c"val")
+            } else {
+                value.get()
+            };
+            return check_nested(cx, payload, ty.ty(), path, ".some");
+        }
+        Type::Result(ty) => {
+            let Some((tag, object)) = tagged(cx, value.get()) else {
+                return mismatch(cx, value.get(), Type::Result(ty), path);
+            };
+            rooted!(&in(cx) let object = object);
+            let payload = match tag.as_str() {
+                "ok" => ty.ok(),
+                "err" => ty.err(),
+                _ => return mismatch(cx, value.get(), Type::Result(ty), path),
+            };
+            if let Some(payload) = payload {
+                let payload_value = get(cx, object.handle(), c"val");
+                return check_nested(cx, payload_value, payload, path, &format!(".{tag}"));
+            }
+            true
+        }
+        Type::Variant(ty) => {
+            let Some((tag, object)) = tagged(cx, value.get()) else {
+                return mismatch(cx, value.get(), Type::Variant(ty), path);
+            };
+            rooted!(&in(cx) let object = object);
+            let Some((_, payload)) = ty.cases().find(|(name, _)| *name == tag.as_str()) else {
+                return mismatch(cx, value.get(), Type::Variant(ty), path);
+            };
+            if let Some(payload) = payload {
+                let payload_value = get(cx, object.handle(), c"val");
+                return check_nested(cx, payload_value, payload, path, &format!(".{tag}"));
+            }
+            true
+        }
+        Type::Record(ty) => {
+            if !value.is_object() {
+                return mismatch(cx, value.get(), Type::Record(ty), path);
+            }
+            rooted!(&in(cx) let object = value.to_object());
+            for (name, field) in ty.fields() {
+                let field_value = get(
+                    cx,
+                    object.handle(),
+                    &CString::new(mangle_name(name)).unwrap(),
+                );
+                check_nested(cx, field_value, field, path, &format!(".{name}"))?;
+            }
+            true
+        }
+        Type::Tuple(ty) => {
+            if !value.is_object() {
+                return mismatch(cx, value.get(), Type::Tuple(ty), path);
+            }
+            rooted!(&in(cx) let object = value.to_object());
+            for (index, element) in ty.types().enumerate() {
+                let element_value = get_element(cx, object.handle(), u32::try_from(index).unwrap());
+                check_nested(cx, element_value, element, path, &format!("[{index}]"))?;
+            }
+            true
+        }
+        Type::List(ty) => {
+            if !value.is_object() {
+                return mismatch(cx, value.get(), Type::List(ty), path);
+            }
+            let array = if use_typed_array(ty.ty()) {
+                to_typed_array(cx, ty.ty(), value.get())
+            } else {
+                UndefinedValue()
+            };
+            if array.is_null() {
+                return mismatch(cx, value.get(), Type::List(ty), path);
+            }
+            if array.is_undefined() {
+                rooted!(&in(cx) let object = value.to_object());
+                for index in 0..get_length(cx, object.handle()) {
+                    let element_value = get_element(cx, object.handle(), index);
+                    check_nested(cx, element_value, ty.ty(), path, &format!("[{index}]"))?;
+                }
+            }
+            true
+        }
+        Type::Flags(ty) => {
+            if !value.is_object() {
+                return mismatch(cx, value.get(), Type::Flags(ty), path);
+            }
+            rooted!(&in(cx) let object = value.to_object());
+            if is_array(cx, object.handle()) {
+                for index in 0..get_length(cx, object.handle()) {
+                    rooted!(&in(cx) let name = get_element(cx, object.handle(), index));
+                    let known = name.is_string() && {
+                        let name = unsafe {
+                            jsstr_to_string(cx.raw_cx(), NonNull::new(name.to_string()).unwrap())
+                        };
+                        flag_index(ty, &name).is_some()
+                    };
+                    if !known {
+                        path.push_str(&format!("[{index}]"));
+                        return mismatch(cx, name.get(), Type::Flags(ty), path);
+                    }
+                }
+            }
+            true
+        }
+        Type::Own(resource) | Type::Borrow(resource) if resource.new().is_none() => {
+            // imported resource type
+            if !value.is_object() {
+                return mismatch(cx, value.get(), ty, path);
+            }
+            rooted!(&in(cx) let object = value.to_object());
+            if !get(cx, object.handle(), HANDLE_FIELD_NAME).is_int32() {
+                return Err(format!(
+                    "invalid resource at `{path}`: its handle has been dropped or moved, \
+                     or it is a borrow which escaped its call"
+                ));
+            }
+            true
+        }
+        Type::Own(_) | Type::Borrow(_) | Type::Future(_) | Type::Stream(_) => value.is_object(),
+        _ => true,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        mismatch(cx, value.get(), ty, path)
+    }
+}
+
+/// Check a value nested within another value, appending `suffix` to `path`
+/// for the duration of the check.
+fn check_nested(
+    cx: &mut JSContext,
+    value: Value,
+    ty: Type,
+    path: &mut String,
+    suffix: &str,
+) -> Result<(), String> {
+    let length = path.len();
+    path.push_str(suffix);
+    check_value(cx, value, ty, path)?;
+    path.truncate(length);
+    Ok(())
+}
+
+/// If `value` is an object with a string `tag` property (i.e. a JS
+/// representation of a `result` or `variant`), return the tag and the object.
+fn tagged(cx: &mut JSContext, value: Value) -> Option<(String, *mut JSObject)> {
+    if !value.is_object() {
+        return None;
+    }
+    rooted!(&in(cx) let object = value.to_object());
+    let tag = get(cx, object.handle(), c"tag");
+    tag.is_string().then(|| {
+        (
+            unsafe { jsstr_to_string(cx.raw_cx(), NonNull::new(tag.to_string()).unwrap()) },
+            object.get(),
+        )
+    })
+}
+
+fn mismatch(cx: &mut JSContext, value: Value, ty: Type, path: &str) -> Result<(), String> {
+    Err(format!(
+        "type mismatch at `{path}`: expected {}, got {}",
+        describe_type(ty),
+        describe_value(cx, value)
+    ))
+}
+
+/// Describe a WIT type for use in error messages.
+fn describe_type(ty: Type) -> String {
+    let list = |types: &mut dyn Iterator<Item = Type>| {
+        types.map(describe_type).collect::<Vec<_>>().join(", ")
+    };
+
+    match ty {
+        Type::Bool => "bool".into(),
+        Type::U8 => "u8".into(),
+        Type::S8 => "s8".into(),
+        Type::U16 => "u16".into(),
+        Type::S16 => "s16".into(),
+        Type::U32 => "u32".into(),
+        Type::S32 => "s32".into(),
+        Type::U64 => "u64".into(),
+        Type::S64 => "s64".into(),
+        Type::F32 => "f32".into(),
+        Type::F64 => "f64".into(),
+        Type::Char => "char".into(),
+        Type::String => "string".into(),
+        Type::Enum(ty) => format!("enum {{ {} }}", ty.names().collect::<Vec<_>>().join(", ")),
+        Type::Flags(ty) => format!("flags {{ {} }}", ty.names().collect::<Vec<_>>().join(", ")),
+        Type::Option(ty) => format!("option<{}>", describe_type(ty.ty())),
+        Type::Result(ty) => {
+            let describe = |ty: Option<Type>| ty.map(describe_type).unwrap_or_else(|| "_".into());
+            format!("result<{}, {}>", describe(ty.ok()), describe(ty.err()))
+        }
+        Type::Variant(ty) => format!(
+            "variant {{ {} }}",
+            ty.cases()
+                .map(|(name, payload)| match payload {
+                    Some(payload) => format!("{name}({})", describe_type(payload)),
+                    None => name.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Type::Record(ty) => format!(
+            "record {{ {} }}",
+            ty.fields()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Type::Tuple(ty) => format!("tuple<{}>", list(&mut ty.types())),
+        Type::List(ty) => format!("list<{}>", describe_type(ty.ty())),
+        Type::Own(ty) => format!("own<{}>", ty.name()),
+        Type::Borrow(ty) => format!("borrow<{}>", ty.name()),
+        Type::Future(_) => "future".into(),
+        Type::Stream(_) => "stream".into(),
+        _ => "value".into(),
+    }
+}
+
+/// Describe the type of a JS value (and, for primitives, the value itself) for
+/// use in error messages.
+fn describe_value(cx: &mut JSContext, value: Value) -> String {
+    if value.is_undefined() {
+        "undefined".into()
+    } else if value.is_null() {
+        "null".into()
+    } else if value.is_boolean() {
+        format!("boolean `{}`", value.to_boolean())
+    } else if value.is_number() {
+        format!("number `{}`", value.to_number())
+    } else if value.is_bigint() {
+        "bigint".into()
+    } else if value.is_string() {
+        let string =
+            unsafe { jsstr_to_string(cx.raw_cx(), NonNull::new(value.to_string()).unwrap()) };
+        format!("string {string:?}")
+    } else if value.is_symbol() {
+        "symbol".into()
+    } else {
+        "object".into()
+    }
+}
+
 // As of this writing, recent Rust `nightly` builds include a version of the
 // `libc` crate that expects `wasi-libc` to define the following global
 // variables, but `wasi-libc` defines them as preprocessor constants which
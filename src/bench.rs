@@ -0,0 +1,226 @@
+//! Measurement of the performance of generated components for the `bench`
+//! subcommand.
+
+use {
+    crate::Ctx,
+    anyhow::{Context as _, bail},
+    serde_json::Value,
+    std::time::{Duration, Instant},
+    wasmtime::{
+        Config, Engine, Store,
+        component::{Component, Linker, ResourceTable, Val, types::Type},
+    },
+    wasmtime_wasi::WasiCtxBuilder,
+};
+
+/// Timings measured by [`bench`].
+pub struct Report {
+    /// Time taken to compile the component.
+    pub compile: Duration,
+    /// Mean time taken to instantiate the component.
+    pub instantiation: Duration,
+    /// Mean time taken by the first call to the export on a new instance.
+    pub first_call: Duration,
+    /// Mean time taken by subsequent calls to the export on the same instance.
+    pub steady_state: Duration,
+}
+
+/// Instantiate `component` `iterations` times, calling the specified export
+/// once per instance, then call it `iterations` more times on a single
+/// instance.
+///
+/// `params` are the parameters to pass to the export, converted according to
+/// its parameter types (see [`to_val`]).  WASI imports are provided by
+/// Wasmtime, and any other imports trap if called.
+pub async fn bench(
+    component: &[u8],
+    export: &str,
+    params: &[Value],
+    iterations: u32,
+) -> anyhow::Result<Report> {
+    let mut config = Config::new();
+    config.async_support(true);
+    config.wasm_component_model(true);
+    config.wasm_component_model_async(true);
+    let engine = Engine::new(&config)?;
+
+    let start = Instant::now();
+    let component = Component::new(&engine, component)?;
+    let compile = start.elapsed();
+
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    wasmtime_wasi::p3::add_to_linker(&mut linker)?;
+    linker.define_unknown_imports_as_traps(&component)?;
+    let pre = linker.instantiate_pre(&component)?;
+
+    let store = || {
+        Store::new(
+            &engine,
+            Ctx {
+                wasi: WasiCtxBuilder::new().build(),
+                table: ResourceTable::new(),
+            },
+        )
+    };
+
+    let mut instantiation = Duration::ZERO;
+    let mut first_call = Duration::ZERO;
+    let mut last = None;
+    for _ in 0..iterations.max(1) {
+        let mut store = store();
+
+        let start = Instant::now();
+        let instance = pre.instantiate_async(&mut store).await?;
+        instantiation += start.elapsed();
+
+        let func = crate::export_func(&mut store, &instance, export)?;
+        let ty = func.ty(&store);
+        if ty.params().len() != params.len() {
+            bail!(
+                "`{export}` expects {} parameter(s); got {}",
+                ty.params().len(),
+                params.len()
+            );
+        }
+        let params = ty
+            .params()
+            .zip(params)
+            .map(|((name, ty), value)| {
+                to_val(&ty, value).with_context(|| format!("invalid value for `{name}`"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut results = vec![Val::Bool(false); ty.results().len()];
+
+        let start = Instant::now();
+        func.call_async(&mut store, &params, &mut results).await?;
+        first_call += start.elapsed();
+
+        last = Some((store, func, params, results));
+    }
+
+    let (mut store, func, params, mut results) = last.unwrap();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        func.call_async(&mut store, &params, &mut results).await?;
+    }
+    let steady_state = start.elapsed();
+
+    let iterations = iterations.max(1);
+    Ok(Report {
+        compile,
+        instantiation: instantiation / iterations,
+        first_call: first_call / iterations,
+        steady_state: steady_state / iterations,
+    })
+}
+
+/// Convert a JSON value to a component model value of the specified type.
+///
+/// Values are represented in the obvious way, except that `null` represents an
+/// `option` `none` value, variants are represented as `{ "tag": ..., "val":
+/// ... }` objects, results as `{ "ok": ... }` or `{ "err": ... }` objects, and
+/// flags as arrays of names.  Resources, futures, and streams are not
+/// supported.
+fn to_val(ty: &Type, value: &Value) -> anyhow::Result<Val> {
+    let int = || {
+        value
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| value.as_u64().map(i128::from))
+            .with_context(|| format!("expected an integer; got `{value}`"))
+    };
+    let float = || {
+        value
+            .as_f64()
+            .with_context(|| format!("expected a number; got `{value}`"))
+    };
+    let optional = |ty: Option<Type>, value: Option<&Value>| {
+        Ok(match (ty, value) {
+            (Some(ty), Some(value)) => Some(Box::new(to_val(&ty, value)?)),
+            (None, None | Some(Value::Null)) => None,
+            _ => bail!("unexpected payload for {ty:?}: {value:?}"),
+        })
+    };
+
+    Ok(match (ty, value) {
+        (Type::Bool, Value::Bool(value)) => Val::Bool(*value),
+        (Type::S8, _) => Val::S8(int()?.try_into()?),
+        (Type::U8, _) => Val::U8(int()?.try_into()?),
+        (Type::S16, _) => Val::S16(int()?.try_into()?),
+        (Type::U16, _) => Val::U16(int()?.try_into()?),
+        (Type::S32, _) => Val::S32(int()?.try_into()?),
+        (Type::U32, _) => Val::U32(int()?.try_into()?),
+        (Type::S64, _) => Val::S64(int()?.try_into()?),
+        (Type::U64, _) => Val::U64(int()?.try_into()?),
+        (Type::Float32, _) => Val::Float32(float()? as f32),
+        (Type::Float64, _) => Val::Float64(float()?),
+        (Type::Char, Value::String(value)) => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(char), None) => Val::Char(char),
+                _ => bail!("expected a single character; got `{value}`"),
+            }
+        }
+        (Type::String, Value::String(value)) => Val::String(value.clone()),
+        (Type::List(list), Value::Array(values)) => Val::List(
+            values
+                .iter()
+                .map(|value| to_val(&list.ty(), value))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        (Type::Record(record), Value::Object(fields)) => Val::Record(
+            record
+                .fields()
+                .map(|field| {
+                    let value = fields.get(field.name).unwrap_or(&Value::Null);
+                    Ok((field.name.to_string(), to_val(&field.ty, value)?))
+                })
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        (Type::Tuple(tuple), Value::Array(values)) if tuple.types().len() == values.len() => {
+            Val::Tuple(
+                tuple
+                    .types()
+                    .zip(values)
+                    .map(|(ty, value)| to_val(&ty, value))
+                    .collect::<anyhow::Result<_>>()?,
+            )
+        }
+        (Type::Variant(variant), Value::Object(object)) => {
+            let tag = object
+                .get("tag")
+                .and_then(Value::as_str)
+                .context("expected a `tag` field")?;
+            let case = variant
+                .cases()
+                .find(|case| case.name == tag)
+                .with_context(|| format!("unknown case: `{tag}`"))?;
+            Val::Variant(tag.into(), optional(case.ty, object.get("val"))?)
+        }
+        (Type::Enum(enum_), Value::String(name)) if enum_.names().any(|v| v == name) => {
+            Val::Enum(name.clone())
+        }
+        (Type::Option(_), Value::Null) => Val::Option(None),
+        (Type::Option(option), value) => Val::Option(Some(Box::new(to_val(&option.ty(), value)?))),
+        (Type::Result(result), Value::Object(object)) if object.len() == 1 => {
+            match object.iter().next().unwrap() {
+                (key, value) if key == "ok" => Val::Result(Ok(optional(result.ok(), Some(value))?)),
+                (key, value) if key == "err" => {
+                    Val::Result(Err(optional(result.err(), Some(value))?))
+                }
+                _ => bail!("expected an `ok` or `err` field"),
+            }
+        }
+        (Type::Flags(flags), Value::Array(names)) => Val::Flags(
+            names
+                .iter()
+                .map(|name| match name.as_str() {
+                    Some(name) if flags.names().any(|v| v == name) => Ok(name.to_string()),
+                    _ => bail!("unknown flag: `{name}`"),
+                })
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        _ => bail!("unable to convert `{value}` to {ty:?}"),
+    })
+}
@@ -1,6 +1,7 @@
 use {
+    crate::ExportLayout,
     heck::{ToLowerCamelCase as _, ToUpperCamelCase as _},
-    std::collections::BTreeMap,
+    std::collections::{BTreeMap, BTreeSet},
     wit_dylib::metadata::{Metadata, Type},
 };
 
@@ -46,7 +47,24 @@ impl<'a> Interface<'a> {
     }
 }
 
-pub fn generate(metadata: &Metadata) -> GeneratedCode {
+/// Generate JS bindings for the world described by `metadata`.
+///
+/// `world` is a JSON description of the same world, which will be exposed to
+/// scripts as `componentModel.world`.
+///
+/// `export_aliases` maps the names of exported functions and interfaces to the
+/// names of the JS bindings which implement them, if different from the
+/// default names determined by `export_layout`.
+///
+/// `console_sink`, if set, is the index of the `wasi:logging/logging#log`
+/// import to which `console` output should be sent at runtime.
+pub fn generate(
+    metadata: &Metadata,
+    world: &str,
+    export_aliases: &[(String, String)],
+    export_layout: ExportLayout,
+    console_sink: Option<usize>,
+) -> GeneratedCode {
     let mut modules = Vec::new();
     let mut world_module = String::new();
 
@@ -85,10 +103,7 @@ pub fn generate(metadata: &Metadata) -> GeneratedCode {
                 .join(",");
             let this = if has_this { "this," } else { "" };
             let value = if func.async_import_elem_index.is_some() {
-                format!(
-                    "new Promise((a,b)=>\
-                     _componentizeJsCallImport({index},[{this}{params}],a,b))"
-                )
+                format!("_componentizeJsCallAsyncImport({index},[{this}{params}])")
             } else {
                 format!("_componentizeJsCallImport({index},[{this}{params}])")
             };
@@ -146,7 +161,32 @@ pub fn generate(metadata: &Metadata) -> GeneratedCode {
     }
 
     // Next, generate wrapper functions for any and all async function exports
-    // so that they call back into the runtime when the promises resolve.
+    // so that they call back into the runtime when the promises resolve, plus
+    // re-exports for any aliased functions and interfaces so the runtime can
+    // find them using their default names.
+
+    let alias = |name: &str| {
+        export_aliases
+            .iter()
+            .find(|(wit_name, _)| wit_name == name)
+            .map(|(_, js_name)| js_name.clone())
+    };
+
+    let export_name = |interface: &str| match export_layout {
+        ExportLayout::Flat => mangle_name(interface),
+        ExportLayout::Qualified => interface.to_string(),
+    };
+
+    let mut aliases = BTreeMap::new();
+    for func in &metadata.export_funcs {
+        let (wit_name, default_name) = match &func.interface {
+            Some(interface) => (interface.as_str(), export_name(interface)),
+            None => (func.name.as_str(), func.name.to_lower_camel_case()),
+        };
+        if let Some(js_name) = alias(wit_name) {
+            aliases.insert(default_name, js_name);
+        }
+    }
 
     let mut async_exports = BTreeMap::<_, Interface>::new();
     for (index, func) in metadata.export_funcs.iter().enumerate() {
@@ -166,11 +206,20 @@ pub fn generate(metadata: &Metadata) -> GeneratedCode {
     let async_exports = async_exports
         .into_iter()
         .map(|(interface_name, interface)| {
-            let interface_name = interface_name.as_deref().map(mangle_name);
+            let interface_name = interface_name.as_deref().map(export_name);
             let fields = {
                 let interface_name = interface_name
                     .as_ref()
-                    .map(|v| format!("{v}."))
+                    .map(|v| match (aliases.get(v), export_layout) {
+                        (Some(js_name), _) => format!("{js_name}."),
+                        (None, ExportLayout::Flat) => format!("{v}."),
+                        // Interface IDs aren't valid identifiers, so we look
+                        // them up in the module namespace instead.
+                        (None, ExportLayout::Qualified) => format!(
+                            "_componentizeJsExports[{}].",
+                            serde_json::to_string(v).unwrap()
+                        ),
+                    })
                     .unwrap_or_else(String::new);
 
                 let params = |n| {
@@ -186,11 +235,16 @@ pub fn generate(metadata: &Metadata) -> GeneratedCode {
                     .map(|index| {
                         let func = &metadata.export_funcs[index];
                         let name = func.name.to_lower_camel_case();
+                        let target = if interface_name.is_empty() {
+                            aliases.get(&name).unwrap_or(&name)
+                        } else {
+                            &name
+                        };
                         let params = params(func.args.len());
                         let comma = if params.is_empty() { "" } else { "," };
                         format!(
                             "{name}:function(t{comma}{params}){{\n\
-                             return {interface_name}{name}({params})\n\
+                             return {interface_name}{target}({params})\n\
                              .then((v)=>_componentizeJsCallTaskReturn({index},v,t,true))\
                              .catch((v)=>_componentizeJsCallTaskReturn({index},v,t,false))}}"
                         )
@@ -237,7 +291,7 @@ pub fn generate(metadata: &Metadata) -> GeneratedCode {
             };
 
             if let Some(interface_name) = interface_name {
-                format!("{interface_name}:{{{fields}}}")
+                format!("{}:{{{fields}}}", export_key(&interface_name))
             } else {
                 fields
             }
@@ -286,20 +340,388 @@ pub fn generate(metadata: &Metadata) -> GeneratedCode {
             .concat(),
     );
 
-    // Next, add some utility code to the global object.
-    let globals = include_str!("globals.js").to_string();
+    // Next, generate `ComponentError` subclasses for any and all variant, enum,
+    // and record types used as `result` error types, so that scripts can use
+    // `instanceof` to distinguish them.  The classes are defined as globals so
+    // the runtime can find them, and exported from `wit-world` (using names
+    // qualified by interface) and from the module for the type's interface, if
+    // any (using unqualified names).
+
+    let mut error_classes = BTreeMap::new();
+    for result in &metadata.results {
+        let Some(mut ty) = result.err else {
+            continue;
+        };
+        while let Type::Alias(alias) = ty {
+            ty = metadata.aliases[alias].ty;
+        }
+
+        let (interface, name, cases) = match ty {
+            Type::Variant(index) => {
+                let ty = &metadata.variants[index];
+                let cases = ty
+                    .cases
+                    .iter()
+                    .map(|(name, _)| (name.as_str(), true))
+                    .collect();
+                (ty.interface.as_deref(), &ty.name, Some(cases))
+            }
+            Type::Enum(index) => {
+                let ty = &metadata.enums[index];
+                let cases = ty.names.iter().map(|name| (name.as_str(), false)).collect();
+                (ty.interface.as_deref(), &ty.name, Some(cases))
+            }
+            Type::Record(index) => {
+                let ty = &metadata.records[index];
+                (ty.interface.as_deref(), &ty.name, None::<Vec<_>>)
+            }
+            _ => continue,
+        };
+
+        let key = if let Some(interface) = interface {
+            format!("{interface}.{name}")
+        } else {
+            name.clone()
+        };
+
+        error_classes.entry(key).or_insert_with(|| {
+            let class = name.to_upper_camel_case();
+            let code = if let Some(cases) = cases {
+                let cases = cases
+                    .into_iter()
+                    .map(|(case, has_payload)| {
+                        let case_class = case.to_upper_camel_case();
+                        let (params, payload) = if has_payload {
+                            ("val", format!("{{tag:'{case}',val}}"))
+                        } else {
+                            ("", format!("'{case}'"))
+                        };
+                        (
+                            case.to_string(),
+                            case_class.clone(),
+                            format!(
+                                "c.{case_class}=class {case_class} extends c{{\
+                                 constructor({params}){{super({payload})}}}}\n"
+                            ),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let map = cases
+                    .iter()
+                    .map(|(case, case_class, _)| format!("'{case}':c.{case_class}"))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let definitions = cases
+                    .into_iter()
+                    .map(|(_, _, code)| code)
+                    .collect::<Vec<_>>()
+                    .concat();
+                format!(
+                    "(()=>{{const c=class {class} extends ComponentError{{\
+                     constructor(v){{super(v);\
+                     if(typeof v!=='string')this.message=`${{v.tag}} (see error.payload)`}}\
+                     get tag(){{return typeof this.payload==='string'?\
+                     this.payload:this.payload.tag}}}}\n\
+                     {definitions}c._componentizeJsCases={{{map}}}\n\
+                     return c}})()"
+                )
+            } else {
+                format!("class {class} extends ComponentError{{}}")
+            };
+            (interface, class, code)
+        });
+    }
+
+    let mut error_globals = String::new();
+    for (key, (interface, class, code)) in &error_classes {
+        let key = serde_json::to_string(key).unwrap();
+        error_globals.push_str(&format!("_componentizeJsErrorClasses[{key}]={code}\n"));
+
+        let value = format!("_componentizeJsErrorClasses[{key}]");
+        if let Some(interface) = interface {
+            let qualified = format!("{}_{class}", mangle_name(interface)).to_upper_camel_case();
+            world_module.push_str(&format!("export const {qualified}={value}\n"));
+            if let Some((_, module)) = modules.iter_mut().find(|(name, _)| name == interface) {
+                module.push_str(&format!("export const {class}={value}\n"));
+            }
+        } else {
+            world_module.push_str(&format!("export const {class}={value}\n"));
+        }
+    }
+
+    // Next, add some utility code to the global object, plus the names the
+    // script is expected to export (so the runtime can warn about any others)
+    // and the `componentModel` global, which holds a deep-frozen description
+    // of the world and functions for registering marshalling hooks, creating
+    // sandboxes, reading stdin, and controlling backpressure.
+    let expected_exports = serde_json::to_string(
+        &metadata
+            .export_funcs
+            .iter()
+            .map(|func| match &func.interface {
+                Some(interface) => export_name(interface),
+                None => func.name.to_lower_camel_case(),
+            })
+            .chain(aliases.values().cloned())
+            .collect::<BTreeSet<_>>(),
+    )
+    .unwrap();
+    let world = serde_json::to_string(world).unwrap();
+    let console_sink = if let Some(index) = console_sink {
+        let call = if metadata.import_funcs[index]
+            .async_import_elem_index
+            .is_some()
+        {
+            "_componentizeJsCallAsyncImport"
+        } else {
+            "_componentizeJsCallImport"
+        };
+        format!("_componentizeJsConsoleSink=(l,m)=>{call}({index},[l,'console',m])\n")
+    } else {
+        String::new()
+    };
+    // If the world imports a WASI 0.3 monotonic clock, use it for timers.
+    let wait_for = metadata
+        .import_funcs
+        .iter()
+        .position(|func| {
+            func.name == "wait-for"
+                && func.async_import_elem_index.is_some()
+                && func.interface.as_deref().is_some_and(|interface| {
+                    interface.starts_with("wasi:clocks/monotonic-clock@0.3.")
+                })
+        })
+        .map(|index| {
+            format!("_componentizeJsWaitFor=(d)=>_componentizeJsCallAsyncImport({index},[d])\n")
+        })
+        .unwrap_or_default();
+    let globals = format!(
+        "{}\n{error_globals}{console_sink}{wait_for}\
+         _componentizeJsExpectedExports={expected_exports}\n\
+         Object.defineProperty(globalThis,'componentModel',\
+         {{value:Object.freeze({{world:JSON.parse({world},(k,v)=>Object.freeze(v)),\
+         registerMarshaller:_componentizeJsRegisterMarshaller,\
+         createSandbox:_componentizeJsCreateSandbox,\
+         readStdin:_componentizeJsReadStdin,\
+         setBackpressure:_componentizeJsSetBackpressure}})}})\n",
+        include_str!("globals.js")
+    );
 
     modules.push(("wit-world".to_string(), world_module));
 
+    if let Some(http) = http_bindings(metadata) {
+        modules.push(("componentize-js:http".to_string(), http));
+    }
+
+    if let Some(fs) = fs_bindings(metadata) {
+        modules.push(("componentize-js:fs".to_string(), fs));
+    }
+
+    if let Some(sockets) = sockets_bindings(metadata) {
+        modules.push(("componentize-js:sockets".to_string(), sockets));
+    }
+
+    let reexports = aliases
+        .iter()
+        .map(|(default_name, js_name)| {
+            format!("export {{{js_name} as {}}}\n", export_key(default_name))
+        })
+        .collect::<Vec<_>>()
+        .concat();
+
     // Finally, return the result:
     GeneratedCode {
         globals,
         modules,
-        script: format!("export const _componentizeJsAsyncExports = {{{async_exports}}}"),
+        script: format!(
+            "{reexports}export const _componentizeJsAsyncExports = {{{async_exports}}}"
+        ),
+    }
+}
+
+/// If the world imports `wasi:http/types@0.3.x`, generate a module which passes
+/// the bindings for that interface (plus `wasi:http/client#send`, if imported)
+/// to `fetch`, etc. (see `_componentizeJsHttp` in `globals.js`), along with
+/// constructors for the byte streams and futures they use.
+///
+/// The module also exports functions for converting between those bindings and
+/// `Request` and `Response` for use in `wasi:http/handler` implementations.
+fn http_bindings(metadata: &Metadata) -> Option<String> {
+    let is_types = |interface: &Option<String>| {
+        interface
+            .as_deref()
+            .is_some_and(|v| v.starts_with("wasi:http/types@0.3."))
+    };
+
+    let types = metadata
+        .resources
+        .iter()
+        .find(|ty| ty.name == "fields" && ty.rep_elem_index.is_none() && is_types(&ty.interface))?
+        .interface
+        .as_deref()?;
+
+    let send = metadata.import_funcs.iter().find(|func| {
+        func.name == "send"
+            && func.async_import_elem_index.is_some()
+            && func
+                .interface
+                .as_deref()
+                .is_some_and(|v| v.starts_with("wasi:http/client@0.3."))
+    });
+
+    let stream = metadata
+        .streams
+        .iter()
+        .position(|stream| matches!(stream.ty, Some(Type::U8)))?;
+
+    // Find the `future<result<_, error-code>>` and
+    // `future<result<option<trailers>, error-code>>` types.
+    let future = |trailers: bool| {
+        metadata.futures.iter().position(|future| {
+            let Some(Type::Result(result)) = future.ty else {
+                return false;
+            };
+            let result = &metadata.results[result];
+            let error_code = matches!(
+                result.err,
+                Some(Type::Variant(ty))
+                    if metadata.variants[ty].name == "error-code"
+                        && is_types(&metadata.variants[ty].interface)
+            );
+            let ok = match result.ok {
+                None => !trailers,
+                Some(Type::Option(ty)) => {
+                    trailers
+                        && matches!(
+                            metadata.options[ty].ty,
+                            Type::Own(ty) if metadata.resources[ty].name == "fields"
+                        )
+                }
+                Some(_) => false,
+            };
+            error_code && ok
+        })
+    };
+    let body_future = future(false)?;
+    let trailers_future = future(true)?;
+
+    let (import_send, send) = if let Some(func) = send {
+        let client = func.interface.as_deref().unwrap();
+        (format!("import {{send}} from '{client}'\n"), "send")
+    } else {
+        (String::new(), "send:undefined")
+    };
+
+    Some(format!(
+        "import {{Fields,Request,Response}} from '{types}'\n\
+         {import_send}\
+         _componentizeJsHttp={{Fields,Request,Response,{send},\
+         stream:()=>_componentizeJsMakeStream({stream}),\
+         bodyFuture:()=>_componentizeJsMakeFuture({body_future},()=>({{tag:'ok'}})),\
+         trailersFuture:()=>_componentizeJsMakeFuture({trailers_future},()=>({{tag:'ok'}}))}}\n\
+         export const fromWasiRequest=_componentizeJsFromWasiRequest,\
+         toWasiResponse=_componentizeJsToWasiResponse\n"
+    ))
+}
+
+/// If the world imports `wasi:filesystem/preopens@0.3.x`, generate a module
+/// which passes `get-directories` to the filesystem functions in `globals.js`
+/// (see `_componentizeJsFs`), along with a constructor for the byte streams
+/// they use, and exports those functions.
+fn fs_bindings(metadata: &Metadata) -> Option<String> {
+    let preopens = metadata
+        .import_funcs
+        .iter()
+        .find(|func| {
+            func.name == "get-directories"
+                && func
+                    .interface
+                    .as_deref()
+                    .is_some_and(|v| v.starts_with("wasi:filesystem/preopens@0.3."))
+        })?
+        .interface
+        .as_deref()?;
+
+    let stream = metadata
+        .streams
+        .iter()
+        .position(|stream| matches!(stream.ty, Some(Type::U8)))?;
+
+    Some(format!(
+        "import {{getDirectories}} from '{preopens}'\n\
+         _componentizeJsFs={{getDirectories,stream:()=>_componentizeJsMakeStream({stream})}}\n\
+         export const readFile=_componentizeJsReadFile,writeFile=_componentizeJsWriteFile,\
+         readdir=_componentizeJsReaddir,stat=_componentizeJsStat,mkdir=_componentizeJsMkdir\n"
+    ))
+}
+
+/// If the world imports `wasi:sockets/types@0.3.x`, generate a module which
+/// passes the bindings for that interface (plus
+/// `wasi:sockets/ip-name-lookup#resolve-addresses`, if imported) to the socket
+/// functions in `globals.js` (see `_componentizeJsSockets`), along with a
+/// constructor for the byte streams they use, and exports those functions.
+fn sockets_bindings(metadata: &Metadata) -> Option<String> {
+    let types = metadata
+        .resources
+        .iter()
+        .find(|ty| {
+            ty.name == "tcp-socket"
+                && ty.rep_elem_index.is_none()
+                && ty
+                    .interface
+                    .as_deref()
+                    .is_some_and(|v| v.starts_with("wasi:sockets/types@0.3."))
+        })?
+        .interface
+        .as_deref()?;
+
+    let resolve = metadata.import_funcs.iter().find(|func| {
+        func.name == "resolve-addresses"
+            && func
+                .interface
+                .as_deref()
+                .is_some_and(|v| v.starts_with("wasi:sockets/ip-name-lookup@0.3."))
+    });
+
+    let stream = metadata
+        .streams
+        .iter()
+        .position(|stream| matches!(stream.ty, Some(Type::U8)))?;
+
+    let (import_resolve, resolve) = if let Some(func) = resolve {
+        let lookup = func.interface.as_deref().unwrap();
+        (
+            format!("import {{resolveAddresses}} from '{lookup}'\n"),
+            "resolveAddresses",
+        )
+    } else {
+        (String::new(), "resolveAddresses:undefined")
+    };
+
+    Some(format!(
+        "import {{TcpSocket,UdpSocket}} from '{types}'\n\
+         {import_resolve}\
+         _componentizeJsSockets={{TcpSocket,UdpSocket,{resolve},\
+         stream:()=>_componentizeJsMakeStream({stream})}}\n\
+         export const connect=_componentizeJsConnect,listen=_componentizeJsListen,\
+         bindDatagram=_componentizeJsBindDatagram\n"
+    ))
+}
+
+/// Return `name` as-is if it is a valid JS identifier, or else as a string
+/// literal, suitable for use as a property key or module export name.
+pub fn export_key(name: &str) -> String {
+    if name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+    {
+        name.into()
+    } else {
+        serde_json::to_string(name).unwrap()
     }
 }
 
-fn mangle_name(name: &str) -> String {
+pub fn mangle_name(name: &str) -> String {
     name.replace(['@', ':', '/', '-', '[', ']', '.'], "_")
         .to_lower_camel_case()
 }
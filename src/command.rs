@@ -1,11 +1,41 @@
 use {
-    crate::Wit,
-    anyhow::Context as _,
+    crate::{
+        Coercion, Componentizer, ConsoleOutput, ExportLayout, Options as ComponentizeOptions,
+        Prefs, WasiHost, Wit,
+        scaffold::{self, Template},
+    },
+    anyhow::{Context as _, bail},
     clap::Parser as _,
-    std::{ffi::OsString, fs, path::PathBuf},
+    serde_json::{Map, Value},
+    std::{
+        ffi::OsString,
+        fs,
+        io::{self, Read as _},
+        path::{Path, PathBuf},
+        slice,
+    },
     tokio::runtime::Runtime,
 };
 
+/// Name of the project manifest read by the `build` subcommand.
+const PROJECT_MANIFEST: &str = "componentize-js.json";
+
+/// Project manifest keys corresponding to options which precede the subcommand.
+const COMMON_OPTIONS: &[&str] = &["wit-path", "world", "quiet", "features", "all-features"];
+
+/// Project manifest keys whose values are paths relative to the project
+/// directory.
+const PATH_OPTIONS: &[&str] = &[
+    "wit-path",
+    "input",
+    "base-directory",
+    "output",
+    "cache-dir",
+    "debug-artifacts-dir",
+    "manifest",
+    "source-map",
+];
+
 /// A utility to convert JavaScript modules into Wasm components
 #[derive(clap::Parser, Debug)]
 #[command(author, version, about)]
@@ -22,7 +52,10 @@ pub struct Common {
     /// Files or directories containing WIT document(s).
     ///
     /// This may be specified more than once, for example:
-    /// `-d ./wit/deps -d ./wit/app`
+    /// `-d ./wit/deps -d ./wit/app`, in which case the packages are resolved in
+    /// the order given, so each must follow any others it uses.  The world is
+    /// looked up in the last package first, and then (if given by an
+    /// unqualified name) in the others.
     #[arg(short = 'd', long)]
     pub wit_path: Vec<PathBuf>,
 
@@ -52,6 +85,97 @@ pub struct Common {
 pub enum Command {
     /// Generate a component from the specified JavaScript module.
     Componentize(Componentize),
+    /// Generate a component as configured by a project manifest.
+    Build(Build),
+    /// Create a new project, including a WIT world, a JavaScript module with
+    /// stub exports, and a project manifest for use with `build`.
+    New(New),
+    /// Measure instantiation time, first-call latency, and steady-state
+    /// throughput of an export of a generated component.
+    Bench(Bench),
+    /// Check that a generated component is valid, targets the specified world,
+    /// and has no unexpected imports, exiting with an error if not.
+    Validate(Validate),
+    /// Generate TypeScript declarations describing the modules a script may
+    /// import and the exports it must provide for the specified world.
+    Types(Types),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Types {
+    /// Directory to which to write `imports.d.ts` and `exports.d.ts`.
+    #[arg(short = 'o', long, default_value = ".")]
+    pub output: PathBuf,
+
+    /// Use the specified JS binding to implement the exported WIT function or
+    /// interface with the specified name (e.g. `get-user=fetchUser`).  This may
+    /// be specified more than once.
+    #[arg(long, value_name = "WIT_NAME=JS_NAME")]
+    pub export_alias: Vec<String>,
+
+    /// How the script names the objects implementing exported interfaces.
+    #[arg(long, value_enum, default_value_t)]
+    pub export_layout: ExportLayout,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Validate {
+    /// The filename of the component to check.
+    pub component: PathBuf,
+
+    /// Reject all imports not present in the world, including WASI imports
+    /// used by the runtime.
+    #[arg(long)]
+    pub strict_imports: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Bench {
+    /// The filename of the component to measure.
+    pub component: PathBuf,
+
+    /// Name of the export to call, e.g. `run` or `wasi:cli/run@0.2.0#run`.
+    #[arg(long)]
+    pub export: String,
+
+    /// JSON array of parameters to pass to the export.
+    ///
+    /// Records are represented as objects, tuples and lists as arrays, and
+    /// `option` `none` values as `null`.  Variants are represented as
+    /// `{ "tag": ..., "val": ... }` objects, results as `{ "ok": ... }` or
+    /// `{ "err": ... }` objects, and flags as arrays of names.
+    #[arg(long, default_value = "[]")]
+    pub args: String,
+
+    /// Number of instances to create and number of calls to make to a single
+    /// instance.
+    #[arg(long, default_value_t = 100)]
+    pub iterations: u32,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct New {
+    /// Kind of project to create.
+    #[arg(value_enum)]
+    pub template: Template,
+
+    /// Directory in which to create the project, which must either not exist
+    /// or be empty.
+    pub directory: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Build {
+    /// Project directory containing a `componentize-js.json` file or a
+    /// `package.json` file with a `componentize-js` field.
+    ///
+    /// The manifest is a JSON object whose keys are the names of the
+    /// `componentize` command's options (e.g. `"wit-path": ["wit"]`,
+    /// `"world": "app"`, or `"freeze-intrinsics": true`), plus `input`, which
+    /// names the JavaScript module (and defaults to `main` in `package.json`).
+    /// Paths are relative to the project directory.
+    #[arg(default_value = ".")]
+    pub project: PathBuf,
 }
 
 #[derive(clap::Args, Debug)]
@@ -67,32 +191,500 @@ pub struct Componentize {
     /// Output file to which to write the resulting component
     #[arg(short = 'o', long, default_value = "js.wasm")]
     pub output: PathBuf,
+
+    /// Enable or disable a JS engine preference, e.g. `--pref shared-memory`
+    /// or `--pref weak-refs=false`.
+    ///
+    /// Supported preferences are `weak-refs`, `shared-memory`,
+    /// `iterator-helpers`, `to-source`, `force-utc`, and `temporal` (which
+    /// requires a build with the `intl` feature).  This may be specified more
+    /// than once.
+    #[arg(long = "pref", value_name = "NAME[=BOOL]")]
+    pub prefs: Vec<String>,
+
+    /// Log each garbage collection (with its reason and duration) to stderr
+    /// when the component runs.
+    #[arg(long)]
+    pub gc_logging: bool,
+
+    /// Print per-class object counts and sizes to stderr just before the
+    /// snapshot is taken.
+    #[arg(long)]
+    pub heap_census: bool,
+
+    /// Print a CPU profile of each export call to stderr in the folded stacks
+    /// format used by flamegraph tools.
+    ///
+    /// This slows the component down considerably and is only intended for
+    /// debugging.
+    #[arg(long)]
+    pub profile: bool,
+
+    /// How strictly JS values are converted to WIT numeric types.
+    #[arg(long, value_enum, default_value_t)]
+    pub coercion: Coercion,
+
+    /// Deep-freeze the standard JS globals and their prototypes after the
+    /// script has been evaluated, so they can't be tampered with at runtime.
+    #[arg(long)]
+    pub freeze_intrinsics: bool,
+
+    /// Maximum size of the JS heap in bytes, beyond which allocations throw an
+    /// "out of memory" exception.
+    #[arg(long, value_name = "BYTES")]
+    pub max_heap_bytes: Option<u32>,
+
+    /// Maximum size of the component's linear memory in bytes, declared in the
+    /// generated component (rounded down to a multiple of 64 KiB).
+    #[arg(long, value_name = "BYTES")]
+    pub max_memory_bytes: Option<u64>,
+
+    /// Size of the runtime's stack in bytes, for scripts which need deeper
+    /// recursion than the default (1 MiB) allows.
+    #[arg(long, value_name = "BYTES")]
+    pub stack_size: Option<u32>,
+
+    /// Replace the host's environment variables with the ones specified using
+    /// `--env` (if any) inside the generated component.
+    #[arg(long)]
+    pub virtualize_env: bool,
+
+    /// Environment variable to provide inside the generated component, in
+    /// place of the host's environment.  This implies `--virtualize-env` and
+    /// may be specified more than once.
+    #[arg(long = "env", value_name = "NAME=VALUE")]
+    pub env: Vec<String>,
+
+    /// Replace the host's wall and monotonic clocks with fixed ones inside the
+    /// generated component.  The wall clock always reports the specified time
+    /// (in seconds since the Unix epoch, or zero if unspecified).
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "0"
+    )]
+    pub virtualize_clock: Option<u64>,
+
+    /// Which WASI implementation to provide while evaluating the script.
+    #[arg(long, value_enum, default_value_t)]
+    pub wasi_host: WasiHost,
+
+    /// Use the specified JS binding to implement the exported WIT function or
+    /// interface with the specified name (e.g. `get-user=fetchUser`).  This may
+    /// be specified more than once.
+    #[arg(long, value_name = "WIT_NAME=JS_NAME")]
+    pub export_alias: Vec<String>,
+
+    /// How the script names the objects implementing exported interfaces.
+    #[arg(long, value_enum, default_value_t)]
+    pub export_layout: ExportLayout,
+
+    /// After generating the component, instantiate it and call the specified
+    /// parameterless export (e.g. `run` or `wasi:cli/run@0.2.0#run`), failing
+    /// if the call traps.
+    #[arg(long, value_name = "EXPORT")]
+    pub smoke_test: Option<String>,
+
+    /// Cache the compiled form of the component used during initialization in
+    /// this directory, speeding up subsequent builds which use the same world.
+    #[arg(long, value_name = "PATH")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Read this process's stdin and make it available to the script while it
+    /// is evaluated (via `componentModel.readStdin()`).
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// Retain the `init` export in the generated component so that a trusted
+    /// host can replace the script at runtime (e.g. for live reloading during
+    /// development).
+    #[arg(long)]
+    pub hot_reload: bool,
+
+    /// Print the duration of each stage of componentization to stderr.
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Source map for the input (e.g. as produced by a bundler), used to
+    /// rewrite stack traces to refer to the original sources.
+    #[arg(long, value_name = "PATH")]
+    pub source_map: Option<PathBuf>,
+
+    /// Where `console` output goes at runtime; `wasi-logging` requires the
+    /// world to import `wasi:logging/logging`.
+    #[arg(long, value_enum, default_value_t)]
+    pub console: ConsoleOutput,
+
+    /// Evaluate the script when the component is first called rather than
+    /// snapshotting it at build time, making builds much faster at the cost of
+    /// startup time (for development only).
+    #[arg(long)]
+    pub skip_snapshot: bool,
+
+    /// Write the intermediate components produced while componentizing (the
+    /// linked, instrumented, and snapshotted components) to this directory.
+    #[arg(long, value_name = "PATH")]
+    pub debug_artifacts_dir: Option<PathBuf>,
+
+    /// Also write a JSON manifest describing the component's world, embedded
+    /// script, and size to the specified file.
+    #[arg(long, value_name = "PATH")]
+    pub manifest: Option<PathBuf>,
+}
+
+fn parse_pairs(pairs: &[String], format: &str) -> anyhow::Result<Vec<(String, String)>> {
+    pairs
+        .iter()
+        .map(|pair| {
+            let (name, value) = pair
+                .split_once('=')
+                .with_context(|| format!("expected `{format}`, got `{pair}`"))?;
+            Ok((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn parse_prefs(prefs: &[String]) -> anyhow::Result<Prefs> {
+    let mut result = Prefs::default();
+    for pref in prefs {
+        let (name, value) = match pref.split_once('=') {
+            Some((name, value)) => (
+                name,
+                value
+                    .parse()
+                    .with_context(|| format!("invalid value for `{name}`: `{value}`"))?,
+            ),
+            None => (pref.as_str(), true),
+        };
+        result.set(name, value)?;
+    }
+    Ok(result)
 }
 
 pub fn run<T: Into<OsString> + Clone, I: IntoIterator<Item = T>>(args: I) -> anyhow::Result<()> {
     let options = Options::parse_from(args);
     match options.command {
         Command::Componentize(opts) => componentize(options.common, opts),
+        Command::Build(opts) => build(options.common, opts),
+        Command::New(opts) => new(options.common, opts),
+        Command::Bench(opts) => bench(options.common, opts),
+        Command::Validate(opts) => validate(options.common, opts),
+        Command::Types(opts) => types(options.common, opts),
     }
 }
 
-fn componentize(common: Common, componentize: Componentize) -> anyhow::Result<()> {
-    let input = fs::read_to_string(&componentize.input)
-        .with_context(|| format!("unable to read `{}`", componentize.input.display()))?;
+fn validate(common: Common, validate: Validate) -> anyhow::Result<()> {
+    let component = fs::read(&validate.component)
+        .with_context(|| format!("unable to read `{}`", validate.component.display()))?;
+
+    let problems = crate::validate(
+        Wit::Paths(&common.wit_path),
+        common.world.as_deref(),
+        &common.features,
+        common.all_features,
+        &component,
+        validate.strict_imports,
+    )?;
+
+    for problem in &problems {
+        eprintln!("error: {problem}");
+    }
+
+    if !problems.is_empty() {
+        bail!(
+            "`{}` failed validation with {} problem(s)",
+            validate.component.display(),
+            problems.len()
+        );
+    }
 
-    let output = Runtime::new()?.block_on(crate::componentize(
+    if !common.quiet {
+        println!("Component is valid");
+    }
+
+    Ok(())
+}
+
+fn types(common: Common, types: Types) -> anyhow::Result<()> {
+    let files = crate::generate_types(
         Wit::Paths(&common.wit_path),
         common.world.as_deref(),
         &common.features,
         common.all_features,
-        &input,
-        Some(&componentize.base_directory),
-        None,
+        &parse_pairs(&types.export_alias, "WIT_NAME=JS_NAME")?,
+        types.export_layout,
+    )?;
+
+    fs::create_dir_all(&types.output)
+        .with_context(|| format!("unable to create `{}`", types.output.display()))?;
+
+    for (name, contents) in files {
+        let path = types.output.join(name);
+        fs::write(&path, contents)
+            .with_context(|| format!("unable to write `{}`", path.display()))?;
+
+        if !common.quiet {
+            println!("Wrote {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn bench(common: Common, bench: Bench) -> anyhow::Result<()> {
+    let component = fs::read(&bench.component)
+        .with_context(|| format!("unable to read `{}`", bench.component.display()))?;
+    let params =
+        serde_json::from_str::<Vec<Value>>(&bench.args).context("`--args` must be a JSON array")?;
+
+    let report = Runtime::new()?.block_on(crate::bench::bench(
+        &component,
+        &bench.export,
+        &params,
+        bench.iterations,
     ))?;
 
+    if !common.quiet {
+        println!("compile:       {:?}", report.compile);
+        println!("instantiation: {:?} (mean)", report.instantiation);
+        println!("first call:    {:?} (mean)", report.first_call);
+        println!(
+            "steady state:  {:?} (mean; {:.0} calls/s)",
+            report.steady_state,
+            1.0 / report.steady_state.as_secs_f64().max(f64::MIN_POSITIVE)
+        );
+    }
+
+    Ok(())
+}
+
+fn new(common: Common, new: New) -> anyhow::Result<()> {
+    scaffold::create(new.template, &new.directory)?;
+
+    if !common.quiet {
+        println!(
+            "Created project in `{}`; run `componentize-js build` there to build it",
+            new.directory.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn build(common: Common, build: Build) -> anyhow::Result<()> {
+    let manifest = read_project_manifest(&build.project)?;
+    let mut args = project_args(&manifest, &build.project)?;
+    if common.quiet {
+        args.insert(1, "--quiet".into());
+    }
+    run(args)
+}
+
+/// Read the `componentize-js.json` file (or the `componentize-js` field of
+/// the `package.json` file) in the specified project directory.
+fn read_project_manifest(project: &Path) -> anyhow::Result<Map<String, Value>> {
+    let read = |path: &Path| -> anyhow::Result<Map<String, Value>> {
+        serde_json::from_str(
+            &fs::read_to_string(path)
+                .with_context(|| format!("unable to read `{}`", path.display()))?,
+        )
+        .with_context(|| format!("unable to parse `{}`", path.display()))
+    };
+
+    let path = project.join(PROJECT_MANIFEST);
+    if path.exists() {
+        return read(&path);
+    }
+
+    let path = project.join("package.json");
+    if path.exists() {
+        let package = read(&path)?;
+        if let Some(Value::Object(manifest)) = package.get("componentize-js") {
+            let mut manifest = manifest.clone();
+            if let (false, Some(main)) = (manifest.contains_key("input"), package.get("main")) {
+                manifest.insert("input".into(), main.clone());
+            }
+            return Ok(manifest);
+        }
+    }
+
+    bail!(
+        "no `{PROJECT_MANIFEST}` file or `package.json` file with a `componentize-js` field \
+         found in `{}`",
+        project.display()
+    )
+}
+
+/// Convert the specified project manifest into the equivalent
+/// `componentize-js componentize` command line.
+pub(crate) fn project_args(
+    manifest: &Map<String, Value>,
+    project: &Path,
+) -> anyhow::Result<Vec<OsString>> {
+    let mut common = Vec::new();
+    let mut rest = Vec::new();
+    let mut input = None;
+
+    for (key, value) in manifest {
+        let values = match value {
+            Value::Array(values) => values.as_slice(),
+            value => slice::from_ref(value),
+        };
+
+        for value in values {
+            let value = match value {
+                Value::Bool(true) => None,
+                Value::Bool(false) => continue,
+                Value::String(value) => Some(value.clone()),
+                Value::Number(value) => Some(value.to_string()),
+                _ => bail!("unsupported value for `{key}` in project manifest: {value}"),
+            };
+            let value = value.map(|value| {
+                if PATH_OPTIONS.contains(&key.as_str()) {
+                    project.join(value).into_os_string()
+                } else {
+                    value.into()
+                }
+            });
+
+            if key == "input" {
+                input = Some(value.context("expected a path for `input` in project manifest")?);
+                continue;
+            }
+
+            let mut arg = OsString::from(format!("--{key}"));
+            if let Some(value) = value {
+                arg.push("=");
+                arg.push(value);
+            }
+
+            if COMMON_OPTIONS.contains(&key.as_str()) {
+                common.push(arg);
+            } else {
+                rest.push(arg);
+            }
+        }
+    }
+
+    let input = input.context("project manifest does not specify an `input`")?;
+
+    // Resolve the defaults for these relative to the project rather than the
+    // current directory.
+    for (key, default) in [
+        ("base-directory", project.to_path_buf()),
+        ("output", project.join("js.wasm")),
+    ] {
+        if !manifest.contains_key(key) {
+            let mut arg = OsString::from(format!("--{key}="));
+            arg.push(default);
+            rest.push(arg);
+        }
+    }
+
+    Ok(["componentize-js".into()]
+        .into_iter()
+        .chain(common)
+        .chain(["componentize".into(), input])
+        .chain(rest)
+        .collect())
+}
+
+fn componentize(common: Common, componentize: Componentize) -> anyhow::Result<()> {
+    let typescript = matches!(
+        componentize.input.extension().and_then(|e| e.to_str()),
+        Some("ts" | "mts" | "cts")
+    );
+    if typescript && !cfg!(feature = "typescript") {
+        bail!(
+            "`{}` appears to be TypeScript, which is only supported directly if \
+             `componentize-js` is built with the `typescript` feature; otherwise, \
+             compile it to JavaScript first, e.g. using `tsc` or `esbuild`",
+            componentize.input.display()
+        );
+    }
+
+    let input = fs::read_to_string(&componentize.input)
+        .with_context(|| format!("unable to read `{}`", componentize.input.display()))?;
+
+    #[cfg(feature = "typescript")]
+    let input = if typescript {
+        crate::strip_types(&input, &componentize.input)?
+    } else {
+        input
+    };
+
+    let options = ComponentizeOptions {
+        prefs: parse_prefs(&componentize.prefs)?,
+        gc_logging: componentize.gc_logging,
+        heap_census: componentize.heap_census,
+        profile: componentize.profile,
+        coercion: componentize.coercion,
+        freeze_intrinsics: componentize.freeze_intrinsics,
+        max_heap_bytes: componentize.max_heap_bytes,
+        max_memory_bytes: componentize.max_memory_bytes,
+        stack_size: componentize.stack_size,
+        virtual_env: if componentize.virtualize_env || !componentize.env.is_empty() {
+            Some(parse_pairs(&componentize.env, "NAME=VALUE")?)
+        } else {
+            None
+        },
+        virtual_clock: componentize.virtualize_clock,
+        wasi_host: componentize.wasi_host,
+        export_aliases: parse_pairs(&componentize.export_alias, "WIT_NAME=JS_NAME")?,
+        export_layout: componentize.export_layout,
+        smoke_test: componentize.smoke_test.map(|export| (export, Vec::new())),
+        cache_dir: componentize.cache_dir,
+        stdin: if componentize.stdin {
+            let mut stdin = Vec::new();
+            io::stdin()
+                .read_to_end(&mut stdin)
+                .context("unable to read stdin")?;
+            stdin
+        } else {
+            Vec::new()
+        },
+        hot_reload: componentize.hot_reload,
+        timings: componentize.timings,
+        source_map: componentize
+            .source_map
+            .map(|path| {
+                fs::read_to_string(&path)
+                    .with_context(|| format!("unable to read `{}`", path.display()))
+            })
+            .transpose()?,
+        console: componentize.console,
+        skip_snapshot: componentize.skip_snapshot,
+        debug_artifacts_dir: componentize.debug_artifacts_dir,
+    };
+
+    let output = Runtime::new()?.block_on(
+        Componentizer::new(Wit::Paths(&common.wit_path), &input)
+            .world(common.world.as_deref())
+            .features(&common.features)
+            .all_features(common.all_features)
+            .base_directory(&componentize.base_directory)
+            .options(options)
+            .componentize(),
+    )?;
+
     fs::write(&componentize.output, &output)
         .with_context(|| format!("unable to write `{}`", componentize.output.display()))?;
 
+    if let Some(path) = &componentize.manifest {
+        let manifest = crate::manifest(
+            Wit::Paths(&common.wit_path),
+            common.world.as_deref(),
+            &common.features,
+            common.all_features,
+            &input,
+            &output,
+        )?;
+
+        fs::write(path, manifest)
+            .with_context(|| format!("unable to write `{}`", path.display()))?;
+    }
+
     if !common.quiet {
         println!("Component built successfully");
     }
@@ -0,0 +1,207 @@
+//! Support for [`Options::skip_snapshot`](crate::Options::skip_snapshot).
+//!
+//! Rather than evaluating the script at build time, we embed the parameters
+//! which would have been passed to the runtime's `init` export in a tiny
+//! library linked alongside the runtime.  The runtime checks that library for a
+//! payload the first time one of the component's exports is called and, if
+//! there is one, initializes itself from it.
+//!
+//! The payload uses a simple binary encoding which must be kept in sync with
+//! `Payload` in the runtime: integers are little-endian `u32`s, `bool`s and
+//! enum discriminants are single bytes, options are a byte (`0` or `1`)
+//! followed by the value if present, and strings and lists are a `u32` length
+//! followed by their contents.
+
+use {
+    crate::bindings::{Coercion, ExportLayout, Options},
+    wasm_encoder::{
+        CodeSection, ConstExpr, CustomSection, DataSection, Encode as _, EntityType, ExportKind,
+        ExportSection, Function, FunctionSection, GlobalType, ImportSection, Instruction,
+        MemoryType, Module, TypeSection, ValType,
+    },
+};
+
+/// Name of the library containing the payload.
+pub const LIBRARY_NAME: &str = "libcomponentize_js_deferred.so";
+
+/// Encode the parameters for the runtime's `init` export.
+pub fn encode(
+    options: &Options,
+    globals: &str,
+    modules: &[(String, String)],
+    script: &str,
+    bindings: &str,
+) -> Vec<u8> {
+    let mut writer = Writer(Vec::new());
+
+    let prefs = &options.prefs;
+    for flag in [
+        prefs.weak_refs,
+        prefs.shared_memory,
+        prefs.iterator_helpers,
+        prefs.to_source,
+        prefs.force_utc,
+        prefs.temporal,
+        options.gc_logging,
+        options.heap_census,
+        options.profile,
+    ] {
+        writer.bool(flag);
+    }
+    writer.u8(match options.coercion {
+        Coercion::Standard => 0,
+        Coercion::Strict => 1,
+        Coercion::Lenient => 2,
+    });
+    writer.bool(options.freeze_intrinsics);
+    writer.option(options.max_heap_bytes, Writer::u32);
+    writer.option(options.stack_size, Writer::u32);
+    writer.u8(match options.export_layout {
+        ExportLayout::Flat => 0,
+        ExportLayout::Qualified => 1,
+    });
+    writer.option(options.source_map.as_ref(), |writer, source_map| {
+        writer.list(&source_map.sources, |writer, source| writer.str(source));
+        writer.list(&source_map.mappings, |writer, mapping| {
+            for value in [
+                mapping.generated_line,
+                mapping.generated_column,
+                mapping.source,
+                mapping.line,
+                mapping.column,
+            ] {
+                writer.u32(value);
+            }
+        });
+    });
+
+    writer.str(globals);
+    writer.list(modules, |writer, (name, code)| {
+        writer.str(name);
+        writer.str(code);
+    });
+    writer.str(script);
+    writer.str(bindings);
+
+    writer.0
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value.into());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(u32::try_from(len).unwrap());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.0.extend(value.as_bytes());
+    }
+
+    fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+
+    fn list<T>(&mut self, values: &[T], mut write: impl FnMut(&mut Self, &T)) {
+        self.len(values.len());
+        for value in values {
+            write(self, value);
+        }
+    }
+}
+
+/// Generate a shared library which places `payload` in linear memory and
+/// exports `componentize_js_deferred_payload` and
+/// `componentize_js_deferred_payload_len` functions returning its address and
+/// length, respectively.
+pub fn library(payload: &[u8]) -> Vec<u8> {
+    let len = u32::try_from(payload.len()).unwrap();
+    let mut module = Module::new();
+
+    // The `dylink.0` section must come first.  It consists of a single
+    // `WASM_DYLINK_MEM_INFO` subsection specifying how much memory the linker
+    // should reserve for the payload.
+    let mut mem_info = Vec::new();
+    for value in [len, 0, 0, 0] {
+        value.encode(&mut mem_info);
+    }
+    let mut dylink = vec![1];
+    mem_info.as_slice().encode(&mut dylink);
+    module.section(&CustomSection {
+        name: "dylink.0".into(),
+        data: dylink.into(),
+    });
+
+    let mut types = TypeSection::new();
+    types.ty().function([], [ValType::I32]);
+    module.section(&types);
+
+    let mut imports = ImportSection::new();
+    imports.import(
+        "env",
+        "memory",
+        EntityType::Memory(MemoryType {
+            minimum: 0,
+            maximum: None,
+            memory64: false,
+            shared: false,
+            page_size_log2: None,
+        }),
+    );
+    imports.import(
+        "env",
+        "__memory_base",
+        EntityType::Global(GlobalType {
+            val_type: ValType::I32,
+            mutable: false,
+            shared: false,
+        }),
+    );
+    module.section(&imports);
+
+    let mut functions = FunctionSection::new();
+    functions.function(0);
+    functions.function(0);
+    module.section(&functions);
+
+    let mut exports = ExportSection::new();
+    exports.export("componentize_js_deferred_payload", ExportKind::Func, 0);
+    exports.export("componentize_js_deferred_payload_len", ExportKind::Func, 1);
+    module.section(&exports);
+
+    let mut code = CodeSection::new();
+    let mut function = Function::new([]);
+    function.instruction(&Instruction::GlobalGet(0));
+    function.instruction(&Instruction::End);
+    code.function(&function);
+    let mut function = Function::new([]);
+    function.instruction(&Instruction::I32Const(
+        i32::try_from(payload.len()).unwrap(),
+    ));
+    function.instruction(&Instruction::End);
+    code.function(&function);
+    module.section(&code);
+
+    let mut data = DataSection::new();
+    if !payload.is_empty() {
+        data.active(0, &ConstExpr::global_get(0), payload.iter().copied());
+    }
+    module.section(&data);
+
+    module.finish()
+}
@@ -0,0 +1,96 @@
+use std::{error::Error, fmt};
+
+/// An error produced by [`Componentizer`](crate::Componentizer), identifying the
+/// stage at which it failed.
+///
+/// This implements [`std::error::Error`], so it converts to `anyhow::Error` via
+/// `?` like any other error type.
+#[derive(Debug)]
+pub enum ComponentizeError {
+    /// The WIT files could not be read, parsed, or resolved.
+    WitParse(anyhow::Error),
+    /// The requested world could not be found, or no world was specified and
+    /// the package does not contain exactly one.
+    WorldSelection(anyhow::Error),
+    /// The source map provided via `Options::source_map` could not be decoded.
+    SourceMap(anyhow::Error),
+    /// The runtime, generated bindings, and supporting libraries could not be
+    /// linked into a component.
+    Link(anyhow::Error),
+    /// The pre-initialization component could not be compiled or instantiated,
+    /// e.g. because a WASI import could not be satisfied.
+    Instantiate(anyhow::Error),
+    /// The runtime failed to initialize, or the script threw an exception
+    /// while being evaluated.
+    InitScript {
+        /// Anything the script wrote to stdout before failing.
+        stdout: String,
+        /// Anything the script wrote to stderr before failing.
+        stderr: String,
+        /// The string representation of the error or thrown value.
+        message: String,
+        /// The JS stack trace, if the thrown value was an `Error`.
+        stack: Option<String>,
+    },
+    /// The initialized component could not be snapshotted.
+    Snapshot(anyhow::Error),
+    /// The snapshotted component could not be post-processed, e.g. because the
+    /// requested memory limit is smaller than the snapshot's initial memory.
+    PostProcess(anyhow::Error),
+    /// The generated component could not be instantiated, or the export
+    /// requested as a smoke test trapped when called.
+    SmokeTest(anyhow::Error),
+    /// An intermediate component could not be written to
+    /// `Options::debug_artifacts_dir`.
+    DebugArtifact(anyhow::Error),
+}
+
+impl fmt::Display for ComponentizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WitParse(_) => write!(f, "failed to parse WIT"),
+            Self::WorldSelection(_) => write!(f, "failed to select WIT world"),
+            Self::SourceMap(_) => write!(f, "failed to decode source map"),
+            Self::Link(_) => write!(f, "failed to link component"),
+            Self::Instantiate(_) => write!(f, "failed to instantiate component for initialization"),
+            Self::InitScript {
+                stdout,
+                stderr,
+                message,
+                stack,
+            } => {
+                write!(f, "initialization failed: {message}")?;
+                if let Some(stack) = stack.as_deref().filter(|s| !s.is_empty()) {
+                    write!(f, "\nstack:\n{}", stack.trim_end())?;
+                }
+                for (name, output) in [("stdout", stdout), ("stderr", stderr)] {
+                    if !output.is_empty() {
+                        write!(f, "\n{name}:\n{}", output.trim_end())?;
+                    }
+                }
+                Ok(())
+            }
+            Self::Snapshot(_) => write!(f, "failed to snapshot initialized component"),
+            Self::PostProcess(_) => write!(f, "failed to post-process snapshotted component"),
+            Self::SmokeTest(_) => write!(f, "smoke test of generated component failed"),
+            Self::DebugArtifact(_) => write!(f, "failed to write debug artifact"),
+        }
+    }
+}
+
+impl Error for ComponentizeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::WitParse(e)
+            | Self::WorldSelection(e)
+            | Self::SourceMap(e)
+            | Self::Link(e)
+            | Self::Instantiate(e)
+            | Self::Snapshot(e)
+            | Self::PostProcess(e)
+            | Self::SmokeTest(e)
+            | Self::DebugArtifact(e) => Some(e.as_ref()),
+            Self::InitScript { .. } => None,
+        }
+    }
+}
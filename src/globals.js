@@ -9,6 +9,100 @@ var ComponentError = class extends Error {
     }
 }
 
+// Subclasses of `ComponentError` generated for variant, enum, and record types
+// used as `result` error types, keyed by qualified type name.  Variant and enum
+// classes have one subclass per case, registered in `_componentizeJsCases`.
+var _componentizeJsErrorClasses = {}
+
+var _componentizeJsMakeError = function(key, payload) {
+    const base = key === undefined ? undefined : _componentizeJsErrorClasses[key]
+    if (base === undefined) {
+        return new ComponentError(payload)
+    }
+    const cases = base._componentizeJsCases
+    if (cases === undefined) {
+        return new base(payload)
+    } else if (typeof payload === 'string') {
+        return new cases[payload]()
+    } else {
+        return new cases[payload.tag](payload.val)
+    }
+}
+
+var _componentizeJsIsComponentError = function(value) {
+    return value instanceof ComponentError
+}
+
+// Names the script is expected to export, per the world's exports.
+var _componentizeJsExpectedExports = []
+
+// Describe each export of the script's module `namespace` which doesn't
+// correspond to any export of the world (e.g. due to a typo or a kebab-case vs.
+// camelCase mix-up), one per line.
+var _componentizeJsUnknownExports = function(namespace) {
+    const normalize = (name) => name.replace(/[-_]/g, '').toLowerCase()
+    const warnings = []
+    for (const name of Object.keys(namespace)) {
+        if (name.startsWith('_componentizeJs') || _componentizeJsExpectedExports.includes(name)) {
+            continue
+        }
+        const similar = _componentizeJsExpectedExports.find((v) => normalize(v) === normalize(name))
+        const hint = similar === undefined ? '' : `; did you mean \`${similar}\`?`
+        warnings.push(`export \`${name}\` does not match any export of the WIT world${hint}`)
+    }
+    return warnings.join('\n')
+}
+
+// Names of the functions exported by the module `namespace`, which are made
+// available to sandboxes.
+var _componentizeJsFunctionNames = function(namespace) {
+    return Object.keys(namespace).filter((name) => typeof namespace[name] === 'function')
+}
+
+// Create a sandbox in which untrusted code (e.g. a plugin) may be evaluated in a
+// separate realm with its own principals.  The code sees only the standard
+// classes plus an `imports` global holding the functions exported by the import
+// modules named in `imports` (e.g. `['local:app/log']`).  Arguments and return
+// values are structured-cloned as they cross between realms, and any other
+// objects passed into the sandbox are opaque to it.
+var _componentizeJsCreateSandbox = function(imports) {
+    const id = _componentizeJsNewSandbox(Array.from(imports, String))
+    return Object.freeze({
+        evaluate: (source) => _componentizeJsEvaluateInSandbox(id, String(source))
+    })
+}
+
+// Typed array classes used to represent lists of numeric WIT types, captured
+// here in case the script replaces the globals.
+var _componentizeJsTypedArrays = {
+    Uint8Array, Int8Array, Uint16Array, Int16Array, Uint32Array, Int32Array,
+    BigUint64Array, BigInt64Array, Float32Array, Float64Array
+}
+
+// Convert `value` to an instance of the typed array class named `type` so it
+// can be lowered as a list of the corresponding numeric WIT type without
+// visiting each element.  `ArrayBuffer`s and `DataView`s (plus any typed array,
+// for byte lists) are reinterpreted as-is.  Returns `null` if `value` is one of
+// those but its size or alignment doesn't fit, or `undefined` if it's something
+// else (e.g. an array), which the runtime will convert element by element.
+var _componentizeJsToTypedArray = function(value, type) {
+    const ctor = _componentizeJsTypedArrays[type]
+    if (value instanceof ctor) {
+        return value
+    }
+    try {
+        if (value instanceof ArrayBuffer) {
+            return new ctor(value)
+        }
+        if (value instanceof DataView || (ctor.BYTES_PER_ELEMENT === 1 && ArrayBuffer.isView(value))) {
+            return new ctor(value.buffer, value.byteOffset, value.byteLength / ctor.BYTES_PER_ELEMENT)
+        }
+    } catch {
+        return null
+    }
+    return undefined
+}
+
 var TextEncoder = class {
     constructor() {}
     encode(value) { return _componentizeJsEncodeUtf8(value) }
@@ -22,13 +116,96 @@ var TextDecoder = class {
 var _componentizeJsWriteAll = async function(buffer) {
     let total = 0
     while (buffer.length > 0 && !this.readerDropped) {
-        count = await this.write(buffer)
+        const count = await this.write(buffer)
         buffer = buffer.slice(count)
         total += count
     }
     return total
 }
 
+// Async iterator for stream readers, yielding each chunk read until the writer
+// is dropped.
+var _componentizeJsStreamIterator = async function*() {
+    while (!this.writerDropped) {
+        const chunk = await this.read(1024)
+        if (chunk.length > 0) {
+            yield chunk
+        }
+    }
+}
+
+// Wrap a stream reader (i.e. `this`) in a `ReadableStream` whose chunks are
+// the non-empty arrays read from it, `count` items at a time at most.
+// Canceling the `ReadableStream` drops the reader.
+var _componentizeJsToReadableStream = function(count = 1024) {
+    const rx = this
+    return new ReadableStream({
+        pull: async (controller) => {
+            while (!rx.writerDropped) {
+                const chunk = await rx.read(count)
+                if (chunk.length > 0) {
+                    controller.enqueue(chunk)
+                    return
+                }
+            }
+            rx[Symbol.dispose]()
+            controller.close()
+        },
+        cancel: () => rx[Symbol.dispose]()
+    }, { highWaterMark: 0 })
+}
+
+// Read the value from a future reader (i.e. `this`), returning a promise for
+// it.  The reader is dropped once the read has finished.
+var _componentizeJsToPromise = function() {
+    const rx = this
+    return rx.read().finally(() => rx[Symbol.dispose]())
+}
+
+// Wrap a stream writer (i.e. `this`) in a `WritableStream` accepting arrays of
+// items to write.  Closing or aborting the `WritableStream` drops the writer.
+var _componentizeJsToWritableStream = function() {
+    const tx = this
+    return new WritableStream({
+        write: async (chunk) => {
+            if (await tx.writeAll(chunk) < chunk.length) {
+                throw new TypeError('stream reader has been dropped')
+            }
+        },
+        close: () => tx[Symbol.dispose](),
+        abort: () => tx[Symbol.dispose]()
+    })
+}
+
+// Call the specified async import, returning a promise with a `cancel` method
+// which may be used to request cancellation of the call if it's still pending.
+var _componentizeJsCallAsyncImport = function(index, params) {
+    let subtask
+    const promise = new Promise((resolve, reject) => {
+        subtask = _componentizeJsCallImport(index, params, resolve, reject)
+    })
+    promise.cancel = () => _componentizeJsCancelImport(subtask)
+    promise.cancelOn = (signal) => {
+        if (signal.aborted) {
+            promise.cancel()
+        } else {
+            const listener = () => promise.cancel()
+            const remove = () => signal.removeEventListener('abort', listener)
+            signal.addEventListener('abort', listener)
+            promise.then(remove, remove)
+        }
+        return promise
+    }
+    return promise
+}
+
+// The reason an async import call promise is rejected after being cancelled.
+var _componentizeJsAbortError = function() {
+    const error = new Error('import call cancelled')
+    error.name = 'AbortError'
+    return error
+}
+
 var _componentizeJsMaybeWriteDefault = function() {
     if (this._componentizeJsHandle) {
         this.write(this.default())
@@ -50,3 +227,3052 @@ var _componentizeJsRegisterFinalizer = function(value) {
 var _componentizeJsUnregisterFinalizer = function(value) {
     _componentizeJsFinalizationRegistry.unregister(value)
 }
+
+// Render `value` for display by `console`, roughly following Node's
+// `util.inspect`.  Strings are quoted unless `top` is set, and objects nested
+// more than two levels deep (or circular references) are abbreviated.
+var _componentizeJsInspect = function(value, top = true, depth = 0, seen = []) {
+    switch (typeof value) {
+    case 'string':
+        return top ? value : `'${value.replace(/\\/g, '\\\\').replace(/'/g, "\\'").replace(/\n/g, '\\n')}'`
+    case 'bigint':
+        return `${value}n`
+    case 'symbol':
+        return value.toString()
+    case 'function': {
+        const source = Function.prototype.toString.call(value)
+        const kind = source.startsWith('class') ? 'class' : 'Function'
+        return value.name ? `[${kind}: ${value.name}]` : `[${kind} (anonymous)]`
+    }
+    case 'object':
+        break
+    default:
+        return String(value)
+    }
+
+    if (value === null) {
+        return 'null'
+    }
+    if (seen.includes(value)) {
+        return '[Circular]'
+    }
+    if (value instanceof Error) {
+        const header = `${value.name}: ${value.message}`
+        return value.stack ? `${header}\n${value.stack.trimEnd()}` : header
+    }
+    if (value instanceof Date) {
+        return Number.isNaN(value.getTime()) ? 'Invalid Date' : value.toISOString()
+    }
+    if (value instanceof RegExp) {
+        return value.toString()
+    }
+    if (value instanceof Promise) {
+        return 'Promise {}'
+    }
+
+    const name = value.constructor?.name
+    const inspect = (v) => _componentizeJsInspect(v, false, depth + 1, [...seen, value])
+    const wrap = (prefix, open, items, close) => {
+        const body = items.length === 0 ? '' : ` ${items.join(', ')} `
+        return `${prefix}${open}${body}${close}`
+    }
+
+    let prefix, open, close, items
+    if (Array.isArray(value) || ArrayBuffer.isView(value) && !(value instanceof DataView)) {
+        prefix = Array.isArray(value) && name === 'Array' ? '' : `${name}(${value.length}) `
+        if (depth > 2) {
+            return `[${name}]`
+        }
+        ;[open, close, items] = ['[', ']', Array.from(value, inspect)]
+    } else if (value instanceof Map) {
+        prefix = `Map(${value.size}) `
+        if (depth > 2) {
+            return '[Map]'
+        }
+        ;[open, close] = ['{', '}']
+        items = Array.from(value, ([k, v]) => `${inspect(k)} => ${inspect(v)}`)
+    } else if (value instanceof Set) {
+        prefix = `Set(${value.size}) `
+        if (depth > 2) {
+            return '[Set]'
+        }
+        ;[open, close, items] = ['{', '}', Array.from(value, inspect)]
+    } else {
+        prefix = name === undefined ? '[Object: null prototype] ' : name === 'Object' ? '' : `${name} `
+        if (depth > 2) {
+            return `[${name ?? 'Object'}]`
+        }
+        ;[open, close] = ['{', '}']
+        items = Object.keys(value).map((key) => {
+            const k = /^[A-Za-z_$][\w$]*$/.test(key) ? key : inspect(key)
+            return `${k}: ${inspect(value[key])}`
+        })
+    }
+    return wrap(prefix, open, items, close)
+}
+
+// Format `console` arguments, applying `printf`-style substitutions (`%s`,
+// `%d`, `%i`, `%f`, `%o`, `%O`, `%j`, `%c`, and `%%`) if the first argument is
+// a string.
+var _componentizeJsFormat = function(args) {
+    let rest = args
+    let prefix = []
+    if (typeof args[0] === 'string') {
+        let index = 1
+        const formatted = args[0].replace(/%([sdifoOjc%])/g, (match, specifier) => {
+            if (specifier === '%') {
+                return '%'
+            }
+            if (index >= args.length) {
+                return match
+            }
+            const arg = args[index++]
+            switch (specifier) {
+            case 's':
+                return typeof arg === 'string' ? arg : _componentizeJsInspect(arg, false)
+            case 'd':
+            case 'i':
+                return typeof arg === 'bigint' ? `${arg}n`
+                    : String(specifier === 'i' ? Math.trunc(Number(arg)) : Number(arg))
+            case 'f':
+                return String(parseFloat(arg))
+            case 'j':
+                return JSON.stringify(arg)
+            case 'c':
+                return ''
+            default:
+                return _componentizeJsInspect(arg, false)
+            }
+        })
+        prefix = [formatted]
+        rest = args.slice(index)
+    }
+    return prefix.concat(rest.map((v) => _componentizeJsInspect(v))).join(' ')
+}
+
+// If set (by the generated bindings), a function which takes a
+// `wasi:logging/logging` level and a message and logs them using that import.
+var _componentizeJsConsoleSink = undefined
+
+// Set by the runtime once the script has been evaluated.  Until then, imports
+// other than WASI are stubbed, so `console` always writes to stdout or stderr.
+var _componentizeJsInitialized = false
+
+var _componentizeJsWriteConsole = function(level, args) {
+    const message = _componentizeJsFormat(args)
+    if (_componentizeJsConsoleSink !== undefined && _componentizeJsInitialized) {
+        _componentizeJsConsoleSink(level, message)
+    } else {
+        _componentizeJsPrint(level === 'warn' || level === 'error' ? 2 : 1, message)
+    }
+}
+
+// A minimal `console`, writing to stdout (`log`, `info`, and `debug`) or
+// stderr (`warn` and `error`), or else to `_componentizeJsConsoleSink`.
+var console = {
+    log: (...args) => _componentizeJsWriteConsole('info', args),
+    info: (...args) => _componentizeJsWriteConsole('info', args),
+    debug: (...args) => _componentizeJsWriteConsole('debug', args),
+    warn: (...args) => _componentizeJsWriteConsole('warn', args),
+    error: (...args) => _componentizeJsWriteConsole('error', args),
+}
+
+// If set (by the generated bindings), a function which takes a duration in
+// nanoseconds and returns a promise which resolves once it has elapsed, with a
+// `cancel` method, using the world's `wasi:clocks/monotonic-clock#wait-for`
+// import.
+var _componentizeJsWaitFor = undefined
+
+// Promises for pending timers created using `setTimeout` or `setInterval`,
+// keyed by timer ID.
+var _componentizeJsTimers = new Map()
+
+var _componentizeJsNextTimerId = 1
+
+var _componentizeJsScheduleTimer = function(id, callback, delay, args, repeat) {
+    // Like the Web, treat invalid delays as zero.  Intervals are always delayed
+    // by at least a millisecond so they can't starve everything else.
+    const milliseconds = Math.max(repeat ? 1 : 0, Number(delay) || 0)
+    let promise
+    if (milliseconds > 0) {
+        if (_componentizeJsWaitFor === undefined) {
+            throw new Error(
+                'timers with a nonzero delay require the world to import ' +
+                    '`wasi:clocks/monotonic-clock@0.3.x`'
+            )
+        }
+        promise = _componentizeJsWaitFor(BigInt(Math.round(milliseconds * 1e6)))
+    } else {
+        promise = Promise.resolve()
+    }
+    _componentizeJsTimers.set(id, promise)
+    promise.then(() => {
+        if (_componentizeJsTimers.get(id) !== promise) {
+            return
+        }
+        if (repeat) {
+            _componentizeJsScheduleTimer(id, callback, delay, args, repeat)
+        } else {
+            _componentizeJsTimers.delete(id)
+        }
+        try {
+            callback(...args)
+        } catch (error) {
+            console.error(error)
+        }
+    }, () => {})
+}
+
+var setTimeout = function(callback, delay, ...args) {
+    const id = _componentizeJsNextTimerId++
+    _componentizeJsScheduleTimer(id, callback, delay, args, false)
+    return id
+}
+
+var setInterval = function(callback, delay, ...args) {
+    const id = _componentizeJsNextTimerId++
+    _componentizeJsScheduleTimer(id, callback, delay, args, true)
+    return id
+}
+
+var clearTimeout = function(id) {
+    const promise = _componentizeJsTimers.get(id)
+    if (promise !== undefined) {
+        _componentizeJsTimers.delete(id)
+        promise.cancel?.()
+    }
+}
+
+var clearInterval = clearTimeout
+
+// Queue `callback` to run at the next microtask checkpoint, i.e. once the
+// current export call, import completion, etc. has finished running JS.
+var queueMicrotask = function(callback) {
+    if (typeof callback !== 'function') {
+        throw new TypeError('callback must be a function')
+    }
+    Promise.resolve().then(() => {
+        try {
+            callback()
+        } catch (error) {
+            console.error(error)
+        }
+    })
+}
+
+// Key for dispatching an `Event`, which only `EventTarget` may do.
+var _componentizeJsDispatch = Symbol('dispatch')
+
+// The DOM's `Event`.  Since there's no node tree, an event is only ever
+// dispatched to its target, i.e. there are no capturing or bubbling phases.
+var Event = class {
+    static NONE = 0
+    static CAPTURING_PHASE = 1
+    static AT_TARGET = 2
+    static BUBBLING_PHASE = 3
+
+    #type
+    #bubbles
+    #cancelable
+    #composed
+    #timeStamp = performance.now()
+    #target = null
+    #currentTarget = null
+    #dispatching = false
+    #defaultPrevented = false
+    #inPassiveListener = false
+    #stopPropagation = false
+    #stopImmediatePropagation = false
+
+    constructor(type, { bubbles = false, cancelable = false, composed = false } = {}) {
+        if (arguments.length < 1) {
+            throw new TypeError('Event constructor requires a type')
+        }
+        this.#type = String(type)
+        this.#bubbles = Boolean(bubbles)
+        this.#cancelable = Boolean(cancelable)
+        this.#composed = Boolean(composed)
+    }
+
+    get type() { return this.#type }
+    get bubbles() { return this.#bubbles }
+    get cancelable() { return this.#cancelable }
+    get composed() { return this.#composed }
+    get timeStamp() { return this.#timeStamp }
+    get isTrusted() { return false }
+    get target() { return this.#target }
+    get srcElement() { return this.#target }
+    get currentTarget() { return this.#currentTarget }
+    get eventPhase() { return this.#dispatching ? Event.AT_TARGET : Event.NONE }
+    get defaultPrevented() { return this.#defaultPrevented }
+
+    get returnValue() { return !this.#defaultPrevented }
+    set returnValue(value) {
+        if (!value) {
+            this.preventDefault()
+        }
+    }
+
+    get cancelBubble() { return this.#stopPropagation }
+    set cancelBubble(value) {
+        if (value) {
+            this.#stopPropagation = true
+        }
+    }
+
+    composedPath() {
+        return this.#dispatching ? [this.#currentTarget] : []
+    }
+
+    preventDefault() {
+        if (this.#cancelable && !this.#inPassiveListener) {
+            this.#defaultPrevented = true
+        }
+    }
+
+    stopPropagation() {
+        this.#stopPropagation = true
+    }
+
+    stopImmediatePropagation() {
+        this.#stopPropagation = true
+        this.#stopImmediatePropagation = true
+    }
+
+    // Call each of `entries` (as registered by `EventTarget.addEventListener`)
+    // in turn, returning `false` if any canceled the event.
+    [_componentizeJsDispatch](target, entries) {
+        if (this.#dispatching) {
+            throw _componentizeJsNamedError('InvalidStateError', 'event is already being dispatched')
+        }
+        this.#dispatching = true
+        this.#target = target
+        this.#currentTarget = target
+        for (const entry of entries) {
+            if (this.#stopImmediatePropagation) {
+                break
+            } else if (entry.removed) {
+                continue
+            }
+            if (entry.once) {
+                entry.remove()
+            }
+            this.#inPassiveListener = entry.passive
+            try {
+                typeof entry.listener === 'function'
+                    ? entry.listener.call(target, this)
+                    : entry.listener.handleEvent(this)
+            } catch (error) {
+                console.error(error)
+            }
+            this.#inPassiveListener = false
+        }
+        this.#dispatching = false
+        this.#currentTarget = null
+        this.#stopPropagation = false
+        this.#stopImmediatePropagation = false
+        return !this.#defaultPrevented
+    }
+}
+
+// The DOM's `CustomEvent`, i.e. an `Event` carrying arbitrary `detail`.
+var CustomEvent = class extends Event {
+    #detail
+
+    constructor(type, options = {}) {
+        super(type, options)
+        this.#detail = options.detail ?? null
+    }
+
+    get detail() { return this.#detail }
+}
+
+// The HTML standard's `MessageEvent`, as dispatched by e.g. `WebSocket`.
+var MessageEvent = class extends Event {
+    #data
+    #origin
+    #lastEventId
+
+    constructor(type, options = {}) {
+        super(type, options)
+        this.#data = options.data ?? null
+        this.#origin = String(options.origin ?? '')
+        this.#lastEventId = String(options.lastEventId ?? '')
+    }
+
+    get data() { return this.#data }
+    get origin() { return this.#origin }
+    get lastEventId() { return this.#lastEventId }
+    get source() { return null }
+    get ports() { return [] }
+}
+
+// The DOM's `EventTarget`.  Listener errors are reported via `console.error`
+// rather than propagated to `dispatchEvent`'s caller.
+var EventTarget = class {
+    #listeners = new Map()
+
+    addEventListener(type, listener, options = {}) {
+        if (listener === null || listener === undefined) {
+            return
+        }
+        const { capture = false, once = false, passive = false, signal } = typeof options === 'boolean'
+            ? { capture: options }
+            : options ?? {}
+        if (signal?.aborted) {
+            return
+        }
+        type = String(type)
+        let entries = this.#listeners.get(type)
+        if (entries === undefined) {
+            entries = []
+            this.#listeners.set(type, entries)
+        }
+        if (entries.some((entry) => entry.listener === listener && entry.capture === Boolean(capture))) {
+            return
+        }
+        const entry = {
+            listener,
+            capture: Boolean(capture),
+            once: Boolean(once),
+            passive: Boolean(passive),
+            removed: false,
+            remove: () => this.removeEventListener(type, listener, { capture })
+        }
+        entries.push(entry)
+        signal?.addEventListener('abort', entry.remove)
+    }
+
+    removeEventListener(type, listener, options = {}) {
+        const capture = Boolean(typeof options === 'boolean' ? options : options?.capture)
+        const entries = this.#listeners.get(String(type)) ?? []
+        const index = entries.findIndex((entry) => entry.listener === listener && entry.capture === capture)
+        if (index >= 0) {
+            entries[index].removed = true
+            entries.splice(index, 1)
+        }
+    }
+
+    dispatchEvent(event) {
+        if (!(event instanceof Event)) {
+            throw new TypeError('expected an Event')
+        }
+        return event[_componentizeJsDispatch](this, (this.#listeners.get(event.type) ?? []).slice())
+    }
+}
+
+// Listeners registered using the global `addEventListener`.
+var _componentizeJsGlobalEventTarget = new EventTarget()
+
+var addEventListener = function(type, listener, options) {
+    _componentizeJsGlobalEventTarget.addEventListener(type, listener, options)
+}
+
+var removeEventListener = function(type, listener, options) {
+    _componentizeJsGlobalEventTarget.removeEventListener(type, listener, options)
+}
+
+var dispatchEvent = function(event) {
+    return _componentizeJsGlobalEventTarget.dispatchEvent(event)
+}
+
+// Keys for creating and aborting `AbortSignal`s, neither of which application
+// code may do directly.
+var _componentizeJsNewAbortSignal = Symbol('newAbortSignal')
+var _componentizeJsAbort = Symbol('abort')
+
+var _componentizeJsNamedError = function(name, message) {
+    const error = new Error(message)
+    error.name = name
+    return error
+}
+
+var AbortSignal = class extends EventTarget {
+    #aborted = false
+    #reason = undefined
+    #onabort = null
+    #onabortListener = (event) => this.#onabort?.call(this, event)
+
+    constructor(key) {
+        if (key !== _componentizeJsNewAbortSignal) {
+            throw new TypeError('illegal constructor')
+        }
+        super()
+    }
+
+    get aborted() { return this.#aborted }
+    get reason() { return this.#reason }
+
+    // Like the DOM's event handler attributes, this is registered as a listener
+    // when first set, and so runs in that position relative to other listeners.
+    get onabort() { return this.#onabort }
+    set onabort(handler) {
+        handler = typeof handler === 'function' ? handler : null
+        if (handler === null) {
+            this.removeEventListener('abort', this.#onabortListener)
+        } else if (this.#onabort === null) {
+            this.addEventListener('abort', this.#onabortListener)
+        }
+        this.#onabort = handler
+    }
+
+    throwIfAborted() {
+        if (this.#aborted) {
+            throw this.#reason
+        }
+    }
+
+    [_componentizeJsAbort](reason) {
+        if (this.#aborted) {
+            return
+        }
+        this.#aborted = true
+        this.#reason = reason === undefined
+            ? _componentizeJsNamedError('AbortError', 'This operation was aborted')
+            : reason
+        this.dispatchEvent(new Event('abort'))
+    }
+
+    static abort(reason) {
+        const signal = new AbortSignal(_componentizeJsNewAbortSignal)
+        signal[_componentizeJsAbort](reason)
+        return signal
+    }
+
+    // Like `setTimeout`, this requires the world to import
+    // `wasi:clocks/monotonic-clock@0.3.x` for a nonzero delay.
+    static timeout(milliseconds) {
+        const signal = new AbortSignal(_componentizeJsNewAbortSignal)
+        setTimeout(() => signal[_componentizeJsAbort](
+            _componentizeJsNamedError('TimeoutError', 'The operation timed out')
+        ), milliseconds)
+        return signal
+    }
+
+    static any(signals) {
+        const signal = new AbortSignal(_componentizeJsNewAbortSignal)
+        signals = Array.from(signals)
+        const aborted = signals.find((source) => source.aborted)
+        if (aborted !== undefined) {
+            signal[_componentizeJsAbort](aborted.reason)
+        } else {
+            for (const source of signals) {
+                source.addEventListener('abort', () => signal[_componentizeJsAbort](source.reason))
+            }
+        }
+        return signal
+    }
+}
+
+var AbortController = class {
+    #signal = new AbortSignal(_componentizeJsNewAbortSignal)
+
+    get signal() { return this.#signal }
+
+    abort(reason) {
+        this.#signal[_componentizeJsAbort](reason)
+    }
+}
+
+// Return the bytes of `data` (an `ArrayBuffer` or view thereof) as a
+// `Uint8Array` sharing the same buffer.
+var _componentizeJsBytes = function(data) {
+    if (data instanceof ArrayBuffer) {
+        return new Uint8Array(data)
+    } else if (ArrayBuffer.isView(data)) {
+        return new Uint8Array(data.buffer, data.byteOffset, data.byteLength)
+    } else {
+        throw new TypeError('expected an ArrayBuffer, typed array, or DataView')
+    }
+}
+
+// Names of environment variables assigned (or, if mapped to `undefined`,
+// deleted) via `process.env`, which take precedence over the host's.
+var _componentizeJsEnvOverrides = new Map()
+
+// Return the current environment variables, including any overrides.
+var _componentizeJsEnvironment = function() {
+    const flat = _componentizeJsGetEnvironment()
+    const vars = new Map()
+    for (let i = 0; i < flat.length; i += 2) {
+        vars.set(flat[i], flat[i + 1])
+    }
+    for (const [name, value] of _componentizeJsEnvOverrides) {
+        value === undefined ? vars.delete(name) : vars.set(name, value)
+    }
+    return vars
+}
+
+// A minimal Node.js-style `process`.  `process.env` reads the host's
+// environment (via `wasi:cli/environment`) on each access rather than
+// capturing it in the snapshot, so it may vary from one instantiation to the
+// next.  Assignments and deletions only affect the current instance.
+var process = {
+    env: new Proxy({}, {
+        get: (_, name) => typeof name === 'string' ? _componentizeJsEnvironment().get(name) : undefined,
+        has: (_, name) => typeof name === 'string' && _componentizeJsEnvironment().has(name),
+        ownKeys: () => Array.from(_componentizeJsEnvironment().keys()),
+        getOwnPropertyDescriptor: (_, name) => {
+            const value = typeof name === 'string' ? _componentizeJsEnvironment().get(name) : undefined
+            return value === undefined
+                ? undefined
+                : { value, writable: true, enumerable: true, configurable: true }
+        },
+        set: (_, name, value) => {
+            _componentizeJsEnvOverrides.set(String(name), String(value))
+            return true
+        },
+        defineProperty: (_, name, descriptor) => {
+            _componentizeJsEnvOverrides.set(String(name), String(descriptor.value))
+            return true
+        },
+        deleteProperty: (_, name) => {
+            _componentizeJsEnvOverrides.set(String(name), undefined)
+            return true
+        }
+    })
+}
+
+// A subset of the Web Crypto API, drawing entropy from the host (via
+// `wasi:random`) on each call.
+var crypto = {
+    getRandomValues: function(array) {
+        if (!ArrayBuffer.isView(array)
+            || array instanceof DataView
+            || array instanceof Float32Array
+            || array instanceof Float64Array) {
+            throw new TypeError('expected an integer typed array')
+        }
+        if (array.byteLength > 65536) {
+            const error = new Error(
+                `byte length of ${array.byteLength} exceeds the maximum of 65536`
+            )
+            error.name = 'QuotaExceededError'
+            throw error
+        }
+        _componentizeJsFillRandom(_componentizeJsBytes(array))
+        return array
+    },
+
+    randomUUID: function() {
+        const bytes = crypto.getRandomValues(new Uint8Array(16))
+        // Set the version (4) and variant (RFC 9562) bits.
+        bytes[6] = (bytes[6] & 0x0f) | 0x40
+        bytes[8] = (bytes[8] & 0x3f) | 0x80
+        const hex = Array.from(bytes, (byte) => byte.toString(16).padStart(2, '0')).join('')
+        return `${hex.slice(0, 8)}-${hex.slice(8, 12)}-${hex.slice(12, 16)}-` +
+            `${hex.slice(16, 20)}-${hex.slice(20)}`
+    },
+
+    subtle: {
+        digest: async function(algorithm, data) {
+            const name = String(typeof algorithm === 'object' ? algorithm?.name : algorithm)
+                .toUpperCase()
+            if (!['SHA-1', 'SHA-256', 'SHA-384', 'SHA-512'].includes(name)) {
+                const error = new Error(`unsupported digest algorithm: ${name}`)
+                error.name = 'NotSupportedError'
+                throw error
+            }
+            return _componentizeJsDigest(name, _componentizeJsBytes(data)).buffer
+        }
+    }
+}
+
+// Entries recorded using `performance.mark` and `performance.measure`.
+var _componentizeJsPerformanceEntries = []
+
+// Resolve a `performance.measure` start or end point, given as either a
+// timestamp or the name of a mark.
+var _componentizeJsMarkTime = function(mark) {
+    if (typeof mark === 'number') {
+        return mark
+    }
+    const entry = _componentizeJsPerformanceEntries.findLast(
+        (entry) => entry.entryType === 'mark' && entry.name === mark
+    )
+    if (entry === undefined) {
+        const error = new Error(`no mark named \`${mark}\``)
+        error.name = 'SyntaxError'
+        throw error
+    }
+    return entry.startTime
+}
+
+// A subset of the Web Performance API, measuring time using the monotonic
+// clock.  The time origin is the first use of `performance` after the snapshot
+// was taken (or during initialization, for code run then).
+var performance = {
+    now: () => _componentizeJsPerformanceNow(),
+
+    get timeOrigin() {
+        return _componentizeJsTimeOrigin()
+    },
+
+    mark: function(name, options = {}) {
+        const entry = {
+            name: String(name),
+            entryType: 'mark',
+            startTime: options.startTime ?? performance.now(),
+            duration: 0,
+            detail: options.detail ?? null
+        }
+        _componentizeJsPerformanceEntries.push(entry)
+        return entry
+    },
+
+    measure: function(name, startOrOptions, endMark) {
+        let start, end, duration, detail = null
+        if (typeof startOrOptions === 'object' && startOrOptions !== null) {
+            ;({ start, end, duration, detail = null } = startOrOptions)
+        } else {
+            [start, end] = [startOrOptions, endMark]
+        }
+        if (start !== undefined) {
+            start = _componentizeJsMarkTime(start)
+        }
+        if (end !== undefined) {
+            end = _componentizeJsMarkTime(end)
+        }
+        if (start === undefined) {
+            start = duration !== undefined && end !== undefined ? end - duration : 0
+        }
+        if (end === undefined) {
+            end = duration !== undefined ? start + duration : performance.now()
+        }
+        const entry = {
+            name: String(name),
+            entryType: 'measure',
+            startTime: start,
+            duration: end - start,
+            detail
+        }
+        _componentizeJsPerformanceEntries.push(entry)
+        return entry
+    },
+
+    getEntries: () => _componentizeJsPerformanceEntries.slice(),
+
+    getEntriesByType: (type) =>
+        _componentizeJsPerformanceEntries.filter((entry) => entry.entryType === type),
+
+    getEntriesByName: (name, type) =>
+        _componentizeJsPerformanceEntries.filter(
+            (entry) => entry.name === name && (type === undefined || entry.entryType === type)
+        ),
+
+    clearMarks: function(name) {
+        _componentizeJsPerformanceEntries = _componentizeJsPerformanceEntries.filter(
+            (entry) => entry.entryType !== 'mark' || (name !== undefined && entry.name !== name)
+        )
+    },
+
+    clearMeasures: function(name) {
+        _componentizeJsPerformanceEntries = _componentizeJsPerformanceEntries.filter(
+            (entry) => entry.entryType !== 'measure' || (name !== undefined && entry.name !== name)
+        )
+    },
+
+    toJSON: () => ({ timeOrigin: performance.timeOrigin })
+}
+
+// Deep-copy `value` per the HTML standard's structured clone algorithm.
+//
+// Any `ArrayBuffer`s listed in `options.transfer` are detached once the clone
+// has been made, which is observably equivalent to transferring them.
+var structuredClone = function(value, options = {}) {
+    const transfer = Array.from(options.transfer ?? [])
+    transfer.forEach((buffer, index) => {
+        if (!(buffer instanceof ArrayBuffer)) {
+            throw _componentizeJsDataCloneError('only `ArrayBuffer`s may be transferred')
+        } else if (transfer.indexOf(buffer) !== index) {
+            throw _componentizeJsDataCloneError('an `ArrayBuffer` may only be transferred once')
+        } else if (buffer.detached) {
+            throw _componentizeJsDataCloneError('cannot transfer a detached `ArrayBuffer`')
+        }
+    })
+
+    let clone
+    try {
+        clone = _componentizeJsStructuredClone(value)
+    } catch (error) {
+        // SpiderMonkey reports uncloneable values as `TypeError`s; anything
+        // else (e.g. thrown by a getter) propagates as-is.
+        throw error instanceof TypeError
+            ? _componentizeJsDataCloneError(error.message, { cause: error })
+            : error
+    }
+
+    for (const buffer of transfer) {
+        buffer.transfer()
+    }
+    return clone
+}
+
+var _componentizeJsDataCloneError = function(message, options) {
+    const error = new Error(message, options)
+    error.name = 'DataCloneError'
+    return error
+}
+
+// Bindings for the world's `wasi:http/types@0.3.x` interface (plus
+// `wasi:http/client#send`, if imported), set by the generated code, along with
+// constructors for the streams and futures they use.
+var _componentizeJsHttp = undefined
+
+var Headers = class {
+    #entries = []
+
+    constructor(init) {
+        if (init instanceof Headers) {
+            this.#entries = init.#entries.map(([name, value]) => [name, value])
+        } else if (init !== undefined && init !== null) {
+            const pairs = typeof init[Symbol.iterator] === 'function'
+                ? Array.from(init, (pair) => Array.from(pair))
+                : Object.entries(init)
+            for (const pair of pairs) {
+                if (pair.length !== 2) {
+                    throw new TypeError('header pairs must contain exactly two items')
+                }
+                this.append(pair[0], pair[1])
+            }
+        }
+    }
+
+    append(name, value) {
+        this.#entries.push([_componentizeJsHeaderName(name), _componentizeJsHeaderValue(value)])
+    }
+
+    delete(name) {
+        name = _componentizeJsHeaderName(name)
+        this.#entries = this.#entries.filter(([k]) => k !== name)
+    }
+
+    get(name) {
+        name = _componentizeJsHeaderName(name)
+        const values = this.#entries.filter(([k]) => k === name).map(([, v]) => v)
+        return values.length === 0 ? null : values.join(', ')
+    }
+
+    getSetCookie() {
+        return this.#entries.filter(([k]) => k === 'set-cookie').map(([, v]) => v)
+    }
+
+    has(name) {
+        name = _componentizeJsHeaderName(name)
+        return this.#entries.some(([k]) => k === name)
+    }
+
+    set(name, value) {
+        name = _componentizeJsHeaderName(name)
+        value = _componentizeJsHeaderValue(value)
+        const index = this.#entries.findIndex(([k]) => k === name)
+        if (index === -1) {
+            this.#entries.push([name, value])
+        } else {
+            this.#entries[index][1] = value
+            this.#entries = this.#entries.filter(([k], i) => k !== name || i <= index)
+        }
+    }
+
+    forEach(callback, thisArg) {
+        for (const [name, value] of this) {
+            callback.call(thisArg, value, name, this)
+        }
+    }
+
+    // Per the Fetch standard, iteration is sorted by name, with values for the
+    // same name combined, except for `set-cookie`.
+    *entries() {
+        const names = [...new Set(this.#entries.map(([k]) => k))].sort()
+        for (const name of names) {
+            if (name === 'set-cookie') {
+                for (const value of this.getSetCookie()) {
+                    yield [name, value]
+                }
+            } else {
+                yield [name, this.get(name)]
+            }
+        }
+    }
+
+    *keys() {
+        for (const [name] of this) {
+            yield name
+        }
+    }
+
+    *values() {
+        for (const [, value] of this) {
+            yield value
+        }
+    }
+
+    [Symbol.iterator]() {
+        return this.entries()
+    }
+}
+
+var _componentizeJsHeaderName = function(name) {
+    name = String(name)
+    if (!/^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/.test(name)) {
+        throw new TypeError(`invalid header name: ${name}`)
+    }
+    return name.toLowerCase()
+}
+
+var _componentizeJsHeaderValue = function(value) {
+    value = String(value).replace(/^[\t\n\r ]+|[\t\n\r ]+$/g, '')
+    if (/[\0\n\r]/.test(value)) {
+        throw new TypeError(`invalid header value: ${value}`)
+    }
+    return value
+}
+
+
+// Key for whether a `ReadableStream` has been read from or canceled, which
+// `Request` and `Response` use to implement `bodyUsed`.
+var _componentizeJsDisturbed = Symbol('disturbed')
+
+// A subset of the Streams standard's `ReadableStream`, supporting default (i.e.
+// not byte) streams and default readers.  Queued chunks are counted against
+// the high water mark individually, regardless of size.
+var ReadableStream = class {
+    #source
+    #controller
+    #highWaterMark
+    #queue = []
+    #state = 'readable'
+    #storedError
+    #started = false
+    #pulling = false
+    #pullAgain = false
+    #closeRequested = false
+    #disturbed = false
+    #reader = null
+    #readerClosed = null
+    #readRequests = []
+
+    constructor(source = {}, strategy = {}) {
+        if (source.type !== undefined) {
+            throw new RangeError(`unsupported stream type: ${source.type}`)
+        }
+        this.#source = source
+        this.#highWaterMark = strategy.highWaterMark ?? 1
+
+        const stream = this
+        this.#controller = {
+            get desiredSize() { return stream.#desiredSize() },
+            enqueue: (chunk) => this.#enqueue(chunk),
+            close: () => this.#close(),
+            error: (error) => this.#error(error)
+        }
+
+        Promise.resolve(source.start?.call(source, this.#controller)).then(
+            () => {
+                this.#started = true
+                this.#pullIfNeeded()
+            },
+            (error) => this.#error(error)
+        )
+    }
+
+    get locked() { return this.#reader !== null }
+
+    get [_componentizeJsDisturbed]() { return this.#disturbed }
+
+    async cancel(reason) {
+        if (this.#reader !== null) {
+            throw new TypeError('stream is locked')
+        }
+        return this.#cancel(reason)
+    }
+
+    getReader({ mode } = {}) {
+        if (mode !== undefined) {
+            throw new RangeError(`unsupported reader mode: ${mode}`)
+        } else if (this.#reader !== null) {
+            throw new TypeError('stream is locked')
+        }
+
+        const stream = this
+        const closed = new Promise((resolve, reject) => {
+            this.#readerClosed = { resolve, reject }
+        })
+        closed.catch(() => {})
+        const released = () => stream.#reader !== reader
+        const reader = {
+            get closed() { return closed },
+
+            read() {
+                return released()
+                    ? Promise.reject(new TypeError('reader has been released'))
+                    : stream.#read()
+            },
+
+            cancel(reason) {
+                return released()
+                    ? Promise.reject(new TypeError('reader has been released'))
+                    : stream.#cancel(reason)
+            },
+
+            releaseLock() {
+                if (released()) {
+                    return
+                }
+                const error = new TypeError('reader has been released')
+                for (const request of stream.#readRequests.splice(0)) {
+                    request.reject(error)
+                }
+                stream.#readerClosed.reject(error)
+                stream.#reader = null
+                stream.#readerClosed = null
+            }
+        }
+        this.#reader = reader
+
+        if (this.#state === 'closed') {
+            this.#readerClosed.resolve()
+        } else if (this.#state === 'errored') {
+            this.#readerClosed.reject(this.#storedError)
+        }
+        return reader
+    }
+
+    // Unlike the standard, this treats both branches as reading from the same
+    // underlying source, so the faster one buffers chunks for the slower one.
+    tee() {
+        const reader = this.getReader()
+        const controllers = []
+        const canceled = [false, false]
+        let reading = null
+        const pull = () => reading ??= reader.read().then(
+            ({ value, done }) => {
+                reading = null
+                controllers.forEach((controller, i) => {
+                    if (!canceled[i]) {
+                        done ? controller.close() : controller.enqueue(value)
+                    }
+                })
+            },
+            (error) => controllers.forEach((controller) => controller.error(error))
+        )
+        return [0, 1].map((i) => new ReadableStream({
+            start: (controller) => { controllers[i] = controller },
+            pull,
+            cancel: (reason) => {
+                canceled[i] = true
+                if (canceled.every((x) => x)) {
+                    return reader.cancel(reason)
+                }
+            }
+        }))
+    }
+
+    async *values({ preventCancel = false } = {}) {
+        const reader = this.getReader()
+        let finished = false
+        try {
+            while (true) {
+                const { value, done } = await reader.read()
+                if (done) {
+                    finished = true
+                    return
+                }
+                yield value
+            }
+        } catch (error) {
+            finished = true
+            throw error
+        } finally {
+            if (!finished && !preventCancel) {
+                await reader.cancel()
+            }
+            reader.releaseLock()
+        }
+    }
+
+    [Symbol.asyncIterator]() {
+        return this.values()
+    }
+
+    async pipeTo(destination, { preventClose = false, preventAbort = false, preventCancel = false, signal } = {}) {
+        const reader = this.getReader()
+        const writer = destination.getWriter()
+        let onAbort
+        const aborted = new Promise((_, reject) => {
+            if (signal !== undefined) {
+                onAbort = () => reject(signal.reason)
+                signal.addEventListener('abort', onAbort)
+            }
+        })
+        aborted.catch(() => {})
+        try {
+            signal?.throwIfAborted()
+            while (true) {
+                await Promise.race([writer.ready, aborted])
+                const { value, done } = await Promise.race([reader.read(), aborted])
+                if (done) {
+                    break
+                }
+                await Promise.race([writer.write(value), aborted])
+            }
+            if (!preventClose) {
+                await writer.close()
+            }
+        } catch (error) {
+            if (!preventAbort) {
+                await writer.abort(error).catch(() => {})
+            }
+            if (!preventCancel) {
+                await reader.cancel(error).catch(() => {})
+            }
+            throw error
+        } finally {
+            signal?.removeEventListener('abort', onAbort)
+            reader.releaseLock()
+            writer.releaseLock()
+        }
+    }
+
+    pipeThrough({ writable, readable }, options) {
+        this.pipeTo(writable, options).catch(() => {})
+        return readable
+    }
+
+    static from(iterable) {
+        const iterator = iterable[Symbol.asyncIterator] !== undefined
+            ? iterable[Symbol.asyncIterator]()
+            : iterable[Symbol.iterator]()
+        return new ReadableStream({
+            pull: async (controller) => {
+                const { value, done } = await iterator.next()
+                done ? controller.close() : controller.enqueue(value)
+            },
+            cancel: async (reason) => {
+                await iterator.return?.(reason)
+            }
+        }, { highWaterMark: 0 })
+    }
+
+    #desiredSize() {
+        switch (this.#state) {
+            case 'errored': return null
+            case 'closed': return 0
+            default: return this.#highWaterMark - this.#queue.length
+        }
+    }
+
+    #enqueue(chunk) {
+        if (this.#closeRequested || this.#state !== 'readable') {
+            throw new TypeError('stream is closed')
+        }
+        if (this.#readRequests.length > 0) {
+            this.#readRequests.shift().resolve({ value: chunk, done: false })
+        } else {
+            this.#queue.push(chunk)
+        }
+        this.#pullIfNeeded()
+    }
+
+    #close() {
+        if (this.#closeRequested || this.#state !== 'readable') {
+            throw new TypeError('stream is closed')
+        }
+        this.#closeRequested = true
+        if (this.#queue.length === 0) {
+            this.#finishClose()
+        }
+    }
+
+    #finishClose() {
+        this.#state = 'closed'
+        for (const request of this.#readRequests.splice(0)) {
+            request.resolve({ value: undefined, done: true })
+        }
+        this.#readerClosed?.resolve()
+    }
+
+    #error(error) {
+        if (this.#state !== 'readable') {
+            return
+        }
+        this.#state = 'errored'
+        this.#storedError = error
+        this.#queue = []
+        for (const request of this.#readRequests.splice(0)) {
+            request.reject(error)
+        }
+        this.#readerClosed?.reject(error)
+    }
+
+    #pullIfNeeded() {
+        if (!this.#started || this.#state !== 'readable' || this.#closeRequested) {
+            return
+        }
+        if (this.#readRequests.length === 0 && this.#desiredSize() <= 0) {
+            return
+        }
+        if (this.#pulling) {
+            this.#pullAgain = true
+            return
+        }
+        this.#pulling = true
+        Promise.resolve().then(() => this.#source.pull?.call(this.#source, this.#controller)).then(
+            () => {
+                this.#pulling = false
+                if (this.#pullAgain) {
+                    this.#pullAgain = false
+                    this.#pullIfNeeded()
+                }
+            },
+            (error) => this.#error(error)
+        )
+    }
+
+    #read() {
+        this.#disturbed = true
+        if (this.#state === 'closed') {
+            return Promise.resolve({ value: undefined, done: true })
+        } else if (this.#state === 'errored') {
+            return Promise.reject(this.#storedError)
+        } else if (this.#queue.length > 0) {
+            const value = this.#queue.shift()
+            if (this.#closeRequested && this.#queue.length === 0) {
+                this.#finishClose()
+            } else {
+                this.#pullIfNeeded()
+            }
+            return Promise.resolve({ value, done: false })
+        } else {
+            const promise = new Promise((resolve, reject) => {
+                this.#readRequests.push({ resolve, reject })
+            })
+            this.#pullIfNeeded()
+            return promise
+        }
+    }
+
+    async #cancel(reason) {
+        this.#disturbed = true
+        if (this.#state === 'closed') {
+            return
+        } else if (this.#state === 'errored') {
+            throw this.#storedError
+        }
+        this.#queue = []
+        this.#finishClose()
+        await this.#source.cancel?.call(this.#source, reason)
+    }
+}
+
+// A subset of the Streams standard's `WritableStream`, supporting default
+// writers.  Queued chunks are counted against the high water mark
+// individually, regardless of size.
+var WritableStream = class {
+    #sink
+    #controller
+    #highWaterMark
+    #queue = []
+    #state = 'writable'
+    #storedError
+    #started = false
+    #inFlight = false
+    #closeRequested = false
+    #writer = null
+    #writerClosed = null
+    #readyWaiters = []
+    #abortController = new AbortController()
+
+    constructor(sink = {}, strategy = {}) {
+        if (sink.type !== undefined) {
+            throw new RangeError(`unsupported stream type: ${sink.type}`)
+        }
+        this.#sink = sink
+        this.#highWaterMark = strategy.highWaterMark ?? 1
+        this.#controller = {
+            signal: this.#abortController.signal,
+            error: (error) => this.#error(error)
+        }
+
+        Promise.resolve(sink.start?.call(sink, this.#controller)).then(
+            () => {
+                this.#started = true
+                this.#advance()
+            },
+            (error) => this.#error(error)
+        )
+    }
+
+    get locked() { return this.#writer !== null }
+
+    async abort(reason) {
+        if (this.#writer !== null) {
+            throw new TypeError('stream is locked')
+        }
+        return this.#abort(reason)
+    }
+
+    async close() {
+        if (this.#writer !== null) {
+            throw new TypeError('stream is locked')
+        }
+        return this.#close()
+    }
+
+    getWriter() {
+        if (this.#writer !== null) {
+            throw new TypeError('stream is locked')
+        }
+
+        const stream = this
+        const closed = new Promise((resolve, reject) => {
+            this.#writerClosed = { resolve, reject }
+        })
+        closed.catch(() => {})
+        const released = () => stream.#writer !== writer
+        const rejectReleased = () => Promise.reject(new TypeError('writer has been released'))
+        const writer = {
+            get closed() { return closed },
+
+            get ready() {
+                return released() ? rejectReleased() : stream.#ready()
+            },
+
+            get desiredSize() {
+                if (released()) {
+                    throw new TypeError('writer has been released')
+                }
+                return stream.#desiredSize()
+            },
+
+            write(chunk) {
+                return released() ? rejectReleased() : stream.#write(chunk)
+            },
+
+            close() {
+                return released() ? rejectReleased() : stream.#close()
+            },
+
+            abort(reason) {
+                return released() ? rejectReleased() : stream.#abort(reason)
+            },
+
+            releaseLock() {
+                if (released()) {
+                    return
+                }
+                stream.#writerClosed.reject(new TypeError('writer has been released'))
+                stream.#writer = null
+                stream.#writerClosed = null
+            }
+        }
+        this.#writer = writer
+
+        if (this.#state === 'closed') {
+            this.#writerClosed.resolve()
+        } else if (this.#state === 'errored') {
+            this.#writerClosed.reject(this.#storedError)
+        }
+        return writer
+    }
+
+    #desiredSize() {
+        switch (this.#state) {
+            case 'errored': return null
+            case 'closed': return 0
+            default: return this.#highWaterMark - this.#queue.length - (this.#inFlight ? 1 : 0)
+        }
+    }
+
+    #ready() {
+        if (this.#state === 'errored') {
+            return Promise.reject(this.#storedError)
+        } else if (this.#state === 'closed' || this.#desiredSize() > 0) {
+            return Promise.resolve()
+        } else {
+            return new Promise((resolve, reject) => this.#readyWaiters.push({ resolve, reject }))
+        }
+    }
+
+    #write(chunk) {
+        if (this.#state === 'errored') {
+            return Promise.reject(this.#storedError)
+        } else if (this.#closeRequested || this.#state === 'closed') {
+            return Promise.reject(new TypeError('stream is closed'))
+        }
+        const promise = new Promise((resolve, reject) => {
+            this.#queue.push({ chunk, resolve, reject })
+        })
+        this.#advance()
+        return promise
+    }
+
+    #close() {
+        if (this.#state === 'errored') {
+            return Promise.reject(this.#storedError)
+        } else if (this.#closeRequested || this.#state === 'closed') {
+            return Promise.reject(new TypeError('stream is closed'))
+        }
+        this.#closeRequested = true
+        const promise = new Promise((resolve, reject) => {
+            this.#queue.push({ close: true, resolve, reject })
+        })
+        this.#advance()
+        return promise
+    }
+
+    async #abort(reason) {
+        if (this.#state !== 'writable') {
+            return
+        }
+        this.#abortController.abort(reason)
+        this.#error(reason)
+        await this.#sink.abort?.call(this.#sink, reason)
+    }
+
+    #error(error) {
+        if (this.#state !== 'writable') {
+            return
+        }
+        this.#state = 'errored'
+        this.#storedError = error
+        for (const request of this.#queue.splice(0)) {
+            request.reject(error)
+        }
+        for (const waiter of this.#readyWaiters.splice(0)) {
+            waiter.reject(error)
+        }
+        this.#writerClosed?.reject(error)
+    }
+
+    #advance() {
+        if (!this.#started || this.#inFlight || this.#state !== 'writable' || this.#queue.length === 0) {
+            return
+        }
+        const request = this.#queue.shift()
+        this.#inFlight = true
+        const sink = this.#sink
+        Promise.resolve()
+            .then(() => request.close
+                ? sink.close?.call(sink)
+                : sink.write?.call(sink, request.chunk, this.#controller))
+            .then(
+                () => {
+                    this.#inFlight = false
+                    if (request.close) {
+                        this.#state = 'closed'
+                        this.#writerClosed?.resolve()
+                    }
+                    request.resolve()
+                    if (this.#desiredSize() > 0 || this.#state === 'closed') {
+                        for (const waiter of this.#readyWaiters.splice(0)) {
+                            waiter.resolve()
+                        }
+                    }
+                    this.#advance()
+                },
+                (error) => {
+                    this.#inFlight = false
+                    this.#error(error)
+                    request.reject(error)
+                }
+            )
+    }
+}
+
+// A subset of the Streams standard's `TransformStream`.
+var TransformStream = class {
+    #readable
+    #writable
+
+    constructor(transformer = {}, writableStrategy = {}, readableStrategy = { highWaterMark: 0 }) {
+        let readableController
+        let writableController
+        // Whether the readable side's queue is full, in which case writes wait
+        // until it's pulled from.
+        let backpressure = true
+        let relieve = () => {}
+
+        const controller = {
+            get desiredSize() { return readableController.desiredSize },
+
+            enqueue: (chunk) => {
+                readableController.enqueue(chunk)
+                backpressure = readableController.desiredSize <= 0
+            },
+
+            error: (error) => {
+                readableController.error(error)
+                writableController.error(error)
+            },
+
+            terminate: () => {
+                readableController.close()
+                writableController.error(new TypeError('stream has been terminated'))
+            }
+        }
+
+        this.#readable = new ReadableStream({
+            start: (controller) => { readableController = controller },
+            pull: () => {
+                backpressure = false
+                relieve()
+            },
+            cancel: async (reason) => {
+                writableController.error(reason)
+                await transformer.cancel?.call(transformer, reason)
+            }
+        }, readableStrategy)
+
+        this.#writable = new WritableStream({
+            start: (controller) => {
+                writableController = controller
+                return transformer.start?.call(transformer, controller)
+            },
+            write: async (chunk) => {
+                while (backpressure) {
+                    await new Promise((resolve) => { relieve = resolve })
+                }
+                if (transformer.transform === undefined) {
+                    controller.enqueue(chunk)
+                } else {
+                    await transformer.transform.call(transformer, chunk, controller)
+                }
+            },
+            close: async () => {
+                await transformer.flush?.call(transformer, controller)
+                readableController.close()
+            },
+            abort: (reason) => {
+                readableController.error(reason)
+            }
+        }, writableStrategy)
+    }
+
+    get readable() { return this.#readable }
+    get writable() { return this.#writable }
+}
+
+// Key for the contents of a `Blob`, which `Request` and `Response` use to
+// extract a body synchronously.
+var _componentizeJsBlobBytes = Symbol('blobBytes')
+
+// The File API's `Blob`, holding its contents in memory.
+var Blob = class {
+    #bytes
+    #type
+
+    constructor(parts = [], { type = '', endings = 'transparent' } = {}) {
+        if (endings !== 'transparent' && endings !== 'native') {
+            throw new TypeError(`invalid endings: ${endings}`)
+        }
+        const encoder = new TextEncoder()
+        const chunks = []
+        for (const part of parts) {
+            if (part instanceof Blob) {
+                chunks.push(part.#bytes)
+            } else if (part instanceof ArrayBuffer || ArrayBuffer.isView(part)) {
+                chunks.push(_componentizeJsBytes(part))
+            } else {
+                const string = String(part)
+                chunks.push(encoder.encode(endings === 'native' ? string.replace(/\r\n?/g, '\n') : string))
+            }
+        }
+        this.#bytes = _componentizeJsConcat(chunks)
+        this.#type = _componentizeJsBlobType(type)
+    }
+
+    get size() { return this.#bytes.length }
+    get type() { return this.#type }
+    get [Symbol.toStringTag]() { return 'Blob' }
+
+    get [_componentizeJsBlobBytes]() { return this.#bytes }
+
+    slice(start = 0, end = this.#bytes.length, type = '') {
+        const blob = new Blob()
+        blob.#bytes = this.#bytes.subarray(start, end)
+        blob.#type = _componentizeJsBlobType(type)
+        return blob
+    }
+
+    async bytes() {
+        return this.#bytes.slice()
+    }
+
+    async arrayBuffer() {
+        return this.#bytes.slice().buffer
+    }
+
+    async text() {
+        return new TextDecoder().decode(this.#bytes)
+    }
+
+    stream() {
+        const bytes = this.#bytes
+        let offset = 0
+        return new ReadableStream({
+            pull: (controller) => {
+                if (offset < bytes.length) {
+                    controller.enqueue(bytes.slice(offset, offset += 64 * 1024))
+                } else {
+                    controller.close()
+                }
+            }
+        }, { highWaterMark: 0 })
+    }
+}
+
+// Normalize a `Blob` type, which must be printable ASCII (or else is ignored).
+var _componentizeJsBlobType = function(type) {
+    type = String(type)
+    return /^[\x20-\x7e]*$/.test(type) ? type.toLowerCase() : ''
+}
+
+// The File API's `File`, i.e. a `Blob` with a name and modification time.
+var File = class extends Blob {
+    #name
+    #lastModified
+
+    constructor(parts, name, options = {}) {
+        if (arguments.length < 2) {
+            throw new TypeError('File constructor requires at least two arguments')
+        }
+        super(parts, options)
+        this.#name = String(name)
+        this.#lastModified = options.lastModified === undefined ? Date.now() : Number(options.lastModified)
+    }
+
+    get name() { return this.#name }
+    get lastModified() { return this.#lastModified }
+    get [Symbol.toStringTag]() { return 'File' }
+}
+
+// Extract a body from what was passed to the `Request` or `Response`
+// constructor, setting a default `content-type` in `headers` if appropriate.
+var _componentizeJsExtractBody = function(body, headers) {
+    if (body === undefined || body === null) {
+        return null
+    } else if (body instanceof ReadableStream) {
+        if (body.locked || body[_componentizeJsDisturbed]) {
+            throw new TypeError('body stream is locked or has already been read')
+        }
+        return body
+    } else if (body instanceof ArrayBuffer || ArrayBuffer.isView(body)) {
+        return _componentizeJsBytes(body).slice()
+    } else if (body instanceof Blob) {
+        if (body.type !== '' && !headers.has('content-type')) {
+            headers.set('content-type', body.type)
+        }
+        return body[_componentizeJsBlobBytes].slice()
+    } else {
+        if (!headers.has('content-type')) {
+            headers.set('content-type', 'text/plain;charset=UTF-8')
+        }
+        return new TextEncoder().encode(String(body))
+    }
+}
+
+// Keys for the internals of `Request` and `Response`, which are used by
+// `fetch`, etc. but hidden from application code.
+var _componentizeJsNew = Symbol('new')
+var _componentizeJsTakeBody = Symbol('takeBody')
+var _componentizeJsCloneBody = Symbol('cloneBody')
+var _componentizeJsSetBody = Symbol('setBody')
+
+// The Fetch standard's `Body` mixin, shared by `Request` and `Response`.
+//
+// A body is either `null`, a `Uint8Array`, a `ReadableStream`, or an
+// `{ rx, done, signal }` object holding a `stream<u8>` reader, a function to
+// call once it has been read to the end, and an optional `AbortSignal` for
+// abandoning it.  The latter is only wrapped in a `ReadableStream` if the
+// application asks for one, since most bodies are either read in full or
+// forwarded as-is.
+var _componentizeJsBody = class {
+    #body
+    #bodyUsed = false
+
+    constructor(body) {
+        this.#body = body
+    }
+
+    get body() {
+        if (this.#body !== null && !(this.#body instanceof ReadableStream)) {
+            this.#body = _componentizeJsBodyStream(this.#body)
+        }
+        return this.#body
+    }
+
+    get bodyUsed() {
+        return this.#bodyUsed
+            || (this.#body instanceof ReadableStream && this.#body[_componentizeJsDisturbed])
+    }
+
+    [_componentizeJsSetBody](body) {
+        this.#body = body
+    }
+
+    [_componentizeJsTakeBody]() {
+        if (this.bodyUsed) {
+            throw new TypeError('body has already been consumed')
+        } else if (this.#body instanceof ReadableStream && this.#body.locked) {
+            throw new TypeError('body stream is locked')
+        }
+        const body = this.#body
+        this.#body = null
+        this.#bodyUsed = body !== null
+        return body
+    }
+
+    [_componentizeJsCloneBody]() {
+        if (this.bodyUsed) {
+            throw new TypeError('body has already been consumed')
+        }
+        if (this.#body === null) {
+            return null
+        } else if (this.#body instanceof Uint8Array) {
+            return this.#body.slice()
+        } else {
+            const [a, b] = this.body.tee()
+            this.#body = a
+            return b
+        }
+    }
+
+    async bytes() {
+        const body = this[_componentizeJsTakeBody]()
+        if (body === null) {
+            return new Uint8Array(0)
+        } else if (body instanceof Uint8Array) {
+            return body
+        } else if (body instanceof ReadableStream) {
+            const chunks = []
+            for await (const chunk of body) {
+                chunks.push(_componentizeJsBytes(chunk))
+            }
+            return _componentizeJsConcat(chunks)
+        } else {
+            try {
+                return await _componentizeJsReadAll(body.rx, body.signal)
+            } finally {
+                body.done()
+            }
+        }
+    }
+
+    async arrayBuffer() {
+        const bytes = await this.bytes()
+        return bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.byteLength)
+    }
+
+    async text() {
+        return new TextDecoder().decode(await this.bytes())
+    }
+
+    async blob() {
+        return new Blob([await this.bytes()], { type: this.headers.get('content-type') ?? '' })
+    }
+
+    async json() {
+        return JSON.parse(await this.text())
+    }
+}
+
+// Wrap a `Uint8Array` or `{ rx, done }` body in a `ReadableStream`.
+var _componentizeJsBodyStream = function(body) {
+    if (body instanceof Uint8Array) {
+        return new ReadableStream({
+            start: (controller) => {
+                controller.enqueue(body)
+                controller.close()
+            }
+        })
+    }
+
+    const { rx, done, signal } = body
+    const finish = () => {
+        rx[Symbol.dispose]()
+        done()
+    }
+    return new ReadableStream({
+        start: (controller) => {
+            signal?.addEventListener('abort', () => controller.error(signal.reason))
+        },
+        pull: async (controller) => {
+            while (!rx.writerDropped) {
+                const chunk = await rx.read(16 * 1024)
+                signal?.throwIfAborted()
+                if (chunk.length > 0) {
+                    controller.enqueue(chunk)
+                    return
+                }
+            }
+            finish()
+            controller.close()
+        },
+        cancel: finish
+    }, { highWaterMark: 0 })
+}
+
+var Request = class extends _componentizeJsBody {
+    #method
+    #url
+    #headers
+    #signal
+
+    constructor(input, init = {}) {
+        const source = input instanceof Request ? input : null
+        const url = source?.url ?? String(input)
+        _componentizeJsParseUrl(url)
+        const method = _componentizeJsNormalizeMethod(init.method ?? source?.method ?? 'GET')
+        const headers = new Headers(init.headers ?? source?.headers)
+        let body
+        if (init.body !== undefined && init.body !== null) {
+            body = _componentizeJsExtractBody(init.body, headers)
+            if (body instanceof ReadableStream && init.duplex !== 'half') {
+                throw new TypeError("`duplex: 'half'` is required for `ReadableStream` bodies")
+            }
+        } else if (source !== null && init.body === undefined) {
+            body = source[_componentizeJsTakeBody]()
+        } else {
+            body = null
+        }
+        if (body !== null && (method === 'GET' || method === 'HEAD')) {
+            throw new TypeError(`${method} requests cannot have a body`)
+        }
+
+        super(body)
+        this.#method = method
+        this.#url = url
+        this.#headers = headers
+        this.#signal = init.signal ?? source?.signal ?? new AbortSignal(_componentizeJsNewAbortSignal)
+    }
+
+    get method() { return this.#method }
+    get url() { return this.#url }
+    get headers() { return this.#headers }
+    get duplex() { return 'half' }
+    get signal() { return this.#signal }
+
+    clone() {
+        return Request[_componentizeJsNew]({
+            method: this.#method,
+            url: this.#url,
+            headers: this.#headers,
+            body: this[_componentizeJsCloneBody](),
+            signal: this.#signal
+        })
+    }
+
+    // Unlike the constructor, this allows any body (e.g. the possibly-empty
+    // stream belonging to an incoming `GET` request).
+    static [_componentizeJsNew]({ method, url, headers, body, signal }) {
+        const request = new Request(url, { method, headers, signal })
+        request[_componentizeJsSetBody](body)
+        return request
+    }
+}
+
+var Response = class extends _componentizeJsBody {
+    #headers
+    #status
+    #statusText
+    #type = 'default'
+    #url = ''
+
+    constructor(body = null, init = {}) {
+        const status = init.status ?? 200
+        if (!Number.isInteger(status) || status < 200 || status > 599) {
+            throw new RangeError(`invalid status: ${status}`)
+        }
+        const statusText = String(init.statusText ?? '')
+        if (/[\r\n]/.test(statusText)) {
+            throw new TypeError(`invalid status text: ${statusText}`)
+        }
+        const headers = new Headers(init.headers)
+        body = _componentizeJsExtractBody(body, headers)
+        if (body !== null && [204, 205, 304].includes(status)) {
+            throw new TypeError(`responses with status ${status} cannot have a body`)
+        }
+
+        super(body)
+        this.#status = status
+        this.#statusText = statusText
+        this.#headers = headers
+    }
+
+    get status() { return this.#status }
+    get statusText() { return this.#statusText }
+    get ok() { return this.#status >= 200 && this.#status <= 299 }
+    get headers() { return this.#headers }
+    get url() { return this.#url }
+    get redirected() { return false }
+    get type() { return this.#type }
+
+    clone() {
+        return Response[_componentizeJsNew]({
+            body: this[_componentizeJsCloneBody](),
+            status: this.#status,
+            statusText: this.#statusText,
+            headers: this.#headers,
+            url: this.#url,
+            type: this.#type
+        })
+    }
+
+    static error() {
+        return Response[_componentizeJsNew]({ body: null, status: 0, type: 'error' })
+    }
+
+    static redirect(url, status = 302) {
+        url = String(url)
+        _componentizeJsParseUrl(url)
+        if (![301, 302, 303, 307, 308].includes(status)) {
+            throw new RangeError(`invalid redirect status: ${status}`)
+        }
+        return new Response(null, { status, headers: { location: url } })
+    }
+
+    static json(data, init = {}) {
+        const text = JSON.stringify(data)
+        if (text === undefined) {
+            throw new TypeError('data is not JSON-serializable')
+        }
+        const headers = new Headers(init.headers)
+        if (!headers.has('content-type')) {
+            headers.set('content-type', 'application/json')
+        }
+        return new Response(text, { ...init, headers })
+    }
+
+    static [_componentizeJsNew]({ body, status, statusText = '', headers, url = '', type = 'default' }) {
+        const response = new Response(null, { headers })
+        response[_componentizeJsSetBody](body)
+        response.#status = status
+        response.#statusText = statusText
+        response.#url = url
+        response.#type = type
+        return response
+    }
+}
+
+// Split an absolute `http:` or `https:` URL into the parts `wasi:http` uses.
+var _componentizeJsParseUrl = function(url) {
+    const match = /^([A-Za-z][A-Za-z0-9+.-]*):\/\/([^/?#]*)([^#]*)/.exec(url)
+    if (match === null) {
+        throw new TypeError(`invalid URL: ${url}`)
+    }
+    const [, scheme, authority, path] = match
+    const tag = scheme.toLowerCase()
+    return {
+        scheme: tag === 'http' ? { tag: 'HTTP' }
+            : tag === 'https' ? { tag: 'HTTPS' }
+            : { tag: 'other', val: scheme },
+        authority,
+        pathWithQuery: path.startsWith('/') ? path : `/${path}`
+    }
+}
+
+// Validate an HTTP method name, normalizing the case of the ones the Fetch
+// standard says to.
+var _componentizeJsNormalizeMethod = function(method) {
+    method = String(method)
+    if (!/^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/.test(method)) {
+        throw new TypeError(`invalid method: ${method}`)
+    }
+    const upper = method.toUpperCase()
+    return ['DELETE', 'GET', 'HEAD', 'OPTIONS', 'POST', 'PUT'].includes(upper) ? upper : method
+}
+
+// Convert an HTTP method name to a `wasi:http/types#method`.
+var _componentizeJsMethod = function(method) {
+    method = String(method)
+    const standard = ['GET', 'HEAD', 'POST', 'PUT', 'DELETE', 'CONNECT', 'OPTIONS', 'TRACE', 'PATCH']
+    return standard.includes(method.toUpperCase())
+        ? { tag: method.toLowerCase() }
+        : { tag: 'other', val: method }
+}
+
+// Convert `Headers` to a `wasi:http/types#fields`.
+var _componentizeJsFields = function(headers) {
+    const encoder = new TextEncoder()
+    return _componentizeJsHttp.Fields.fromList(
+        Array.from(headers, ([name, value]) => [name, encoder.encode(value)])
+    )
+}
+
+// Convert a `wasi:http/types#fields` to `Headers`, taking ownership of it.
+var _componentizeJsHeaders = function(fields) {
+    using _fields = fields
+    const decoder = new TextDecoder()
+    return new Headers(fields.copyAll().map(([name, value]) => [name, decoder.decode(value)]))
+}
+
+// Concatenate the specified `Uint8Array`s.
+var _componentizeJsConcat = function(chunks) {
+    const bytes = new Uint8Array(chunks.reduce((length, chunk) => length + chunk.length, 0))
+    let offset = 0
+    for (const chunk of chunks) {
+        bytes.set(chunk, offset)
+        offset += chunk.length
+    }
+    return bytes
+}
+
+// Read all remaining bytes from a `stream<u8>` reader, stopping early if
+// `signal` (if any) is aborted.
+var _componentizeJsReadAll = async function(rx, signal) {
+    using _rx = rx
+    const chunks = []
+    while (!rx.writerDropped) {
+        signal?.throwIfAborted()
+        chunks.push(await rx.read(16 * 1024))
+    }
+    return _componentizeJsConcat(chunks)
+}
+
+// Start writing a `Request` or `Response` body to a new `stream<u8>`,
+// returning the reader for it (if any) plus a reader for its trailers.
+//
+// The writing happens concurrently with sending the request or returning the
+// response, since the host won't read the body until then.  If that fails, the
+// host drops the reader, ending the write (and canceling the body, if it's a
+// `ReadableStream`).
+var _componentizeJsWriteBody = function(body) {
+    const http = _componentizeJsHttp
+    const [bodyTx, bodyRx] = body === null ? [] : http.stream()
+    const [trailersTx, trailersRx] = http.trailersFuture()
+    ;(async () => {
+        if (bodyTx !== undefined) {
+            using _bodyTx = bodyTx
+            if (body instanceof Uint8Array) {
+                await bodyTx.writeAll(body)
+            } else if (body instanceof ReadableStream) {
+                for await (const chunk of body) {
+                    await bodyTx.writeAll(_componentizeJsBytes(chunk))
+                    if (bodyTx.readerDropped) {
+                        break
+                    }
+                }
+            } else {
+                using _rx = body.rx
+                try {
+                    while (!body.rx.writerDropped && !bodyTx.readerDropped) {
+                        await bodyTx.writeAll(await body.rx.read(16 * 1024))
+                    }
+                } finally {
+                    body.done()
+                }
+            }
+        }
+        trailersTx[Symbol.dispose]()
+    })().catch(() => {})
+    return [bodyRx, trailersRx]
+}
+
+// Convert a `wasi:http/types#request` (e.g. as passed to
+// `wasi:http/handler#handle`) to a `Request`, taking ownership of it.  This is
+// exported from the `componentize-js:http` module as `fromWasiRequest`.
+var _componentizeJsFromWasiRequest = function(request) {
+    const http = _componentizeJsHttp
+    const method = request.getMethod()
+    const scheme = request.getScheme()
+    const authority = request.getAuthority() ?? 'localhost'
+    const pathWithQuery = request.getPathWithQuery() ?? '/'
+    const headers = _componentizeJsHeaders(request.getHeaders())
+    const [resultTx, resultRx] = http.bodyFuture()
+    const [rx, trailers] = http.Request.consumeBody(request, resultRx)
+    trailers[Symbol.dispose]()
+
+    return Request[_componentizeJsNew]({
+        method: method.tag === 'other' ? method.val : method.tag.toUpperCase(),
+        url: `${scheme === undefined || scheme === null ? 'http'
+            : scheme.tag === 'other' ? scheme.val
+            : scheme.tag.toLowerCase()}://${authority}${pathWithQuery}`,
+        headers,
+        body: { rx, done: () => resultTx[Symbol.dispose]() }
+    })
+}
+
+// Convert a `Response` to a `wasi:http/types#response` (e.g. to return from
+// `wasi:http/handler#handle`), consuming its body.  This is exported from the
+// `componentize-js:http` module as `toWasiResponse`.
+var _componentizeJsToWasiResponse = function(response) {
+    if (!(response instanceof Response)) {
+        throw new TypeError('expected a `Response`')
+    } else if (response.type === 'error') {
+        throw new TypeError('cannot convert an error response')
+    }
+    const http = _componentizeJsHttp
+    const fields = _componentizeJsFields(response.headers)
+    const [bodyRx, trailersRx] = _componentizeJsWriteBody(response[_componentizeJsTakeBody]())
+    const [result, transmitted] = http.Response.new(fields, bodyRx, trailersRx)
+    transmitted[Symbol.dispose]()
+    result.setStatusCode(response.status)
+    return result
+}
+
+// A subset of the Fetch API, sending requests using `wasi:http/client`.
+var fetch = async function(input, init = {}) {
+    const http = _componentizeJsHttp
+    if (http?.send === undefined) {
+        throw new TypeError('`fetch` requires the world to import `wasi:http/client@0.3.x`')
+    }
+
+    const request = new Request(input, init)
+    const signal = request.signal
+    signal.throwIfAborted()
+    const { scheme, authority, pathWithQuery } = _componentizeJsParseUrl(request.url)
+    const fields = _componentizeJsFields(request.headers)
+    const [bodyRx, trailersRx] = _componentizeJsWriteBody(request[_componentizeJsTakeBody]())
+    const [outgoing, transmitted] = http.Request.new(fields, bodyRx, trailersRx, undefined)
+    transmitted[Symbol.dispose]()
+    outgoing.setMethod(_componentizeJsMethod(request.method))
+    outgoing.setScheme(scheme)
+    outgoing.setAuthority(authority)
+    outgoing.setPathWithQuery(pathWithQuery)
+
+    let response
+    try {
+        response = await http.send(outgoing).cancelOn(signal)
+    } catch (error) {
+        signal.throwIfAborted()
+        throw new TypeError('fetch failed', { cause: error })
+    }
+
+    const headers = _componentizeJsHeaders(response.getHeaders())
+    const status = response.getStatusCode()
+    const [resultTx, resultRx] = http.bodyFuture()
+    const [rx, trailers] = http.Response.consumeBody(response, resultRx)
+    trailers[Symbol.dispose]()
+    return Response[_componentizeJsNew]({
+        body: { rx, done: () => resultTx[Symbol.dispose](), signal },
+        status,
+        headers,
+        url: request.url
+    })
+}
+
+// Bindings for the world's `wasi:filesystem/preopens@0.3.x` interface, set by
+// the generated code, along with a constructor for the byte streams used to
+// write files.
+var _componentizeJsFs = undefined
+
+// Node.js error codes corresponding to `wasi:filesystem/types#error-code`
+// cases, where there's an obvious equivalent.
+var _componentizeJsFsErrorCodes = {
+    'access': 'EACCES',
+    'already': 'EALREADY',
+    'bad-descriptor': 'EBADF',
+    'busy': 'EBUSY',
+    'exist': 'EEXIST',
+    'file-too-large': 'EFBIG',
+    'interrupted': 'EINTR',
+    'invalid': 'EINVAL',
+    'io': 'EIO',
+    'is-directory': 'EISDIR',
+    'loop': 'ELOOP',
+    'name-too-long': 'ENAMETOOLONG',
+    'no-entry': 'ENOENT',
+    'insufficient-memory': 'ENOMEM',
+    'insufficient-space': 'ENOSPC',
+    'not-directory': 'ENOTDIR',
+    'not-empty': 'ENOTEMPTY',
+    'unsupported': 'ENOTSUP',
+    'not-permitted': 'EPERM',
+    'read-only': 'EROFS',
+    'cross-device': 'EXDEV'
+}
+
+// Make a Node.js-style error (with `code`, `syscall`, and `path` properties)
+// for a `wasi:filesystem/types#error-code`.
+var _componentizeJsFsError = function(errorCode, syscall, path, cause) {
+    const code = _componentizeJsFsErrorCodes[errorCode] ?? 'EIO'
+    const error = new Error(`${code}: ${errorCode}, ${syscall} '${path}'`, { cause })
+    error.code = code
+    error.syscall = syscall
+    error.path = path
+    return error
+}
+
+// Split `path` into its normalized segments, returning them along with whether
+// the path is absolute.
+var _componentizeJsPathSegments = function(path) {
+    const segments = []
+    for (const segment of path.split('/')) {
+        if (segment === '..') {
+            segments.pop()
+        } else if (segment !== '' && segment !== '.') {
+            segments.push(segment)
+        }
+    }
+    return [path.startsWith('/'), segments]
+}
+
+// Call `f` with the preopened directory containing `path`, the path relative
+// to it, and the normalized path of the directory itself, converting any
+// `wasi:filesystem` error to a Node.js-style error.
+//
+// Relative paths are resolved against a preopen named `.`, if any, or else
+// against `/`.  The preopens are requested anew for each call (and released
+// afterward) since handles obtained during initialization aren't valid once
+// the snapshot is instantiated.
+var _componentizeJsWithPreopen = async function(path, syscall, f) {
+    const fs = _componentizeJsFs
+    if (fs === undefined) {
+        throw new TypeError('filesystem access requires the world to import `wasi:filesystem/preopens@0.3.x`')
+    }
+    path = String(path)
+    const preopens = fs.getDirectories()
+    try {
+        const [absolute, segments] = _componentizeJsPathSegments(path)
+        const candidates = absolute ? [[true, segments]] : [[false, segments], [true, segments]]
+        for (const [absolute, segments] of candidates) {
+            let best
+            for (const [descriptor, name] of preopens) {
+                const [preopenAbsolute, prefix] = _componentizeJsPathSegments(name)
+                if (preopenAbsolute === absolute
+                    && prefix.every((segment, i) => segments[i] === segment)
+                    && (best === undefined || prefix.length > best[1].length)) {
+                    best = [descriptor, prefix]
+                }
+            }
+            if (best !== undefined) {
+                const relative = segments.slice(best[1].length).join('/')
+                const root = absolute ? `/${best[1].join('/')}` : best[1].join('/')
+                try {
+                    return await f(best[0], relative === '' ? '.' : relative, root)
+                } catch (error) {
+                    if (typeof error?.payload === 'string') {
+                        throw _componentizeJsFsError(error.payload, syscall, path, error)
+                    }
+                    throw error
+                }
+            }
+        }
+        throw _componentizeJsFsError('no-entry', syscall, path)
+    } finally {
+        for (const [descriptor] of preopens) {
+            descriptor[Symbol.dispose]()
+        }
+    }
+}
+
+// Return the encoding specified by the `options` argument of e.g. `readFile`,
+// which may be either an encoding name or an object with an `encoding`
+// property.  Only UTF-8 is supported.
+var _componentizeJsFsEncoding = function(options) {
+    const encoding = typeof options === 'string' ? options : options?.encoding ?? null
+    if (encoding !== null && !/^utf-?8$/i.test(encoding)) {
+        throw new RangeError(`unsupported encoding: ${encoding}`)
+    }
+    return encoding
+}
+
+// Convert a `wasi:clocks/system-clock#instant` (if any) to milliseconds since
+// the Unix epoch.
+var _componentizeJsInstantToMillis = function(instant) {
+    return instant === undefined ? 0 : Number(instant.seconds) * 1000 + instant.nanoseconds / 1e6
+}
+
+var _componentizeJsFsStats = class {
+    #type
+
+    constructor(stat) {
+        this.#type = stat.type
+        this.nlink = Number(stat.linkCount)
+        this.size = Number(stat.size)
+        this.atimeMs = _componentizeJsInstantToMillis(stat.dataAccessTimestamp)
+        this.mtimeMs = _componentizeJsInstantToMillis(stat.dataModificationTimestamp)
+        this.ctimeMs = _componentizeJsInstantToMillis(stat.statusChangeTimestamp)
+        this.atime = new Date(this.atimeMs)
+        this.mtime = new Date(this.mtimeMs)
+        this.ctime = new Date(this.ctimeMs)
+    }
+
+    isFile() { return this.#type === 'regular-file' }
+    isDirectory() { return this.#type === 'directory' }
+    isSymbolicLink() { return this.#type === 'symbolic-link' }
+}
+
+var _componentizeJsFsDirent = class {
+    #type
+
+    constructor(entry, parentPath) {
+        this.#type = entry.type
+        this.name = entry.name
+        this.parentPath = parentPath
+    }
+
+    isFile() { return this.#type === 'regular-file' }
+    isDirectory() { return this.#type === 'directory' }
+    isSymbolicLink() { return this.#type === 'symbolic-link' }
+}
+
+// The following implement the `componentize-js:fs` module, a subset of
+// Node.js's `fs/promises` built on `wasi:filesystem`.
+
+var _componentizeJsReadFile = function(path, options) {
+    const encoding = _componentizeJsFsEncoding(options)
+    return _componentizeJsWithPreopen(path, 'open', async (directory, relative) => {
+        using file = await directory.openAt({ symlinkFollow: true }, relative, {}, { read: true })
+        const [rx, result] = file.readViaStream(0)
+        const bytes = await _componentizeJsReadAll(rx, options?.signal)
+        const { tag, val } = await result.read()
+        if (tag === 'err') {
+            throw _componentizeJsFsError(val, 'read', String(path))
+        }
+        return encoding === null ? bytes : new TextDecoder().decode(bytes)
+    })
+}
+
+var _componentizeJsWriteFile = function(path, data, options) {
+    _componentizeJsFsEncoding(options)
+    const bytes = typeof data === 'string'
+        ? new TextEncoder().encode(data)
+        : _componentizeJsBytes(data)
+    const append = typeof options === 'object' && options?.flag === 'a'
+    return _componentizeJsWithPreopen(path, 'open', async (directory, relative) => {
+        using file = await directory.openAt(
+            { symlinkFollow: true },
+            relative,
+            { create: true, truncate: !append },
+            { write: true }
+        )
+        const [tx, rx] = _componentizeJsFs.stream()
+        const written = append ? file.appendViaStream(rx) : file.writeViaStream(rx, 0)
+        {
+            using _tx = tx
+            await tx.writeAll(bytes)
+        }
+        await written
+    })
+}
+
+var _componentizeJsReaddir = function(path, options) {
+    return _componentizeJsWithPreopen(path, 'scandir', async (directory, relative) => {
+        using dir = relative === '.'
+            ? undefined
+            : await directory.openAt({ symlinkFollow: true }, relative, { directory: true }, { read: true })
+        const [rx, result] = await (dir ?? directory).readDirectory()
+        const entries = []
+        {
+            using _rx = rx
+            while (!rx.writerDropped) {
+                entries.push(...await rx.read(64))
+            }
+        }
+        const { tag, val } = await result.read()
+        if (tag === 'err') {
+            throw _componentizeJsFsError(val, 'scandir', String(path))
+        }
+        return options?.withFileTypes
+            ? entries.map((entry) => new _componentizeJsFsDirent(entry, String(path)))
+            : entries.map((entry) => entry.name)
+    })
+}
+
+var _componentizeJsStat = function(path) {
+    return _componentizeJsWithPreopen(path, 'stat', async (directory, relative) => {
+        return new _componentizeJsFsStats(await directory.statAt({ symlinkFollow: true }, relative))
+    })
+}
+
+var _componentizeJsMkdir = function(path, options) {
+    return _componentizeJsWithPreopen(path, 'mkdir', async (directory, relative, root) => {
+        if (!options?.recursive) {
+            await directory.createDirectoryAt(relative)
+            return undefined
+        }
+        // Like Node.js, return the first directory created, if any.
+        const segments = relative.split('/')
+        let first
+        for (let i = 1; i <= segments.length; ++i) {
+            const prefix = segments.slice(0, i).join('/')
+            try {
+                await directory.createDirectoryAt(prefix)
+                first ??= prefix
+            } catch (error) {
+                if (error?.payload !== 'exist'
+                    || (await directory.statAt({ symlinkFollow: true }, prefix)).type !== 'directory') {
+                    throw error
+                }
+            }
+        }
+        return first === undefined ? undefined : [root, first].filter((part) => part !== '').join('/').replace(/^\/\//, '/')
+    })
+}
+
+// Bindings for the world's `wasi:sockets/types@0.3.x` interface (plus
+// `wasi:sockets/ip-name-lookup#resolve-addresses`, if imported), set by the
+// generated code, along with a constructor for the byte streams used to send
+// data.
+var _componentizeJsSockets = undefined
+
+// Parse an IPv4 or IPv6 address literal into a `wasi:sockets/types#ip-address`,
+// returning `undefined` if `hostname` isn't one.
+var _componentizeJsParseIp = function(hostname) {
+    const ipv4 = /^(\d{1,3})\.(\d{1,3})\.(\d{1,3})\.(\d{1,3})$/.exec(hostname)
+    if (ipv4 !== null) {
+        const parts = ipv4.slice(1).map(Number)
+        return parts.every((part) => part <= 255) ? { tag: 'ipv4', val: parts } : undefined
+    }
+
+    const ipv6 = hostname.replace(/^\[(.*)\]$/, '$1')
+    const halves = ipv6.split('::')
+    if (!ipv6.includes(':') || halves.length > 2) {
+        return undefined
+    }
+    const [head, tail] = halves.map((half) => half === '' ? [] : half.split(':'))
+    const missing = 8 - head.length - (tail?.length ?? 0)
+    if (tail === undefined ? missing !== 0 : missing < 1) {
+        return undefined
+    }
+    const parts = [...head, ...Array(tail === undefined ? 0 : missing).fill('0'), ...(tail ?? [])]
+    return parts.every((part) => /^[0-9a-f]{1,4}$/i.test(part))
+        ? { tag: 'ipv6', val: parts.map((part) => parseInt(part, 16)) }
+        : undefined
+}
+
+// Format a `wasi:sockets/types#ip-address`, compressing the longest run of
+// zeros in an IPv6 address.
+var _componentizeJsFormatIp = function(ip) {
+    if (ip.tag === 'ipv4') {
+        return Array.from(ip.val).join('.')
+    }
+    const parts = Array.from(ip.val, (part) => part.toString(16))
+    let [start, length] = [-1, 1]
+    for (let i = 0; i < parts.length; ++i) {
+        let j = i
+        while (j < parts.length && parts[j] === '0') {
+            ++j
+        }
+        if (j - i > length) {
+            [start, length] = [i, j - i]
+        }
+    }
+    return start < 0
+        ? parts.join(':')
+        : `${parts.slice(0, start).join(':')}::${parts.slice(start + length).join(':')}`
+}
+
+// Convert an IP address and port to a `wasi:sockets/types#ip-socket-address`.
+var _componentizeJsSocketAddress = function(ip, port) {
+    return ip.tag === 'ipv4'
+        ? { tag: 'ipv4', val: { port, address: ip.val } }
+        : { tag: 'ipv6', val: { port, flowInfo: 0, address: ip.val, scopeId: 0 } }
+}
+
+// Convert a `wasi:sockets/types#ip-socket-address` to `{ hostname, port }`.
+var _componentizeJsFromSocketAddress = function(address) {
+    return {
+        hostname: _componentizeJsFormatIp({ tag: address.tag, val: address.val.address }),
+        port: address.val.port
+    }
+}
+
+// Resolve `hostname` (which may be an address literal) to an IP address,
+// using the first address returned by `wasi:sockets/ip-name-lookup` if
+// necessary.
+var _componentizeJsResolveHost = async function(hostname) {
+    const sockets = _componentizeJsSockets
+    if (sockets === undefined) {
+        throw new TypeError('sockets require the world to import `wasi:sockets/types@0.3.x`')
+    }
+    const ip = _componentizeJsParseIp(String(hostname))
+    if (ip !== undefined) {
+        return ip
+    } else if (sockets.resolveAddresses === undefined) {
+        throw new TypeError('resolving host names requires the world to import `wasi:sockets/ip-name-lookup@0.3.x`')
+    }
+    const [first] = await sockets.resolveAddresses(String(hostname))
+    if (first === undefined) {
+        throw new TypeError(`no addresses found for ${hostname}`)
+    }
+    return first
+}
+
+// A connected TCP socket, which reads and writes data as `Uint8Array` chunks
+// via `readable` and `writable`.
+var _componentizeJsTcpConnection = class {
+    #socket
+    #readable
+    #writable
+    #sent
+    #closed = false
+
+    constructor(socket) {
+        this.#socket = socket
+
+        const [rx, result] = socket.receive()
+        let done = false
+        this.#readable = new ReadableStream({
+            pull: async (controller) => {
+                while (!rx.writerDropped) {
+                    const chunk = await rx.read(16 * 1024)
+                    if (chunk.length > 0) {
+                        controller.enqueue(chunk)
+                        return
+                    }
+                }
+                done = true
+                rx[Symbol.dispose]()
+                const { tag, val } = await result.read()
+                tag === 'err' ? controller.error(new ComponentError(val)) : controller.close()
+            },
+            cancel: () => {
+                if (!done) {
+                    done = true
+                    rx[Symbol.dispose]()
+                    result[Symbol.dispose]()
+                }
+            }
+        }, { highWaterMark: 0 })
+
+        const [tx, sendRx] = _componentizeJsSockets.stream()
+        this.#sent = socket.send(sendRx).catch(() => {})
+        this.#writable = tx.toWritableStream()
+    }
+
+    get readable() { return this.#readable }
+    get writable() { return this.#writable }
+    get localAddress() { return _componentizeJsFromSocketAddress(this.#socket.getLocalAddress()) }
+    get remoteAddress() { return _componentizeJsFromSocketAddress(this.#socket.getRemoteAddress()) }
+
+    // Close both directions of the connection and release the socket, waiting
+    // for any pending send to finish first since it borrows the socket.
+    async close() {
+        if (this.#closed) {
+            return
+        }
+        this.#closed = true
+        await Promise.allSettled([
+            this.#readable.locked ? undefined : this.#readable.cancel(),
+            this.#writable.locked ? undefined : this.#writable.close()
+        ])
+        await this.#sent
+        this.#socket[Symbol.dispose]()
+    }
+}
+
+// A listening TCP socket, which yields a `_componentizeJsTcpConnection` for
+// each connection accepted.
+var _componentizeJsTcpListener = class {
+    #socket
+    #rx
+
+    constructor(socket, rx) {
+        this.#socket = socket
+        this.#rx = rx
+    }
+
+    get localAddress() { return _componentizeJsFromSocketAddress(this.#socket.getLocalAddress()) }
+
+    async accept() {
+        while (!this.#rx.writerDropped) {
+            const [socket] = await this.#rx.read(1)
+            if (socket !== undefined) {
+                return new _componentizeJsTcpConnection(socket)
+            }
+        }
+        throw new TypeError('listener has been closed')
+    }
+
+    async *[Symbol.asyncIterator]() {
+        while (!this.#rx.writerDropped) {
+            const [socket] = await this.#rx.read(1)
+            if (socket !== undefined) {
+                yield new _componentizeJsTcpConnection(socket)
+            }
+        }
+    }
+
+    close() {
+        this.#rx[Symbol.dispose]()
+        this.#socket[Symbol.dispose]()
+    }
+}
+
+// A bound UDP socket.
+var _componentizeJsUdpSocket = class {
+    #socket
+
+    constructor(socket) {
+        this.#socket = socket
+    }
+
+    get localAddress() { return _componentizeJsFromSocketAddress(this.#socket.getLocalAddress()) }
+
+    // Restrict this socket to communicating with the specified peer, which
+    // `send` will then use by default.
+    async connect({ hostname, port }) {
+        this.#socket.connect(_componentizeJsSocketAddress(await _componentizeJsResolveHost(hostname), port))
+    }
+
+    async send(data, to) {
+        const bytes = typeof data === 'string' ? new TextEncoder().encode(data) : _componentizeJsBytes(data)
+        const address = to === undefined
+            ? undefined
+            : _componentizeJsSocketAddress(await _componentizeJsResolveHost(to.hostname), to.port)
+        await this.#socket.send(bytes, address)
+    }
+
+    async receive() {
+        const [data, address] = await this.#socket.receive()
+        return { data: Uint8Array.from(data), remoteAddress: _componentizeJsFromSocketAddress(address) }
+    }
+
+    close() {
+        this.#socket[Symbol.dispose]()
+    }
+}
+
+// The following implement the `componentize-js:sockets` module, built on
+// `wasi:sockets`.
+
+var _componentizeJsConnect = async function({ hostname, port }) {
+    const ip = await _componentizeJsResolveHost(hostname)
+    const socket = _componentizeJsSockets.TcpSocket.create(ip.tag)
+    try {
+        await socket.connect(_componentizeJsSocketAddress(ip, port))
+    } catch (error) {
+        socket[Symbol.dispose]()
+        throw error
+    }
+    return new _componentizeJsTcpConnection(socket)
+}
+
+var _componentizeJsListen = async function({ hostname = '0.0.0.0', port = 0 } = {}) {
+    const ip = await _componentizeJsResolveHost(hostname)
+    const socket = _componentizeJsSockets.TcpSocket.create(ip.tag)
+    try {
+        socket.bind(_componentizeJsSocketAddress(ip, port))
+        return new _componentizeJsTcpListener(socket, socket.listen())
+    } catch (error) {
+        socket[Symbol.dispose]()
+        throw error
+    }
+}
+
+var _componentizeJsBindDatagram = async function({ hostname = '0.0.0.0', port = 0 } = {}) {
+    const ip = await _componentizeJsResolveHost(hostname)
+    const socket = _componentizeJsSockets.UdpSocket.create(ip.tag)
+    try {
+        socket.bind(_componentizeJsSocketAddress(ip, port))
+    } catch (error) {
+        socket[Symbol.dispose]()
+        throw error
+    }
+    return new _componentizeJsUdpSocket(socket)
+}
+
+// Define `on<type>` event handler attributes for each of `types` on `klass`'s
+// prototype.  Like the DOM's, each is registered as a listener when first set,
+// and so runs in that position relative to other listeners.
+var _componentizeJsDefineEventHandlers = function(klass, types) {
+    const handlers = new WeakMap()
+    for (const type of types) {
+        Object.defineProperty(klass.prototype, `on${type}`, {
+            configurable: true,
+            enumerable: true,
+            get() {
+                return handlers.get(this)?.[type]?.handler ?? null
+            },
+            set(handler) {
+                handler = typeof handler === 'function' ? handler : null
+                let entries = handlers.get(this)
+                if (entries === undefined) {
+                    entries = {}
+                    handlers.set(this, entries)
+                }
+                let entry = entries[type]
+                if (entry === undefined) {
+                    entry = { handler: null, listener: (event) => entry.handler?.call(this, event) }
+                    entries[type] = entry
+                }
+                if (handler === null) {
+                    this.removeEventListener(type, entry.listener)
+                } else if (entry.handler === null) {
+                    this.addEventListener(type, entry.listener)
+                }
+                entry.handler = handler
+            }
+        })
+    }
+}
+
+// Encode `bytes` as base64.
+var _componentizeJsBase64 = function(bytes) {
+    const alphabet = 'ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/'
+    let result = ''
+    for (let i = 0; i < bytes.length; i += 3) {
+        const [a, b = 0, c = 0] = bytes.subarray(i, i + 3)
+        const n = (a << 16) | (b << 8) | c
+        result += alphabet[n >> 18] + alphabet[(n >> 12) & 63]
+            + (i + 1 < bytes.length ? alphabet[(n >> 6) & 63] : '=')
+            + (i + 2 < bytes.length ? alphabet[n & 63] : '=')
+    }
+    return result
+}
+
+var _componentizeJsConcatBytes = function(a, b) {
+    const result = new Uint8Array(a.length + b.length)
+    result.set(a)
+    result.set(b, a.length)
+    return result
+}
+
+// Find the blank line ending an HTTP/1.1 response head, returning its offset or
+// -1 if it hasn't been received yet.
+var _componentizeJsFindHeadersEnd = function(buffer) {
+    for (let i = 0; i + 3 < buffer.length; ++i) {
+        if (buffer[i] === 13 && buffer[i + 1] === 10 && buffer[i + 2] === 13 && buffer[i + 3] === 10) {
+            return i
+        }
+    }
+    return -1
+}
+
+// Appended to the client's key to produce the server's expected
+// `Sec-WebSocket-Accept` value (RFC 6455, section 1.3).
+var _componentizeJsWebSocketGuid = '258EAFA5-E914-47DA-95CA-C5AB0DC85B11'
+
+// Encode a single, masked, final WebSocket frame, as clients must send.
+var _componentizeJsEncodeFrame = function(opcode, payload) {
+    const length = payload.length
+    const header = length < 126 ? 2 : length < 65536 ? 4 : 10
+    const frame = new Uint8Array(header + 4 + length)
+    frame[0] = 0x80 | opcode
+    if (length < 126) {
+        frame[1] = 0x80 | length
+    } else if (length < 65536) {
+        frame[1] = 0x80 | 126
+        new DataView(frame.buffer).setUint16(2, length)
+    } else {
+        frame[1] = 0x80 | 127
+        new DataView(frame.buffer).setBigUint64(2, BigInt(length))
+    }
+    const mask = crypto.getRandomValues(new Uint8Array(4))
+    frame.set(mask, header)
+    for (let i = 0; i < length; ++i) {
+        frame[header + 4 + i] = payload[i] ^ mask[i & 3]
+    }
+    return frame
+}
+
+// Decode the (unmasked, as servers must send) WebSocket frame at the start of
+// `buffer`, returning `undefined` if it's incomplete.
+var _componentizeJsDecodeFrame = function(buffer) {
+    if (buffer.length < 2) {
+        return undefined
+    }
+    const fin = (buffer[0] & 0x80) !== 0
+    const opcode = buffer[0] & 0x0f
+    if ((buffer[0] & 0x70) !== 0 || (buffer[1] & 0x80) !== 0 || ![0, 1, 2, 8, 9, 10].includes(opcode)) {
+        throw new TypeError('invalid WebSocket frame')
+    }
+    const view = new DataView(buffer.buffer, buffer.byteOffset, buffer.byteLength)
+    let length = buffer[1] & 0x7f
+    let offset = 2
+    if (length === 126) {
+        if (buffer.length < 4) {
+            return undefined
+        }
+        length = view.getUint16(2)
+        offset = 4
+    } else if (length === 127) {
+        if (buffer.length < 10) {
+            return undefined
+        }
+        length = Number(view.getBigUint64(2))
+        offset = 10
+    }
+    if (opcode >= 8 && (!fin || length > 125)) {
+        throw new TypeError('invalid WebSocket control frame')
+    }
+    return buffer.length < offset + length
+        ? undefined
+        : { fin, opcode, payload: buffer.slice(offset, offset + length), size: offset + length }
+}
+
+// The HTML standard's `CloseEvent`, as dispatched by `WebSocket`.
+var CloseEvent = class extends Event {
+    #wasClean
+    #code
+    #reason
+
+    constructor(type, options = {}) {
+        super(type, options)
+        this.#wasClean = Boolean(options.wasClean)
+        this.#code = Number(options.code ?? 0)
+        this.#reason = String(options.reason ?? '')
+    }
+
+    get wasClean() { return this.#wasClean }
+    get code() { return this.#code }
+    get reason() { return this.#reason }
+}
+
+// A WebSocket client built on `componentize-js:sockets`, and so only available
+// if the world imports `wasi:sockets/types@0.3.x`.  Only `ws:` URLs are
+// supported, since WASI 0.3 has no TLS interface yet, and no extensions (e.g.
+// compression) are negotiated.
+var WebSocket = class extends EventTarget {
+    static CONNECTING = 0
+    static OPEN = 1
+    static CLOSING = 2
+    static CLOSED = 3
+
+    #url
+    #origin
+    #readyState = WebSocket.CONNECTING
+    #protocol = ''
+    #binaryType = 'blob'
+    #bufferedAmount = 0
+    #connection = null
+    #reader = null
+    #writer = null
+    #writes = Promise.resolve()
+    #closeSent = false
+
+    constructor(url, protocols = []) {
+        super()
+        url = String(url)
+        const match = /^(wss?|https?):\/\/(?:[^@/?#]*@)?(\[[^\]/?#]*\]|[^:/?#]*)(?::(\d*))?([^#]*)(#.*)?$/i.exec(url)
+        if (match === null || match[2] === '' || match[5] !== undefined) {
+            throw _componentizeJsNamedError('SyntaxError', `invalid WebSocket URL: ${url}`)
+        }
+        const [, scheme, host, port, path] = match
+        if (['wss', 'https'].includes(scheme.toLowerCase())) {
+            throw _componentizeJsNamedError(
+                'NotSupportedError',
+                'secure WebSockets are not supported since WASI 0.3 has no TLS interface'
+            )
+        }
+        protocols = typeof protocols === 'string' ? [protocols] : Array.from(protocols, String)
+        if (new Set(protocols).size !== protocols.length
+            || protocols.some((protocol) => !/^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/.test(protocol))) {
+            throw _componentizeJsNamedError('SyntaxError', 'invalid or duplicate WebSocket protocol')
+        }
+        if (_componentizeJsSockets === undefined) {
+            throw new TypeError('WebSocket requires the world to import `wasi:sockets/types@0.3.x`')
+        }
+
+        const authority = `${host.toLowerCase()}${port ? `:${port}` : ''}`
+        const pathWithQuery = path.startsWith('/') ? path : `/${path}`
+        this.#origin = `http://${authority}`
+        this.#url = `ws://${authority}${pathWithQuery}`
+        this.#open(host.replace(/^\[(.*)\]$/, '$1'), port ? Number(port) : 80, authority, pathWithQuery, protocols)
+            .catch(() => this.#fail())
+    }
+
+    get url() { return this.#url }
+    get readyState() { return this.#readyState }
+    get protocol() { return this.#protocol }
+    get extensions() { return '' }
+    get bufferedAmount() { return this.#bufferedAmount }
+
+    get binaryType() { return this.#binaryType }
+    set binaryType(value) {
+        if (value === 'blob' || value === 'arraybuffer') {
+            this.#binaryType = value
+        }
+    }
+
+    send(data) {
+        if (this.#readyState === WebSocket.CONNECTING) {
+            throw _componentizeJsNamedError('InvalidStateError', 'WebSocket is still connecting')
+        }
+        const [opcode, payload] = typeof data === 'string'
+            ? [1, new TextEncoder().encode(data)]
+            : data instanceof Blob
+            ? [2, data[_componentizeJsBlobBytes]]
+            : ArrayBuffer.isView(data) || data instanceof ArrayBuffer
+            ? [2, _componentizeJsBytes(data).slice()]
+            : [1, new TextEncoder().encode(String(data))]
+        if (this.#readyState === WebSocket.OPEN) {
+            this.#send(opcode, payload)
+        }
+    }
+
+    close(code, reason) {
+        if (code !== undefined && code !== 1000 && !(code >= 3000 && code <= 4999)) {
+            throw _componentizeJsNamedError('InvalidAccessError', `invalid close code: ${code}`)
+        }
+        const reasonBytes = new TextEncoder().encode(reason ?? '')
+        if (reasonBytes.length > 123) {
+            throw _componentizeJsNamedError('SyntaxError', 'close reason must be at most 123 bytes')
+        }
+        if (this.#readyState === WebSocket.CONNECTING) {
+            this.#readyState = WebSocket.CLOSING
+            queueMicrotask(() => this.#fail())
+        } else if (this.#readyState === WebSocket.OPEN) {
+            this.#readyState = WebSocket.CLOSING
+            this.#sendClose(code === undefined ? new Uint8Array(0) : _componentizeJsConcatBytes(
+                new Uint8Array([code >> 8, code & 0xff]),
+                reasonBytes
+            ))
+        }
+    }
+
+    // Connect, perform the opening handshake (RFC 6455, section 4.1), and
+    // receive messages until the connection closes.
+    async #open(hostname, port, authority, pathWithQuery, protocols) {
+        const connection = await _componentizeJsConnect({ hostname, port })
+        this.#connection = connection
+        if (this.#readyState !== WebSocket.CONNECTING) {
+            throw new TypeError('WebSocket closed while connecting')
+        }
+        this.#writer = connection.writable.getWriter()
+        const key = _componentizeJsBase64(crypto.getRandomValues(new Uint8Array(16)))
+        await this.#writer.write(new TextEncoder().encode([
+            `GET ${pathWithQuery} HTTP/1.1`,
+            `Host: ${authority}`,
+            'Upgrade: websocket',
+            'Connection: Upgrade',
+            `Sec-WebSocket-Key: ${key}`,
+            'Sec-WebSocket-Version: 13',
+            ...(protocols.length > 0 ? [`Sec-WebSocket-Protocol: ${protocols.join(', ')}`] : []),
+            '',
+            ''
+        ].join('\r\n')))
+
+        const reader = connection.readable.getReader()
+        this.#reader = reader
+        let buffer = new Uint8Array(0)
+        let end
+        while ((end = _componentizeJsFindHeadersEnd(buffer)) < 0) {
+            const { value, done } = await reader.read()
+            if (done) {
+                throw new TypeError('connection closed during WebSocket handshake')
+            }
+            buffer = _componentizeJsConcatBytes(buffer, value)
+        }
+        const [status, ...lines] = new TextDecoder().decode(buffer.subarray(0, end)).split('\r\n')
+        const headers = new Map(lines.map((line) => {
+            const colon = line.indexOf(':')
+            return [line.slice(0, colon).trim().toLowerCase(), line.slice(colon + 1).trim()]
+        }))
+        const accept = _componentizeJsBase64(_componentizeJsDigest(
+            'SHA-1',
+            new TextEncoder().encode(key + _componentizeJsWebSocketGuid)
+        ))
+        const protocol = headers.get('sec-websocket-protocol') ?? ''
+        if (!/^HTTP\/1\.1 101\b/.test(status)
+            || headers.get('upgrade')?.toLowerCase() !== 'websocket'
+            || !/(^|,)\s*upgrade\s*(,|$)/i.test(headers.get('connection') ?? '')
+            || headers.get('sec-websocket-accept') !== accept
+            || headers.has('sec-websocket-extensions')
+            || (protocol !== '' && !protocols.includes(protocol))
+            || this.#readyState !== WebSocket.CONNECTING) {
+            throw new TypeError(`WebSocket handshake failed: ${status}`)
+        }
+
+        this.#protocol = protocol
+        this.#readyState = WebSocket.OPEN
+        this.dispatchEvent(new Event('open'))
+        await this.#receive(reader, buffer.subarray(end + 4))
+    }
+
+    async #receive(reader, buffer) {
+        let message = null
+        while (true) {
+            let frame
+            try {
+                frame = _componentizeJsDecodeFrame(buffer)
+            } catch (error) {
+                this.#sendClose(new Uint8Array([1002 >> 8, 1002 & 0xff]))
+                throw error
+            }
+            if (frame === undefined) {
+                const { value, done } = await reader.read()
+                if (done) {
+                    throw new TypeError('WebSocket connection closed without a close frame')
+                }
+                buffer = _componentizeJsConcatBytes(buffer, value)
+                continue
+            }
+            buffer = buffer.subarray(frame.size)
+
+            const { fin, opcode, payload } = frame
+            if (opcode === 8) {
+                if (!this.#closeSent) {
+                    this.#sendClose(payload.subarray(0, 2))
+                }
+                this.#readyState = WebSocket.CLOSED
+                await this.#shutdown()
+                this.dispatchEvent(new CloseEvent('close', {
+                    wasClean: true,
+                    code: payload.length >= 2 ? (payload[0] << 8) | payload[1] : 1005,
+                    reason: new TextDecoder().decode(payload.subarray(2))
+                }))
+                return
+            } else if (opcode === 9) {
+                if (!this.#closeSent) {
+                    this.#send(10, payload)
+                }
+            } else if (opcode !== 10) {
+                if ((opcode === 0) !== (message !== null)) {
+                    this.#sendClose(new Uint8Array([1002 >> 8, 1002 & 0xff]))
+                    throw new TypeError('unexpected WebSocket continuation frame')
+                }
+                message ??= { opcode, chunks: [] }
+                message.chunks.push(payload)
+                if (fin) {
+                    this.#deliver(message.opcode, message.chunks.reduce(_componentizeJsConcatBytes))
+                    message = null
+                }
+            }
+        }
+    }
+
+    #deliver(opcode, payload) {
+        if (this.#readyState !== WebSocket.OPEN) {
+            return
+        }
+        const data = opcode === 1
+            ? new TextDecoder().decode(payload)
+            : this.#binaryType === 'arraybuffer'
+            ? payload.buffer
+            : new Blob([payload])
+        this.dispatchEvent(new MessageEvent('message', { data, origin: this.#origin }))
+    }
+
+    #send(opcode, payload) {
+        this.#bufferedAmount += payload.length
+        this.#writes = this.#writes
+            .then(() => this.#writer.write(_componentizeJsEncodeFrame(opcode, payload)))
+            .catch(() => {})
+            .then(() => {
+                this.#bufferedAmount -= payload.length
+            })
+    }
+
+    #sendClose(payload) {
+        if (!this.#closeSent) {
+            this.#closeSent = true
+            this.#send(8, payload)
+        }
+    }
+
+    // Wait for pending writes, then close the underlying connection.
+    async #shutdown() {
+        await this.#writes
+        this.#reader?.releaseLock()
+        this.#writer?.releaseLock()
+        await this.#connection?.close()
+    }
+
+    // Fail the connection (e.g. due to a network or protocol error, or `close`
+    // being called while connecting), firing `error` and then `close`.
+    #fail() {
+        if (this.#readyState === WebSocket.CLOSED) {
+            return
+        }
+        this.#readyState = WebSocket.CLOSED
+        this.#shutdown().catch(() => {})
+        this.dispatchEvent(new Event('error'))
+        this.dispatchEvent(new CloseEvent('close', { wasClean: false, code: 1006, reason: '' }))
+    }
+}
+
+_componentizeJsDefineEventHandlers(WebSocket, ['open', 'message', 'error', 'close'])
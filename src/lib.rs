@@ -1,19 +1,29 @@
 #![deny(warnings)]
 
 use {
-    anyhow::{Context as _, anyhow},
+    anyhow::{Context as _, anyhow, bail},
+    bindings::Init,
     bytes::Bytes,
     indexmap::IndexSet,
+    sha2::{Digest as _, Sha256},
     std::{
         borrow::Cow,
         collections::HashMap,
+        convert::Infallible,
+        fs,
+        hash::{self, Hash as _},
         io::Cursor,
         path::{Path, PathBuf},
+        process,
+        time::{Duration, Instant},
+    },
+    wasm_encoder::{
+        CustomSection, Section as _,
+        reencode::{self, Reencode, ReencodeComponent},
     },
-    wasm_encoder::{CustomSection, Section as _},
     wasmtime::{
         Config, Engine, Store,
-        component::{Component, Linker, ResourceTable, ResourceType},
+        component::{Component, Func, Instance, Linker, ResourceTable, ResourceType, Val},
     },
     wasmtime_wasi::p2::pipe::{MemoryInputPipe, MemoryOutputPipe},
     wasmtime_wasi::{DirPerms, FilePerms, WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView},
@@ -25,16 +35,33 @@ use {
     },
 };
 
-wasmtime::component::bindgen!({
-    path: "init.wit",
-    world: "init",
-    exports: { default: async },
-});
+mod bindings {
+    wasmtime::component::bindgen!({
+        path: "init.wit",
+        world: "init",
+        exports: { default: async },
+    });
+}
 
+mod bench;
 mod codegen;
 pub mod command;
+mod deferred;
+mod error;
+mod manifest;
+mod scaffold;
+mod source_map;
+#[cfg(feature = "typescript")]
+mod strip_types;
 #[cfg(test)]
 mod tests;
+mod typescript;
+mod validate;
+mod virtualize;
+
+pub use error::ComponentizeError;
+#[cfg(feature = "typescript")]
+pub use strip_types::strip_types;
 
 pub struct Ctx {
     wasi: WasiCtx,
@@ -50,21 +77,941 @@ impl WasiView for Ctx {
     }
 }
 
+/// The WIT from which to select a world.
 pub enum Wit<'a, P = PathBuf> {
+    /// A single WIT document.
     String(&'a str),
+    /// WIT files and/or directories.
+    ///
+    /// A directory is parsed as a package, with any packages it references
+    /// resolved from its `deps` subdirectory (e.g. `wit/deps/http.wit` or
+    /// `wit/deps/http/*.wit` for `wasi:http`), as `wasm-tools` and
+    /// `wit-bindgen` do.
     Paths(&'a [P]),
 }
 
-#[expect(clippy::type_complexity)]
-pub async fn componentize(
+/// JS engine preferences which enable or disable language features in the
+/// generated component.
+#[derive(Clone, Debug)]
+pub struct Prefs {
+    /// Expose `WeakRef` and `FinalizationRegistry` to user code.
+    pub weak_refs: bool,
+    /// Expose `SharedArrayBuffer` and `Atomics`.
+    pub shared_memory: bool,
+    /// Expose the iterator helper methods (`Iterator.prototype.map`, etc.).
+    pub iterator_helpers: bool,
+    /// Expose the non-standard `toSource` methods.
+    pub to_source: bool,
+    /// Use UTC as the local time zone regardless of the host environment.
+    pub force_utc: bool,
+    /// Expose the `Temporal` API.
+    ///
+    /// `Temporal` depends on SpiderMonkey's internationalization support, so
+    /// this requires building with the `intl` feature.  Like `Date`,
+    /// `Temporal.Now` reads the time from `wasi:clocks/wall-clock` (or the
+    /// virtualized clock, if any) whenever it's called.
+    pub temporal: bool,
+}
+
+impl Default for Prefs {
+    fn default() -> Self {
+        Self {
+            weak_refs: true,
+            shared_memory: false,
+            iterator_helpers: true,
+            to_source: false,
+            force_utc: false,
+            temporal: false,
+        }
+    }
+}
+
+impl Prefs {
+    /// Set the preference named `name` (in kebab-case, e.g. `weak-refs`) to
+    /// `value`.
+    pub fn set(&mut self, name: &str, value: bool) -> anyhow::Result<()> {
+        *match name {
+            "weak-refs" => &mut self.weak_refs,
+            "shared-memory" => &mut self.shared_memory,
+            "iterator-helpers" => &mut self.iterator_helpers,
+            "to-source" => &mut self.to_source,
+            "force-utc" => &mut self.force_utc,
+            "temporal" => &mut self.temporal,
+            _ => bail!("unknown JS engine preference: `{name}`"),
+        } = value;
+        Ok(())
+    }
+}
+
+/// How strictly JS values are converted to WIT numeric types when passed to
+/// imports or returned from exports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Coercion {
+    /// Integer types require integral numbers within the type's range.
+    /// `u64` and `s64` also accept `BigInt`s, and are passed to JS as numbers
+    /// when they fit in 32 bits and as `BigInt`s otherwise.
+    #[default]
+    Standard,
+    /// Like `Standard`, except that `u64` and `s64` require `BigInt`s and are
+    /// always passed to JS as `BigInt`s.
+    Strict,
+    /// Any value is accepted for a numeric type and converted using JS
+    /// `ToNumber`, then truncated (and saturated) to the target type.
+    Lenient,
+}
+
+/// How the script is expected to name the objects implementing exported
+/// interfaces.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportLayout {
+    /// Interface IDs are converted to camelCase identifiers, with punctuation
+    /// replaced by underscores, e.g. `wasiHttpHandler030` for
+    /// `wasi:http/handler@0.3.0`.  This matches upstream `ComponentizeJS`.
+    #[default]
+    Flat,
+    /// Interface IDs are used as-is, e.g. `export { handler as
+    /// 'wasi:http/handler@0.3.0' }`, which is unambiguous and avoids name
+    /// collisions between interfaces which mangle to the same identifier.
+    Qualified,
+}
+
+/// Where output written using `console` (e.g. `console.log`) goes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConsoleOutput {
+    /// `console.log`, `console.info`, and `console.debug` write to stdout,
+    /// while `console.warn` and `console.error` write to stderr.
+    #[default]
+    Stdio,
+    /// Each call emits a record with the corresponding level (e.g. `warn` for
+    /// `console.warn`, and `info` for `console.log`) and a context of `console`
+    /// using the world's `wasi:logging/logging` import, which must be present.
+    ///
+    /// Output written while the script is being evaluated still goes to stdout
+    /// or stderr, since imports other than WASI are stubbed at that point.
+    WasiLogging,
+}
+
+/// Which WASI implementation is provided to the component while the script is
+/// being evaluated (and the snapshot is taken).
+///
+/// Note that this is ignored if an `add_to_linker` function is passed to
+/// [`Componentizer::add_to_linker`], in which case that function is
+/// responsible for adding WASI to the linker.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum WasiHost {
+    /// WASI 0.2 only; any WASI 0.3 imports are stubbed and trap if called.
+    #[default]
+    P2,
+    /// Both WASI 0.2 and WASI 0.3, allowing the script to call WASI 0.3
+    /// functions at the top level.
+    P3,
+}
+
+/// Options which control how a component is generated.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// JS engine preferences applied when creating the runtime's global realm.
+    pub prefs: Prefs,
+    /// Log the start, end, reason, and duration of each garbage collection to
+    /// stderr, both during initialization and at runtime.
+    ///
+    /// Only whole collections are logged, not the individual slices of an
+    /// incremental collection.
+    pub gc_logging: bool,
+    /// Print a census of the JS heap (object counts and sizes grouped by
+    /// class) to stderr just before the snapshot is taken.
+    ///
+    /// This is useful for finding out what is making a component's snapshot
+    /// larger than expected, e.g. a parsed dataset retained at module scope.
+    pub heap_census: bool,
+    /// Profile each call to an exported function, printing the time spent in
+    /// each JS call stack to stderr in the "folded stacks" format accepted by
+    /// `flamegraph.pl` and `inferno-flamegraph`.
+    ///
+    /// This instruments every JS function call and therefore slows the
+    /// component down considerably; it is only intended for debugging.
+    pub profile: bool,
+    /// How strictly JS values are converted to WIT numeric types.
+    pub coercion: Coercion,
+    /// Deep-freeze the standard JS globals (`Object`, `Array`, `Math`, etc.),
+    /// their prototypes, and other intrinsics such as the iterator prototypes
+    /// after the script has been evaluated but before the snapshot is taken.
+    ///
+    /// This prevents code running in the component from tampering with the
+    /// intrinsics, e.g. by replacing `Array.prototype.push`.  Note that
+    /// assigning to a property of an ordinary object which shadows a frozen
+    /// inherited property (e.g. `object.toString = ...`) will then throw a
+    /// `TypeError` in strict mode code, so some libraries may need to use
+    /// `Object.defineProperty` instead.
+    pub freeze_intrinsics: bool,
+    /// Maximum size of the JS garbage-collected heap, in bytes.
+    ///
+    /// Once the limit is reached (and a garbage collection fails to free enough
+    /// memory), further allocations throw an "out of memory" exception which
+    /// can be caught by the script, rather than growing linear memory until the
+    /// instance itself runs out.
+    ///
+    /// Note that SpiderMonkey does not provide a way to bound the execution
+    /// time of individual regular expressions, nor to lower its built-in string
+    /// and array length limits; use e.g. Wasmtime's epoch interruption to bound
+    /// execution time on the host side.
+    pub max_heap_bytes: Option<u32>,
+    /// Maximum size of the component's linear memory, in bytes, declared as the
+    /// memory's maximum in the generated component.
+    ///
+    /// This is rounded down to a whole number of 64 KiB Wasm pages and must be
+    /// no smaller than the memory used by the snapshot.  Hosts will refuse to
+    /// grow the memory beyond this limit regardless of their own store limits.
+    pub max_memory_bytes: Option<u64>,
+    /// Size of the shadow stack used by the runtime, in bytes.
+    ///
+    /// Deeply recursive scripts may exhaust the default (1 MiB) stack, causing
+    /// SpiderMonkey to throw "too much recursion" errors.  Raising this limit
+    /// (and the engine's stack quota along with it) allows deeper recursion at
+    /// the cost of more linear memory per instance.
+    pub stack_size: Option<u32>,
+    /// If set, replace the host's environment variables with these values by
+    /// virtualizing `wasi:cli/environment#get-environment` inside the generated
+    /// component, which then no longer imports that function.
+    pub virtual_env: Option<Vec<(String, String)>>,
+    /// If set, virtualize the `wasi:clocks/wall-clock` and
+    /// `wasi:clocks/monotonic-clock` `now` and `resolution` functions inside
+    /// the generated component.  The wall clock will always report this time
+    /// (in seconds since the Unix epoch) and the monotonic clock will always
+    /// report zero, making e.g. `Date.now()` deterministic.
+    ///
+    /// Note that this applies during initialization as well as afterward.
+    /// Virtualizing the filesystem (e.g. with an embedded snapshot) is not yet
+    /// supported.
+    pub virtual_clock: Option<u64>,
+    /// Which WASI implementation to use during initialization.
+    pub wasi_host: WasiHost,
+    /// Names of JS bindings to use for exported functions and interfaces, keyed
+    /// by the WIT name of the function (e.g. `get-user`) or interface (e.g.
+    /// `wasi:http/handler@0.3.0`).
+    ///
+    /// By default, the script is expected to export a function or object named
+    /// after each export using camelCase (e.g. `getUser` or
+    /// `wasiHttpHandler030`).  Aliases allow existing code to be componentized
+    /// without renaming its public functions to match.
+    pub export_aliases: Vec<(String, String)>,
+    /// How exported interfaces are named.
+    pub export_layout: ExportLayout,
+    /// If set, instantiate the generated component after snapshotting it and
+    /// call the specified export with the specified parameters, failing if the
+    /// call traps.
+    ///
+    /// The export is named using `#` to separate interface and function names,
+    /// e.g. `wasi:http/handler@0.3.0#handle`, or just the function name for
+    /// world-level exports.  This is intended to catch breakage which only
+    /// manifests after the snapshot (e.g. state which wasn't reset) before the
+    /// component is deployed.  Imports are provided as during initialization.
+    pub smoke_test: Option<(String, Vec<Val>)>,
+    /// Directory in which to cache the compiled form of the component used
+    /// during initialization, which only depends on the runtime, supporting
+    /// libraries, and WIT world (not the script), so repeated builds can skip
+    /// recompiling it.
+    ///
+    /// Cached files are loaded without validation, so this directory must not
+    /// be writable by untrusted users.
+    pub cache_dir: Option<PathBuf>,
+    /// Bytes to provide as stdin while evaluating the script (e.g. generated
+    /// configuration piped into the build), which the script may read using
+    /// `componentModel.readStdin()`.  Otherwise, stdin is empty.
+    pub stdin: Vec<u8>,
+    /// Retain the runtime's `init` export in the generated component, allowing
+    /// a trusted host (e.g. a development server) to replace the script at
+    /// runtime while reusing the snapshotted engine.
+    ///
+    /// To do so, the host calls `init` with the new script; the other
+    /// parameters are ignored.  Exports are looked up in the new script's
+    /// module on each call, while imports, globals, and any state outside the
+    /// module (e.g. on `globalThis`) are retained.  This should not be enabled
+    /// for production builds, since it allows the host to run arbitrary code.
+    pub hot_reload: bool,
+    /// Print the duration of each stage of componentization (e.g. linking,
+    /// compiling, evaluating the script, and taking the snapshot) to stderr.
+    ///
+    /// This is useful for deciding how to speed up a slow build, e.g. by
+    /// setting `cache_dir` if compiling dominates, or by shrinking the script
+    /// if evaluating it does.
+    pub timings: bool,
+    /// Source map (in JSON form) for the script, e.g. as produced by a bundler
+    /// or TypeScript compiler.
+    ///
+    /// If set, positions within the script in stack traces (both for errors
+    /// thrown during initialization and in trap messages at runtime) are
+    /// rewritten to refer to the original sources.  Note that `error.stack` as
+    /// seen by the script itself is not affected.
+    pub source_map: Option<String>,
+    /// Where `console` output goes.
+    pub console: ConsoleOutput,
+    /// Skip evaluating the script and taking the snapshot at build time,
+    /// embedding the script in the generated component instead and evaluating
+    /// it when the first export is called (or resource destructor run).
+    ///
+    /// This makes componentization much faster at the cost of a much slower
+    /// first call per instance, so it's only intended for use during
+    /// development.  Note that the runtime's `init` export is retained (as with
+    /// `hot_reload`), errors thrown by the script's top level become traps at
+    /// runtime rather than build errors, and warnings are printed to stderr
+    /// at runtime.  Modules imported using relative paths are read when the
+    /// script is evaluated, so the host must preopen the base directory as
+    /// `/`; likewise, `stdin` is ignored in favor of the host's stdin.
+    pub skip_snapshot: bool,
+    /// If set, write the intermediate components produced while
+    /// componentizing to this directory (creating it if necessary) for
+    /// debugging: `linked.wasm` (the runtime, bindings, and supporting
+    /// libraries linked together), `instrumented.wasm` (as instantiated to
+    /// evaluate the script), and `snapshot.wasm` (the snapshot, before any
+    /// post-processing).  The latter two are omitted if `skip_snapshot` is set.
+    pub debug_artifacts_dir: Option<PathBuf>,
+}
+
+impl From<&Options> for bindings::Options {
+    fn from(options: &Options) -> Self {
+        let Prefs {
+            weak_refs,
+            shared_memory,
+            iterator_helpers,
+            to_source,
+            force_utc,
+            temporal,
+        } = options.prefs;
+
+        Self {
+            prefs: bindings::Prefs {
+                weak_refs,
+                shared_memory,
+                iterator_helpers,
+                to_source,
+                force_utc,
+                temporal,
+            },
+            gc_logging: options.gc_logging,
+            heap_census: options.heap_census,
+            profile: options.profile,
+            coercion: match options.coercion {
+                Coercion::Standard => bindings::Coercion::Standard,
+                Coercion::Strict => bindings::Coercion::Strict,
+                Coercion::Lenient => bindings::Coercion::Lenient,
+            },
+            freeze_intrinsics: options.freeze_intrinsics,
+            max_heap_bytes: options.max_heap_bytes,
+            stack_size: options.stack_size,
+            export_layout: match options.export_layout {
+                ExportLayout::Flat => bindings::ExportLayout::Flat,
+                ExportLayout::Qualified => bindings::ExportLayout::Qualified,
+            },
+            // Decoding may fail, so `componentize` fills this in separately.
+            source_map: None,
+        }
+    }
+}
+
+/// Generates a component from a script and a WIT world, e.g.:
+///
+/// ```no_run
+/// # async fn example() -> Result<(), componentize_js::ComponentizeError> {
+/// use componentize_js::{Componentizer, Options, Wit};
+///
+/// let component = Componentizer::new(Wit::Paths(&["wit"]), "export function run() {}")
+///     .world("my-world")
+///     .options(Options {
+///         freeze_intrinsics: true,
+///         ..Options::default()
+///     })
+///     .componentize()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Everything other than the WIT and the script is optional, and new settings
+/// are added as builder methods or [`Options`] fields, so existing callers
+/// needn't change as this API grows.
+pub struct Componentizer<'a, P = PathBuf> {
+    wit: Wit<'a, P>,
+    world: Option<&'a str>,
+    features: &'a [String],
+    all_features: bool,
+    js: &'a str,
+    base_directory: Option<PathBuf>,
+    options: Options,
+    add_to_linker: Option<&'a dyn Fn(&mut Linker<Ctx>) -> anyhow::Result<()>>,
+}
+
+impl<'a, P: AsRef<Path>> Componentizer<'a, P> {
+    /// Create a builder which generates a component from the script `js`,
+    /// targeting the default world of `wit` unless [`Self::world`] is called.
+    pub fn new(wit: Wit<'a, P>, js: &'a str) -> Self {
+        Self {
+            wit,
+            world: None,
+            features: &[],
+            all_features: false,
+            js,
+            base_directory: None,
+            options: Options::default(),
+            add_to_linker: None,
+        }
+    }
+
+    /// Select the named world from the WIT rather than its default world (or
+    /// revert to the default world if `None`).
+    pub fn world(mut self, world: impl Into<Option<&'a str>>) -> Self {
+        self.world = world.into();
+        self
+    }
+
+    /// Enable the specified WIT features, each of which may be a comma- or
+    /// whitespace-separated list.
+    pub fn features(mut self, features: &'a [String]) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Enable (or disable) all WIT features.
+    pub fn all_features(mut self, all_features: bool) -> Self {
+        self.all_features = all_features;
+        self
+    }
+
+    /// Make the specified directory available to the script (e.g. for
+    /// importing other modules) as `/` while it is being evaluated.
+    pub fn base_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.base_directory = Some(directory.into());
+        self
+    }
+
+    /// Set the [`Options`] controlling how the component is generated.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Use the specified function to add imports (including WASI) to the linker
+    /// used while evaluating the script, rather than the default WASI
+    /// implementation (see [`Options::wasi_host`]) plus stubs for everything
+    /// else.
+    pub fn add_to_linker(
+        mut self,
+        add_to_linker: &'a dyn Fn(&mut Linker<Ctx>) -> anyhow::Result<()>,
+    ) -> Self {
+        self.add_to_linker = Some(add_to_linker);
+        self
+    }
+
+    /// Generate the component, forwarding anything the script wrote to stdout or
+    /// stderr while being evaluated (including e.g. the output of
+    /// [`Options::heap_census`]) to this process's stdout and stderr, and
+    /// printing any warnings (e.g. about exports which don't match the world) to
+    /// stderr.
+    pub async fn componentize(self) -> Result<Vec<u8>, ComponentizeError> {
+        let (component, diagnostics) = self.componentize_with_diagnostics().await?;
+
+        print!("{}", diagnostics.stdout);
+        eprint!("{}", diagnostics.stderr);
+        for warning in diagnostics.warnings {
+            eprintln!("warning: {warning}");
+        }
+
+        Ok(component)
+    }
+
+    /// Like [`Self::componentize`], but return any warnings, along with
+    /// anything the script wrote to stdout or stderr while being evaluated and
+    /// the duration of each stage, instead of printing or discarding them.
+    pub async fn componentize_with_diagnostics(
+        self,
+    ) -> Result<(Vec<u8>, Diagnostics), ComponentizeError> {
+        let Self {
+            wit,
+            world,
+            features,
+            all_features,
+            js,
+            base_directory: js_base_directory,
+            options,
+            add_to_linker,
+        } = self;
+        let options = &options;
+
+        let mut timings = Timings::new();
+
+        let source_map = options
+            .source_map
+            .as_deref()
+            .map(source_map::decode)
+            .transpose()
+            .map_err(ComponentizeError::SourceMap)?;
+
+        let (resolve, world) = resolve_world(wit, world, features, all_features)?;
+        timings.finish("resolve WIT");
+
+        let (mut bindings, metadata) = wit_dylib::create_with_metadata(
+            &resolve,
+            world,
+            Some(&mut DylibOpts {
+                interpreter: Some("libcomponentize_js_runtime.so".into()),
+                async_: Default::default(),
+            }),
+        );
+
+        CustomSection {
+            name: Cow::Borrowed("component-type:componentize-js"),
+            data: Cow::Owned(
+                metadata::encode(&resolve, world, wit_component::StringEncoding::UTF8, None)
+                    .map_err(ComponentizeError::Link)?,
+            ),
+        }
+        .append_to(&mut bindings);
+
+        if let Some((name, _)) = options.export_aliases.iter().find(|(name, _)| {
+            !metadata
+                .export_funcs
+                .iter()
+                .any(|func| func.interface.as_ref().unwrap_or(&func.name) == name)
+        }) {
+            return Err(ComponentizeError::WorldSelection(anyhow!(
+                "cannot alias `{name}`: no such function or interface exported by the world"
+            )));
+        }
+
+        let console_sink = match options.console {
+            ConsoleOutput::Stdio => None,
+            ConsoleOutput::WasiLogging => Some(
+                metadata
+                    .import_funcs
+                    .iter()
+                    .position(|func| {
+                        func.name == "log"
+                            && func.interface.as_deref().is_some_and(|interface| {
+                                interface == "wasi:logging/logging"
+                                    || interface.starts_with("wasi:logging/logging@")
+                            })
+                    })
+                    .ok_or_else(|| {
+                        ComponentizeError::WorldSelection(anyhow!(
+                            "cannot send console output to `wasi:logging/logging`: \
+                             the world does not import that interface"
+                        ))
+                    })?,
+            ),
+        };
+
+        let generated_code = codegen::generate(
+            &metadata,
+            &manifest::World::new(&resolve, world).to_json(),
+            &options.export_aliases,
+            options.export_layout,
+            console_sink,
+        );
+        timings.finish("generate bindings");
+
+        let init_options = bindings::Options {
+            source_map,
+            ..options.into()
+        };
+
+        // When skipping the snapshot, the runtime initializes itself from this
+        // payload on first use instead.  Otherwise, we link an empty payload so
+        // the runtime's imports are still satisfied.
+        let payload = if options.skip_snapshot {
+            deferred::encode(
+                &init_options,
+                &generated_code.globals,
+                &generated_code.modules,
+                js,
+                &generated_code.script,
+            )
+        } else {
+            Vec::new()
+        };
+
+        let component = (|| -> anyhow::Result<_> {
+            let mut linker = wit_component::Linker::default()
+                .validate(true)
+                .use_built_in_libdl(true);
+
+            if let Some(stack_size) = options.stack_size {
+                linker = linker.stack_size(stack_size);
+            }
+
+            linker = linker.library(
+                "libcomponentize_js_runtime.so",
+                &zstd::decode_all(Cursor::new(include_bytes!(concat!(
+                    env!("OUT_DIR"),
+                    "/libcomponentize_js_runtime.so.zst"
+                ))))?,
+                false,
+            )?;
+
+            linker = linker.library("libcomponentize_js_bindings.so", &bindings, false)?;
+
+            linker = linker.library(deferred::LIBRARY_NAME, &deferred::library(&payload), false)?;
+
+            linker = linker.library(
+                "libc.so",
+                &zstd::decode_all(Cursor::new(include_bytes!(concat!(
+                    env!("OUT_DIR"),
+                    "/libc.so.zst"
+                ))))?,
+                false,
+            )?;
+
+            linker = linker.library(
+                "libwasi-emulated-getpid.so",
+                &zstd::decode_all(Cursor::new(include_bytes!(concat!(
+                    env!("OUT_DIR"),
+                    "/libwasi-emulated-getpid.so.zst"
+                ))))?,
+                false,
+            )?;
+
+            // Although the runtime is built for `wasm32-wasip2`, `libc.so` (and
+            // the runtime's use of `reset_adapter_state`) still rely on WASIp1
+            // imports, so we continue to use the adapter for now.  Once `wasi-libc`
+            // targets WASIp2 natively, we can drop it, along with the reset hack in
+            // the runtime.
+            let mut adapter = zstd::decode_all(Cursor::new(include_bytes!(concat!(
+                env!("OUT_DIR"),
+                "/wasi_snapshot_preview1.reactor.wasm.zst"
+            ))))?;
+
+            let virtualization = virtualize::Virtualization {
+                env: options.virtual_env.as_deref(),
+                clock: options.virtual_clock,
+            };
+            if !virtualization.is_empty() {
+                adapter = virtualize::virtualize(&adapter, &virtualization)?;
+            }
+
+            linker = linker.adapter("wasi_snapshot_preview1", &adapter)?;
+
+            linker.encode().map_err(|e| anyhow!(e))
+        })()
+        .map_err(ComponentizeError::Link)?;
+        timings.finish("link");
+        write_debug_artifact(options, "linked.wasm", &component)?;
+
+        let mut config = Config::new();
+        config.async_support(true);
+        config.wasm_component_model(true);
+        config.wasm_component_model_async(true);
+
+        let engine = Engine::new(&config).map_err(ComponentizeError::Instantiate)?;
+        let new_linker = || {
+            let mut linker = Linker::new(&engine);
+            if let Some(add_to_linker) = add_to_linker {
+                add_to_linker(&mut linker)?;
+            } else {
+                add_wasi_and_stubs(
+                    &resolve,
+                    &[world].into_iter().collect(),
+                    options.wasi_host,
+                    &mut linker,
+                )?;
+            }
+            anyhow::Ok(linker)
+        };
+
+        let (component, warnings, stdout, stderr) = if options.skip_snapshot {
+            (component, Vec::new(), String::new(), String::new())
+        } else {
+            let stdout = MemoryOutputPipe::new(INIT_OUTPUT_CAPACITY);
+            let stderr = MemoryOutputPipe::new(INIT_OUTPUT_CAPACITY);
+
+            let mut wasi = WasiCtxBuilder::new();
+            if let Some(dir) = js_base_directory {
+                wasi.preopened_dir(dir, "/", DirPerms::all(), FilePerms::all())
+                    .map_err(ComponentizeError::Instantiate)?;
+            }
+            let wasi = wasi
+                .stdin(MemoryInputPipe::new(Bytes::from(options.stdin.clone())))
+                .stdout(stdout.clone())
+                .stderr(stderr.clone())
+                .build();
+            let table = ResourceTable::new();
+
+            let mut store = Store::new(&engine, Ctx { wasi, table });
+
+            let mut wizer = Wizer::new();
+            if options.hot_reload {
+                wizer.keep_init_func(true);
+            }
+            let (cx, instrumented) = wizer
+                .instrument_component(&component)
+                .map_err(ComponentizeError::Instantiate)?;
+            write_debug_artifact(options, "instrumented.wasm", &instrumented)?;
+
+            let instance = async {
+                let component =
+                    compile_cached(&engine, &instrumented, options.cache_dir.as_deref())?;
+                timings.finish("compile");
+
+                let instance = new_linker()?
+                    .instantiate_async(&mut store, &component)
+                    .await?;
+                timings.finish("instantiate");
+                anyhow::Ok(instance)
+            }
+            .await
+            .map_err(ComponentizeError::Instantiate)?;
+
+            let warnings = {
+                let init_error = |message, stack| ComponentizeError::InitScript {
+                    stdout: String::from_utf8_lossy(&stdout.contents()).into_owned(),
+                    stderr: String::from_utf8_lossy(&stderr.contents()).into_owned(),
+                    message,
+                    stack,
+                };
+
+                let instance =
+                    Init::new(&mut store, &instance).map_err(ComponentizeError::Instantiate)?;
+                match instance
+                    .call_init(
+                        &mut store,
+                        init_options,
+                        &generated_code.globals,
+                        &generated_code.modules,
+                        js,
+                        &generated_code.script,
+                    )
+                    .await
+                {
+                    Ok(Ok(warnings)) => warnings,
+                    Ok(Err(bindings::InitError { message, stack })) => {
+                        return Err(init_error(message, stack));
+                    }
+                    Err(trap) => return Err(init_error(format!("{trap:?}"), None)),
+                }
+            };
+            timings.finish("evaluate");
+
+            let component = wizer
+                .snapshot_component(
+                    cx,
+                    &mut WasmtimeWizerComponent {
+                        store: &mut store,
+                        instance,
+                    },
+                )
+                .await
+                .map_err(ComponentizeError::Snapshot)?;
+
+            timings.finish("snapshot");
+            write_debug_artifact(options, "snapshot.wasm", &component)?;
+
+            (
+                component,
+                warnings,
+                String::from_utf8_lossy(&stdout.contents()).into_owned(),
+                String::from_utf8_lossy(&stderr.contents()).into_owned(),
+            )
+        };
+
+        let component = if let Some(bytes) = options.max_memory_bytes {
+            let component =
+                set_memory_maximum(&component, bytes).map_err(ComponentizeError::PostProcess)?;
+            timings.finish("post-process");
+            component
+        } else {
+            component
+        };
+
+        if let Some((export, params)) = &options.smoke_test {
+            async {
+                let mut store = Store::new(
+                    &engine,
+                    Ctx {
+                        wasi: WasiCtxBuilder::new().build(),
+                        table: ResourceTable::new(),
+                    },
+                );
+                let instance = new_linker()?
+                    .instantiate_async(&mut store, &Component::new(&engine, &component)?)
+                    .await?;
+                let func = export_func(&mut store, &instance, export)?;
+                let mut results = vec![Val::Bool(false); func.ty(&store).results().len()];
+                func.call_async(&mut store, params, &mut results).await?;
+                anyhow::Ok(())
+            }
+            .await
+            .map_err(ComponentizeError::SmokeTest)?;
+            timings.finish("smoke test");
+        }
+
+        if options.timings {
+            timings.print();
+        }
+
+        Ok((
+            component,
+            Diagnostics {
+                stdout,
+                stderr,
+                warnings,
+                timings: timings.stages,
+            },
+        ))
+    }
+}
+
+/// Maximum number of bytes captured from each of stdout and stderr while the
+/// script is evaluated; heap censuses and GC logs can be fairly large.
+const INIT_OUTPUT_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// Information gathered by [`Componentizer::componentize_with_diagnostics`]
+/// while generating a component.
+#[derive(Clone, Debug, Default)]
+pub struct Diagnostics {
+    /// Anything the script wrote to stdout while being evaluated.
+    pub stdout: String,
+    /// Anything the script wrote to stderr while being evaluated.
+    pub stderr: String,
+    /// Warnings produced while evaluating the script, e.g. about exports which
+    /// don't match the world.
+    pub warnings: Vec<String>,
+    /// The duration of each stage of componentization (e.g. `link` or
+    /// `snapshot`), in the order they ran.
+    pub timings: Vec<(&'static str, Duration)>,
+}
+
+/// Durations of each stage of [`Componentizer::componentize`], printed if
+/// [`Options::timings`] is set and returned in [`Diagnostics::timings`].
+struct Timings {
+    start: Instant,
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Record the time elapsed since the previous stage finished as the
+    /// duration of `stage`.
+    fn finish(&mut self, stage: &'static str) {
+        let now = Instant::now();
+        self.stages.push((stage, now - self.start));
+        self.start = now;
+    }
+
+    fn print(&self) {
+        for (stage, duration) in &self.stages {
+            eprintln!("componentize-js: {stage}: {duration:.2?}");
+        }
+        let total = self
+            .stages
+            .iter()
+            .map(|(_, duration)| *duration)
+            .sum::<Duration>();
+        eprintln!("componentize-js: total: {total:.2?}");
+    }
+}
+
+/// Write `bytes` to a file named `name` in [`Options::debug_artifacts_dir`], if
+/// set.
+fn write_debug_artifact(
+    options: &Options,
+    name: &str,
+    bytes: &[u8],
+) -> Result<(), ComponentizeError> {
+    if let Some(dir) = &options.debug_artifacts_dir {
+        let path = dir.join(name);
+        fs::create_dir_all(dir)
+            .and_then(|()| fs::write(&path, bytes))
+            .with_context(|| format!("unable to write `{}`", path.display()))
+            .map_err(ComponentizeError::DebugArtifact)?;
+    }
+    Ok(())
+}
+
+/// Look up the function exported by `instance` as `name`, which uses `#` to
+/// separate interface and function names, e.g. `wasi:cli/run@0.2.0#run`.
+fn export_func(store: &mut Store<Ctx>, instance: &Instance, name: &str) -> anyhow::Result<Func> {
+    let index = name
+        .split('#')
+        .try_fold(None, |parent, name| {
+            instance
+                .get_export_index(&mut *store, parent.as_ref(), name)
+                .map(Some)
+        })
+        .flatten()
+        .with_context(|| format!("no such export: `{name}`"))?;
+    instance
+        .get_func(store, &index)
+        .with_context(|| format!("export `{name}` is not a function"))
+}
+
+/// Compile `component`, reusing a copy previously compiled by a compatible
+/// `engine` and saved to `cache_dir`, if available.
+fn compile_cached(
+    engine: &Engine,
+    component: &[u8],
+    cache_dir: Option<&Path>,
+) -> anyhow::Result<Component> {
+    struct Hasher(Sha256);
+
+    impl hash::Hasher for Hasher {
+        fn finish(&self) -> u64 {
+            unreachable!()
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.update(bytes);
+        }
+    }
+
+    let Some(cache_dir) = cache_dir else {
+        return Component::new(engine, component);
+    };
+
+    let mut hasher = Hasher(Sha256::new());
+    engine.precompile_compatibility_hash().hash(&mut hasher);
+    hasher.0.update(component);
+    let key = hasher
+        .0
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    let path = cache_dir.join(format!("{key}.cwasm"));
+
+    if path.exists() {
+        // SAFETY: Per the documentation for `Options::cache_dir`, the cache
+        // directory must only contain files written by this function.
+        if let Ok(component) = unsafe { Component::deserialize_file(engine, &path) } {
+            return Ok(component);
+        }
+    }
+
+    let compiled = Component::new(engine, component)?;
+
+    // Write to a temporary file and rename it so that concurrent builds never
+    // see a partially-written file.
+    fs::create_dir_all(cache_dir)
+        .with_context(|| format!("unable to create `{}`", cache_dir.display()))?;
+    let tmp = cache_dir.join(format!("{key}.{}.tmp", process::id()));
+    fs::write(&tmp, compiled.serialize()?)
+        .with_context(|| format!("unable to write `{}`", tmp.display()))?;
+    fs::rename(&tmp, &path).with_context(|| format!("unable to write `{}`", path.display()))?;
+
+    Ok(compiled)
+}
+
+/// Parse the specified WIT and select the specified world (or the default world
+/// if `None`) from it.
+fn resolve_world(
     wit: Wit<'_, impl AsRef<Path>>,
     world: Option<&str>,
     features: &[String],
     all_features: bool,
-    js: &str,
-    js_base_directory: Option<impl AsRef<Path>>,
-    add_to_linker: Option<&dyn Fn(&mut Linker<Ctx>) -> anyhow::Result<()>>,
-) -> anyhow::Result<Vec<u8>> {
+) -> Result<(Resolve, WorldId), ComponentizeError> {
     let mut resolve = Resolve {
         all_features,
         ..Default::default()
@@ -80,156 +1027,191 @@ pub async fn componentize(
         }
     }
 
-    let package = match wit {
-        Wit::String(wit) => resolve.push_str("wit", wit)?,
-        Wit::Paths(paths) => {
-            let mut last_pkg = None;
-            for path in paths.iter().map(AsRef::as_ref) {
-                let pkg = if path.is_dir() {
-                    resolve.push_dir(path)?.0
-                } else {
-                    let pkg = UnresolvedPackageGroup::parse_file(path)?;
-                    resolve.push_group(pkg)?
-                };
-                last_pkg = Some(pkg);
+    let packages = match wit {
+        Wit::String(wit) => resolve.push_str("wit", wit).map(|pkg| vec![pkg]),
+        Wit::Paths(paths) => (|| -> anyhow::Result<_> {
+            if paths.is_empty() {
+                bail!("no WIT paths specified");
+            }
+            paths
+                .iter()
+                .map(AsRef::as_ref)
+                .map(|path| {
+                    let pkg = if path.is_dir() {
+                        resolve.push_dir(path).map(|(pkg, _)| pkg)
+                    } else {
+                        UnresolvedPackageGroup::parse_file(path)
+                            .and_then(|pkg| resolve.push_group(pkg))
+                    };
+                    if paths.len() > 1 {
+                        pkg.with_context(|| {
+                            format!(
+                                "unable to resolve `{}` (note that packages are resolved in \
+                                 the order given, so any package it uses which isn't under a \
+                                 `deps` directory must be specified before it)",
+                                path.display()
+                            )
+                        })
+                    } else {
+                        pkg
+                    }
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })(),
+    }
+    .map_err(ComponentizeError::WitParse)?;
+
+    // Look for the world in the last package given (e.g. the application's own
+    // package, following any it depends on) first.  If it isn't there and was
+    // specified by an unqualified name, look in the other packages, too.
+    let last = &packages[packages.len() - 1..];
+    let world = match (resolve.select_world(last, world), world) {
+        (Ok(world), _) => world,
+        (Err(error), Some(name)) if !name.contains(':') => {
+            let found = packages
+                .iter()
+                .filter_map(|&pkg| resolve.packages[pkg].worlds.get(name).copied())
+                .collect::<IndexSet<_>>();
+            match found.len() {
+                0 => return Err(ComponentizeError::WorldSelection(error)),
+                1 => found[0],
+                _ => {
+                    return Err(ComponentizeError::WorldSelection(anyhow!(
+                        "world `{name}` is defined by more than one package; \
+                         specify one of {} instead",
+                        found
+                            .iter()
+                            .map(|&world| {
+                                let package = resolve.worlds[world].package.unwrap();
+                                format!("`{}`", resolve.id_of_name(package, name))
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )));
+                }
             }
-            last_pkg.unwrap() // The paths should not be empty
         }
+        (Err(error), _) => return Err(ComponentizeError::WorldSelection(error)),
     };
-    let world = resolve.select_world(&[package], world)?;
 
-    let (mut bindings, metadata) = wit_dylib::create_with_metadata(
+    Ok((resolve, world))
+}
+
+/// Generate a JSON manifest describing a component previously generated by
+/// a [`Componentizer`] using the same `wit`, `world`, `features`,
+/// `all_features`, and `js` parameters.
+///
+/// The manifest includes the world's imports and exports (with function
+/// signatures rendered as WIT), whether each function is async, the size and
+/// SHA-256 hash of both the script and the component, and the name and version
+/// of this crate.  It is intended for deployment platforms which want to
+/// inspect a component without parsing it.
+pub fn manifest(
+    wit: Wit<'_, impl AsRef<Path>>,
+    world: Option<&str>,
+    features: &[String],
+    all_features: bool,
+    js: &str,
+    component: &[u8],
+) -> Result<String, ComponentizeError> {
+    let (resolve, world) = resolve_world(wit, world, features, all_features)?;
+    Ok(manifest::Manifest::new(&resolve, world, js, component).to_json())
+}
+
+/// Check that `component` (e.g. one previously generated by a [`Componentizer`])
+/// is a valid component targeting the world selected by `wit`, `world`,
+/// `features`, and `all_features`, returning a description of each problem
+/// found.
+///
+/// This checks that the component passes component model validation, that its
+/// exports match the world's exports, and that it has no imports other than
+/// those of the world and (unless `strict_imports` is set) WASI interfaces.
+/// The latter is useful for verifying that e.g. `virtual_env` and
+/// `virtual_clock` removed the imports they were meant to remove.
+pub fn validate(
+    wit: Wit<'_, impl AsRef<Path>>,
+    world: Option<&str>,
+    features: &[String],
+    all_features: bool,
+    component: &[u8],
+    strict_imports: bool,
+) -> Result<Vec<String>, ComponentizeError> {
+    let (resolve, world) = resolve_world(wit, world, features, all_features)?;
+    Ok(validate::validate(
         &resolve,
         world,
-        Some(&mut DylibOpts {
-            interpreter: Some("libcomponentize_js_runtime.so".into()),
-            async_: Default::default(),
-        }),
-    );
-
-    CustomSection {
-        name: Cow::Borrowed("component-type:componentize-js"),
-        data: Cow::Owned(metadata::encode(
-            &resolve,
-            world,
-            wit_component::StringEncoding::UTF8,
-            None,
-        )?),
-    }
-    .append_to(&mut bindings);
-
-    let generated_code = codegen::generate(&metadata);
-    let generated_script = &generated_code.script;
-    let js = &format!("{js}\n{generated_script}");
+        component,
+        strict_imports,
+    ))
+}
 
-    let component = {
-        let mut linker = wit_component::Linker::default()
-            .validate(true)
-            .use_built_in_libdl(true);
+/// Generate TypeScript declarations describing the JS bindings for the world
+/// selected by `wit`, `world`, `features`, and `all_features`, returning the
+/// name and contents of each file.
+///
+/// `imports.d.ts` declares the modules the script may import (one per imported
+/// interface, plus `wit-world`), and `exports.d.ts` declares an `Exports`
+/// interface describing what the script must export, given the same
+/// `export_aliases` and `export_layout` as passed to a [`Componentizer`] via
+/// [`Options`].
+pub fn generate_types(
+    wit: Wit<'_, impl AsRef<Path>>,
+    world: Option<&str>,
+    features: &[String],
+    all_features: bool,
+    export_aliases: &[(String, String)],
+    export_layout: ExportLayout,
+) -> Result<Vec<(String, String)>, ComponentizeError> {
+    let (resolve, world) = resolve_world(wit, world, features, all_features)?;
+    Ok(typescript::generate(
+        &resolve,
+        world,
+        export_aliases,
+        export_layout,
+    ))
+}
 
-        linker = linker.library(
-            "libcomponentize_js_runtime.so",
-            &zstd::decode_all(Cursor::new(include_bytes!(concat!(
-                env!("OUT_DIR"),
-                "/libcomponentize_js_runtime.so.zst"
-            ))))?,
-            false,
-        )?;
+/// Rewrite `component` such that every memory type (whether defined or
+/// imported) has a maximum of `bytes`, rounded down to a whole number of pages.
+fn set_memory_maximum(component: &[u8], bytes: u64) -> anyhow::Result<Vec<u8>> {
+    struct MemoryMaximum {
+        pages: u64,
+        too_small: Option<u64>,
+    }
 
-        linker = linker.library("libcomponentize_js_bindings.so", &bindings, false)?;
+    impl Reencode for MemoryMaximum {
+        type Error = Infallible;
 
-        linker = linker.library(
-            "libc.so",
-            &zstd::decode_all(Cursor::new(include_bytes!(concat!(
-                env!("OUT_DIR"),
-                "/libc.so.zst"
-            ))))?,
-            false,
-        )?;
-
-        linker = linker.library(
-            "libwasi-emulated-getpid.so",
-            &zstd::decode_all(Cursor::new(include_bytes!(concat!(
-                env!("OUT_DIR"),
-                "/libwasi-emulated-getpid.so.zst"
-            ))))?,
-            false,
-        )?;
-
-        linker = linker.adapter(
-            "wasi_snapshot_preview1",
-            &zstd::decode_all(Cursor::new(include_bytes!(concat!(
-                env!("OUT_DIR"),
-                "/wasi_snapshot_preview1.reactor.wasm.zst"
-            ))))?,
-        )?;
+        fn memory_type(&mut self, ty: wasmparser::MemoryType) -> wasm_encoder::MemoryType {
+            let mut ty = reencode::utils::memory_type(self, ty);
+            if ty.minimum > self.pages {
+                self.too_small = Some(ty.minimum);
+            }
+            ty.maximum = Some(self.pages);
+            ty
+        }
+    }
 
-        linker.encode().map_err(|e| anyhow::anyhow!(e))
-    }?;
+    impl ReencodeComponent for MemoryMaximum {}
 
-    let stdout = MemoryOutputPipe::new(10000);
-    let stderr = MemoryOutputPipe::new(10000);
+    const PAGE_SIZE: u64 = 64 * 1024;
 
-    let mut wasi = WasiCtxBuilder::new();
-    if let Some(dir) = js_base_directory {
-        wasi.preopened_dir(dir, "/", DirPerms::all(), FilePerms::all())?;
-    }
-    let wasi = wasi
-        .stdin(MemoryInputPipe::new(Bytes::new()))
-        .stdout(stdout.clone())
-        .stderr(stderr.clone())
-        .build();
-    let table = ResourceTable::new();
-
-    let mut config = Config::new();
-    config.async_support(true);
-    config.wasm_component_model(true);
-    config.wasm_component_model_async(true);
-
-    let engine = Engine::new(&config)?;
-    let mut store = Store::new(&engine, Ctx { wasi, table });
-
-    let wizer = Wizer::new();
-    let (cx, component) = wizer.instrument_component(&component)?;
-    let component = Component::new(&engine, &component)?;
-
-    let mut linker = Linker::new(&engine);
-    if let Some(add_to_linker) = add_to_linker {
-        add_to_linker(&mut linker)?;
-    } else {
-        add_wasi_and_stubs(&resolve, &[world].into_iter().collect(), &mut linker)?;
-    }
+    let mut reencoder = MemoryMaximum {
+        pages: bytes / PAGE_SIZE,
+        too_small: None,
+    };
+    let mut output = wasm_encoder::Component::new();
+    reencoder.parse_component(&mut output, wasmparser::Parser::new(0), component)?;
 
-    let instance = linker.instantiate_async(&mut store, &component).await?;
-    {
-        let instance = Init::new(&mut store, &instance)?;
-        instance
-            .call_init(
-                &mut store,
-                &generated_code.globals,
-                &generated_code.modules,
-                js,
-            )
-            .await
-            .and_then(|v| v.map_err(|e| anyhow!("{e}")))
-            .with_context(move || {
-                format!(
-                    "{}{}",
-                    String::from_utf8_lossy(&stdout.contents()),
-                    String::from_utf8_lossy(&stderr.contents())
-                )
-            })?;
+    if let Some(minimum) = reencoder.too_small {
+        bail!(
+            "maximum memory size of {bytes} bytes is smaller than the snapshot's \
+             initial memory size of {} bytes",
+            minimum * PAGE_SIZE
+        );
     }
 
-    wizer
-        .snapshot_component(
-            cx,
-            &mut WasmtimeWizerComponent {
-                store: &mut store,
-                instance,
-            },
-        )
-        .await
+    Ok(output.finish())
 }
 
 // Stolen from https://github.com/bytecodealliance/componentize-py/blob/89af297898960efc48575d4c166d03b399568269/src/lib.rs#L761-L911
@@ -239,9 +1221,13 @@ pub async fn componentize(
 fn add_wasi_and_stubs(
     resolve: &Resolve,
     worlds: &IndexSet<WorldId>,
+    wasi_host: WasiHost,
     linker: &mut Linker<Ctx>,
 ) -> anyhow::Result<()> {
     wasmtime_wasi::p2::add_to_linker_async(linker)?;
+    if let WasiHost::P3 = wasi_host {
+        wasmtime_wasi::p3::add_to_linker(linker)?;
+    }
 
     enum Stub<'a> {
         Function(&'a String, &'a FunctionKind),
@@ -297,10 +1283,10 @@ fn add_wasi_and_stubs(
     for (interface_name, stubs) in stubs {
         if let Some(interface_name) = interface_name {
             // Note that we do _not_ stub interfaces which appear to be part of
-            // WASIp2 since those should be provided by the
-            // `wasmtime_wasi::add_to_linker_async` call above, and adding stubs
-            // to those same interfaces would just cause trouble.
-            if !is_wasip2_cli(&interface_name)
+            // WASIp2 (or WASIp3, if enabled) since those should be provided by
+            // the `add_to_linker` calls above, and adding stubs to those same
+            // interfaces would just cause trouble.
+            if !is_wasi_cli(&interface_name, wasi_host)
                 && let Ok(mut instance) = linker.instance(&interface_name)
             {
                 for stub in stubs {
@@ -378,12 +1364,13 @@ fn add_wasi_and_stubs(
     Ok(())
 }
 
-fn is_wasip2_cli(interface_name: &str) -> bool {
+fn is_wasi_cli(interface_name: &str, wasi_host: WasiHost) -> bool {
     (interface_name.starts_with("wasi:cli/")
         || interface_name.starts_with("wasi:clocks/")
         || interface_name.starts_with("wasi:random/")
         || interface_name.starts_with("wasi:io/")
         || interface_name.starts_with("wasi:filesystem/")
         || interface_name.starts_with("wasi:sockets/"))
-        && interface_name.contains("@0.2.")
+        && (interface_name.contains("@0.2.")
+            || (wasi_host == WasiHost::P3 && interface_name.contains("@0.3.")))
 }
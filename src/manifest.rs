@@ -0,0 +1,282 @@
+//! Generation of JSON descriptions of a WIT world and of a generated component,
+//! intended to be consumed by deployment platforms which want metadata about a
+//! component without parsing it, and by scripts via the `componentModel.world`
+//! global.
+
+use {
+    serde::Serialize,
+    sha2::{Digest as _, Sha256},
+    wit_parser::{
+        Function, Handle, Resolve, Type, TypeDefKind, TypeOwner, WorldId, WorldItem, WorldKey,
+    },
+};
+
+#[derive(Serialize)]
+pub struct World {
+    /// The fully-qualified name of the world.
+    name: String,
+    imports: Vec<Item>,
+    exports: Vec<Item>,
+}
+
+#[derive(Serialize)]
+pub struct Manifest {
+    /// The world the component targets.
+    world: World,
+    /// The name and version of the tool which generated the component.
+    runtime: Runtime,
+    /// The size and hash of the JS source embedded in the component.
+    script: Blob,
+    /// The size and hash of the component itself.
+    component: Blob,
+}
+
+#[derive(Serialize)]
+struct Runtime {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+struct Blob {
+    bytes: usize,
+    sha256: String,
+}
+
+impl Blob {
+    fn new(data: &[u8]) -> Self {
+        Self {
+            bytes: data.len(),
+            sha256: Sha256::digest(data)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Item {
+    Interface { name: String, functions: Vec<Func> },
+    Function(Func),
+    Type { name: String, definition: String },
+}
+
+#[derive(Serialize, PartialEq)]
+pub struct Func {
+    name: String,
+    #[serde(rename = "async")]
+    async_: bool,
+    params: Vec<Param>,
+    result: Option<String>,
+}
+
+#[derive(Serialize, PartialEq)]
+pub struct Param {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+impl World {
+    pub fn new(resolve: &Resolve, world: WorldId) -> Self {
+        let world = &resolve.worlds[world];
+        let items = |items: &mut dyn Iterator<Item = (&WorldKey, &WorldItem)>| {
+            items
+                .map(|(key, item)| match item {
+                    WorldItem::Interface { id, .. } => Item::Interface {
+                        name: resolve.name_world_key(key),
+                        functions: resolve.interfaces[*id]
+                            .functions
+                            .values()
+                            .map(|function| func(resolve, function))
+                            .collect(),
+                    },
+                    WorldItem::Function(function) => Item::Function(func(resolve, function)),
+                    WorldItem::Type { id, .. } => Item::Type {
+                        name: resolve.name_world_key(key),
+                        definition: definition(resolve, &resolve.types[*id].kind),
+                    },
+                })
+                .collect::<Vec<_>>()
+        };
+
+        Self {
+            name: match world.package {
+                Some(package) => format!("{}/{}", resolve.packages[package].name, world.name),
+                None => world.name.clone(),
+            },
+            imports: items(&mut world.imports.iter()),
+            exports: items(&mut world.exports.iter()),
+        }
+    }
+
+    pub fn imports(&self) -> &[Item] {
+        &self.imports
+    }
+
+    pub fn exports(&self) -> &[Item] {
+        &self.exports
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl Item {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Interface { name, .. } | Self::Type { name, .. } => name,
+            Self::Function(func) => &func.name,
+        }
+    }
+}
+
+impl Manifest {
+    pub fn new(resolve: &Resolve, world: WorldId, js: &str, component: &[u8]) -> Self {
+        Self {
+            world: World::new(resolve, world),
+            runtime: Runtime {
+                name: env!("CARGO_PKG_NAME"),
+                version: env!("CARGO_PKG_VERSION"),
+            },
+            script: Blob::new(js.as_bytes()),
+            component: Blob::new(component),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+fn func(resolve: &Resolve, function: &Function) -> Func {
+    Func {
+        name: function.name.clone(),
+        async_: function.kind.is_async(),
+        params: function
+            .params
+            .iter()
+            .map(|(name, ty)| Param {
+                name: name.clone(),
+                ty: type_name(resolve, ty),
+            })
+            .collect(),
+        result: function.result.as_ref().map(|ty| type_name(resolve, ty)),
+    }
+}
+
+/// Render `ty` using WIT syntax, referring to named types by name.
+fn type_name(resolve: &Resolve, ty: &Type) -> String {
+    match ty {
+        Type::Bool => "bool".into(),
+        Type::U8 => "u8".into(),
+        Type::U16 => "u16".into(),
+        Type::U32 => "u32".into(),
+        Type::U64 => "u64".into(),
+        Type::S8 => "s8".into(),
+        Type::S16 => "s16".into(),
+        Type::S32 => "s32".into(),
+        Type::S64 => "s64".into(),
+        Type::F32 => "f32".into(),
+        Type::F64 => "f64".into(),
+        Type::Char => "char".into(),
+        Type::String => "string".into(),
+        Type::ErrorContext => "error-context".into(),
+        Type::Id(id) => {
+            let ty = &resolve.types[*id];
+            match &ty.name {
+                Some(name) => match ty.owner {
+                    TypeOwner::Interface(interface) => match resolve.id_of(interface) {
+                        Some(interface) => format!("{interface}.{name}"),
+                        None => name.clone(),
+                    },
+                    _ => name.clone(),
+                },
+                None => definition(resolve, &ty.kind),
+            }
+        }
+    }
+}
+
+/// Render the definition of a type using WIT syntax.
+fn definition(resolve: &Resolve, kind: &TypeDefKind) -> String {
+    let optional = |ty: &Option<Type>| {
+        ty.as_ref()
+            .map(|ty| type_name(resolve, ty))
+            .unwrap_or_else(|| "_".into())
+    };
+
+    match kind {
+        TypeDefKind::Record(record) => format!(
+            "record {{ {} }}",
+            record
+                .fields
+                .iter()
+                .map(|field| format!("{}: {}", field.name, type_name(resolve, &field.ty)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        TypeDefKind::Resource => "resource".into(),
+        TypeDefKind::Handle(Handle::Own(id)) => {
+            format!("own<{}>", type_name(resolve, &Type::Id(*id)))
+        }
+        TypeDefKind::Handle(Handle::Borrow(id)) => {
+            format!("borrow<{}>", type_name(resolve, &Type::Id(*id)))
+        }
+        TypeDefKind::Flags(flags) => format!(
+            "flags {{ {} }}",
+            flags
+                .flags
+                .iter()
+                .map(|flag| flag.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        TypeDefKind::Tuple(tuple) => format!(
+            "tuple<{}>",
+            tuple
+                .types
+                .iter()
+                .map(|ty| type_name(resolve, ty))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        TypeDefKind::Variant(variant) => format!(
+            "variant {{ {} }}",
+            variant
+                .cases
+                .iter()
+                .map(|case| match &case.ty {
+                    Some(ty) => format!("{}({})", case.name, type_name(resolve, ty)),
+                    None => case.name.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        TypeDefKind::Enum(enum_) => format!(
+            "enum {{ {} }}",
+            enum_
+                .cases
+                .iter()
+                .map(|case| case.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        TypeDefKind::Option(ty) => format!("option<{}>", type_name(resolve, ty)),
+        TypeDefKind::Result(result) => {
+            format!(
+                "result<{}, {}>",
+                optional(&result.ok),
+                optional(&result.err)
+            )
+        }
+        TypeDefKind::List(ty) => format!("list<{}>", type_name(resolve, ty)),
+        TypeDefKind::Future(ty) => format!("future<{}>", optional(ty)),
+        TypeDefKind::Stream(ty) => format!("stream<{}>", optional(ty)),
+        TypeDefKind::Type(ty) => type_name(resolve, ty),
+        _ => "unknown".into(),
+    }
+}
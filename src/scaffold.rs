@@ -0,0 +1,133 @@
+//! Generation of starter projects for the `new` subcommand.
+
+use {
+    anyhow::{Context as _, bail},
+    std::{fs, path::Path},
+};
+
+/// Version of the WASI interfaces targeted by generated projects.
+const WASI_VERSION: &str = "0.3.0-rc-2026-01-06";
+
+/// A kind of starter project.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Template {
+    /// A `wasi:cli/command` which prints "Hello, world!".
+    Cli,
+    /// A `wasi:http/service` which responds to every request with a greeting.
+    Http,
+}
+
+impl Template {
+    fn world(&self) -> String {
+        match self {
+            Self::Cli => format!("wasi:cli/command@{WASI_VERSION}"),
+            Self::Http => format!("wasi:http/service@{WASI_VERSION}"),
+        }
+    }
+
+    fn script(&self) -> &'static str {
+        match self {
+            Self::Cli => include_str!("templates/cli.js"),
+            Self::Http => include_str!("templates/http.js"),
+        }
+    }
+
+    fn deps(&self) -> &'static [(&'static str, &'static str)] {
+        macro_rules! deps {
+            ($($name:literal),*) => {
+                &[$((
+                    concat!($name, "-0.3.0-rc-2026-01-06.wit"),
+                    include_str!(concat!("../wit/deps/", $name, "-0.3.0-rc-2026-01-06.wit")),
+                )),*]
+            };
+        }
+
+        match self {
+            Self::Cli => deps!("cli", "clocks", "filesystem", "random", "sockets"),
+            Self::Http => deps!("cli", "clocks", "filesystem", "random", "sockets", "http"),
+        }
+    }
+
+    fn manifest(&self) -> serde_json::Value {
+        let mut manifest = serde_json::json!({
+            "wit-path": ["wit"],
+            "world": "app",
+            "input": "app.js",
+            "output": "app.wasm",
+            "wasi-host": "p3",
+        });
+        if let Self::Cli = self {
+            manifest["smoke-test"] = format!("wasi:cli/run@{WASI_VERSION}#run").into();
+        }
+        manifest
+    }
+
+    fn run_command(&self) -> &'static str {
+        match self {
+            Self::Cli => "wasmtime run -Sp3 -Wcomponent-model-async app.wasm",
+            Self::Http => "wasmtime serve -Sp3,common -Wcomponent-model-async app.wasm",
+        }
+    }
+}
+
+/// Create a new project from `template` in `directory`, which must either not
+/// exist or be empty.
+pub fn create(template: Template, directory: &Path) -> anyhow::Result<()> {
+    if directory.exists()
+        && fs::read_dir(directory)
+            .with_context(|| format!("unable to read `{}`", directory.display()))?
+            .next()
+            .is_some()
+    {
+        bail!("`{}` already exists and is not empty", directory.display());
+    }
+
+    let world = template.world();
+    let manifest = serde_json::to_string_pretty(&template.manifest())?;
+    let run = template.run_command();
+    let files = [
+        (
+            "wit/app.wit".to_string(),
+            format!(
+                "package local:app;\n\n\
+                 world app {{\n  \
+                   include {world};\n\
+                 }}\n"
+            ),
+        ),
+        ("app.js".into(), template.script().into()),
+        ("componentize-js.json".into(), format!("{manifest}\n")),
+        (".gitignore".into(), "*.wasm\n".into()),
+        (
+            "README.md".into(),
+            format!(
+                "# `app`\n\n\
+                 A JavaScript component targeting the `{world}` world.\n\n\
+                 To build and run it:\n\n\
+                 ```\n\
+                 componentize-js build\n\
+                 {run}\n\
+                 ```\n\n\
+                 Add imports and exports to the world in `wit/app.wit` as needed; \
+                 WIT dependencies live in `wit/deps`.\n"
+            ),
+        ),
+    ]
+    .into_iter()
+    .chain(
+        template
+            .deps()
+            .iter()
+            .map(|(name, wit)| (format!("wit/deps/{name}"), wit.to_string())),
+    );
+
+    for (name, contents) in files {
+        let path = directory.join(name);
+        fs::create_dir_all(path.parent().unwrap())
+            .with_context(|| format!("unable to create `{}`", path.parent().unwrap().display()))?;
+        fs::write(&path, contents)
+            .with_context(|| format!("unable to write `{}`", path.display()))?;
+    }
+
+    Ok(())
+}
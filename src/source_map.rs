@@ -0,0 +1,122 @@
+//! Decoding of [source maps](https://tc39.es/ecma426/) into the form the
+//! runtime uses to rewrite stack traces.
+
+use {
+    crate::bindings::{Mapping, SourceMap},
+    anyhow::{Context as _, bail, ensure},
+    serde::Deserialize,
+};
+
+#[derive(Deserialize)]
+struct Json {
+    version: u32,
+    #[serde(rename = "sourceRoot", default)]
+    source_root: Option<String>,
+    #[serde(default)]
+    sources: Vec<Option<String>>,
+    #[serde(default)]
+    mappings: Option<String>,
+}
+
+/// Decode the specified source map (in JSON form).
+///
+/// Index maps (i.e. those with `sections`) are not supported.
+pub fn decode(json: &str) -> anyhow::Result<SourceMap> {
+    let json = serde_json::from_str::<Json>(json).context("invalid source map")?;
+    ensure!(
+        json.version == 3,
+        "unsupported source map version {}",
+        json.version
+    );
+    let Some(mappings) = json.mappings else {
+        bail!("source map has no `mappings`; index maps are not supported");
+    };
+
+    let root = match json.source_root.as_deref() {
+        Some("") | None => String::new(),
+        Some(root) if root.ends_with('/') => root.into(),
+        Some(root) => format!("{root}/"),
+    };
+    let sources = json
+        .sources
+        .into_iter()
+        .map(|source| format!("{root}{}", source.unwrap_or_default()))
+        .collect::<Vec<_>>();
+
+    let mut decoded = Vec::new();
+    let (mut source, mut line, mut column) = (0_i64, 0_i64, 0_i64);
+    for (generated_line, group) in mappings.split(';').enumerate() {
+        let mut generated_column = 0_i64;
+        for segment in group.split(',').filter(|s| !s.is_empty()) {
+            let fields = decode_vlq(segment)?;
+            match fields[..] {
+                [delta] => generated_column += delta,
+                [column_delta, source_delta, line_delta, delta]
+                | [column_delta, source_delta, line_delta, delta, _] => {
+                    generated_column += column_delta;
+                    source += source_delta;
+                    line += line_delta;
+                    column += delta;
+
+                    let field = |value: i64| {
+                        u32::try_from(value).with_context(|| {
+                            format!(
+                                "invalid source map segment `{segment}` on line {generated_line}"
+                            )
+                        })
+                    };
+                    ensure!(
+                        usize::try_from(source).is_ok_and(|source| source < sources.len()),
+                        "source map segment `{segment}` refers to a nonexistent source"
+                    );
+                    decoded.push(Mapping {
+                        generated_line: u32::try_from(generated_line)?,
+                        generated_column: field(generated_column)?,
+                        source: field(source)?,
+                        line: field(line)?,
+                        column: field(column)?,
+                    });
+                }
+                _ => bail!("invalid source map segment `{segment}` on line {generated_line}"),
+            }
+        }
+    }
+
+    decoded.sort_by_key(|mapping| (mapping.generated_line, mapping.generated_column));
+
+    Ok(SourceMap {
+        sources,
+        mappings: decoded,
+    })
+}
+
+/// Decode a sequence of base64 VLQ values, e.g. `AAgBC`.
+fn decode_vlq(segment: &str) -> anyhow::Result<Vec<i64>> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0_i64, 0);
+    for byte in segment.bytes() {
+        let digit = i64::from(match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => bail!("invalid character in source map segment `{segment}`"),
+        });
+        value += (digit & 0b11111) << shift;
+        if digit & 0b100000 != 0 {
+            shift += 5;
+            ensure!(shift < 32, "source map segment `{segment}` is out of range");
+        } else {
+            values.push(if value & 1 != 0 {
+                -(value >> 1)
+            } else {
+                value >> 1
+            });
+            value = 0;
+            shift = 0;
+        }
+    }
+    ensure!(shift == 0, "truncated source map segment `{segment}`");
+    Ok(values)
+}
@@ -0,0 +1,45 @@
+//! Host-side conversion of TypeScript input to JavaScript, enabled by the
+//! `typescript` feature.
+
+use {
+    anyhow::{Context as _, anyhow, bail},
+    oxc_allocator::Allocator,
+    oxc_codegen::Codegen,
+    oxc_parser::Parser,
+    oxc_semantic::SemanticBuilder,
+    oxc_span::SourceType,
+    oxc_transformer::{TransformOptions, Transformer},
+    std::path::Path,
+};
+
+/// Strip the type annotations (and other TypeScript-only syntax, e.g. `enum`s
+/// and parameter properties) from `source`, returning the equivalent
+/// JavaScript.
+///
+/// `path` is used to determine the dialect (e.g. `.mts` or `.tsx`) and in error
+/// messages.  Only `source` itself is transformed; any TypeScript modules it
+/// imports must be compiled or bundled separately.
+pub fn strip_types(source: &str, path: &Path) -> anyhow::Result<String> {
+    let allocator = Allocator::default();
+    let source_type = SourceType::from_path(path)
+        .map_err(|e| anyhow!("{e}"))
+        .with_context(|| format!("`{}` is not a TypeScript file", path.display()))?;
+
+    let parsed = Parser::new(&allocator, source, source_type).parse();
+    if let Some(error) = parsed.errors.first() {
+        bail!("failed to parse `{}`: {error}", path.display());
+    }
+    let mut program = parsed.program;
+
+    let scoping = SemanticBuilder::new()
+        .build(&program)
+        .semantic
+        .into_scoping();
+    let transformed = Transformer::new(&allocator, path, &TransformOptions::default())
+        .build_with_scoping(scoping, &mut program);
+    if let Some(error) = transformed.errors.first() {
+        bail!("failed to strip types from `{}`: {error}", path.display());
+    }
+
+    Ok(Codegen::new().build(&program).code)
+}
@@ -0,0 +1,28 @@
+import { Response, Fields } from "wasi:http/types@0.3.0-rc-2026-01-06"
+import * as witWorld from "wit-world"
+
+const encoder = new TextEncoder()
+
+export const wasiHttpHandler030Rc20260106 = {
+    handle: async function(request) {
+        const [tx, rx] = witWorld.u8Stream()
+        const response = Response.new(
+            Fields.fromList([["content-type", encoder.encode("text/plain")]]),
+            rx,
+            trailersFuture()
+        )[0]
+        writeBody(tx, `Hello from ${request.getPathWithQuery()}!\n`)
+        return response
+    }
+}
+
+async function writeBody(tx, body) {
+    using _tx = tx
+    await tx.writeAll(encoder.encode(body))
+}
+
+function trailersFuture() {
+    return witWorld.resultOptionWasiHttpTypes030Rc20260106FieldsWasiHttpTypes030Rc20260106ErrorCodeFuture(
+        () => ({ tag: 'ok' })
+    )[1]
+}
@@ -250,12 +250,36 @@ export const componentizeJsTestsStreamsAndFutures = {
             .catch((error) => _componentizeJsLog(error.toString()))
         return Promise.resolve(rx)
     },
+    concatStreamU8: async function(stream) {
+        using _stream = stream
+        const bytes = []
+        for await (const chunk of stream) {
+            bytes.push(...chunk)
+        }
+        return bytes
+    },
+    upperStreamU8: function(stream) {
+        let [tx, rx] = witWorld.u8Stream()
+        const upper = new TransformStream({
+            transform(chunk, controller) {
+                controller.enqueue(chunk.map((byte) => 0x61 <= byte && byte <= 0x7a ? byte - 0x20 : byte))
+            }
+        })
+        stream.toReadableStream()
+            .pipeThrough(upper)
+            .pipeTo(tx.toWritableStream())
+            .catch((error) => _componentizeJsLog(error.toString()))
+        return Promise.resolve(rx)
+    },
     echoFutureString: function(future) {
         let [tx, rx] = witWorld.stringFuture()
         pipeStrings(future, tx)
             .catch((error) => _componentizeJsLog(error.toString()))
         return Promise.resolve(rx)
     },
+    awaitFutureString: function(future) {
+        return future.toPromise()
+    },
     shortReads: function(stream) {
         let [tx, rx] = witWorld.componentizeJsTestsStreamsAndFuturesThingStream()
         pipeThings(stream, tx, Thing)
@@ -283,3 +307,26 @@ export const componentizeJsTestsStreamsAndFutures = {
     },
     Thing
 }
+
+export async function cancelWaitForever() {
+    const promise = witWorld.waitForever()
+    promise.cancel()
+    try {
+        await promise
+        return 'not cancelled'
+    } catch (error) {
+        return `${error.name}: ${error.message}`
+    }
+}
+
+export async function cancelWaitForeverOnAbort() {
+    const controller = new AbortController()
+    const promise = witWorld.waitForever().cancelOn(controller.signal)
+    controller.abort()
+    try {
+        await promise
+        return 'not cancelled'
+    } catch (error) {
+        return `${error.name}: ${error.message}`
+    }
+}
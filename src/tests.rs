@@ -1,5 +1,9 @@
 use {
-    crate::{Ctx, Wit},
+    crate::{
+        Coercion, ComponentizeError, Componentizer, ConsoleOutput, Ctx, ExportLayout, Options,
+        Prefs, WasiHost, Wit,
+    },
+    clap::Parser as _,
     componentize_js::tests::echoes::{EnumType, FlagsType, RecordType, ResourceType, VariantType},
     exports::componentize_js::tests::streams_and_futures,
     futures::{FutureExt as _, TryStreamExt as _, stream::FuturesUnordered},
@@ -10,8 +14,11 @@ use {
     rand::RngExt,
     std::{
         collections::BTreeMap,
-        env, mem,
+        env,
+        ffi::OsString,
+        mem,
         ops::DerefMut,
+        path::Path,
         pin::Pin,
         sync::{Arc, LazyLock, Mutex},
         task::{self, Context, Poll},
@@ -22,12 +29,16 @@ use {
         component::{
             Accessor, Component, Destination, FutureConsumer, FutureProducer, FutureReader,
             HasSelf, Lift, Linker, Resource, ResourceTable, Source, StreamConsumer, StreamProducer,
-            StreamReader, StreamResult, VecBuffer,
+            StreamReader, StreamResult, Val, VecBuffer,
         },
     },
-    wasmtime_wasi::{WasiCtxBuilder, WasiView as _},
+    wasmtime_wasi::{
+        DirPerms, FilePerms, WasiCtxBuilder, WasiView as _, p2::pipe::MemoryOutputPipe,
+    },
 };
 
+mod generated;
+
 wasmtime::component::bindgen!({
     path: "src/tests.wit",
     world: "tests",
@@ -61,15 +72,12 @@ async fn pre() -> &'static TestsPre<Ctx> {
         TestsPre::new(
             linker.instantiate_pre(&Component::new(
                 &ENGINE,
-                crate::componentize(
+                Componentizer::new(
                     Wit::<String>::String(include_str!("tests.wit")),
-                    None,
-                    &[],
-                    false,
                     include_str!("tests.js"),
-                    None::<String>,
-                    Some(&add_to_linker),
                 )
+                .add_to_linker(&add_to_linker)
+                .componentize()
                 .await?,
             )?)?,
         )
@@ -95,6 +103,10 @@ impl TestsImportsWithStore for HasSelf<Ctx> {
         delay_via_yield().await;
         Ok(())
     }
+
+    async fn wait_forever<T>(_: &Accessor<T, Self>) -> anyhow::Result<()> {
+        std::future::pending().await
+    }
 }
 
 #[tokio::test]
@@ -184,6 +196,36 @@ async fn simple_async_import_and_export() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn cancel_async_import() -> anyhow::Result<()> {
+    let mut store = store();
+    let instance = pre().await.instantiate_async(&mut store).await?;
+    assert_eq!(
+        "AbortError: import call cancelled",
+        store
+            .run_concurrent(async |accessor| instance.call_cancel_wait_forever(accessor).await)
+            .await??
+            .0
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancel_async_import_on_abort() -> anyhow::Result<()> {
+    let mut store = store();
+    let instance = pre().await.instantiate_async(&mut store).await?;
+    assert_eq!(
+        "AbortError: import call cancelled",
+        store
+            .run_concurrent(async |accessor| {
+                instance.call_cancel_wait_forever_on_abort(accessor).await
+            })
+            .await??
+            .0
+    );
+    Ok(())
+}
+
 impl componentize_js::tests::types::HostResourceType for Ctx {
     async fn drop(&mut self, v: Resource<ResourceType>) -> anyhow::Result<()> {
         _ = v;
@@ -1390,6 +1432,63 @@ async fn test_echo_stream_u8(delay: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn concat_stream_u8() -> anyhow::Result<()> {
+    let mut store = store();
+    let instance = pre().await.instantiate_async(&mut store).await?;
+    store
+        .run_concurrent(async |store| {
+            let expected =
+                b"Beware the Jabberwock, my son!\n\tThe jaws that bite, the claws that catch!"
+                    .repeat(64);
+            let stream = store
+                .with(|store| StreamReader::new(store, VecProducer::new(expected.clone(), true)));
+
+            let (received, task) = instance
+                .componentize_js_tests_streams_and_futures()
+                .call_concat_stream_u8(store, stream)
+                .await?;
+
+            task.block(store).await;
+
+            assert_eq!(expected, received);
+
+            anyhow::Ok(())
+        })
+        .await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn upper_stream_u8() -> anyhow::Result<()> {
+    let mut store = store();
+    let instance = pre().await.instantiate_async(&mut store).await?;
+    store
+        .run_concurrent(async |store| {
+            let input = b"One, two! One, two! And through and through\n\tThe vorpal blade went snicker-snack!";
+            let stream =
+                store.with(|store| StreamReader::new(store, VecProducer::new(input.to_vec(), true)));
+
+            let (stream, task) = instance
+                .componentize_js_tests_streams_and_futures()
+                .call_upper_stream_u8(store, stream)
+                .await?;
+
+            let received = Arc::new(Mutex::new(Vec::with_capacity(input.len())));
+            store.with(|store| stream.pipe(store, VecConsumer::new(received.clone(), true)));
+
+            task.block(store).await;
+
+            assert_eq!(input.to_ascii_uppercase(), &received.lock().unwrap()[..]);
+
+            anyhow::Ok(())
+        })
+        .await??;
+
+    Ok(())
+}
+
 struct OptionProducer<T> {
     source: Option<T>,
     sleep: Pin<Box<dyn Future<Output = ()> + Send>>,
@@ -1507,6 +1606,34 @@ async fn test_echo_future_string(delay: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn await_future_string() -> anyhow::Result<()> {
+    let mut store = store();
+    let instance = pre().await.instantiate_async(&mut store).await?;
+    store
+        .run_concurrent(async |store| {
+            let expected =
+                "And, as in uffish thought he stood,\n\tThe Jabberwock, with eyes of flame";
+            let future = store.with(|store| {
+                FutureReader::new(store, OptionProducer::new(Some(expected.to_string()), true))
+            });
+
+            let (received, task) = instance
+                .componentize_js_tests_streams_and_futures()
+                .call_await_future_string(store, future)
+                .await?;
+
+            task.block(store).await;
+
+            assert_eq!(expected, received);
+
+            anyhow::Ok(())
+        })
+        .await??;
+
+    Ok(())
+}
+
 struct OneAtATime<T> {
     destination: Arc<Mutex<Vec<T>>>,
     sleep: Pin<Box<dyn Future<Output = ()> + Send>>,
@@ -1845,3 +1972,3364 @@ async fn test_dropped_future_reader_host(delay: bool) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+const RUN_WIT: &str = "package componentize-js:run;
+
+world run {
+  export run: func() -> string;
+}";
+
+/// Generate a component from `js` using a minimal world which exports a single
+/// `run: func() -> string` function, call that function, and return the result.
+async fn run_script(js: &str, options: &Options) -> anyhow::Result<String> {
+    run_script_in(js, options, store()).await
+}
+
+/// Like `run_script`, but also return whatever the component writes to stderr
+/// while `run` is called.
+async fn run_script_capturing_stderr(
+    js: &str,
+    options: &Options,
+) -> anyhow::Result<(String, String)> {
+    let stderr = MemoryOutputPipe::new(1024 * 1024);
+    let store = Store::new(
+        &ENGINE,
+        Ctx {
+            wasi: WasiCtxBuilder::new().stderr(stderr.clone()).build(),
+            table: ResourceTable::default(),
+        },
+    );
+    let result = run_script_in(js, options, store).await?;
+    Ok((
+        result,
+        String::from_utf8_lossy(&stderr.contents()).into_owned(),
+    ))
+}
+
+async fn run_script_in(
+    js: &str,
+    options: &Options,
+    mut store: Store<Ctx>,
+) -> anyhow::Result<String> {
+    let component = Componentizer::new(Wit::<String>::String(RUN_WIT), js)
+        .options(options.clone())
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+    Ok(run.call_async(&mut store, ()).await?.0)
+}
+
+#[tokio::test]
+async fn prefs() -> anyhow::Result<()> {
+    let js = "export function run() {
+        return `${typeof WeakRef} ${typeof Iterator} ${typeof [].values().map}`
+    }";
+
+    assert_eq!(
+        "function function function",
+        run_script(js, &Options::default()).await?
+    );
+
+    let options = Options {
+        prefs: Prefs {
+            weak_refs: false,
+            iterator_helpers: false,
+            ..Prefs::default()
+        },
+        ..Options::default()
+    };
+    assert_eq!(
+        "undefined undefined undefined",
+        run_script(js, &options).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn componentize_errors() -> anyhow::Result<()> {
+    let componentize = async |wit: &str, world: Option<&str>, js: &str| {
+        Componentizer::new(Wit::<String>::String(wit), js)
+            .world(world)
+            .componentize()
+            .await
+    };
+
+    assert!(matches!(
+        componentize("package componentize-js:run; world run {", None, "").await,
+        Err(ComponentizeError::WitParse(_))
+    ));
+
+    assert!(matches!(
+        componentize(RUN_WIT, Some("missing"), "").await,
+        Err(ComponentizeError::WorldSelection(_))
+    ));
+
+    let js = "function fail() { throw new TypeError('bad init') }
+    fail()
+    export function run() { return '' }";
+    match componentize(RUN_WIT, None, js).await {
+        Err(ComponentizeError::InitScript { message, stack, .. }) => {
+            assert_eq!("TypeError: bad init", message);
+            assert!(stack.unwrap().contains("fail"));
+        }
+        result => panic!("expected `InitScript` error; got {:?}", result.err()),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn source_map() -> anyhow::Result<()> {
+    let componentize = async |source_map: &str| {
+        let options = Options {
+            source_map: Some(source_map.into()),
+            ..Options::default()
+        };
+        Componentizer::new(
+            Wit::<String>::String(RUN_WIT),
+            "function fail() { throw new TypeError('bad init') }
+    fail()
+    export function run() { return '' }",
+        )
+        .options(options.clone())
+        .componentize()
+        .await
+    };
+
+    // Map the first line of the script to line 10 of `src/app.ts` and the
+    // second to line 21.
+    let source_map = r#"{
+        "version": 3,
+        "sources": ["app.ts"],
+        "sourceRoot": "src",
+        "names": [],
+        "mappings": "AASA;AAWA"
+    }"#;
+    match componentize(source_map).await {
+        Err(ComponentizeError::InitScript { stack, .. }) => {
+            let stack = stack.unwrap();
+            assert!(stack.contains("fail@src/app.ts:10:1"), "{stack}");
+            assert!(stack.contains("@src/app.ts:21:1"), "{stack}");
+        }
+        result => panic!("expected `InitScript` error; got {:?}", result.err()),
+    }
+
+    assert!(matches!(
+        componentize(r#"{ "version": 3, "sources": [], "mappings": "AA!A" }"#).await,
+        Err(ComponentizeError::SourceMap(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn heap_census() -> anyhow::Result<()> {
+    let js = "const data = Array.from({ length: 1000 }, (_, i) => ({ i }))
+    export function run() { return String(data.length) }";
+
+    let options = Options {
+        heap_census: true,
+        ..Options::default()
+    };
+    assert_eq!("1000", run_script(js, &options).await?);
+
+    let (_, diagnostics) = Componentizer::new(Wit::<String>::String(RUN_WIT), js)
+        .options(options)
+        .componentize_with_diagnostics()
+        .await?;
+    let (_, census) = diagnostics
+        .stderr
+        .split_once("componentize-js: heap census before snapshot:\n")
+        .expect("missing heap census");
+    let rows = census
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(["bytes", "count", "class"].as_slice(), rows[0]);
+    // Each of the 1000 elements is an `Object`, all held by an `Array`.
+    assert!(rows.iter().any(|row| {
+        row.len() == 3 && row[2] == "Object" && row[1].parse::<u32>().is_ok_and(|n| n >= 1000)
+    }));
+    assert!(rows.iter().any(|row| row.len() == 3 && row[2] == "Array"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn timings() -> anyhow::Result<()> {
+    let options = Options {
+        timings: true,
+        max_memory_bytes: Some(512 * 1024 * 1024),
+        ..Options::default()
+    };
+    assert_eq!(
+        "ok",
+        run_script("export function run() { return 'ok' }", &options).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn gc_logging() -> anyhow::Result<()> {
+    // Allocate far more garbage than the heap limit allows, forcing major
+    // collections while `run` is executing.
+    let js = "export function run() {
+        let total = 0
+        for (let i = 0; i < 64; i++) {
+            total += new Array(256 * 1024).fill(i).length
+        }
+        return String(total)
+    }";
+
+    let options = Options {
+        gc_logging: true,
+        max_heap_bytes: Some(32 * 1024 * 1024),
+        ..Options::default()
+    };
+    let (result, stderr) = run_script_capturing_stderr(js, &options).await?;
+    assert_eq!((64 * 256 * 1024).to_string(), result);
+
+    assert!(
+        stderr
+            .lines()
+            .any(|line| line.starts_with("componentize-js: GC begin (reason: ")),
+        "{stderr}"
+    );
+    assert!(
+        stderr.lines().any(|line| {
+            line.starts_with("componentize-js: GC end (reason: ")
+                && line
+                    .rsplit_once(", duration: ")
+                    .is_some_and(|(_, duration)| duration.ends_with("s)"))
+        }),
+        "{stderr}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn profile() -> anyhow::Result<()> {
+    let js = "function fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2) }
+    export function run() { return String(fib(15)) }";
+
+    let options = Options {
+        profile: true,
+        ..Options::default()
+    };
+    let (result, stderr) = run_script_capturing_stderr(js, &options).await?;
+    assert_eq!("610", result);
+
+    // Expect e.g. `run;run (script:2);fib (script:1);fib (script:1) 42`.
+    let (_, report) = stderr
+        .split_once("componentize-js: profile (folded stacks, microseconds):\n")
+        .expect("missing profile");
+    assert!(
+        report.lines().any(|line| {
+            line.rsplit_once(' ').is_some_and(|(stack, micros)| {
+                stack.starts_with("run;run (")
+                    && stack.contains(";fib (")
+                    && micros.parse::<u64>().is_ok()
+            })
+        }),
+        "{report}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn type_mismatch() -> anyhow::Result<()> {
+    let wit = "package componentize-js:mismatch;
+
+world mismatch {
+  record header { name: string, value: string }
+
+  import send: func(headers: list<header>);
+
+  export run: func() -> string;
+}";
+
+    let js = "import { send } from 'wit-world'
+
+export function run() {
+    try {
+        send([{ name: 'a', value: 'b' }, { name: 'c', value: 42 }])
+        return 'no error'
+    } catch (error) {
+        return `${error.name}: ${error.message}`
+    }
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(wit), js)
+        .componentize()
+        .await?;
+    let component = Component::new(&ENGINE, &component)?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    linker.define_unknown_imports_as_traps(&component)?;
+    let mut store = store();
+    let instance = linker.instantiate_async(&mut store, &component).await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+
+    assert_eq!(
+        "TypeError: type mismatch at `send.param[0][1].value`: expected string, got number `42`",
+        run.call_async(&mut store, ()).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn coercion() -> anyhow::Result<()> {
+    let wit = "package componentize-js:coercion;
+
+world coercion {
+  export string-to-u8: func() -> u8;
+  export number-to-u64: func() -> u64;
+  export typeof-u64: func(v: u64) -> string;
+}";
+
+    let js = "export function stringToU8() { return '42' }
+export function numberToU64() { return 42 }
+export function typeofU64(v) { return typeof v }";
+
+    #[derive(Debug, PartialEq)]
+    struct Outcome {
+        string_to_u8: Option<u8>,
+        number_to_u64: Option<u64>,
+        typeof_u64: String,
+    }
+
+    let run = async |coercion| {
+        let component = Componentizer::new(Wit::<String>::String(wit), js)
+            .options(Options {
+                coercion,
+                ..Options::default()
+            })
+            .componentize()
+            .await?;
+        let component = Component::new(&ENGINE, &component)?;
+
+        let mut linker = Linker::new(&ENGINE);
+        wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+
+        // Each export is called on a fresh instance, since a trap poisons the
+        // instance it occurs in.
+        let string_to_u8 = {
+            let mut store = store();
+            let instance = linker.instantiate_async(&mut store, &component).await?;
+            let func = instance.get_typed_func::<(), (u8,)>(&mut store, "string-to-u8")?;
+            func.call_async(&mut store, ()).await.ok().map(|v| v.0)
+        };
+
+        let number_to_u64 = {
+            let mut store = store();
+            let instance = linker.instantiate_async(&mut store, &component).await?;
+            let func = instance.get_typed_func::<(), (u64,)>(&mut store, "number-to-u64")?;
+            func.call_async(&mut store, ()).await.ok().map(|v| v.0)
+        };
+
+        let typeof_u64 = {
+            let mut store = store();
+            let instance = linker.instantiate_async(&mut store, &component).await?;
+            let func = instance.get_typed_func::<(u64,), (String,)>(&mut store, "typeof-u64")?;
+            func.call_async(&mut store, (7,)).await?.0
+        };
+
+        anyhow::Ok(Outcome {
+            string_to_u8,
+            number_to_u64,
+            typeof_u64,
+        })
+    };
+
+    assert_eq!(
+        Outcome {
+            string_to_u8: None,
+            number_to_u64: Some(42),
+            typeof_u64: "number".into(),
+        },
+        run(Coercion::Standard).await?
+    );
+
+    assert_eq!(
+        Outcome {
+            string_to_u8: None,
+            number_to_u64: None,
+            typeof_u64: "bigint".into(),
+        },
+        run(Coercion::Strict).await?
+    );
+
+    assert_eq!(
+        Outcome {
+            string_to_u8: Some(42),
+            number_to_u64: Some(42),
+            typeof_u64: "number".into(),
+        },
+        run(Coercion::Lenient).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn freeze_intrinsics() -> anyhow::Result<()> {
+    let js = "export function run() {
+        const frozen = [Object.prototype, Array.prototype, Math, JSON, Promise]
+            .every((v) => Object.isFrozen(v))
+        let replaced
+        try {
+            Array.prototype.push = null
+            replaced = 'replaced'
+        } catch (error) {
+            replaced = error.constructor.name
+        }
+        globalThis.custom = 42
+        return `${frozen} ${replaced} ${globalThis.custom}`
+    }";
+
+    assert_eq!(
+        "false replaced 42",
+        run_script(js, &Options::default()).await?
+    );
+
+    let options = Options {
+        freeze_intrinsics: true,
+        ..Options::default()
+    };
+    assert_eq!("true TypeError 42", run_script(js, &options).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_heap_bytes() -> anyhow::Result<()> {
+    let js = "export function run() {
+        let error
+        try {
+            const chunks = []
+            for (let i = 0; i < 1024; i++) {
+                chunks.push(new Array(1024 * 1024).fill(i))
+            }
+        } catch (e) {
+            error = String(e)
+        }
+        return `${error} ${[1, 2, 3].map((v) => v * 2)}`
+    }";
+
+    let options = Options {
+        max_heap_bytes: Some(64 * 1024 * 1024),
+        ..Options::default()
+    };
+    assert_eq!("out of memory 2,4,6", run_script(js, &options).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_memory_bytes() -> anyhow::Result<()> {
+    let componentize = async |max_memory_bytes| {
+        Componentizer::new(
+            Wit::<String>::String(RUN_WIT),
+            "export function run() { return '' }",
+        )
+        .options(Options {
+            max_memory_bytes,
+            ..Options::default()
+        })
+        .componentize()
+        .await
+    };
+
+    let component = componentize(Some(512 * 1024 * 1024)).await?;
+
+    let mut maximums = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(&component) {
+        if let wasmparser::Payload::MemorySection(reader) = payload? {
+            for memory in reader {
+                maximums.push(memory?.maximum);
+            }
+        }
+    }
+    assert!(!maximums.is_empty());
+    assert!(maximums.iter().all(|&v| v == Some(8192)));
+
+    // The component should still work:
+    let component = Component::new(&ENGINE, &component)?;
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = store();
+    let instance = linker.instantiate_async(&mut store, &component).await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+    assert_eq!("", run.call_async(&mut store, ()).await?.0);
+
+    assert!(matches!(
+        componentize(Some(64 * 1024)).await,
+        Err(ComponentizeError::PostProcess(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn manifest() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:manifest;
+
+interface types {
+  record point { x: s32, y: s32 }
+}
+
+world manifest {
+  use types.{point};
+
+  import log: func(message: string);
+  export add: func(a: point, b: point) -> point;
+  export fetch: async func(urls: list<string>) -> result<string, u16>;
+}";
+
+    const SCRIPT: &str = "export function add(a, b) { return { x: a.x + b.x, y: a.y + b.y } }
+export async function fetch(urls) { return '' }";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let manifest = crate::manifest(
+        Wit::<String>::String(WIT),
+        None,
+        &[],
+        false,
+        SCRIPT,
+        &component,
+    )?;
+    let manifest = serde_json::from_str::<serde_json::Value>(&manifest)?;
+
+    assert_eq!(
+        "componentize-js:manifest/manifest",
+        manifest["world"]["name"]
+    );
+    assert_eq!(env!("CARGO_PKG_VERSION"), manifest["runtime"]["version"]);
+    assert_eq!(SCRIPT.len(), manifest["script"]["bytes"]);
+    assert_eq!(component.len(), manifest["component"]["bytes"]);
+    assert_eq!(64, manifest["component"]["sha256"].as_str().unwrap().len());
+
+    let exports = manifest["world"]["exports"].as_array().unwrap();
+    assert_eq!(2, exports.len());
+    assert_eq!("add", exports[0]["name"]);
+    assert_eq!(Some(false), exports[0]["async"].as_bool());
+    assert_eq!("point", exports[0]["params"][0]["type"]);
+    assert_eq!("fetch", exports[1]["name"]);
+    assert_eq!(Some(true), exports[1]["async"].as_bool());
+    assert_eq!("list<string>", exports[1]["params"][0]["type"]);
+    assert_eq!("result<string, u16>", exports[1]["result"]);
+
+    let imports = manifest["world"]["imports"].as_array().unwrap();
+    assert!(imports.iter().any(
+        |item| item["kind"] == "interface" && item["name"] == "componentize-js:manifest/types"
+    ));
+    assert!(imports.iter().any(|item| item["kind"] == "function"
+        && item["name"] == "log"
+        && item["result"].is_null()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn world_reflection() -> anyhow::Result<()> {
+    let js = "export function run() {
+        const { world } = componentModel
+        const [run] = world.exports
+        return `${world.name} ${run.kind} ${run.name} ${run.async} ${run.params.length} \
+            ${run.result} ${Object.isFrozen(world)} ${Object.isFrozen(run.params)}`
+    }";
+
+    assert_eq!(
+        "componentize-js:run/run function run false 0 string true true",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marshallers() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:marshallers;
+
+world marshallers {
+  record point { x: s32, y: s32 }
+
+  export scale: func(p: point, factor: s32) -> point;
+}";
+
+    const SCRIPT: &str = "class Point {
+    constructor(x, y) { this.coords = [x, y] }
+    scale(factor) { return new Point(...this.coords.map((v) => v * factor)) }
+}
+
+componentModel.registerMarshaller('point', {
+    fromWit: ({ x, y }) => new Point(x, y),
+    toWit: ({ coords: [x, y] }) => ({ x, y })
+})
+
+export function scale(p, factor) {
+    if (!(p instanceof Point)) {
+        throw new Error('expected a Point')
+    }
+    return p.scale(factor)
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let point = |x, y| Val::Record(vec![("x".into(), Val::S32(x)), ("y".into(), Val::S32(y))]);
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let scale = instance.get_func(&mut store, "scale").unwrap();
+    let mut results = [Val::Bool(false)];
+    scale
+        .call_async(&mut store, &[point(2, -3), Val::S32(4)], &mut results)
+        .await?;
+    assert_eq!(point(8, -12), results[0]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn error_classes() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:error-classes;
+
+world error-classes {
+  variant lookup-error { not-found, invalid(string) }
+
+  import lookup: func(name: string) -> result<string, lookup-error>;
+  export run: func(name: string) -> result<string, lookup-error>;
+}";
+
+    const SCRIPT: &str = "import { lookup, LookupError } from 'wit-world'
+
+export function run(name) {
+    try {
+        return lookup(name)
+    } catch (error) {
+        if (error instanceof LookupError.NotFound) {
+            throw new LookupError.Invalid(
+                `${error.tag} ${error instanceof LookupError} ${error instanceof ComponentError}`
+            )
+        }
+        throw error
+    }
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    linker.root().func_new("lookup", |_, _, params, results| {
+        results[0] = match &params[0] {
+            Val::String(name) if name == "found" => {
+                Val::Result(Ok(Some(Box::new(Val::String("yes".into())))))
+            }
+            _ => Val::Result(Err(Some(Box::new(Val::Variant("not-found".into(), None))))),
+        };
+        Ok(())
+    })?;
+    let pre = linker.instantiate_pre(&Component::new(&ENGINE, &component)?)?;
+
+    let run = async |name: &str| -> anyhow::Result<Val> {
+        let mut store = store();
+        let instance = pre.instantiate_async(&mut store).await?;
+        let run = instance.get_func(&mut store, "run").unwrap();
+        let mut results = [Val::Bool(false)];
+        run.call_async(&mut store, &[Val::String(name.into())], &mut results)
+            .await?;
+        let [result] = results;
+        Ok(result)
+    };
+
+    assert_eq!(
+        Val::Result(Ok(Some(Box::new(Val::String("yes".into()))))),
+        run("found").await?
+    );
+    assert_eq!(
+        Val::Result(Err(Some(Box::new(Val::Variant(
+            "invalid".into(),
+            Some(Box::new(Val::String("not-found true true".into())))
+        ))))),
+        run("missing").await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fs_module() -> anyhow::Result<()> {
+    let js = "import { readFile, writeFile, readdir, stat, mkdir } from 'componentize-js:fs'
+
+    export const wasiCliRun030Rc20260106 = {
+        run: async function() {
+            const made = await mkdir('/data/a/b', { recursive: true })
+            await writeFile('/data/a/b/hello.txt', 'hello, ')
+            await writeFile('/data/a/b/hello.txt', 'world', { flag: 'a' })
+            const text = await readFile('/data/a/b/hello.txt', 'utf8')
+            const bytes = await readFile('data/seed.txt')
+            const info = await stat('/data/a/b/hello.txt')
+            const names = await readdir('/data')
+            const entries = await readdir('/data/a', { withFileTypes: true })
+            const missing = await readFile('/data/nope').catch((error) => error.code)
+            const outside = await stat('/elsewhere').catch((error) => error.code)
+            await writeFile('/data/result.txt', [
+                made,
+                text,
+                bytes.length,
+                info.size,
+                info.isFile(),
+                names.sort().join(','),
+                entries.map((entry) => `${entry.name}:${entry.isDirectory()}`).join(','),
+                missing,
+                outside
+            ].join(' '))
+        }
+    }";
+
+    let component = Componentizer::new(Wit::Paths(&["wit"]), js)
+        .world("wasi:cli/command@0.3.0-rc-2026-01-06")
+        .componentize()
+        .await?;
+
+    let dir = env::temp_dir().join(format!("componentize-js-fs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+
+    let result = async {
+        std::fs::write(dir.join("seed.txt"), "seed")?;
+
+        let mut linker = Linker::new(&ENGINE);
+        wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+        wasmtime_wasi::p3::add_to_linker(&mut linker)?;
+        let mut store = Store::new(
+            &ENGINE,
+            Ctx {
+                wasi: WasiCtxBuilder::new()
+                    .preopened_dir(&dir, "/data", DirPerms::all(), FilePerms::all())?
+                    .build(),
+                table: ResourceTable::default(),
+            },
+        );
+        let instance = linker
+            .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+            .await?;
+        let command = wasmtime_wasi::p3::bindings::Command::new(&mut store, &instance)?;
+        store
+            .run_concurrent(async |store| command.wasi_cli_run().call_run(store).await)
+            .await??
+            .0
+            .map_err(|()| anyhow::anyhow!("command failed"))?;
+
+        assert_eq!(
+            "/data/a hello, world 4 12 true a,seed.txt b:true ENOENT ENOENT",
+            std::fs::read_to_string(dir.join("result.txt"))?
+        );
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    _ = std::fs::remove_dir_all(&dir);
+
+    result
+}
+
+#[tokio::test]
+async fn sockets_module() -> anyhow::Result<()> {
+    let js = "import { connect, listen, bindDatagram } from 'componentize-js:sockets'
+
+    const upper = () => new TransformStream({
+        transform(chunk, controller) {
+            controller.enqueue(chunk.map((byte) => byte >= 97 && byte <= 122 ? byte - 32 : byte))
+        }
+    })
+
+    export const wasiCliRun030Rc20260106 = {
+        run: async function() {
+            const listener = await listen({ hostname: '127.0.0.1' })
+            const { port } = listener.localAddress
+            const served = (async () => {
+                for await (const connection of listener) {
+                    await connection.readable.pipeThrough(upper()).pipeTo(connection.writable)
+                    await connection.close()
+                    break
+                }
+            })()
+
+            const client = await connect({ hostname: '127.0.0.1', port })
+            const writer = client.writable.getWriter()
+            await writer.write(new TextEncoder().encode('ping, '))
+            await writer.write(new TextEncoder().encode('pong'))
+            await writer.close()
+            const decoder = new TextDecoder()
+            let text = ''
+            for await (const chunk of client.readable) {
+                text += decoder.decode(chunk, { stream: true })
+            }
+            const remote = client.remoteAddress
+            await served
+            await client.close()
+            listener.close()
+
+            const a = await bindDatagram({ hostname: '127.0.0.1' })
+            const b = await bindDatagram({ hostname: '127.0.0.1' })
+            await a.send('hello', b.localAddress)
+            const { data, remoteAddress } = await b.receive()
+            a.close()
+            b.close()
+
+            console.log([
+                text,
+                remote.hostname,
+                remote.port === port,
+                decoder.decode(data),
+                remoteAddress.port === a.localAddress.port
+            ].join(' '))
+        }
+    }";
+
+    let component = Componentizer::new(Wit::Paths(&["wit"]), js)
+        .world("wasi:cli/command@0.3.0-rc-2026-01-06")
+        .componentize()
+        .await?;
+
+    let stdout = MemoryOutputPipe::new(10000);
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    wasmtime_wasi::p3::add_to_linker(&mut linker)?;
+    let mut store = Store::new(
+        &ENGINE,
+        Ctx {
+            wasi: WasiCtxBuilder::new()
+                .inherit_network()
+                .stdout(stdout.clone())
+                .build(),
+            table: ResourceTable::default(),
+        },
+    );
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let command = wasmtime_wasi::p3::bindings::Command::new(&mut store, &instance)?;
+    store
+        .run_concurrent(async |store| command.wasi_cli_run().call_run(store).await)
+        .await??
+        .0
+        .map_err(|()| anyhow::anyhow!("command failed"))?;
+
+    assert_eq!(
+        "PING, PONG 127.0.0.1 true hello true\n",
+        String::from_utf8_lossy(&stdout.contents())
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn websocket() -> anyhow::Result<()> {
+    // This serves a single WebSocket connection from within the component,
+    // uppercasing each (unfragmented) text message and echoing the close frame.
+    let js = r#"import { listen } from 'componentize-js:sockets'
+
+    const base64 = (bytes) => {
+        const alphabet = 'ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/'
+        let result = ''
+        for (let i = 0; i < bytes.length; i += 3) {
+            const n = (bytes[i] << 16) | ((bytes[i + 1] ?? 0) << 8) | (bytes[i + 2] ?? 0)
+            result += [18, 12, 6, 0].map((shift, j) => i + j - 1 < bytes.length ? alphabet[(n >> shift) & 63] : '=').join('')
+        }
+        return result
+    }
+
+    const serve = async (connection) => {
+        const reader = connection.readable.getReader()
+        const writer = connection.writable.getWriter()
+        let buffer = new Uint8Array(0)
+        const read = async (length) => {
+            while (buffer.length < length) {
+                const { value } = await reader.read()
+                const next = new Uint8Array(buffer.length + value.length)
+                next.set(buffer)
+                next.set(value, buffer.length)
+                buffer = next
+            }
+            const result = buffer.slice(0, length)
+            buffer = buffer.slice(length)
+            return result
+        }
+
+        let head = ''
+        while (!head.endsWith('\r\n\r\n')) {
+            head += new TextDecoder().decode(await read(1))
+        }
+        const key = /Sec-WebSocket-Key: (.*)\r\n/.exec(head)[1]
+        const accept = base64(new Uint8Array(await crypto.subtle.digest(
+            'SHA-1',
+            new TextEncoder().encode(key + '258EAFA5-E914-47DA-95CA-C5AB0DC85B11')
+        )))
+        await writer.write(new TextEncoder().encode('HTTP/1.1 101 Switching Protocols\r\n'
+            + 'Upgrade: websocket\r\nConnection: Upgrade\r\n'
+            + `Sec-WebSocket-Accept: ${accept}\r\nSec-WebSocket-Protocol: chat\r\n\r\n`))
+
+        while (true) {
+            const [first, second] = await read(2)
+            const mask = await read(4)
+            const payload = (await read(second & 0x7f)).map((byte, i) => byte ^ mask[i & 3])
+            const opcode = first & 0x0f
+            const reply = opcode === 1
+                ? new TextEncoder().encode(new TextDecoder().decode(payload).toUpperCase())
+                : payload
+            await writer.write(new Uint8Array([0x80 | opcode, reply.length, ...reply]))
+            if (opcode === 8) {
+                await writer.close()
+                reader.releaseLock()
+                await connection.close()
+                return
+            }
+        }
+    }
+
+    export const wasiCliRun030Rc20260106 = {
+        run: async function() {
+            const listener = await listen({ hostname: '127.0.0.1' })
+            const served = listener.accept().then(serve)
+
+            const log = []
+            const socket = new WebSocket(`ws://127.0.0.1:${listener.localAddress.port}/chat`, ['chat'])
+            socket.onopen = () => {
+                log.push(`open:${socket.protocol}`)
+                socket.send('hello')
+            }
+            socket.onmessage = (event) => {
+                log.push(event.data)
+                socket.close(1000, 'done')
+            }
+            await new Promise((resolve) => socket.addEventListener('close', (event) => {
+                log.push(`close:${event.code}:${event.reason}:${event.wasClean}`)
+                resolve()
+            }))
+            await served
+            listener.close()
+
+            console.log(log.join(' '))
+        }
+    }"#;
+
+    let component = Componentizer::new(Wit::Paths(&["wit"]), js)
+        .world("wasi:cli/command@0.3.0-rc-2026-01-06")
+        .componentize()
+        .await?;
+
+    let stdout = MemoryOutputPipe::new(10000);
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    wasmtime_wasi::p3::add_to_linker(&mut linker)?;
+    let mut store = Store::new(
+        &ENGINE,
+        Ctx {
+            wasi: WasiCtxBuilder::new()
+                .inherit_network()
+                .stdout(stdout.clone())
+                .build(),
+            table: ResourceTable::default(),
+        },
+    );
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let command = wasmtime_wasi::p3::bindings::Command::new(&mut store, &instance)?;
+    store
+        .run_concurrent(async |store| command.wasi_cli_run().call_run(store).await)
+        .await??
+        .0
+        .map_err(|()| anyhow::anyhow!("command failed"))?;
+
+    assert_eq!(
+        "open:chat HELLO close:1000:done:true\n",
+        String::from_utf8_lossy(&stdout.contents())
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn process_env() -> anyhow::Result<()> {
+    // The environment is empty during initialization, so `initial` should be
+    // `undefined` even though `GREETING` is set when the component runs.
+    let js = "const initial = process.env.GREETING
+
+    export function run() {
+        const before = process.env.GREETING
+        process.env.EXTRA = 42
+        delete process.env.HOME
+        return [
+            String(initial),
+            before,
+            'GREETING' in process.env,
+            Object.keys(process.env).sort().join(','),
+            process.env.EXTRA,
+            String(process.env.HOME),
+            JSON.stringify({ ...process.env }.GREETING)
+        ].join(' ')
+    }";
+
+    let component = Componentizer::new(Wit::<String>::String(RUN_WIT), js)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = Store::new(
+        &ENGINE,
+        Ctx {
+            wasi: WasiCtxBuilder::new()
+                .env("GREETING", "hello")
+                .env("HOME", "/root")
+                .build(),
+            table: ResourceTable::default(),
+        },
+    );
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+
+    assert_eq!(
+        "undefined hello true EXTRA,GREETING 42 undefined \"hello\"",
+        run.call_async(&mut store, ()).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn math_random_reseeded() -> anyhow::Result<()> {
+    // Calling `Math.random` at the top level seeds the generator during
+    // initialization, so without reseeding, every instance of the snapshot
+    // would produce the same sequence.
+    let js = "const initial = Math.random()
+
+    export function run() {
+        return `${initial} ${Math.random()} ${Math.random()}`
+    }";
+
+    let component = Componentizer::new(Wit::<String>::String(RUN_WIT), js)
+        .componentize()
+        .await?;
+    let component = Component::new(&ENGINE, &component)?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+
+    let mut results = Vec::new();
+    for _ in 0..2 {
+        let mut store = Store::new(
+            &ENGINE,
+            Ctx {
+                wasi: WasiCtxBuilder::new().build(),
+                table: ResourceTable::default(),
+            },
+        );
+        let instance = linker.instantiate_async(&mut store, &component).await?;
+        let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+        let (result,) = run.call_async(&mut store, ()).await?;
+        results.push(result.split(' ').map(str::to_owned).collect::<Vec<_>>());
+    }
+
+    // The value computed during initialization is part of the snapshot, but
+    // subsequent values should differ between instances.
+    assert_eq!(results[0][0], results[1][0]);
+    assert_ne!(results[0][1..], results[1][1..]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn virtualization() -> anyhow::Result<()> {
+    let js = "export function run() {
+        return `${Date.now()} ${new Date().toISOString()} ${JSON.stringify({ ...process.env })}`
+    }";
+
+    let options = Options {
+        virtual_env: Some(vec![("GREETING".into(), "hello".into())]),
+        virtual_clock: Some(1_000_000_000),
+        ..Options::default()
+    };
+
+    // The host's environment should not be visible to the component.
+    let store = Store::new(
+        &ENGINE,
+        Ctx {
+            wasi: WasiCtxBuilder::new()
+                .env("GREETING", "host")
+                .env("SECRET", "hunter2")
+                .build(),
+            table: ResourceTable::default(),
+        },
+    );
+    assert_eq!(
+        r#"1000000000000 2001-09-09T01:46:40.000Z {"GREETING":"hello"}"#,
+        run_script_in(js, &options, store).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn wasi_host() -> anyhow::Result<()> {
+    // `waitFor` is an async import, which may be called at the top level as
+    // long as it completes immediately.
+    let js = "import { now, waitFor } from 'wasi:clocks/monotonic-clock@0.3.0-rc-2026-01-06'
+    const start = now()
+    await waitFor(0n)
+    export const wasiCliRun030Rc20260106 = { run: async function() {} }";
+
+    let componentize = async |wasi_host| {
+        Componentizer::new(Wit::Paths(&["wit"]), js)
+            .world("wasi:cli/command@0.3.0-rc-2026-01-06")
+            .options(Options {
+                wasi_host,
+                ..Options::default()
+            })
+            .componentize()
+            .await
+    };
+
+    // WASIp3 interfaces are stubbed by default, so calling one at the top
+    // level fails.
+    assert!(matches!(
+        componentize(WasiHost::P2).await,
+        Err(ComponentizeError::InitScript { .. })
+    ));
+
+    componentize(WasiHost::P3).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stack_size() -> anyhow::Result<()> {
+    let js = "function depth(n) { try { return depth(n + 1) } catch { return n } }
+    export function run() { return String(depth(0)) }";
+
+    let default = run_script(js, &Options::default()).await?.parse::<u32>()?;
+    let large = run_script(
+        js,
+        &Options {
+            stack_size: Some(8 * 1024 * 1024),
+            ..Options::default()
+        },
+    )
+    .await?
+    .parse::<u32>()?;
+
+    assert!(large > default * 2, "{large} <= 2 * {default}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn multiple_wit_packages() -> anyhow::Result<()> {
+    let dir = env::temp_dir().join(format!("componentize-js-wit-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+
+    let result = async {
+        // `app` uses `test:types`, so it must come after it, while `app` itself
+        // is defined by neither the last package nor the first.
+        let types = dir.join("types.wit");
+        std::fs::write(
+            &types,
+            "package test:types;\n\
+             interface types { type message = string; }\n\
+             world app { export run: func() -> string; }",
+        )?;
+        let app = dir.join("app.wit");
+        std::fs::write(
+            &app,
+            "package test:app;\n\
+             world app { use test:types/types.{message}; export run: func() -> message; }",
+        )?;
+        let other = dir.join("other.wit");
+        std::fs::write(
+            &other,
+            "package test:other;\nworld other { export run: func() -> string; }",
+        )?;
+
+        let js = "export function run() { return 'hi' }";
+        let componentize = async |paths: &[&Path], world: &str| {
+            Componentizer::new(Wit::Paths(paths), js)
+                .world(world)
+                .componentize()
+                .await
+        };
+
+        // Unqualified names are ambiguous when more than one package defines
+        // the world, but qualified names are not.
+        match componentize(&[&types, &app, &other], "app").await {
+            Err(ComponentizeError::WorldSelection(error)) => {
+                let error = error.to_string();
+                assert!(
+                    error.contains("`test:types/app`, `test:app/app`"),
+                    "{error}"
+                );
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+        componentize(&[&types, &app, &other], "test:app/app").await?;
+
+        // Otherwise, the world may come from any package.
+        componentize(&[&types, &other], "app").await?;
+
+        // Dependencies must be given before the packages which use them.
+        match componentize(&[&app, &types], "app").await {
+            Err(ComponentizeError::WitParse(error)) => {
+                let error = error.to_string();
+                assert!(
+                    error.contains("packages are resolved in the order given"),
+                    "{error}"
+                );
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    _ = std::fs::remove_dir_all(&dir);
+
+    result
+}
+
+#[tokio::test]
+async fn diagnostics() -> anyhow::Result<()> {
+    let (_, diagnostics) = Componentizer::new(
+        Wit::<String>::String(RUN_WIT),
+        "console.log('hello')
+console.error('world')
+export function run() { return '' }
+export function helper() {}",
+    )
+    .componentize_with_diagnostics()
+    .await?;
+
+    assert_eq!("hello\n", diagnostics.stdout);
+    assert_eq!("world\n", diagnostics.stderr);
+    assert_eq!(
+        vec!["export `helper` does not match any export of the WIT world"],
+        diagnostics.warnings
+    );
+    assert!(
+        diagnostics
+            .timings
+            .iter()
+            .any(|(stage, _)| *stage == "snapshot")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_export_warnings() -> anyhow::Result<()> {
+    let warnings = async |js| {
+        Componentizer::new(Wit::<String>::String(RUN_WIT), js)
+            .componentize_with_diagnostics()
+            .await
+            .map(|(_, diagnostics)| diagnostics.warnings)
+    };
+
+    assert!(
+        warnings("export function run() { return '' }")
+            .await?
+            .is_empty()
+    );
+
+    assert_eq!(
+        vec![
+            "export `Run` does not match any export of the WIT world; did you mean `run`?",
+            "export `helper` does not match any export of the WIT world",
+        ],
+        warnings("export function Run() { return '' }; export function helper() {}").await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn export_aliases() -> anyhow::Result<()> {
+    let js = "function doRun() { return 'aliased' }";
+
+    let options = Options {
+        export_aliases: vec![("run".into(), "doRun".into())],
+        ..Options::default()
+    };
+    assert_eq!("aliased", run_script(js, &options).await?);
+
+    let options = Options {
+        export_aliases: vec![("walk".into(), "doRun".into())],
+        ..Options::default()
+    };
+    assert!(run_script(js, &options).await.is_err_and(|e| matches!(
+        e.downcast_ref::<ComponentizeError>(),
+        Some(ComponentizeError::WorldSelection(_))
+    )));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn qualified_export_layout() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:layout;
+
+interface greeter {
+  greet: func(name: string) -> string;
+}
+
+world layout {
+  export greeter;
+}";
+
+    const SCRIPT: &str = "const greeter = { greet: (name) => `Hello, ${name}!` }
+export { greeter as 'componentize-js:layout/greeter' }";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .options(Options {
+            export_layout: ExportLayout::Qualified,
+            ..Options::default()
+        })
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let interface = instance
+        .get_export_index(&mut store, None, "componentize-js:layout/greeter")
+        .unwrap();
+    let greet = instance
+        .get_export_index(&mut store, Some(&interface), "greet")
+        .unwrap();
+    let greet = instance.get_typed_func::<(String,), (String,)>(&mut store, &greet)?;
+    assert_eq!(
+        "Hello, world!",
+        greet.call_async(&mut store, ("world".into(),)).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn import_and_export_same_interface() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:middleware;
+
+interface handler {
+  enum error-code { bad-request, unavailable }
+
+  handle: func(request: string) -> result<string, error-code>;
+}
+
+world middleware {
+  import handler;
+  export handler;
+}";
+
+    // The imported interface is available via its module specifier, while the
+    // exported one is looked up by (depending on the layout) either its
+    // mangled name or its ID in the script's exports, so the two don't
+    // collide, even when the latter matches the specifier exactly.
+    const HANDLER: &str =
+        "import { handle as next, ErrorCode } from 'componentize-js:middleware/handler'
+
+const handler = {
+    handle(request) {
+        if (request === '') {
+            throw new ErrorCode.BadRequest()
+        }
+        try {
+            return `middleware(${next(request.toUpperCase())})`
+        } catch (error) {
+            if (error instanceof ErrorCode) {
+                return `caught(${error.tag})`
+            }
+            throw error
+        }
+    }
+}
+";
+
+    for (export_layout, export) in [
+        (
+            ExportLayout::Flat,
+            "export const componentizeJsMiddlewareHandler = handler",
+        ),
+        (
+            ExportLayout::Qualified,
+            "export { handler as 'componentize-js:middleware/handler' }",
+        ),
+    ] {
+        let component =
+            Componentizer::new(Wit::<String>::String(WIT), &format!("{HANDLER}{export}"))
+                .options(Options {
+                    export_layout,
+                    ..Options::default()
+                })
+                .componentize()
+                .await?;
+
+        let mut linker = Linker::new(&ENGINE);
+        wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+        linker
+            .root()
+            .instance("componentize-js:middleware/handler")?
+            .func_new("handle", |_, _, params, results| {
+                results[0] = match &params[0] {
+                    Val::String(request) if request == "DOWN" => {
+                        Val::Result(Err(Some(Box::new(Val::Enum("unavailable".into())))))
+                    }
+                    Val::String(request) => {
+                        Val::Result(Ok(Some(Box::new(Val::String(format!("inner({request})"))))))
+                    }
+                    _ => unreachable!(),
+                };
+                Ok(())
+            })?;
+        let mut store = store();
+        let instance = linker
+            .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+            .await?;
+        let interface = instance
+            .get_export_index(&mut store, None, "componentize-js:middleware/handler")
+            .unwrap();
+        let handle = instance
+            .get_export_index(&mut store, Some(&interface), "handle")
+            .unwrap();
+        let handle = instance.get_func(&mut store, &handle).unwrap();
+
+        for (request, expected) in [
+            (
+                "hello",
+                Val::Result(Ok(Some(Box::new(Val::String(
+                    "middleware(inner(HELLO))".into(),
+                ))))),
+            ),
+            (
+                "down",
+                Val::Result(Ok(Some(Box::new(Val::String(
+                    "caught(unavailable)".into(),
+                ))))),
+            ),
+            (
+                "",
+                Val::Result(Err(Some(Box::new(Val::Enum("bad-request".into()))))),
+            ),
+        ] {
+            let mut results = [Val::Bool(false)];
+            handle
+                .call_async(&mut store, &[Val::String(request.into())], &mut results)
+                .await?;
+            assert_eq!(expected, results[0], "{export_layout:?}: `{request}`");
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn smoke_test() -> anyhow::Result<()> {
+    let componentize = async |js, export: &str| {
+        Componentizer::new(Wit::<String>::String(RUN_WIT), js)
+            .options(Options {
+                smoke_test: Some((export.into(), Vec::new())),
+                ..Options::default()
+            })
+            .componentize()
+            .await
+    };
+
+    componentize("export function run() { return 'ok' }", "run").await?;
+
+    assert!(matches!(
+        componentize("export function run() { throw new Error('oops') }", "run").await,
+        Err(ComponentizeError::SmokeTest(_))
+    ));
+
+    assert!(matches!(
+        componentize("export function run() { return 'ok' }", "walk").await,
+        Err(ComponentizeError::SmokeTest(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn debug_artifacts_dir() -> anyhow::Result<()> {
+    let dir = env::temp_dir().join(format!(
+        "componentize-js-debug-artifacts-{}",
+        std::process::id()
+    ));
+    let options = Options {
+        debug_artifacts_dir: Some(dir.clone()),
+        ..Options::default()
+    };
+
+    let result = async {
+        assert_eq!(
+            "ok",
+            run_script("export function run() { return 'ok' }", &options).await?
+        );
+        for name in ["linked.wasm", "instrumented.wasm", "snapshot.wasm"] {
+            assert!(std::fs::read(dir.join(name))?.starts_with(b"\0asm"));
+        }
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    _ = std::fs::remove_dir_all(&dir);
+
+    result
+}
+
+#[tokio::test]
+async fn cache_dir() -> anyhow::Result<()> {
+    let cache_dir = env::temp_dir().join(format!("componentize-js-cache-{}", std::process::id()));
+    let options = Options {
+        cache_dir: Some(cache_dir.clone()),
+        ..Options::default()
+    };
+
+    let result = async {
+        assert_eq!(
+            "1",
+            run_script("export function run() { return '1' }", &options).await?
+        );
+        let entries = || std::fs::read_dir(&cache_dir).map(|v| v.count());
+        assert_eq!(1, entries()?);
+
+        // A second build using the same world should reuse the cached
+        // component, even though the script differs.
+        assert_eq!(
+            "2",
+            run_script("export function run() { return '2' }", &options).await?
+        );
+        assert_eq!(1, entries()?);
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    _ = std::fs::remove_dir_all(&cache_dir);
+
+    result
+}
+
+#[tokio::test]
+async fn stdin() -> anyhow::Result<()> {
+    let js = "const input = new TextDecoder().decode(componentModel.readStdin())
+export function run() { return input }";
+
+    assert_eq!("", run_script(js, &Options::default()).await?);
+    assert_eq!(
+        "{\"name\": \"config\"}",
+        run_script(
+            js,
+            &Options {
+                stdin: b"{\"name\": \"config\"}".to_vec(),
+                ..Options::default()
+            }
+        )
+        .await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn backpressure() -> anyhow::Result<()> {
+    let js = "export function run() {
+    componentModel.setBackpressure(true)
+    componentModel.setBackpressure(false)
+    return 'ok'
+}";
+
+    assert_eq!("ok", run_script(js, &Options::default()).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn hot_reload() -> anyhow::Result<()> {
+    let component = Componentizer::new(
+        Wit::<String>::String(RUN_WIT),
+        "globalThis.count = 1\nexport function run() { return `a${count}` }",
+    )
+    .options(Options {
+        hot_reload: true,
+        ..Options::default()
+    })
+    .componentize()
+    .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+
+    assert_eq!("a1", run.call_async(&mut store, ()).await?.0);
+
+    // Replace the script, keeping the state on `globalThis`.
+    let warnings = crate::bindings::Init::new(&mut store, &instance)?
+        .call_init(
+            &mut store,
+            (&Options::default()).into(),
+            "",
+            &[],
+            "globalThis.count++\nexport function run() { return `b${count}` }",
+            "",
+        )
+        .await?
+        .map_err(|e| anyhow::anyhow!("{}", e.message))?;
+
+    assert!(warnings.is_empty());
+    assert_eq!("b2", run.call_async(&mut store, ()).await?.0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn skip_snapshot() -> anyhow::Result<()> {
+    let options = Options {
+        prefs: Prefs {
+            weak_refs: false,
+            ..Prefs::default()
+        },
+        skip_snapshot: true,
+        ..Options::default()
+    };
+
+    assert_eq!(
+        "1 undefined",
+        run_script(
+            "let count = 0\nexport function run() { return `${++count} ${typeof WeakRef}` }",
+            &options
+        )
+        .await?
+    );
+
+    // Errors thrown by the top level of the script are deferred until the
+    // first call.
+    assert!(
+        run_script(
+            "throw new Error('oops')\nexport function run() { return 'ok' }",
+            &options
+        )
+        .await
+        .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn project_manifest() -> anyhow::Result<()> {
+    let manifest = serde_json::json!({
+        "wit-path": ["wit", "deps"],
+        "world": "app",
+        "input": "app.js",
+        "output": "app.wasm",
+        "freeze-intrinsics": true,
+        "gc-logging": false,
+        "max-heap-bytes": 1048576,
+        "env": ["A=1", "B=2"]
+    });
+
+    let args = crate::command::project_args(manifest.as_object().unwrap(), Path::new("project"))?;
+    assert_eq!(
+        [
+            "componentize-js",
+            "--wit-path=project/wit",
+            "--wit-path=project/deps",
+            "--world=app",
+            "componentize",
+            "project/app.js",
+            "--env=A=1",
+            "--env=B=2",
+            "--freeze-intrinsics",
+            "--max-heap-bytes=1048576",
+            "--output=project/app.wasm",
+            "--base-directory=project",
+        ]
+        .map(OsString::from)
+        .as_slice(),
+        args.as_slice()
+    );
+
+    // The result should be accepted by the CLI as-is.
+    crate::command::Options::try_parse_from(args)?;
+
+    // If omitted, the base directory and output default to the project
+    // directory rather than the current one.
+    let manifest = serde_json::json!({ "input": "app.js" });
+    let args = crate::command::project_args(manifest.as_object().unwrap(), Path::new("project"))?;
+    assert_eq!(
+        [
+            "componentize-js",
+            "componentize",
+            "project/app.js",
+            "--base-directory=project",
+            "--output=project/js.wasm",
+        ]
+        .map(OsString::from)
+        .as_slice(),
+        args.as_slice()
+    );
+    crate::command::Options::try_parse_from(args)?;
+
+    Ok(())
+}
+
+#[test]
+fn new_project() -> anyhow::Result<()> {
+    for template in ["cli", "http"] {
+        let dir = env::temp_dir().join(format!(
+            "componentize-js-new-{template}-{}",
+            std::process::id()
+        ));
+
+        let result = (|| {
+            crate::command::run([
+                OsString::from("componentize-js"),
+                "--quiet".into(),
+                "new".into(),
+                template.into(),
+                dir.clone().into(),
+            ])?;
+
+            // Note that this also runs the smoke test specified by the `cli`
+            // template's manifest.
+            crate::command::run([
+                OsString::from("componentize-js"),
+                "--quiet".into(),
+                "build".into(),
+                dir.clone().into(),
+            ])?;
+
+            assert!(dir.join("app.wasm").exists());
+
+            anyhow::Ok(())
+        })();
+
+        _ = std::fs::remove_dir_all(&dir);
+
+        result?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn bench() -> anyhow::Result<()> {
+    const COUNT_WIT: &str = "package test:test;
+
+world test {
+  export count: func(n: u32, strings: list<string>) -> u32;
+}";
+
+    let component = Componentizer::new(
+        Wit::<String>::String(COUNT_WIT),
+        "export function count(n, strings) { return n + strings.length }",
+    )
+    .componentize()
+    .await?;
+
+    let args = serde_json::json!([1, ["a", "b"]]);
+    crate::bench::bench(&component, "count", args.as_array().unwrap(), 3).await?;
+
+    assert!(
+        crate::bench::bench(&component, "count", &[], 1)
+            .await
+            .is_err()
+    );
+    assert!(
+        crate::bench::bench(&component, "count", &[args[1].clone(), args[0].clone()], 1)
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn validate() -> anyhow::Result<()> {
+    let component = Componentizer::new(
+        Wit::<String>::String(RUN_WIT),
+        "export function run() { return 'ok' }",
+    )
+    .componentize()
+    .await?;
+
+    let validate = |wit: &str, component: &[u8], strict_imports| {
+        crate::validate(
+            Wit::<String>::String(wit),
+            None,
+            &[],
+            false,
+            component,
+            strict_imports,
+        )
+    };
+
+    assert!(validate(RUN_WIT, &component, false)?.is_empty());
+
+    // The runtime imports WASI interfaces which aren't part of the world.
+    assert!(
+        validate(RUN_WIT, &component, true)?
+            .iter()
+            .all(|problem| problem.starts_with("unexpected import: `wasi:"))
+    );
+    assert!(!validate(RUN_WIT, &component, true)?.is_empty());
+
+    let problems = validate(
+        "package componentize-js:run; world run { export walk: func() -> string; }",
+        &component,
+        false,
+    )?;
+    assert!(problems.contains(&"missing export: `walk`".to_string()));
+    assert!(problems.contains(&"unexpected export: `run`".to_string()));
+
+    let problems = validate(
+        "package componentize-js:run; world run { export run: func() -> u32; }",
+        &component,
+        false,
+    )?;
+    assert_eq!(1, problems.len());
+    assert!(problems[0].starts_with("export `run` does not match the world"));
+
+    assert_eq!(1, validate(RUN_WIT, b"not a component", false)?.len());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sandbox() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:sandbox;
+
+interface host {
+  value: func() -> u32;
+}
+
+interface secret {
+  value: func() -> u32;
+}
+
+world sandbox {
+  import host;
+  import secret;
+
+  export run: func() -> string;
+}";
+
+    const SCRIPT: &str =
+        "const sandbox = componentModel.createSandbox(['componentize-js:sandbox/host'])
+
+const plugin = sandbox.evaluate(`({
+    run(object) {
+        const escape = (f) => {
+            try {
+                return typeof f().componentModel
+            } catch (error) {
+                return error.constructor.name
+            }
+        }
+        const value = imports['componentize-js:sandbox/host'].value
+        return [
+            value(),
+            typeof imports['componentize-js:sandbox/secret'],
+            typeof componentModel,
+            Object.isFrozen(imports),
+            escape(() => value.constructor('return globalThis')()),
+            escape(() => object.constructor.constructor('return globalThis')())
+        ].join(' ')
+    }
+})`)
+
+let unknown
+try {
+    componentModel.createSandbox(['componentize-js:sandbox/nope'])
+} catch (error) {
+    unknown = error.message
+}
+
+let thrown
+try {
+    sandbox.evaluate(`throw new Error('oops')`)
+} catch (error) {
+    thrown = error.message
+}
+
+export function run() {
+    return `${plugin.run({})}; ${unknown}; ${thrown}`
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+    let component = Component::new(&ENGINE, &component)?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    linker
+        .root()
+        .instance("componentize-js:sandbox/host")?
+        .func_wrap("value", |_, ()| Ok((42_u32,)))?;
+    linker.define_unknown_imports_as_traps(&component)?;
+    let mut store = store();
+    let instance = linker.instantiate_async(&mut store, &component).await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+
+    assert_eq!(
+        "42 undefined undefined true TypeError TypeError; \
+         no such import: `componentize-js:sandbox/nope`; oops",
+        run.call_async(&mut store, ()).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn numeric_list_inputs() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:lists;
+
+world lists {
+  import echo-bytes: func(v: list<u8>) -> list<u8>;
+  import echo-f64s: func(v: list<f64>) -> list<f64>;
+
+  export run: func() -> string;
+}";
+
+    const SCRIPT: &str = "import { echoBytes, echoF64s } from 'wit-world'
+
+const show = (v) => `${v.constructor.name}[${Array.from(v).join(',')}]`
+
+export function run() {
+    const results = [
+        echoBytes(new Uint8Array([1, 2])),
+        echoBytes(new Uint8Array([1, 2, 3]).buffer),
+        echoBytes(new DataView(new Uint8Array([4, 5, 6]).buffer, 1)),
+        echoBytes(new Uint16Array([0x0807])),
+        echoBytes([9, 10]),
+        echoF64s([1.5, 2]),
+        echoF64s(new Int32Array([3, 4])),
+        echoF64s(new Float64Array([5.5]).buffer),
+    ].map(show)
+    try {
+        echoF64s(new ArrayBuffer(3))
+    } catch (error) {
+        results.push(error.name)
+    }
+    try {
+        echoBytes([1, 'two'])
+    } catch (error) {
+        results.push(error.name)
+    }
+    return results.join(' ')
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut root = linker.root();
+    root.func_wrap("echo-bytes", |_, (v,): (Vec<u8>,)| Ok((v,)))?;
+    root.func_wrap("echo-f64s", |_, (v,): (Vec<f64>,)| Ok((v,)))?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+
+    assert_eq!(
+        "Uint8Array[1,2] Uint8Array[1,2,3] Uint8Array[5,6] Uint8Array[7,8] Uint8Array[9,10] \
+         Float64Array[1.5,2] Float64Array[3,4] Float64Array[5.5] TypeError TypeError",
+        run.call_async(&mut store, ()).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flags_objects_and_names() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:flags;
+
+world flags {
+  flags permissions { read, write, run-as-root }
+
+  import echo: func(v: permissions) -> permissions;
+
+  export run: func() -> string;
+}";
+
+    const SCRIPT: &str = "import { echo } from 'wit-world'
+
+const show = (v) => JSON.stringify(v)
+
+export function run() {
+    const results = [
+        echo({ read: true, runAsRoot: 1 }),
+        echo({}),
+        echo(['write', 'run-as-root']),
+        echo(['runAsRoot']),
+    ].map(show)
+    try {
+        echo(['read', 'delete'])
+    } catch (error) {
+        results.push(error.message)
+    }
+    return results.join(' ')
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    linker.root().func_new("echo", |_, _, params, results| {
+        results[0] = params[0].clone();
+        Ok(())
+    })?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+
+    assert_eq!(
+        r#"{"read":true,"write":false,"runAsRoot":true} {"read":false,"write":false,"runAsRoot":false} {"read":false,"write":true,"runAsRoot":true} {"read":false,"write":false,"runAsRoot":true} type mismatch at `echo.param[0][1]`: expected flags { read, write, run-as-root }, got string "delete""#,
+        run.call_async(&mut store, ()).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn option_representations() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:options;
+
+world options {
+  import echo-string: func(v: option<string>) -> option<string>;
+  import echo-nested: func(v: option<option<u32>>) -> option<option<u32>>;
+
+  export run: func() -> string;
+}";
+
+    const SCRIPT: &str = "import { echoString, echoNested } from 'wit-world'
+
+const show = (v) => v === undefined ? 'undefined' : JSON.stringify(v)
+
+export function run() {
+    const results = [
+        echoString('hi'),
+        echoString(null),
+        echoString(undefined),
+        echoNested(null),
+        echoNested({ tag: 'some', val: undefined }),
+        echoNested({ tag: 'some', val: null }),
+        echoNested({ tag: 'some', val: 7 }),
+    ].map(show)
+    try {
+        echoNested(7)
+    } catch (error) {
+        results.push(error.message)
+    }
+    return results.join(' ')
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut root = linker.root();
+    root.func_wrap("echo-string", |_, (v,): (Option<String>,)| Ok((v,)))?;
+    root.func_wrap("echo-nested", |_, (v,): (Option<Option<u32>>,)| Ok((v,)))?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+
+    assert_eq!(
+        r#""hi" undefined undefined undefined {"tag":"some"} {"tag":"some"} {"tag":"some","val":7} type mismatch at `echo-nested.param[0]`: expected option<option<u32>>, got number `7`"#,
+        run.call_async(&mut store, ()).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn exported_resource_dispose() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:dispose;
+
+interface things {
+  resource thing { constructor(name: string); }
+  resource gadget { constructor(name: string); }
+  resource widget { constructor(name: string); }
+
+  disposed: func() -> string;
+}
+
+world dispose {
+  export things;
+}";
+
+    const SCRIPT: &str = "const disposed = []
+
+class Thing {
+    constructor(name) { this.name = name }
+    [Symbol.dispose]() { disposed.push(`${this.name}: Symbol.dispose`) }
+    dispose() { disposed.push(`${this.name}: dispose`) }
+}
+
+class Gadget {
+    constructor(name) { this.name = name }
+    dispose() { disposed.push(`${this.name}: dispose`) }
+}
+
+class Widget {
+    constructor(name) { this.name = name }
+}
+
+export const componentizeJsDisposeThings = {
+    Thing,
+    Gadget,
+    Widget,
+    disposed: () => disposed.join('; ')
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let interface = instance
+        .get_export_index(&mut store, None, "componentize-js:dispose/things")
+        .unwrap();
+
+    for (resource, name) in [("thing", "a"), ("gadget", "b"), ("widget", "c")] {
+        let constructor = instance
+            .get_export_index(
+                &mut store,
+                Some(&interface),
+                &format!("[constructor]{resource}"),
+            )
+            .unwrap();
+        let constructor = instance.get_func(&mut store, &constructor).unwrap();
+        let mut results = [Val::Bool(false)];
+        constructor
+            .call_async(&mut store, &[Val::String(name.into())], &mut results)
+            .await?;
+        let Val::Resource(resource) = &results[0] else {
+            unreachable!()
+        };
+        resource.resource_drop_async(&mut store).await?;
+    }
+
+    let disposed = instance
+        .get_export_index(&mut store, Some(&interface), "disposed")
+        .unwrap();
+    let disposed = instance.get_typed_func::<(), (String,)>(&mut store, &disposed)?;
+    assert_eq!(
+        "a: Symbol.dispose; b: dispose",
+        disposed.call_async(&mut store, ()).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn escaped_borrow() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:borrows;
+
+interface host {
+  resource counter {
+    value: func() -> u32;
+  }
+}
+
+world borrows {
+  use host.{counter};
+
+  export keep: func(c: borrow<counter>) -> u32;
+  export use-kept: func() -> string;
+}";
+
+    const SCRIPT: &str = "let kept
+
+export function keep(c) {
+    kept = c
+    return c.value()
+}
+
+export function useKept() {
+    try {
+        return `${kept.value()}`
+    } catch (error) {
+        return `${error.name}: ${error.message}`
+    }
+}";
+
+    struct Counter;
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut host = linker.root().instance("componentize-js:borrows/host")?;
+    host.resource(
+        "counter",
+        wasmtime::component::ResourceType::host::<Counter>(),
+        |_, _| Ok(()),
+    )?;
+    host.func_wrap(
+        "[method]counter.value",
+        |_, (counter,): (Resource<Counter>,)| Ok((counter.rep(),)),
+    )?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+
+    let keep = instance.get_typed_func::<(Resource<Counter>,), (u32,)>(&mut store, "keep")?;
+    assert_eq!(
+        42,
+        keep.call_async(&mut store, (Resource::new_own(42),))
+            .await?
+            .0
+    );
+
+    let use_kept = instance.get_typed_func::<(), (String,)>(&mut store, "use-kept")?;
+    assert_eq!(
+        "TypeError: invalid resource at `[method]counter.value.param[0]`: its handle has been \
+         dropped or moved, or it is a borrow which escaped its call",
+        use_kept.call_async(&mut store, ()).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn exceptions_as_result_errors() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:exceptions;
+
+world exceptions {
+  record failure { code: u32 }
+
+  export parse: func(s: string) -> result<u32, string>;
+  export check: func(s: string) -> result;
+  export lookup: func(s: string) -> result<u32, failure>;
+}";
+
+    const SCRIPT: &str = "export function parse(s) {
+    if (s === '') {
+        throw 'empty'
+    }
+    const value = Number(s)
+    if (Number.isNaN(value)) {
+        throw new Error(`not a number: ${s}`)
+    }
+    return value
+}
+
+export function check(s) {
+    if (s !== 'ok') {
+        throw new Error('not ok')
+    }
+}
+
+export function lookup(s) {
+    throw { code: s.length }
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+
+    let parse = instance.get_typed_func::<(&str,), (Result<u32, String>,)>(&mut store, "parse")?;
+    for (input, expected) in [
+        ("42", Ok(42)),
+        ("", Err("empty".to_string())),
+        ("forty-two", Err("not a number: forty-two".to_string())),
+    ] {
+        assert_eq!(expected, parse.call_async(&mut store, (input,)).await?.0);
+    }
+
+    let check = instance.get_typed_func::<(&str,), (Result<(), ()>,)>(&mut store, "check")?;
+    assert_eq!(Ok(()), check.call_async(&mut store, ("ok",)).await?.0);
+    assert_eq!(Err(()), check.call_async(&mut store, ("nope",)).await?.0);
+
+    let lookup = instance.get_func(&mut store, "lookup").unwrap();
+    let mut results = [Val::Bool(false)];
+    lookup
+        .call_async(&mut store, &[Val::String("abc".into())], &mut results)
+        .await?;
+    assert_eq!(
+        Val::Result(Err(Some(Box::new(Val::Record(vec![(
+            "code".into(),
+            Val::U32(3)
+        )]))))),
+        results[0]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn export_exception_traps() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:traps;
+
+world traps {
+  export run: func() -> u32;
+}";
+
+    const SCRIPT: &str = "function fail() {
+    throw new Error('boom')
+}
+
+export function run() {
+    return fail()
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let stderr = MemoryOutputPipe::new(10000);
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = Store::new(
+        &ENGINE,
+        Ctx {
+            wasi: WasiCtxBuilder::new().stderr(stderr.clone()).build(),
+            table: ResourceTable::default(),
+        },
+    );
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+
+    let run = instance.get_typed_func::<(), (u32,)>(&mut store, "run")?;
+    assert!(run.call_async(&mut store, ()).await.is_err());
+
+    let stderr = String::from_utf8_lossy(&stderr.contents()).into_owned();
+    assert!(
+        stderr.contains("export `run` threw: Error: boom"),
+        "unexpected stderr: {stderr}"
+    );
+    assert!(stderr.contains("fail@"), "missing stack trace: {stderr}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn console() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:console;
+
+world console {
+  export run: func();
+}";
+
+    const SCRIPT: &str = "export function run() {
+    console.log('%s has %d items costing %f', 'cart', 3, 1.5, 'extra')
+    console.info({ a: [1, 'two', { b: null }], m: new Map([[1, 2n]]) })
+    const cycle = { name: 'cycle' }
+    cycle.self = cycle
+    console.debug(cycle, new Set(['x']), new Uint8Array([1, 2]), () => {}, '100%')
+    console.warn('careful: %j', { x: 1 })
+    console.error(new TypeError('bad'))
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let stdout = MemoryOutputPipe::new(10000);
+    let stderr = MemoryOutputPipe::new(10000);
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = Store::new(
+        &ENGINE,
+        Ctx {
+            wasi: WasiCtxBuilder::new()
+                .stdout(stdout.clone())
+                .stderr(stderr.clone())
+                .build(),
+            table: ResourceTable::default(),
+        },
+    );
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+
+    let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+    run.call_async(&mut store, ()).await?;
+
+    let stdout = String::from_utf8_lossy(&stdout.contents()).into_owned();
+    assert_eq!(
+        stdout,
+        "cart has 3 items costing 1.5 extra
+{ a: [ 1, 'two', { b: null } ], m: Map(1) { 1 => 2n } }
+{ name: 'cycle', self: [Circular] } Set(1) { 'x' } Uint8Array(2) [ 1, 2 ] [Function (anonymous)] 100%
+"
+    );
+
+    let stderr = String::from_utf8_lossy(&stderr.contents()).into_owned();
+    assert!(
+        stderr.starts_with("careful: {\"x\":1}\nTypeError: bad\nrun@"),
+        "unexpected stderr: {stderr}"
+    );
+
+    // Output written while the script is evaluated is captured by the host and
+    // reported alongside any error.
+    let result = Componentizer::new(
+        Wit::<String>::String(WIT),
+        "console.log('initializing'); throw new Error('oops')",
+    )
+    .componentize()
+    .await;
+
+    match result {
+        Err(ComponentizeError::InitScript { stdout, .. }) => assert_eq!(stdout, "initializing\n"),
+        result => panic!("expected `InitScript` error; got {:?}", result.err()),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn console_wasi_logging() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:console-logging;
+
+world console-logging {
+  import wasi:logging/logging;
+  export run: func();
+}
+
+package wasi:logging {
+  interface logging {
+    enum level { trace, debug, info, warn, error, critical }
+    log: func(level: level, context: string, message: string);
+  }
+}";
+
+    const SCRIPT: &str = "console.log('evaluating')
+
+export function run() {
+    console.log('hello, %s', 'world')
+    console.debug({ a: 1 })
+    console.warn('careful')
+    console.error('oops')
+}";
+
+    let options = Options {
+        console: ConsoleOutput::WasiLogging,
+        ..Options::default()
+    };
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .options(options.clone())
+        .componentize()
+        .await?;
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let stdout = MemoryOutputPipe::new(10000);
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    linker
+        .root()
+        .instance("wasi:logging/logging")?
+        .func_new("log", {
+            let records = records.clone();
+            move |_, _, params, _| {
+                let [Val::Enum(level), Val::String(context), Val::String(message)] = params else {
+                    unreachable!()
+                };
+                records
+                    .lock()
+                    .unwrap()
+                    .push((level.clone(), context.clone(), message.clone()));
+                Ok(())
+            }
+        })?;
+    let mut store = Store::new(
+        &ENGINE,
+        Ctx {
+            wasi: WasiCtxBuilder::new().stdout(stdout.clone()).build(),
+            table: ResourceTable::default(),
+        },
+    );
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+
+    let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+    run.call_async(&mut store, ()).await?;
+
+    assert_eq!(
+        *records.lock().unwrap(),
+        [
+            ("info", "hello, world"),
+            ("debug", "{ a: 1 }"),
+            ("warn", "careful"),
+            ("error", "oops"),
+        ]
+        .map(|(level, message)| (
+            level.to_string(),
+            "console".to_string(),
+            message.to_string()
+        ))
+    );
+    assert!(stdout.contents().is_empty());
+
+    // The world must import `wasi:logging/logging`.
+    let result = Componentizer::new(
+        Wit::<String>::String("package componentize-js:run; world run { export run: func(); }"),
+        "export function run() {}",
+    )
+    .options(options.clone())
+    .componentize()
+    .await;
+
+    assert!(
+        matches!(result, Err(ComponentizeError::WorldSelection(_))),
+        "expected `WorldSelection` error; got {:?}",
+        result.err()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn timers() -> anyhow::Result<()> {
+    const WIT: &str = "package componentize-js:timers;
+
+world timers {
+  import wasi:clocks/monotonic-clock@0.3.0-rc-2026-01-06;
+  export run: async func() -> string;
+}
+
+package wasi:clocks@0.3.0-rc-2026-01-06 {
+  interface monotonic-clock {
+    wait-for: async func(how-long: u64);
+  }
+}";
+
+    const SCRIPT: &str = "export async function run() {
+    const events = []
+    clearTimeout(setTimeout(() => events.push('cancelled'), 10))
+
+    let ticks = 0
+    await new Promise((resolve) => {
+        const interval = setInterval(() => {
+            events.push(`tick ${++ticks}`)
+            if (ticks === 3) {
+                clearInterval(interval)
+                resolve()
+            }
+        }, 5)
+    })
+
+    setTimeout((a, b) => events.push(`immediate ${a} ${b}`), 0, 'x', 'y')
+    await new Promise((resolve) => setTimeout(resolve, 20))
+    return events.join(', ')
+}";
+
+    let component = Componentizer::new(Wit::<String>::String(WIT), SCRIPT)
+        .componentize()
+        .await?;
+
+    let durations = Arc::new(Mutex::new(Vec::new()));
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    linker
+        .root()
+        .instance("wasi:clocks/monotonic-clock@0.3.0-rc-2026-01-06")?
+        .func_new_concurrent("wait-for", {
+            let durations = durations.clone();
+            move |_, _, params, _| {
+                let Val::U64(duration) = params[0] else {
+                    unreachable!()
+                };
+                durations.lock().unwrap().push(duration);
+                Box::pin(async move {
+                    delay_via_yield().await;
+                    Ok(())
+                })
+            }
+        })?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+    assert_eq!(
+        "tick 1, tick 2, tick 3, immediate x y",
+        store
+            .run_concurrent(async |accessor| run.call_concurrent(accessor, ()).await)
+            .await??
+            .0
+    );
+
+    // Each timer with a nonzero delay waits using the imported clock, including
+    // the cancelled timer and the interval's fourth (cancelled) iteration.
+    assert_eq!(
+        *durations.lock().unwrap(),
+        [10, 5, 5, 5, 5, 20].map(|milliseconds| milliseconds * 1_000_000)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn crypto() -> anyhow::Result<()> {
+    let js = "const hex = (buffer) => Array.from(
+        new Uint8Array(buffer),
+        (byte) => byte.toString(16).padStart(2, '0')
+    ).join('')
+
+    // The view only covers `abc`.
+    const bytes = new Uint8Array([0, 97, 98, 99, 0]).subarray(1, 4)
+    const sha256 = hex(await crypto.subtle.digest('SHA-256', bytes))
+    const sha512 = hex(await crypto.subtle.digest({ name: 'sha-512' }, bytes.slice().buffer))
+    const unsupported = await crypto.subtle.digest('MD5', bytes).catch((error) => error.name)
+
+    export function run() {
+        const uuids = [crypto.randomUUID(), crypto.randomUUID()]
+        const words = crypto.getRandomValues(new Uint32Array(16))
+        let quota
+        try {
+            crypto.getRandomValues(new Uint8Array(65537))
+        } catch (error) {
+            quota = error.name
+        }
+        return [
+            sha256,
+            sha512.slice(0, 16),
+            unsupported,
+            uuids.every((uuid) => /^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$/.test(uuid)),
+            uuids[0] !== uuids[1],
+            words.some((word) => word !== 0),
+            quota
+        ].join(' ')
+    }";
+
+    assert_eq!(
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad \
+         ddaf35a193617aba NotSupportedError true true true QuotaExceededError",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn performance() -> anyhow::Result<()> {
+    let js = "performance.mark('init')
+
+    export function run() {
+        const start = performance.now()
+        performance.mark('a', { startTime: 10 })
+        performance.mark('b', { startTime: 25, detail: 'x' })
+        const between = performance.measure('a-to-b', 'a', 'b')
+        const after = performance.measure('after-a', { start: 'a', duration: 5 })
+        let missing
+        try {
+            performance.measure('bad', 'nope')
+        } catch (error) {
+            missing = error.name
+        }
+        performance.clearMarks('a')
+        return [
+            performance.now() >= start,
+            Math.abs(performance.timeOrigin + performance.now() - Date.now()) < 1000,
+            between.duration,
+            after.startTime,
+            after.duration,
+            missing,
+            performance.getEntriesByType('mark').map((entry) => entry.name).join('/'),
+            performance.getEntries().length
+        ].join(' ')
+    }";
+
+    assert_eq!(
+        "true true 15 10 5 SyntaxError init/b 4",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "intl")]
+#[tokio::test]
+async fn intl() -> anyhow::Result<()> {
+    let js = "export function run() {
+        return [
+            new Intl.NumberFormat('de-DE').format(1234.5),
+            new Intl.DateTimeFormat('en-US', { timeZone: 'UTC' }).format(new Date(0)),
+            new Intl.PluralRules('en-US').select(1),
+            ['b', 'a', 'C'].sort(new Intl.Collator('en').compare).join('')
+        ].join(' ')
+    }";
+
+    assert_eq!(
+        "1.234,5 1/1/1970 one abC",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "intl"))]
+#[tokio::test]
+async fn no_intl() -> anyhow::Result<()> {
+    let js = "export function run() {
+        return typeof Intl
+    }";
+
+    assert_eq!("undefined", run_script(js, &Options::default()).await?);
+
+    Ok(())
+}
+
+#[cfg(feature = "intl")]
+#[tokio::test]
+async fn temporal() -> anyhow::Result<()> {
+    let js = "export function run() {
+        const date = Temporal.PlainDate.from('2024-02-28').add({ days: 2 })
+        return [
+            date.toString(),
+            Temporal.Now.instant().epochMilliseconds > 0,
+            Temporal.Duration.from({ hours: 1, minutes: 90 }).round({ largestUnit: 'hour' }).toString()
+        ].join(' ')
+    }";
+
+    let options = Options {
+        prefs: Prefs {
+            temporal: true,
+            ..Prefs::default()
+        },
+        ..Options::default()
+    };
+    assert_eq!("2024-03-01 true PT2H30M", run_script(js, &options).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn temporal_disabled_by_default() -> anyhow::Result<()> {
+    let js = "export function run() {
+        return typeof Temporal
+    }";
+
+    assert_eq!("undefined", run_script(js, &Options::default()).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn structured_clone() -> anyhow::Result<()> {
+    let js = "export function run() {
+        const original = {
+            date: new Date(0),
+            map: new Map([[1, { x: 1n }]]),
+            set: new Set(['a']),
+            bytes: new Uint8Array([1, 2, 3]),
+            regexp: /x/gi,
+            error: new RangeError('bad')
+        }
+        original.self = original
+        const clone = structuredClone(original)
+        clone.map.get(1).x = 2n
+        clone.bytes[0] = 9
+
+        const buffer = new Uint8Array([4, 5]).buffer
+        const moved = structuredClone({ buffer }, { transfer: [buffer] })
+
+        const errors = [
+            () => structuredClone(() => {}),
+            () => structuredClone({}, { transfer: [buffer] }),
+            () => structuredClone(Symbol())
+        ].map((f) => {
+            try {
+                f()
+            } catch (error) {
+                return error.name
+            }
+        })
+
+        return [
+            clone !== original,
+            clone.self === clone,
+            clone.date.getTime(),
+            original.map.get(1).x,
+            original.bytes[0],
+            clone.regexp.flags,
+            clone.error instanceof RangeError && clone.error.message,
+            buffer.byteLength,
+            new Uint8Array(moved.buffer).join(','),
+            errors.join('/')
+        ].join(' ')
+    }";
+
+    assert_eq!(
+        "true true 0 1 1 gi bad 0 4,5 DataCloneError/DataCloneError/DataCloneError",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_response_headers() -> anyhow::Result<()> {
+    let js = "const headers = new Headers([['B', '1'], ['a', ' 2 '], ['set-cookie', 'x'], ['set-cookie', 'y']])
+    headers.append('b', '3')
+
+    const request = new Request('https://example.com/path?q', {
+        method: 'post',
+        headers: { 'X-Test': 'yes' },
+        body: 'hello'
+    })
+    const copy = request.clone()
+    const requestText = await request.text()
+    const consumed = await request.text().catch((error) => error.name)
+
+    const json = Response.json({ a: [1] }, { status: 201 })
+    const redirect = Response.redirect('https://example.com/', 307)
+
+    export async function run() {
+        return [
+            Array.from(headers, ([k, v]) => `${k}=${v}`).join(';'),
+            request.method,
+            request.url,
+            request.headers.get('content-type'),
+            requestText,
+            request.bodyUsed,
+            consumed,
+            await copy.text(),
+            new Request(copy, { method: 'GET', body: null }).method,
+            json.status,
+            json.ok,
+            json.headers.get('content-type'),
+            JSON.stringify(await json.clone().json()),
+            (await json.bytes()).length,
+            redirect.headers.get('location'),
+            Response.error().type,
+            Response.error().status,
+            [() => new Request('GET /'), () => new Response('x', { status: 204 })]
+                .map((f) => { try { f() } catch (error) { return error.name } })
+                .join('/')
+        ].join(' ')
+    }";
+
+    assert_eq!(
+        "a=2;b=1, 3;set-cookie=x;set-cookie=y POST https://example.com/path?q \
+         text/plain;charset=UTF-8 hello true TypeError hello GET 201 true application/json \
+         {\"a\":[1]} 9 https://example.com/ error 0 TypeError/TypeError",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn readable_stream() -> anyhow::Result<()> {
+    let js = "const encoder = new TextEncoder()
+
+    let count = 0
+    const counter = new ReadableStream({
+        pull(controller) {
+            count < 3 ? controller.enqueue(count++) : controller.close()
+        }
+    })
+    const [a, b] = counter.tee()
+    const seen = []
+    for await (const value of a) {
+        seen.push(value)
+    }
+
+    const reader = b.getReader()
+    const first = await reader.read()
+    reader.releaseLock()
+    const rest = []
+    for await (const value of b.values()) {
+        rest.push(value)
+    }
+
+    const failing = new ReadableStream({ start(controller) { controller.error(new Error('oops')) } })
+    const failed = await failing.getReader().read().catch((error) => error.message)
+
+    const response = new Response(ReadableStream.from(['ab', 'cd'].map((s) => encoder.encode(s))))
+    const clone = response.clone()
+    const sizes = []
+    for await (const chunk of response.body) {
+        sizes.push(chunk.length)
+    }
+
+    const request = new Request('https://example.com/', {
+        method: 'PUT',
+        body: ReadableStream.from([encoder.encode('streamed')]),
+        duplex: 'half'
+    })
+    const halfDuplex = (() => {
+        try {
+            new Request('https://example.com/', { method: 'PUT', body: new ReadableStream() })
+        } catch (error) {
+            return error.name
+        }
+    })()
+    const cloned = await clone.text()
+    const requestText = await request.text()
+
+    export function run() {
+        return [
+            seen.join(','),
+            counter.locked,
+            first.value,
+            rest.join(','),
+            failed,
+            sizes.join(','),
+            response.bodyUsed,
+            cloned,
+            requestText,
+            halfDuplex
+        ].join(' ')
+    }";
+
+    assert_eq!(
+        "0,1,2 true 0 1,2 oops 2,2 true abcd streamed TypeError",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn web_streams() -> anyhow::Result<()> {
+    let js = "const written = []
+    const sink = new WritableStream({
+        write(chunk) { written.push(chunk) },
+        close() { written.push('closed') }
+    })
+    const writer = sink.getWriter()
+    await writer.ready
+    writer.write('a')
+    await writer.write('b')
+    const desired = writer.desiredSize
+    await writer.close()
+    await writer.closed
+    const writeAfterClose = await writer.write('c').catch((error) => error.name)
+
+    const upper = new TransformStream({
+        transform(chunk, controller) { controller.enqueue(chunk.toUpperCase()) },
+        flush(controller) { controller.enqueue('!') }
+    })
+    const piped = []
+    for await (const chunk of ReadableStream.from(['x', 'y']).pipeThrough(upper)) {
+        piped.push(chunk)
+    }
+
+    const failing = new WritableStream({ write() { throw new Error('full') } })
+    const canceled = []
+    const pipeError = await new ReadableStream({
+        pull(controller) { controller.enqueue(1) },
+        cancel(reason) { canceled.push(reason.message) }
+    }).pipeTo(failing).catch((error) => error.message)
+
+    const controller = new AbortController()
+    const aborted = []
+    const pending = new ReadableStream().pipeTo(new WritableStream({
+        abort(reason) { aborted.push(reason.name) }
+    }), { signal: controller.signal })
+    controller.abort()
+    const abortError = await pending.catch((error) => error.name)
+
+    export function run() {
+        return [
+            written.join(','),
+            desired,
+            writeAfterClose,
+            piped.join(''),
+            pipeError,
+            canceled.join(','),
+            abortError,
+            aborted.join(',')
+        ].join(' ')
+    }";
+
+    assert_eq!(
+        "a,b,closed 1 TypeError XY! full full AbortError AbortError",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn blob_and_file() -> anyhow::Result<()> {
+    let js = "const blob = new Blob(['ab', new Uint8Array([99, 100]), new Blob(['e'])], { type: 'Text/Plain' })
+    const file = new File(['hello'], 'hello.txt', { type: 'text/plain', lastModified: 42 })
+    const chunks = []
+    for await (const chunk of blob.stream()) {
+        chunks.push(...chunk)
+    }
+    const response = new Response(file)
+    const fromBody = await new Response('xyz', { headers: { 'content-type': 'text/x' } }).blob()
+
+    const results = [
+        blob.size,
+        blob.type,
+        await blob.text(),
+        await blob.slice(1, -1).text(),
+        await blob.slice(-2).text(),
+        new Uint8Array(await blob.arrayBuffer()).join(','),
+        chunks.length,
+        file.name,
+        file.lastModified,
+        file instanceof Blob,
+        String(file),
+        response.headers.get('content-type'),
+        await response.text(),
+        fromBody.type,
+        await fromBody.text()
+    ].join(' ')
+
+    export function run() {
+        return results
+    }";
+
+    assert_eq!(
+        "5 text/plain abcde bcd de 97,98,99,100,101 5 hello.txt 42 true [object File] text/plain \
+         hello text/x xyz",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn microtasks() -> anyhow::Result<()> {
+    let js = "const log = []
+
+    export function run() {
+        const seen = log.splice(0).join(',')
+        queueMicrotask(() => { throw new Error('reported, not propagated') })
+        queueMicrotask(() => log.push('microtask'))
+        Promise.resolve().then(() => log.push('then'))
+        log.push('sync')
+        let invalid
+        try {
+            queueMicrotask('nope')
+        } catch (error) {
+            invalid = error.name
+        }
+        return `${seen} ${invalid}`
+    }";
+
+    let component = Componentizer::new(Wit::<String>::String(RUN_WIT), js)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    let mut store = store();
+    let instance = linker
+        .instantiate_async(&mut store, &Component::new(&ENGINE, &component)?)
+        .await?;
+    let run = instance.get_typed_func::<(), (String,)>(&mut store, "run")?;
+
+    // Microtasks queued by a sync export run before it returns, so the next
+    // call sees their effects.
+    assert_eq!(" TypeError", run.call_async(&mut store, ()).await?.0);
+    assert_eq!(
+        "sync,microtask,then TypeError",
+        run.call_async(&mut store, ()).await?.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn events() -> anyhow::Result<()> {
+    let js = "export function run() {
+        const log = []
+        const target = new EventTarget()
+        const listener = (event) => log.push(`a:${event.eventPhase}:${event.currentTarget === target}`)
+        target.addEventListener('ping', listener)
+        target.addEventListener('ping', listener)
+        target.addEventListener('ping', listener, true)
+        target.addEventListener('ping', { handleEvent: (event) => log.push(`b:${event.detail}`) }, { once: true })
+        target.addEventListener('ping', (event) => event.preventDefault(), { passive: true })
+        target.addEventListener('ping', (event) => {
+            event.preventDefault()
+            event.stopImmediatePropagation()
+        })
+        target.addEventListener('ping', () => log.push('unreachable'))
+        const controller = new AbortController()
+        target.addEventListener('ping', () => log.push('aborted'), { signal: controller.signal })
+        controller.abort()
+
+        const first = target.dispatchEvent(new CustomEvent('ping', { detail: 42, cancelable: true }))
+        target.removeEventListener('ping', listener, { capture: true })
+        const event = new Event('ping')
+        const second = target.dispatchEvent(event)
+
+        let fired = 0
+        addEventListener('custom', () => fired++)
+        dispatchEvent(new Event('custom'))
+        dispatchEvent(new Event('other'))
+
+        let invalid
+        try {
+            target.dispatchEvent({ type: 'ping' })
+        } catch (error) {
+            invalid = error.name
+        }
+
+        return [
+            log.join(','),
+            first,
+            second,
+            event.target === target,
+            event.currentTarget === null,
+            event.eventPhase,
+            fired,
+            invalid,
+            new AbortController().signal instanceof EventTarget
+        ].join(' ')
+    }";
+
+    assert_eq!(
+        "a:2:true,a:2:true,b:42,a:2:true false true true true 0 1 TypeError true",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn abort_controller() -> anyhow::Result<()> {
+    let js = "export function run() {
+        const events = []
+        const controller = new AbortController()
+        const { signal } = controller
+        signal.onabort = () => events.push('onabort')
+        const listener = (event) => events.push(`${event.type}:${event.target === signal}`)
+        signal.addEventListener('abort', listener)
+        signal.addEventListener('abort', listener)
+        signal.addEventListener('abort', { handleEvent: () => events.push('object') })
+        const removed = () => events.push('removed')
+        signal.addEventListener('abort', removed)
+        signal.removeEventListener('abort', removed)
+        const before = signal.aborted
+        controller.abort()
+        controller.abort('again')
+
+        let thrown
+        try {
+            signal.throwIfAborted()
+        } catch (error) {
+            thrown = error.name
+        }
+
+        const custom = AbortSignal.abort('why')
+        const any = AbortSignal.any([new AbortController().signal, custom])
+        const request = new Request('https://example.com/', { signal })
+        let illegal
+        try {
+            new AbortSignal()
+        } catch (error) {
+            illegal = error.name
+        }
+
+        return [
+            before,
+            signal.aborted,
+            events.join(','),
+            signal.reason.name,
+            thrown,
+            custom.reason,
+            any.aborted && any.reason,
+            request.signal === signal,
+            new Request('https://example.com/').signal.aborted,
+            illegal
+        ].join(' ')
+    }";
+
+    assert_eq!(
+        "false true onabort,abort:true,object AbortError AbortError why why true false TypeError",
+        run_script(js, &Options::default()).await?
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "typescript"))]
+#[test]
+fn typescript_input() {
+    let error = crate::command::run([
+        OsString::from("componentize-js"),
+        "--quiet".into(),
+        "componentize".into(),
+        "app.ts".into(),
+    ])
+    .unwrap_err();
+
+    assert!(
+        error.to_string().contains("appears to be TypeScript"),
+        "unexpected error: {error:?}"
+    );
+}
+
+#[test]
+fn typescript_declarations() -> anyhow::Result<()> {
+    const WIT: &str = "package test:types;
+
+interface host {
+  resource conn {
+    constructor(url: string);
+    send: async func(data: list<u8>) -> result<u32, string>;
+  }
+  now: func() -> u64;
+}
+
+interface api {
+  use host.{conn};
+
+  record point { x-coord: f64, y: option<option<u8>> }
+
+  resource counter {
+    constructor(start: u32);
+    next: func() -> u32;
+  }
+
+  move: func(p: point, c: borrow<conn>, delete: list<string>) -> point;
+}
+
+world types {
+  import host;
+  import log: func(msg: string);
+  export api;
+  export run: async func() -> result<string, string>;
+}";
+
+    let files = crate::generate_types(
+        Wit::<String>::String(WIT),
+        None,
+        &[],
+        false,
+        &[],
+        ExportLayout::Flat,
+    )?
+    .into_iter()
+    .collect::<BTreeMap<_, _>>();
+
+    let imports = &files["imports.d.ts"];
+    for expected in [
+        "declare module 'wit-world' {",
+        "    export function log(msg: string): void;",
+        "declare module 'test:types/host' {",
+        "    export class Conn {",
+        "        constructor(url: string);",
+        "        send(data: Uint8Array | ArrayBuffer | Array<number>): \
+         Promise<number> & { cancel(): void; cancelOn(signal: AbortSignal): Promise<number> };",
+        "        [Symbol.dispose](): void;",
+        "    export function now(): bigint | number;",
+    ] {
+        assert!(
+            imports.contains(expected),
+            "missing `{expected}` in:\n{imports}"
+        );
+    }
+
+    let exports = &files["exports.d.ts"];
+    for expected in [
+        "export interface TestTypesApiCounter {\n    next(): number;",
+        "export interface Exports {",
+        "    testTypesApi: {",
+        "        move(p: { xCoord: number; y: { tag: 'some'; val: number | undefined } | undefined; }, \
+         c: import('test:types/host').Conn, delete_: Array<string>): \
+         { xCoord: number; y: { tag: 'some'; val: number | null | undefined } | null | undefined; };",
+        "        Counter: {\n            new (start: number): TestTypesApiCounter;\n        };",
+        "    run(): Promise<string>;",
+    ] {
+        assert!(
+            exports.contains(expected),
+            "missing `{expected}` in:\n{exports}"
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "typescript")]
+#[tokio::test]
+async fn typescript_input() -> anyhow::Result<()> {
+    let ts = "enum Greeting { Hello = 'hello' }
+    interface Named { name: string }
+    function greet(value: Named): string { return `${Greeting.Hello} ${value.name}` }
+    export function run(): string { return greet({ name: 'world' } satisfies Named) }";
+
+    let js = crate::strip_types(ts, Path::new("app.ts"))?;
+    assert_eq!("hello world", run_script(&js, &Options::default()).await?);
+
+    assert!(crate::strip_types("let x: = 1", Path::new("app.ts")).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,330 @@
+//! Property tests which generate random WIT worlds, along with scripts which
+//! echo values of the generated types through both an import and an export,
+//! and then verify that random values of those types round-trip intact.
+
+use {
+    super::{ENGINE, SEED, store},
+    crate::{Componentizer, Wit},
+    proptest::{
+        prelude::{Just, Strategy, any},
+        prop_oneof,
+        strategy::BoxedStrategy,
+        test_runner::{self, TestRng, TestRunner},
+    },
+    std::env,
+    tokio::runtime::Runtime,
+    wasmtime::component::{Component, Linker, Val},
+};
+
+/// Default number of worlds to generate; override using the
+/// `COMPONENTIZE_JS_GENERATED_CASES` environment variable.
+///
+/// Note that each world requires a full `componentize` run, so this is much
+/// smaller than the usual `proptest` default.
+const DEFAULT_CASES: u32 = 8;
+const VALUES_PER_WORLD: usize = 16;
+const MAX_PARAMS: usize = 4;
+const MAX_LIST_SIZE: usize = 20;
+
+#[derive(Debug, Clone)]
+enum Ty {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    S8,
+    S16,
+    S32,
+    S64,
+    Char,
+    String,
+    Enum(usize),
+    Flags(usize),
+    List(Box<Ty>),
+    Option(Box<Ty>),
+    Result(Option<Box<Ty>>, Option<Box<Ty>>),
+    Tuple(Vec<Ty>),
+    Record(Vec<Ty>),
+    Variant(Vec<Option<Ty>>),
+}
+
+fn ty() -> impl Strategy<Value = Ty> {
+    let leaf = prop_oneof![
+        Just(Ty::Bool),
+        Just(Ty::U8),
+        Just(Ty::U16),
+        Just(Ty::U32),
+        Just(Ty::U64),
+        Just(Ty::S8),
+        Just(Ty::S16),
+        Just(Ty::S32),
+        Just(Ty::S64),
+        Just(Ty::Char),
+        Just(Ty::String),
+        (1..5_usize).prop_map(Ty::Enum),
+        (1..9_usize).prop_map(Ty::Flags),
+    ];
+
+    leaf.prop_recursive(5, 48, 4, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|ty| Ty::List(Box::new(ty))),
+            inner.clone().prop_map(|ty| Ty::Option(Box::new(ty))),
+            (
+                proptest::option::of(inner.clone()),
+                proptest::option::of(inner.clone())
+            )
+                .prop_map(|(ok, err)| Ty::Result(ok.map(Box::new), err.map(Box::new))),
+            proptest::collection::vec(inner.clone(), 1..4).prop_map(Ty::Tuple),
+            proptest::collection::vec(inner.clone(), 1..4).prop_map(Ty::Record),
+            proptest::collection::vec(proptest::option::of(inner), 1..4).prop_map(Ty::Variant),
+        ]
+    })
+}
+
+fn value(ty: &Ty) -> BoxedStrategy<Val> {
+    match ty {
+        Ty::Bool => any::<bool>().prop_map(Val::Bool).boxed(),
+        Ty::U8 => any::<u8>().prop_map(Val::U8).boxed(),
+        Ty::U16 => any::<u16>().prop_map(Val::U16).boxed(),
+        Ty::U32 => any::<u32>().prop_map(Val::U32).boxed(),
+        Ty::U64 => any::<u64>().prop_map(Val::U64).boxed(),
+        Ty::S8 => any::<i8>().prop_map(Val::S8).boxed(),
+        Ty::S16 => any::<i16>().prop_map(Val::S16).boxed(),
+        Ty::S32 => any::<i32>().prop_map(Val::S32).boxed(),
+        Ty::S64 => any::<i64>().prop_map(Val::S64).boxed(),
+        Ty::Char => proptest::char::any().prop_map(Val::Char).boxed(),
+        Ty::String => proptest::string::string_regex(".{0,32}")
+            .unwrap()
+            .prop_map(Val::String)
+            .boxed(),
+        &Ty::Enum(count) => (0..count).prop_map(|i| Val::Enum(format!("e{i}"))).boxed(),
+        &Ty::Flags(count) => proptest::collection::vec(any::<bool>(), count)
+            .prop_map(|bits| {
+                Val::Flags(
+                    bits.into_iter()
+                        .enumerate()
+                        .filter(|&(_, bit)| bit)
+                        .map(|(i, _)| format!("g{i}"))
+                        .collect(),
+                )
+            })
+            .boxed(),
+        Ty::List(ty) => proptest::collection::vec(value(ty), 0..MAX_LIST_SIZE)
+            .prop_map(Val::List)
+            .boxed(),
+        Ty::Option(ty) => proptest::option::of(value(ty))
+            .prop_map(|v| Val::Option(v.map(Box::new)))
+            .boxed(),
+        Ty::Result(ok, err) => {
+            let payload = |ty: &Option<Box<Ty>>| match ty {
+                Some(ty) => value(ty).prop_map(|v| Some(Box::new(v))).boxed(),
+                None => Just(None).boxed(),
+            };
+            prop_oneof![
+                payload(ok).prop_map(|v| Val::Result(Ok(v))),
+                payload(err).prop_map(|v| Val::Result(Err(v))),
+            ]
+            .boxed()
+        }
+        Ty::Tuple(types) => types
+            .iter()
+            .map(value)
+            .collect::<Vec<_>>()
+            .prop_map(Val::Tuple)
+            .boxed(),
+        Ty::Record(types) => types
+            .iter()
+            .map(value)
+            .collect::<Vec<_>>()
+            .prop_map(|values| {
+                Val::Record(
+                    values
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, v)| (format!("f{i}"), v))
+                        .collect(),
+                )
+            })
+            .boxed(),
+        Ty::Variant(cases) => {
+            let cases = cases.clone();
+            (0..cases.len())
+                .prop_flat_map(move |i| match &cases[i] {
+                    Some(ty) => value(ty)
+                        .prop_map(move |v| Val::Variant(format!("c{i}"), Some(Box::new(v))))
+                        .boxed(),
+                    None => Just(Val::Variant(format!("c{i}"), None)).boxed(),
+                })
+                .boxed()
+        }
+    }
+}
+
+fn define(definitions: &mut Vec<String>, kind: &str, body: &[String]) -> String {
+    let name = format!("t{}", definitions.len());
+    definitions.push(format!("{kind} {name} {{ {} }}", body.join(", ")));
+    name
+}
+
+/// Render `ty` as WIT, adding definitions for any named types it uses to
+/// `definitions`.
+fn render(ty: &Ty, definitions: &mut Vec<String>) -> String {
+    match ty {
+        Ty::Bool => "bool".into(),
+        Ty::U8 => "u8".into(),
+        Ty::U16 => "u16".into(),
+        Ty::U32 => "u32".into(),
+        Ty::U64 => "u64".into(),
+        Ty::S8 => "s8".into(),
+        Ty::S16 => "s16".into(),
+        Ty::S32 => "s32".into(),
+        Ty::S64 => "s64".into(),
+        Ty::Char => "char".into(),
+        Ty::String => "string".into(),
+        &Ty::Enum(count) => {
+            let cases = (0..count).map(|i| format!("e{i}")).collect::<Vec<_>>();
+            define(definitions, "enum", &cases)
+        }
+        &Ty::Flags(count) => {
+            let flags = (0..count).map(|i| format!("g{i}")).collect::<Vec<_>>();
+            define(definitions, "flags", &flags)
+        }
+        Ty::List(ty) => format!("list<{}>", render(ty, definitions)),
+        Ty::Option(ty) => format!("option<{}>", render(ty, definitions)),
+        Ty::Result(ok, err) => {
+            let ok = ok.as_ref().map(|ty| render(ty, definitions));
+            let err = err.as_ref().map(|ty| render(ty, definitions));
+            match (ok, err) {
+                (None, None) => "result".into(),
+                (Some(ok), None) => format!("result<{ok}>"),
+                (None, Some(err)) => format!("result<_, {err}>"),
+                (Some(ok), Some(err)) => format!("result<{ok}, {err}>"),
+            }
+        }
+        Ty::Tuple(types) => {
+            let types = types
+                .iter()
+                .map(|ty| render(ty, definitions))
+                .collect::<Vec<_>>();
+            format!("tuple<{}>", types.join(", "))
+        }
+        Ty::Record(types) => {
+            let fields = types
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| format!("f{i}: {}", render(ty, definitions)))
+                .collect::<Vec<_>>();
+            define(definitions, "record", &fields)
+        }
+        Ty::Variant(cases) => {
+            let cases = cases
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| match ty {
+                    Some(ty) => format!("c{i}({})", render(ty, definitions)),
+                    None => format!("c{i}"),
+                })
+                .collect::<Vec<_>>();
+            define(definitions, "variant", &cases)
+        }
+    }
+}
+
+/// Generate a world which imports and exports an `echo` function taking
+/// parameters of the specified types and returning them as a tuple.
+fn world(types: &[Ty]) -> String {
+    let mut definitions = Vec::new();
+    let types = types
+        .iter()
+        .map(|ty| render(ty, &mut definitions))
+        .collect::<Vec<_>>();
+    let params = types
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| format!("v{i}: {ty}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let results = types.join(", ");
+    let definitions = definitions.join("\n  ");
+
+    format!(
+        "package componentize-js:generated;
+
+world generated {{
+  {definitions}
+
+  import echo-import: func({params}) -> tuple<{results}>;
+  export echo: func({params}) -> tuple<{results}>;
+}}"
+    )
+}
+
+const SCRIPT: &str = "import { echoImport } from 'wit-world'
+
+export function echo(...args) {
+    return echoImport(...args)
+}";
+
+async fn test(types: &[Ty], values: &[Vec<Val>]) -> anyhow::Result<()> {
+    let wit = world(types);
+    let component = Componentizer::new(Wit::<String>::String(&wit), SCRIPT)
+        .componentize()
+        .await?;
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    linker
+        .root()
+        .func_new("echo-import", |_, _, params, results| {
+            results[0] = Val::Tuple(params.to_vec());
+            Ok(())
+        })?;
+    let pre = linker.instantiate_pre(&Component::new(&ENGINE, &component)?)?;
+
+    for params in values {
+        let mut store = store();
+        let instance = pre.instantiate_async(&mut store).await?;
+        let echo = instance.get_func(&mut store, "echo").unwrap();
+        let mut results = [Val::Bool(false)];
+        echo.call_async(&mut store, params, &mut results).await?;
+
+        assert_eq!(
+            Val::Tuple(params.clone()),
+            results[0],
+            "round trip failed for world:\n{wit}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn generated_worlds() -> anyhow::Result<()> {
+    let cases = if let Ok(cases) = env::var("COMPONENTIZE_JS_GENERATED_CASES") {
+        cases.parse()?
+    } else {
+        DEFAULT_CASES
+    };
+
+    let strategy = proptest::collection::vec(ty(), 1..=MAX_PARAMS).prop_flat_map(|types| {
+        let values = proptest::collection::vec(
+            types.iter().map(value).collect::<Vec<_>>(),
+            1..=VALUES_PER_WORLD,
+        );
+        (Just(types), values)
+    });
+
+    let runtime = Runtime::new()?;
+    let config = test_runner::Config {
+        cases,
+        ..test_runner::Config::default()
+    };
+    let algorithm = config.rng_algorithm;
+    let mut runner = TestRunner::new_with_rng(config, TestRng::from_seed(algorithm, &*SEED));
+
+    Ok(runner.run(&strategy, move |(types, values)| {
+        runtime.block_on(test(&types, &values)).unwrap();
+        Ok(())
+    })?)
+}
@@ -0,0 +1,693 @@
+//! Generation of TypeScript declarations for a WIT world, describing the
+//! modules available to a script for the world's imports and the shape of the
+//! exports it must provide, using the same naming conventions as `codegen.rs`
+//! and the runtime.
+
+use {
+    crate::{
+        ExportLayout,
+        codegen::{export_key, mangle_name},
+    },
+    heck::{ToLowerCamelCase as _, ToUpperCamelCase as _},
+    indexmap::IndexMap,
+    std::collections::HashMap,
+    wit_parser::{
+        Function, FunctionKind, Handle, Resolve, Type, TypeDefKind, TypeId, WorldId, WorldItem,
+    },
+};
+
+/// Declarations for the stream and future objects created by the runtime,
+/// which are placed in the `wit-world` module.
+const STREAMS_AND_FUTURES: &str = "    export interface StreamReader<T> {
+        read(count: number): Promise<ArrayLike<T>>;
+        readonly writerDropped: boolean;
+        [Symbol.asyncIterator](): AsyncIterator<ArrayLike<T>>;
+        toReadableStream(count?: number): ReadableStream<ArrayLike<T>>;
+        [Symbol.dispose](): void;
+    }
+    export interface StreamWriter<T> {
+        write(values: ArrayLike<T>): Promise<number>;
+        writeAll(values: ArrayLike<T>): Promise<number>;
+        readonly readerDropped: boolean;
+        toWritableStream(): WritableStream<ArrayLike<T>>;
+        [Symbol.dispose](): void;
+    }
+    export interface FutureReader<T> {
+        read(): Promise<T>;
+        toPromise(): Promise<T>;
+        [Symbol.dispose](): void;
+    }
+    export interface FutureWriter<T> {
+        write(value: T): Promise<boolean>;
+        [Symbol.dispose](): void;
+    }
+";
+
+const FS_MODULE: &str = "    export interface Stats {
+        nlink: number;
+        size: number;
+        atimeMs: number;
+        mtimeMs: number;
+        ctimeMs: number;
+        atime: Date;
+        mtime: Date;
+        ctime: Date;
+        isFile(): boolean;
+        isDirectory(): boolean;
+        isSymbolicLink(): boolean;
+    }
+    export interface Dirent {
+        name: string;
+        parentPath: string;
+        isFile(): boolean;
+        isDirectory(): boolean;
+        isSymbolicLink(): boolean;
+    }
+    type Encoding = 'utf8' | 'utf-8';
+    export function readFile(path: string, options?: { encoding?: null; signal?: AbortSignal }): Promise<Uint8Array>;
+    export function readFile(path: string, options: Encoding | { encoding: Encoding; signal?: AbortSignal }): Promise<string>;
+    export function writeFile(path: string, data: string | ArrayBuffer | ArrayBufferView, options?: Encoding | { encoding?: Encoding | null; flag?: 'w' | 'a' }): Promise<void>;
+    export function readdir(path: string, options?: { withFileTypes?: false }): Promise<string[]>;
+    export function readdir(path: string, options: { withFileTypes: true }): Promise<Dirent[]>;
+    export function stat(path: string): Promise<Stats>;
+    export function mkdir(path: string, options?: { recursive?: boolean }): Promise<string | undefined>;
+";
+
+const SOCKETS_MODULE: &str = "    export interface SocketAddress {
+        hostname: string;
+        port: number;
+    }
+    export interface TcpConnection {
+        readonly readable: ReadableStream<Uint8Array>;
+        readonly writable: WritableStream<Uint8Array>;
+        readonly localAddress: SocketAddress;
+        readonly remoteAddress: SocketAddress;
+        close(): Promise<void>;
+    }
+    export interface TcpListener extends AsyncIterable<TcpConnection> {
+        readonly localAddress: SocketAddress;
+        accept(): Promise<TcpConnection>;
+        close(): void;
+    }
+    export interface DatagramSocket {
+        readonly localAddress: SocketAddress;
+        connect(address: SocketAddress): Promise<void>;
+        send(data: string | ArrayBuffer | ArrayBufferView, to?: SocketAddress): Promise<void>;
+        receive(): Promise<{ data: Uint8Array; remoteAddress: SocketAddress }>;
+        close(): void;
+    }
+    export function connect(address: SocketAddress): Promise<TcpConnection>;
+    export function listen(address?: Partial<SocketAddress>): Promise<TcpListener>;
+    export function bindDatagram(address?: Partial<SocketAddress>): Promise<DatagramSocket>;
+";
+
+/// Words which can't be used as parameter names in TypeScript.
+const RESERVED: &[&str] = &[
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "new",
+    "null",
+    "return",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+];
+
+struct Generator<'a> {
+    resolve: &'a Resolve,
+    /// How each resource type is referred to, keyed by type ID.
+    resources: HashMap<TypeId, String>,
+}
+
+/// Generate `imports.d.ts` and `exports.d.ts` for the specified world,
+/// returning each file's name and contents.
+///
+/// `imports.d.ts` declares one module per imported interface (named using the
+/// interface ID, e.g. `wasi:clocks/wall-clock@0.2.0`) plus `wit-world`, which
+/// holds world-level imports.  `exports.d.ts` declares an `Exports` interface
+/// describing the bindings the script must export, named according to
+/// `export_aliases` and `export_layout`.
+pub fn generate(
+    resolve: &Resolve,
+    world: WorldId,
+    export_aliases: &[(String, String)],
+    export_layout: ExportLayout,
+) -> Vec<(String, String)> {
+    let world = &resolve.worlds[world];
+    let mut generator = Generator {
+        resolve,
+        resources: HashMap::new(),
+    };
+
+    // First, decide how to refer to each resource type: imported resources are
+    // classes exported by the module for their interface, while exported
+    // resources are described by interfaces in `exports.d.ts`.
+
+    for (key, item) in &world.imports {
+        match item {
+            WorldItem::Interface { id, .. } => {
+                let module = resolve.name_world_key(key);
+                for (name, &ty) in &resolve.interfaces[*id].types {
+                    if let TypeDefKind::Resource = resolve.types[ty].kind {
+                        generator.resources.insert(
+                            ty,
+                            format!("import({}).{}", quote(&module), name.to_upper_camel_case()),
+                        );
+                    }
+                }
+            }
+            WorldItem::Type { id, .. } => {
+                let ty = &resolve.types[*id];
+                if let (TypeDefKind::Resource, Some(name)) = (&ty.kind, &ty.name) {
+                    generator.resources.insert(
+                        *id,
+                        format!("import('wit-world').{}", name.to_upper_camel_case()),
+                    );
+                }
+            }
+            WorldItem::Function(_) => {}
+        }
+    }
+
+    for (key, item) in &world.exports {
+        if let WorldItem::Interface { id, .. } = item {
+            let interface = resolve.name_world_key(key);
+            for (name, &ty) in &resolve.interfaces[*id].types {
+                if let TypeDefKind::Resource = resolve.types[ty].kind {
+                    generator.resources.insert(
+                        ty,
+                        format!("{}_{name}", mangle_name(&interface)).to_upper_camel_case(),
+                    );
+                }
+            }
+        }
+    }
+
+    // Next, generate a module for each imported interface, plus `wit-world`.
+
+    let mut modules = IndexMap::new();
+    let mut world_resources = Vec::new();
+    let mut world_functions = Vec::new();
+    for (key, item) in &world.imports {
+        match item {
+            WorldItem::Interface { id, .. } => {
+                let interface = &resolve.interfaces[*id];
+                modules.insert(
+                    resolve.name_world_key(key),
+                    generator.import_module(
+                        interface
+                            .types
+                            .values()
+                            .copied()
+                            .filter(|&ty| generator.resources.contains_key(&ty)),
+                        interface.functions.values(),
+                    ),
+                );
+            }
+            WorldItem::Type { id, .. } => {
+                if generator.resources.contains_key(id) {
+                    world_resources.push(*id);
+                }
+            }
+            WorldItem::Function(function) => world_functions.push(function),
+        }
+    }
+
+    let world_module = format!(
+        "{STREAMS_AND_FUTURES}{}",
+        generator.import_module(world_resources.into_iter(), world_functions.into_iter())
+    );
+
+    // If the world imports `wasi:http/types@0.3.x`, the runtime also provides
+    // `componentize-js:http` (see `codegen::http_bindings`).
+    let http_module = modules
+        .keys()
+        .find(|name| name.starts_with("wasi:http/types@0.3."))
+        .map(|types| {
+            let types = quote(types);
+            (
+                "componentize-js:http".to_string(),
+                format!(
+                    "    export function fromWasiRequest(request: import({types}).Request): Request;
+    export function toWasiResponse(response: Response): import({types}).Response;
+"
+                ),
+            )
+        });
+
+    // Likewise for `wasi:filesystem/preopens@0.3.x` and `componentize-js:fs`
+    // (see `codegen::fs_bindings`).
+    let fs_module = modules
+        .keys()
+        .any(|name| name.starts_with("wasi:filesystem/preopens@0.3."))
+        .then(|| ("componentize-js:fs".to_string(), FS_MODULE.to_string()));
+
+    // Likewise for `wasi:sockets/types@0.3.x` and `componentize-js:sockets`
+    // (see `codegen::sockets_bindings`).
+    let sockets_module = modules
+        .keys()
+        .any(|name| name.starts_with("wasi:sockets/types@0.3."))
+        .then(|| {
+            (
+                "componentize-js:sockets".to_string(),
+                SOCKETS_MODULE.to_string(),
+            )
+        });
+
+    let imports = [("wit-world".to_string(), world_module)]
+        .into_iter()
+        .chain(modules)
+        .chain(http_module)
+        .chain(fs_module)
+        .chain(sockets_module)
+        .map(|(name, body)| format!("declare module {} {{\n{body}}}\n", quote(&name)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // Finally, generate the `Exports` interface, plus an interface for
+    // instances of each exported resource.
+
+    let alias = |name: &str| {
+        export_aliases
+            .iter()
+            .find(|(wit_name, _)| wit_name == name)
+            .map(|(_, js_name)| js_name.clone())
+    };
+
+    let mut instances = String::new();
+    let mut members = String::new();
+    for (key, item) in &world.exports {
+        match item {
+            WorldItem::Interface { id, .. } => {
+                let interface_name = resolve.name_world_key(key);
+                let name = alias(&interface_name).unwrap_or_else(|| match export_layout {
+                    ExportLayout::Flat => mangle_name(&interface_name),
+                    ExportLayout::Qualified => interface_name.clone(),
+                });
+                let interface = &resolve.interfaces[*id];
+
+                let mut classes = IndexMap::<_, Vec<_>>::new();
+                let mut methods = IndexMap::<_, Vec<_>>::new();
+                for &ty in interface.types.values() {
+                    if let TypeDefKind::Resource = resolve.types[ty].kind {
+                        classes.insert(ty, Vec::new());
+                        methods.insert(ty, Vec::new());
+                    }
+                }
+
+                let mut fields = String::new();
+                for function in interface.functions.values() {
+                    match function.kind {
+                        FunctionKind::Freestanding | FunctionKind::AsyncFreestanding => {
+                            fields.push_str(&format!(
+                                "        {}{};\n",
+                                function.name.to_lower_camel_case(),
+                                generator.signature(function, false)
+                            ));
+                        }
+                        FunctionKind::Constructor(ty) => {
+                            classes[&ty]
+                                .push(format!("new {}", generator.signature(function, false)));
+                        }
+                        FunctionKind::Method(ty) | FunctionKind::AsyncMethod(ty) => {
+                            methods[&ty].push(format!(
+                                "{}{}",
+                                function.item_name().to_lower_camel_case(),
+                                generator.signature(function, false)
+                            ));
+                        }
+                        FunctionKind::Static(ty) | FunctionKind::AsyncStatic(ty) => {
+                            classes[&ty].push(format!(
+                                "{}{}",
+                                function.item_name().to_lower_camel_case(),
+                                generator.signature(function, false)
+                            ));
+                        }
+                    }
+                }
+
+                for (ty, statics) in classes {
+                    let name = resolve.types[ty].name.as_deref().unwrap();
+                    let statics = statics
+                        .into_iter()
+                        .map(|s| format!("            {s};\n"))
+                        .collect::<String>();
+                    fields.push_str(&format!(
+                        "        {}: {{\n{statics}        }};\n",
+                        name.to_upper_camel_case()
+                    ));
+                }
+
+                for (ty, methods) in methods {
+                    let methods = methods
+                        .into_iter()
+                        .map(|m| format!("    {m};\n"))
+                        .collect::<String>();
+                    instances.push_str(&format!(
+                        "export interface {} {{\n{methods}    [Symbol.dispose]?(): void;\n}}\n\n",
+                        generator.resources[&ty]
+                    ));
+                }
+
+                members.push_str(&format!("    {}: {{\n{fields}    }};\n", export_key(&name)));
+            }
+            WorldItem::Function(function) => {
+                let name =
+                    alias(&function.name).unwrap_or_else(|| function.name.to_lower_camel_case());
+                members.push_str(&format!(
+                    "    {}{};\n",
+                    export_key(&name),
+                    generator.signature(function, false)
+                ));
+            }
+            WorldItem::Type { .. } => {}
+        }
+    }
+
+    let exports = format!(
+        "// The bindings the script must export, e.g. `export const foo = {{ ... }}\n\
+         // satisfies Exports['foo']`.\n\n\
+         {instances}export interface Exports {{\n{members}}}\n"
+    );
+
+    vec![
+        ("imports.d.ts".to_string(), imports),
+        ("exports.d.ts".to_string(), exports),
+    ]
+}
+
+impl Generator<'_> {
+    /// Generate the body of the module for an imported interface (or for
+    /// world-level imports).
+    fn import_module<'b>(
+        &self,
+        resources: impl Iterator<Item = TypeId>,
+        functions: impl Iterator<Item = &'b Function>,
+    ) -> String {
+        let mut classes = resources
+            .map(|ty| (ty, Vec::new()))
+            .collect::<IndexMap<_, _>>();
+        let mut body = String::new();
+
+        for function in functions {
+            match function.kind {
+                FunctionKind::Freestanding | FunctionKind::AsyncFreestanding => {
+                    body.push_str(&format!(
+                        "    export function {}{};\n",
+                        function.name.to_lower_camel_case(),
+                        self.signature(function, true)
+                    ));
+                }
+                FunctionKind::Constructor(ty) => {
+                    classes[&ty].push(format!("constructor{}", self.params(function, true)));
+                }
+                FunctionKind::Method(ty) | FunctionKind::AsyncMethod(ty) => {
+                    classes[&ty].push(format!(
+                        "{}{}",
+                        function.item_name().to_lower_camel_case(),
+                        self.signature(function, true)
+                    ));
+                }
+                FunctionKind::Static(ty) | FunctionKind::AsyncStatic(ty) => {
+                    classes[&ty].push(format!(
+                        "static {}{}",
+                        function.item_name().to_lower_camel_case(),
+                        self.signature(function, true)
+                    ));
+                }
+            }
+        }
+
+        for (ty, members) in classes {
+            let name = self.resolve.types[ty].name.as_deref().unwrap();
+            let members = members
+                .into_iter()
+                .chain(Some("[Symbol.dispose](): void".to_string()))
+                .map(|m| format!("        {m};\n"))
+                .collect::<String>();
+            body.push_str(&format!(
+                "    export class {} {{\n{members}    }}\n",
+                name.to_upper_camel_case()
+            ));
+        }
+
+        body
+    }
+
+    /// Render the parameter list of `function`, omitting `self` for methods.
+    ///
+    /// Parameters of imported functions are lowered by the runtime, while those
+    /// of exported functions are lifted.
+    fn params(&self, function: &Function, import: bool) -> String {
+        let skip = match function.kind {
+            FunctionKind::Method(_) | FunctionKind::AsyncMethod(_) => 1,
+            _ => 0,
+        };
+        let params = function
+            .params
+            .iter()
+            .skip(skip)
+            .map(|(name, ty)| {
+                let mut name = name.to_lower_camel_case();
+                if RESERVED.contains(&name.as_str()) {
+                    name.push('_');
+                }
+                format!("{name}: {}", self.ty(ty, import))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("({params})")
+    }
+
+    /// Render the parameter list and return type of `function`.
+    ///
+    /// A `result` return type is rendered as its `ok` type, since the `err`
+    /// case is represented by a thrown `ComponentError`.
+    fn signature(&self, function: &Function, import: bool) -> String {
+        let result = function
+            .result
+            .as_ref()
+            .and_then(|ty| match self.dealias(ty) {
+                Type::Id(id) => match &self.resolve.types[*id].kind {
+                    TypeDefKind::Result(result) => result.ok.as_ref(),
+                    _ => Some(ty),
+                },
+                _ => Some(ty),
+            })
+            .map(|ty| self.ty(ty, !import))
+            .unwrap_or_else(|| "void".into());
+
+        let result = match (function.kind.is_async(), import) {
+            (true, true) => format!(
+                "Promise<{result}> & {{ cancel(): void; \
+                 cancelOn(signal: AbortSignal): Promise<{result}> }}"
+            ),
+            (true, false) => format!("Promise<{result}>"),
+            (false, _) => result,
+        };
+
+        format!("{}: {result}", self.params(function, import))
+    }
+
+    /// Follow type aliases (e.g. `type foo = bar`) to the underlying type.
+    fn dealias<'b>(&'b self, mut ty: &'b Type) -> &'b Type {
+        while let Type::Id(id) = ty
+            && let TypeDefKind::Type(inner) = &self.resolve.types[*id].kind
+        {
+            ty = inner;
+        }
+        ty
+    }
+
+    /// Render `ty` as a TypeScript type.
+    ///
+    /// If `lowered` is true, the value is passed from JS to the runtime, which
+    /// accepts more representations (e.g. `null` for `none`) than it produces.
+    fn ty(&self, ty: &Type, lowered: bool) -> String {
+        match ty {
+            Type::Bool => "boolean".into(),
+            Type::U8
+            | Type::U16
+            | Type::U32
+            | Type::S8
+            | Type::S16
+            | Type::S32
+            | Type::F32
+            | Type::F64 => "number".into(),
+            Type::U64 | Type::S64 => "bigint | number".into(),
+            Type::Char | Type::String => "string".into(),
+            Type::ErrorContext => "unknown".into(),
+            Type::Id(id) => self.ty_def(*id, lowered),
+        }
+    }
+
+    fn ty_def(&self, id: TypeId, lowered: bool) -> String {
+        let optional = |ty: &Option<Type>| {
+            ty.as_ref()
+                .map(|ty| self.ty(ty, lowered))
+                .unwrap_or_else(|| "void".into())
+        };
+        let tagged = |tag: &str, ty: &Option<Type>| match ty {
+            Some(ty) => format!("{{ tag: '{tag}'; val: {} }}", self.ty(ty, lowered)),
+            None => format!("{{ tag: '{tag}' }}"),
+        };
+
+        match &self.resolve.types[id].kind {
+            TypeDefKind::Type(ty) => self.ty(ty, lowered),
+            TypeDefKind::Record(record) => format!(
+                "{{ {} }}",
+                record
+                    .fields
+                    .iter()
+                    .map(|field| format!(
+                        "{}: {};",
+                        mangle_name(&field.name),
+                        self.ty(&field.ty, lowered)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            TypeDefKind::Resource => self.resource(id),
+            TypeDefKind::Handle(Handle::Own(ty) | Handle::Borrow(ty)) => self.resource(*ty),
+            TypeDefKind::Flags(flags) => {
+                let object = format!(
+                    "{{ {} }}",
+                    flags
+                        .flags
+                        .iter()
+                        .map(|flag| format!("{}?: boolean;", mangle_name(&flag.name)))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                );
+                if lowered {
+                    let names = flags
+                        .flags
+                        .iter()
+                        .map(|flag| format!("'{}'", flag.name))
+                        .collect::<Vec<_>>()
+                        .join(" | ");
+                    format!("{object} | Array<{names}>")
+                } else {
+                    object
+                }
+            }
+            TypeDefKind::Tuple(tuple) => format!(
+                "[{}]",
+                tuple
+                    .types
+                    .iter()
+                    .map(|ty| self.ty(ty, lowered))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            TypeDefKind::Variant(variant) => variant
+                .cases
+                .iter()
+                .map(|case| tagged(&case.name, &case.ty))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            TypeDefKind::Enum(enum_) => enum_
+                .cases
+                .iter()
+                .map(|case| format!("'{}'", case.name))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            TypeDefKind::Option(ty) => {
+                let nested = matches!(
+                    self.dealias(ty),
+                    Type::Id(id) if matches!(self.resolve.types[*id].kind, TypeDefKind::Option(_))
+                );
+                let some = if nested {
+                    tagged("some", &Some(*ty))
+                } else {
+                    self.ty(ty, lowered)
+                };
+                if lowered {
+                    format!("{some} | null | undefined")
+                } else {
+                    format!("{some} | undefined")
+                }
+            }
+            TypeDefKind::Result(result) => format!(
+                "{} | {}",
+                tagged("ok", &result.ok),
+                tagged("err", &result.err)
+            ),
+            TypeDefKind::List(ty) => match (typed_array(self.dealias(ty)), lowered) {
+                (Some(class), true) => {
+                    format!("{class} | ArrayBuffer | Array<{}>", self.ty(ty, lowered))
+                }
+                (Some(class), false) => class.into(),
+                (None, _) => format!("Array<{}>", self.ty(ty, lowered)),
+            },
+            TypeDefKind::Future(ty) => {
+                format!("import('wit-world').FutureReader<{}>", optional(ty))
+            }
+            TypeDefKind::Stream(ty) => {
+                format!("import('wit-world').StreamReader<{}>", optional(ty))
+            }
+            _ => "unknown".into(),
+        }
+    }
+
+    fn resource(&self, id: TypeId) -> String {
+        let mut id = id;
+        while let TypeDefKind::Type(Type::Id(inner)) = self.resolve.types[id].kind {
+            id = inner;
+        }
+        self.resources
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| "object".into())
+    }
+}
+
+/// Return the name of the typed array class the runtime uses for lists of
+/// `ty`, if any.
+fn typed_array(ty: &Type) -> Option<&'static str> {
+    Some(match ty {
+        Type::U8 => "Uint8Array",
+        Type::S8 => "Int8Array",
+        Type::U16 => "Uint16Array",
+        Type::S16 => "Int16Array",
+        Type::U32 => "Uint32Array",
+        Type::S32 => "Int32Array",
+        Type::U64 => "BigUint64Array",
+        Type::S64 => "BigInt64Array",
+        Type::F32 => "Float32Array",
+        Type::F64 => "Float64Array",
+        _ => return None,
+    })
+}
+
+fn quote(name: &str) -> String {
+    format!("'{name}'")
+}
@@ -0,0 +1,75 @@
+//! Checks applied to generated components by the `validate` subcommand.
+
+use {
+    crate::manifest::{Item, World},
+    wasmparser::{Validator, WasmFeatures},
+    wit_component::DecodedWasm,
+    wit_parser::{Resolve, WorldId},
+};
+
+/// Check that `component` is a valid component which targets the specified
+/// world, returning a description of each problem found.
+///
+/// The component must export exactly the items the world exports, with the
+/// same signatures.  It may import anything the world imports, plus (unless
+/// `strict_imports` is set) WASI interfaces used by the runtime.
+pub fn validate(
+    resolve: &Resolve,
+    world: WorldId,
+    component: &[u8],
+    strict_imports: bool,
+) -> Vec<String> {
+    if let Err(e) = Validator::new_with_features(WasmFeatures::all()).validate_all(component) {
+        return vec![format!("component is not valid: {e}")];
+    }
+
+    let (actual_resolve, actual_world) = match wit_component::decode(component) {
+        Ok(DecodedWasm::Component(resolve, world)) => (resolve, world),
+        Ok(DecodedWasm::WitPackage(..)) => {
+            return vec!["expected a component; got a WIT package".into()];
+        }
+        Err(e) => return vec![format!("unable to decode component type: {e:#}")],
+    };
+
+    let expected = World::new(resolve, world);
+    let actual = World::new(&actual_resolve, actual_world);
+
+    let mut problems = Vec::new();
+
+    for item in expected.exports() {
+        match find(actual.exports(), item.name()) {
+            None => problems.push(format!("missing export: `{}`", item.name())),
+            Some(actual) if actual != item => problems.push(format!(
+                "export `{}` does not match the world: expected {}; got {}",
+                item.name(),
+                serde_json::to_string(item).unwrap(),
+                serde_json::to_string(actual).unwrap()
+            )),
+            Some(_) => {}
+        }
+    }
+
+    for item in actual.exports() {
+        if find(expected.exports(), item.name()).is_none() {
+            problems.push(format!("unexpected export: `{}`", item.name()));
+        }
+    }
+
+    for item in actual.imports() {
+        if let Item::Type { .. } = item {
+            continue;
+        }
+        let name = item.name();
+        if find(expected.imports(), name).is_none()
+            && (strict_imports || !name.starts_with("wasi:"))
+        {
+            problems.push(format!("unexpected import: `{name}`"));
+        }
+    }
+
+    problems
+}
+
+fn find<'a>(items: &'a [Item], name: &str) -> Option<&'a Item> {
+    items.iter().find(|item| item.name() == name)
+}
@@ -0,0 +1,273 @@
+//! Build-time virtualization of selected WASI imports.
+//!
+//! This works by rewriting the `wasi_snapshot_preview1` adapter such that calls
+//! to the virtualized WASI 0.2 functions are redirected to functions defined in
+//! the adapter itself, which return fixed values.  `wit-component` then removes
+//! the no-longer-used imports when linking, so the generated component does not
+//! import them at all.
+
+use {
+    anyhow::{Context as _, anyhow},
+    std::{collections::HashMap, convert::Infallible},
+    wasm_encoder::{
+        CodeSection, Function, FunctionSection, ImportSection, Instruction, MemArg, ValType,
+        reencode::{self, Reencode},
+    },
+};
+
+/// WASI imports to replace with fixed implementations.
+#[derive(Clone, Debug, Default)]
+pub struct Virtualization<'a> {
+    /// Environment variables to return from
+    /// `wasi:cli/environment#get-environment`, if virtualized.
+    pub env: Option<&'a [(String, String)]>,
+    /// Time (in seconds since the Unix epoch) to return from
+    /// `wasi:clocks/wall-clock#now`, if virtualized.  The monotonic clock is
+    /// virtualized along with it and always returns zero.
+    pub clock: Option<u64>,
+}
+
+impl Virtualization<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.env.is_none() && self.clock.is_none()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Replacement {
+    GetEnvironment,
+    WallClockNow,
+    WallClockResolution,
+    MonotonicClockNow,
+    MonotonicClockResolution,
+}
+
+impl Replacement {
+    fn find(virtualization: &Virtualization, module: &str, name: &str) -> Option<Self> {
+        let (interface, _version) = module.split_once('@')?;
+        match (interface, name) {
+            ("wasi:cli/environment", "get-environment") if virtualization.env.is_some() => {
+                Some(Self::GetEnvironment)
+            }
+            ("wasi:clocks/wall-clock", "now") if virtualization.clock.is_some() => {
+                Some(Self::WallClockNow)
+            }
+            ("wasi:clocks/wall-clock", "resolution") if virtualization.clock.is_some() => {
+                Some(Self::WallClockResolution)
+            }
+            ("wasi:clocks/monotonic-clock", "now") if virtualization.clock.is_some() => {
+                Some(Self::MonotonicClockNow)
+            }
+            ("wasi:clocks/monotonic-clock", "resolution") if virtualization.clock.is_some() => {
+                Some(Self::MonotonicClockResolution)
+            }
+            _ => None,
+        }
+    }
+}
+
+struct Virtualizer<'a> {
+    virtualization: &'a Virtualization<'a>,
+    /// Number of functions imported by the adapter.
+    imported: u32,
+    /// Number of functions defined by the adapter.
+    defined: u32,
+    /// Imported functions to replace, keyed by function index.
+    replaced: HashMap<u32, (u32, Replacement)>,
+    /// The order in which replacement functions are appended.
+    order: Vec<u32>,
+    /// Index of the adapter's `cabi_import_realloc` export.
+    realloc: Option<u32>,
+}
+
+impl Reencode for Virtualizer<'_> {
+    type Error = Infallible;
+
+    fn function_index(&mut self, func: u32) -> Result<u32, reencode::Error<Infallible>> {
+        Ok(
+            match self.order.iter().position(|&replaced| replaced == func) {
+                Some(position) => self.imported + self.defined + u32::try_from(position).unwrap(),
+                None => func,
+            },
+        )
+    }
+
+    fn parse_import_section(
+        &mut self,
+        imports: &mut ImportSection,
+        section: wasmparser::ImportSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Infallible>> {
+        for import in section.clone().into_imports() {
+            let import = import?;
+            if let wasmparser::TypeRef::Func(ty) = import.ty {
+                if let Some(replacement) =
+                    Replacement::find(self.virtualization, import.module, import.name)
+                {
+                    self.replaced.insert(self.imported, (ty, replacement));
+                    self.order.push(self.imported);
+                }
+                self.imported += 1;
+            }
+        }
+
+        reencode::utils::parse_import_section(self, imports, section)
+    }
+
+    fn parse_function_section(
+        &mut self,
+        functions: &mut FunctionSection,
+        section: wasmparser::FunctionSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Infallible>> {
+        self.defined = section.count();
+        reencode::utils::parse_function_section(self, functions, section)?;
+        for index in &self.order {
+            functions.function(self.replaced[index].0);
+        }
+        Ok(())
+    }
+
+    fn parse_code_section(
+        &mut self,
+        code: &mut CodeSection,
+        section: wasmparser::CodeSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Infallible>> {
+        reencode::utils::parse_code_section(self, code, section)?;
+        for index in self.order.clone() {
+            code.function(&self.body(self.replaced[&index].1));
+        }
+        Ok(())
+    }
+}
+
+impl Virtualizer<'_> {
+    fn body(&self, replacement: Replacement) -> Function {
+        let mem = |offset| MemArg {
+            offset,
+            align: 0,
+            memory_index: 0,
+        };
+
+        match replacement {
+            Replacement::GetEnvironment => {
+                let env = self.virtualization.env.unwrap();
+                let realloc = self.realloc.unwrap();
+                // Locals: 0 = return pointer, 1 = list pointer, 2 = string pointer
+                let mut function = Function::new([(2, ValType::I32)]);
+                let alloc = |function: &mut Function, align, size: usize| {
+                    function.instruction(&Instruction::I32Const(0));
+                    function.instruction(&Instruction::I32Const(0));
+                    function.instruction(&Instruction::I32Const(align));
+                    function.instruction(&Instruction::I32Const(i32::try_from(size).unwrap()));
+                    function.instruction(&Instruction::Call(realloc));
+                };
+
+                if env.is_empty() {
+                    function.instruction(&Instruction::I32Const(0));
+                } else {
+                    alloc(&mut function, 4, env.len() * 16);
+                }
+                function.instruction(&Instruction::LocalSet(1));
+
+                for (index, (key, value)) in env.iter().enumerate() {
+                    for (field, string) in [key, value].into_iter().enumerate() {
+                        let offset = u64::try_from(index * 16 + field * 8).unwrap();
+                        if string.is_empty() {
+                            function.instruction(&Instruction::I32Const(0));
+                        } else {
+                            alloc(&mut function, 1, string.len());
+                        }
+                        function.instruction(&Instruction::LocalSet(2));
+                        for (position, &byte) in string.as_bytes().iter().enumerate() {
+                            let position = u64::try_from(position).unwrap();
+                            function.instruction(&Instruction::LocalGet(2));
+                            function.instruction(&Instruction::I32Const(byte.into()));
+                            function.instruction(&Instruction::I32Store8(mem(position)));
+                        }
+                        function.instruction(&Instruction::LocalGet(1));
+                        function.instruction(&Instruction::LocalGet(2));
+                        function.instruction(&Instruction::I32Store(mem(offset)));
+                        function.instruction(&Instruction::LocalGet(1));
+                        function.instruction(&Instruction::I32Const(
+                            i32::try_from(string.len()).unwrap(),
+                        ));
+                        function.instruction(&Instruction::I32Store(mem(offset + 4)));
+                    }
+                }
+
+                function.instruction(&Instruction::LocalGet(0));
+                function.instruction(&Instruction::LocalGet(1));
+                function.instruction(&Instruction::I32Store(mem(0)));
+                function.instruction(&Instruction::LocalGet(0));
+                function.instruction(&Instruction::I32Const(i32::try_from(env.len()).unwrap()));
+                function.instruction(&Instruction::I32Store(mem(4)));
+                function.instruction(&Instruction::End);
+                function
+            }
+            Replacement::WallClockNow | Replacement::WallClockResolution => {
+                // Store a `datetime` record to the return pointer.
+                let (seconds, nanoseconds) = if let Replacement::WallClockNow = replacement {
+                    (self.virtualization.clock.unwrap(), 0)
+                } else {
+                    (0, 1)
+                };
+                let mut function = Function::new([]);
+                function.instruction(&Instruction::LocalGet(0));
+                function.instruction(&Instruction::I64Const(seconds.cast_signed()));
+                function.instruction(&Instruction::I64Store(mem(0)));
+                function.instruction(&Instruction::LocalGet(0));
+                function.instruction(&Instruction::I32Const(nanoseconds));
+                function.instruction(&Instruction::I32Store(mem(8)));
+                function.instruction(&Instruction::End);
+                function
+            }
+            Replacement::MonotonicClockNow | Replacement::MonotonicClockResolution => {
+                let value = if let Replacement::MonotonicClockNow = replacement {
+                    0
+                } else {
+                    1
+                };
+                let mut function = Function::new([]);
+                function.instruction(&Instruction::I64Const(value));
+                function.instruction(&Instruction::End);
+                function
+            }
+        }
+    }
+}
+
+/// Rewrite `adapter` (a `wasi_snapshot_preview1` adapter module) to replace
+/// the WASI imports selected by `virtualization` with fixed implementations.
+pub fn virtualize(adapter: &[u8], virtualization: &Virtualization) -> anyhow::Result<Vec<u8>> {
+    let mut realloc = None;
+    for payload in wasmparser::Parser::new(0).parse_all(adapter) {
+        if let wasmparser::Payload::ExportSection(reader) = payload? {
+            for export in reader {
+                let export = export?;
+                if export.name == "cabi_import_realloc"
+                    && export.kind == wasmparser::ExternalKind::Func
+                {
+                    realloc = Some(export.index);
+                }
+            }
+        }
+    }
+
+    if virtualization.env.is_some() && realloc.is_none() {
+        return Err(anyhow!("adapter does not export `cabi_import_realloc`"));
+    }
+
+    let mut virtualizer = Virtualizer {
+        virtualization,
+        imported: 0,
+        defined: 0,
+        replaced: HashMap::new(),
+        order: Vec::new(),
+        realloc,
+    };
+    let mut output = wasm_encoder::Module::new();
+    virtualizer
+        .parse_core_module(&mut output, wasmparser::Parser::new(0), adapter)
+        .context("failed to virtualize WASI imports")?;
+
+    Ok(output.finish())
+}
@@ -2,8 +2,14 @@
 
 use {
     anyhow::{Context as _, anyhow, bail},
-    componentize_js::Wit,
+    bytes::Bytes,
+    componentize_js::{Componentizer, Wit},
     http_body_util::BodyExt as _,
+    std::{
+        io::{BufRead as _, BufReader, Write as _},
+        net::TcpListener,
+        thread,
+    },
     tokio::fs,
     wasmtime::{
         Config, Engine, Store,
@@ -96,15 +102,13 @@ async fn test(
 #[tokio::test]
 async fn cli() -> anyhow::Result<()> {
     test(
-        &componentize_js::componentize(
+        &Componentizer::new(
             Wit::Paths(&["wit"]),
-            Some("wasi:cli/command@0.3.0-rc-2026-01-06"),
-            &[],
-            false,
             &fs::read_to_string("examples/cli/app.js").await?,
-            Some("examples/cli"),
-            None,
         )
+        .world("wasi:cli/command@0.3.0-rc-2026-01-06")
+        .base_directory("examples/cli")
+        .componentize()
         .await?,
         async |store, instance, stdout| {
             let command = wasmtime_wasi::p3::bindings::Command::new(&mut *store, instance)?;
@@ -122,78 +126,161 @@ async fn cli() -> anyhow::Result<()> {
     .await
 }
 
+/// Send `request` to the `wasi:http/handler` exported by `instance`, returning
+/// the response with its body collected.
+async fn handle(
+    store: &mut Store<Ctx>,
+    instance: &Instance,
+    request: http::Request<http_body_util::Full<Bytes>>,
+) -> anyhow::Result<http::Response<Bytes>> {
+    let service = Service::new(&mut *store, instance)?;
+    let request = store.data_mut().table.push(Request::from_http(request).0)?;
+
+    store
+        .run_concurrent(async |store| {
+            let response = service
+                .wasi_http_handler()
+                .call_handle(store, request)
+                .await?
+                .0?;
+
+            let response = store.with(|mut store| {
+                store
+                    .get()
+                    .table
+                    .delete(response)?
+                    .into_http(store, async { Ok(()) })
+            })?;
+
+            let (parts, body) = response.into_parts();
+            let body = body.collect().await.context("failed to collect body")?;
+
+            anyhow::Ok(http::Response::from_parts(parts, body.to_bytes()))
+        })
+        .await?
+}
+
+async fn http_service() -> anyhow::Result<Vec<u8>> {
+    Ok(Componentizer::new(
+        Wit::Paths(&["wit"]),
+        &fs::read_to_string("examples/http/app.js").await?,
+    )
+    .world("wasi:http/service@0.3.0-rc-2026-01-06")
+    .base_directory("examples/http")
+    .componentize()
+    .await?)
+}
+
 #[tokio::test]
 async fn http() -> anyhow::Result<()> {
-    test(
-        &componentize_js::componentize(
-            Wit::Paths(&["wit"]),
-            Some("wasi:http/service@0.3.0-rc-2026-01-06"),
-            &[],
-            false,
-            &fs::read_to_string("examples/http/app.js").await?,
-            Some("examples/http"),
-            None,
-        )
-        .await?,
-        async |store, instance, _| {
-            let service = Service::new(&mut *store, instance)?;
-
-            let body = "’Twas brillig, and the slithy toves
+    test(&http_service().await?, async |store, instance, _| {
+        let body = "’Twas brillig, and the slithy toves
       Did gyre and gimble in the wabe:
 All mimsy were the borogoves,
       And the mome raths outgrabe.";
 
-            let request = store.data_mut().table.push(
-                Request::from_http(
-                    http::Request::builder()
-                        .uri("http://localhost/echo")
-                        .method(http::Method::POST)
-                        .header("content-type", "text/plain")
-                        .body(http_body_util::Full::from(body))?,
-                )
-                .0,
-            )?;
-
-            let response = store
-                .run_concurrent(async |store| {
-                    let response = service
-                        .wasi_http_handler()
-                        .call_handle(store, request)
-                        .await?
-                        .0?;
-
-                    let response = store.with(|mut store| {
-                        store
-                            .get()
-                            .table
-                            .delete(response)?
-                            .into_http(store, async { Ok(()) })
-                    })?;
-
-                    let (parts, body) = response.into_parts();
-                    let body = body.collect().await.context("failed to collect body")?;
-
-                    anyhow::Ok(http::Response::from_parts(parts, body))
-                })
-                .await??;
-
-            if !response.status().is_success() {
-                bail!("unexpected response status: {}", response.status());
-            }
-            assert_eq!(
-                Some("text/plain"),
-                response
-                    .headers()
-                    .get("content-type")
-                    .and_then(|v| v.to_str().ok())
-            );
-            assert_eq!(
-                body,
-                String::from_utf8_lossy(&response.into_body().to_bytes())
-            );
+        let response = handle(
+            store,
+            instance,
+            http::Request::builder()
+                .uri("http://localhost/echo")
+                .method(http::Method::POST)
+                .header("content-type", "text/plain")
+                .body(http_body_util::Full::from(body))?,
+        )
+        .await?;
 
-            Ok(())
-        },
-    )
+        if !response.status().is_success() {
+            bail!("unexpected response status: {}", response.status());
+        }
+        assert_eq!(
+            Some("text/plain"),
+            response
+                .headers()
+                .get("content-type")
+                .and_then(|v| v.to_str().ok())
+        );
+        assert_eq!(body, String::from_utf8_lossy(response.body()));
+
+        Ok(())
+    })
     .await
 }
+
+#[tokio::test]
+async fn http_bad_request() -> anyhow::Result<()> {
+    test(&http_service().await?, async |store, instance, _| {
+        let response = handle(
+            store,
+            instance,
+            http::Request::builder()
+                .uri("http://localhost/nonexistent")
+                .body(http_body_util::Full::default())?,
+        )
+        .await?;
+
+        assert_eq!(http::StatusCode::BAD_REQUEST, response.status());
+        assert_eq!(
+            Some("text/plain;charset=UTF-8"),
+            response
+                .headers()
+                .get("content-type")
+                .and_then(|v| v.to_str().ok())
+        );
+        assert_eq!("bad request\n", String::from_utf8_lossy(response.body()));
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn http_fetch() -> anyhow::Result<()> {
+    // Serve a fixed response to a single request, which the app will fetch
+    // using `fetch` (and thus `wasi:http/client`).
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/greeting", listener.local_addr()?);
+    let server = thread::spawn(move || -> anyhow::Result<String> {
+        let (mut stream, _) = listener.accept()?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            if line.trim_end().is_empty() {
+                break;
+            }
+        }
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\nconnection: close\r\n\r\nhello")?;
+        Ok(request_line)
+    });
+
+    test(&http_service().await?, async |store, instance, _| {
+        let response = handle(
+            store,
+            instance,
+            http::Request::builder()
+                .uri("http://localhost/hash-all")
+                .header("url", &url)
+                .body(http_body_util::Full::default())?,
+        )
+        .await?;
+
+        if !response.status().is_success() {
+            bail!("unexpected response status: {}", response.status());
+        }
+        assert_eq!(
+            format!("{url}: 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824\n"),
+            String::from_utf8_lossy(response.body())
+        );
+
+        Ok(())
+    })
+    .await?;
+
+    assert_eq!("GET /greeting HTTP/1.1\r\n", server.join().unwrap()?);
+
+    Ok(())
+}
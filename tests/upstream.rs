@@ -0,0 +1,183 @@
+#![deny(warnings)]
+
+//! Differential tests which build the same script and WIT world using both
+//! upstream [ComponentizeJS](https://github.com/bytecodealliance/ComponentizeJS)
+//! and this crate, then compare the results of calling each export.
+//!
+//! These tests are skipped unless the `COMPONENTIZE_JS_UPSTREAM` environment
+//! variable is set to the path of the upstream `componentize-js` CLI (e.g.
+//! `node_modules/.bin/componentize-js`).
+
+use {
+    anyhow::{Context as _, bail},
+    componentize_js::{Componentizer, Wit},
+    std::{env, path::Path, process::Command},
+    tokio::fs,
+    wasmtime::{
+        Config, Engine, Store,
+        component::{Component, Linker, ResourceTable, Val},
+    },
+    wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView},
+};
+
+const WIT_PATH: &str = "tests/upstream/world.wit";
+const JS_PATH: &str = "tests/upstream/app.js";
+
+struct Ctx {
+    wasi: WasiCtx,
+    table: ResourceTable,
+}
+
+impl WasiView for Ctx {
+    fn ctx(&mut self) -> WasiCtxView<'_> {
+        WasiCtxView {
+            ctx: &mut self.wasi,
+            table: &mut self.table,
+        }
+    }
+}
+
+fn build_upstream(command: &Path, output: &Path) -> anyhow::Result<Vec<u8>> {
+    let status = Command::new(command)
+        .args(["--wit", WIT_PATH, "--world-name", "upstream", "--out"])
+        .arg(output)
+        .arg(JS_PATH)
+        .status()
+        .with_context(|| format!("unable to run `{}`", command.display()))?;
+
+    if !status.success() {
+        bail!("`{}` exited with {status}", command.display());
+    }
+
+    Ok(std::fs::read(output)?)
+}
+
+/// Instantiate `component` and call the export named `name` with `params`,
+/// returning the results, or the error message if the call trapped.
+async fn call(
+    engine: &Engine,
+    component: &Component,
+    name: &str,
+    params: &[Val],
+) -> anyhow::Result<Result<Vec<Val>, String>> {
+    let mut store = Store::new(
+        engine,
+        Ctx {
+            wasi: WasiCtxBuilder::new().inherit_stderr().build(),
+            table: ResourceTable::new(),
+        },
+    );
+
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+    // Upstream components may import interfaces (e.g. `wasi:http`) which this
+    // test doesn't use, so we stub them out:
+    linker.define_unknown_imports_as_traps(component)?;
+
+    let instance = linker.instantiate_async(&mut store, component).await?;
+    let func = instance
+        .get_func(&mut store, name)
+        .with_context(|| format!("export `{name}` not found"))?;
+    let mut results = vec![Val::Bool(false); func.ty(&store).results().len()];
+
+    Ok(func
+        .call_async(&mut store, params, &mut results)
+        .await
+        .map(|()| results)
+        .map_err(|e| format!("{e:?}")))
+}
+
+#[tokio::test]
+async fn differential() -> anyhow::Result<()> {
+    let Some(command) = env::var_os("COMPONENTIZE_JS_UPSTREAM") else {
+        eprintln!("skipping differential tests; set COMPONENTIZE_JS_UPSTREAM to enable them");
+        return Ok(());
+    };
+
+    let mut config = Config::new();
+    config.async_support(true);
+    config.wasm_component_model(true);
+    config.wasm_component_model_async(true);
+    let engine = Engine::new(&config)?;
+
+    let tmp = env::temp_dir().join(format!(
+        "componentize-js-upstream-{}.wasm",
+        std::process::id()
+    ));
+    let upstream = Component::new(&engine, build_upstream(Path::new(&command), &tmp)?)?;
+    _ = std::fs::remove_file(&tmp);
+
+    let ours = Component::new(
+        &engine,
+        Componentizer::new(Wit::Paths(&[WIT_PATH]), &fs::read_to_string(JS_PATH).await?)
+            .world("upstream")
+            .componentize()
+            .await?,
+    )?;
+
+    let point = |x, y| Val::Record(vec![("x".into(), Val::S32(x)), ("y".into(), Val::S32(y))]);
+
+    let cases = [
+        ("add", vec![Val::U32(2), Val::U32(40)]),
+        ("add", vec![Val::U32(0x8000_0000), Val::U32(1)]),
+        ("greet", vec![Val::String("world".into())]),
+        ("greet", vec![Val::String("Grüße, 世界 🌍".into())]),
+        ("parse", vec![Val::String("42".into())]),
+        ("parse", vec![Val::String("forty-two".into())]),
+        ("translate", vec![point(1, -2), Val::S32(-3), Val::S32(4)]),
+        (
+            "describe",
+            vec![Val::Variant("circle".into(), Some(Box::new(Val::U32(7))))],
+        ),
+        (
+            "describe",
+            vec![Val::Variant(
+                "rectangle".into(),
+                Some(Box::new(point(3, 4))),
+            )],
+        ),
+        ("describe", vec![Val::Variant("empty".into(), None)]),
+        (
+            "reverse",
+            vec![Val::List(vec![
+                Val::String("a".into()),
+                Val::String("b".into()),
+            ])],
+        ),
+        ("maybe-double", vec![Val::Option(None)]),
+        (
+            "maybe-double",
+            vec![Val::Option(Some(Box::new(Val::U64(21))))],
+        ),
+        (
+            "maybe-double",
+            vec![Val::Option(Some(Box::new(Val::U64(1 << 40))))],
+        ),
+    ];
+
+    let mut mismatches = Vec::new();
+    for (name, params) in cases {
+        let expected = call(&engine, &upstream, name, &params).await?;
+        let actual = call(&engine, &ours, name, &params).await?;
+
+        // We only compare whether or not the call trapped, not the trap
+        // messages, which are expected to differ.
+        let matches = match (&expected, &actual) {
+            (Ok(expected), Ok(actual)) => expected == actual,
+            (Err(_), Err(_)) => true,
+            _ => false,
+        };
+
+        if !matches {
+            mismatches.push(format!(
+                "{name}({params:?}): upstream returned {expected:?}; we returned {actual:?}"
+            ));
+        }
+    }
+
+    if !mismatches.is_empty() {
+        bail!("behavior differs from upstream:\n{}", mismatches.join("\n"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+// This script is built with both upstream ComponentizeJS and this crate, so it
+// should only use features supported by both.
+
+// Both implementations treat an exception with a `payload` property as the
+// `err` case of a `result`, but only this crate provides `ComponentError` as a
+// global, so we define our own here.
+class ComponentError extends Error {
+    constructor(payload) {
+        super(String(payload))
+        this.payload = payload
+    }
+}
+
+export function add(a, b) {
+    return a + b
+}
+
+export function greet(name) {
+    return `Hello, ${name}!`
+}
+
+export function parse(s) {
+    const value = Number.parseInt(s, 10)
+    if (Number.isNaN(value) || value < 0) {
+        throw new ComponentError(`not a u32: ${s}`)
+    }
+    return value
+}
+
+export function translate(p, dx, dy) {
+    return { x: p.x + dx, y: p.y + dy }
+}
+
+export function describe(s) {
+    switch (s.tag) {
+    case "circle": return `circle of radius ${s.val}`
+    case "rectangle": return `rectangle to (${s.val.x}, ${s.val.y})`
+    case "empty": return "nothing"
+    }
+}
+
+export function reverse(v) {
+    return v.slice().reverse()
+}
+
+export function maybeDouble(v) {
+    return v === undefined ? undefined : BigInt(v) * 2n
+}